@@ -0,0 +1,54 @@
+//! Times parsing a PNG of several megabytes: `cargo bench --bench parse`.
+//!
+//! "rebuild" is how parsing used to work, verifying each chunk's CRC and then building it with
+//! `Chunk::new`, which copies the data again and computes the CRC a second time.
+
+use std::{
+	hint::black_box,
+	time::{Duration, Instant},
+};
+
+use pngme::{Chunk, ChunkRef, ChunkType, Png, PngRef};
+
+const CHUNK_LEN: usize = 1 << 16;
+const CHUNKS: usize = 128;
+const ROUNDS: u32 = 10;
+
+fn main() {
+	let ihdr = [0, 0, 1, 0, 0, 0, 1, 0, 8, 6, 0, 0, 0];
+	let mut chunks = vec![Chunk::new(ChunkType::IHDR, ihdr.to_vec())];
+	for i in 0..CHUNKS {
+		let data = (0..CHUNK_LEN).map(|j| (i * 31 + j * 7) as u8).collect();
+		chunks.push(Chunk::new(ChunkType::IDAT, data));
+	}
+	chunks.push(Chunk::new(ChunkType::IEND, Vec::new()));
+	let bytes = Png::from_chunks(chunks).as_bytes();
+	println!("{:.1} MiB, {} rounds", bytes.len() as f64 / (1024.0 * 1024.0), ROUNDS);
+
+	report("borrow", time(|| PngRef::try_from(bytes.as_slice()).unwrap().chunks().len()));
+	report("parse", time(|| Png::try_from(bytes.as_slice()).unwrap().chunks().len()));
+	report(
+		"rebuild",
+		time(|| {
+			let png = PngRef::try_from(bytes.as_slice()).unwrap();
+			png.chunks().iter().map(rebuild).count()
+		}),
+	);
+}
+
+fn rebuild(chunk: &ChunkRef) -> Chunk {
+	let chunk_type = ChunkType::try_from(chunk.chunk_type().bytes()).unwrap();
+	Chunk::new(chunk_type, chunk.data().to_vec())
+}
+
+fn time(mut f: impl FnMut() -> usize) -> Duration {
+	let start = Instant::now();
+	for _ in 0..ROUNDS {
+		black_box(f());
+	}
+	start.elapsed() / ROUNDS
+}
+
+fn report(name: &str, elapsed: Duration) {
+	println!("{:<8} {:>8.2} ms", name, elapsed.as_secs_f64() * 1000.0);
+}
@@ -0,0 +1,118 @@
+//! Statistics for spotting hidden or unusual data.
+
+/// Block characters for [`ByteStats::sparkline`], from lowest to highest.
+const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Returns how many times each byte value occurs in `data`.
+pub fn histogram(data: &[u8]) -> [usize; 256] {
+	let mut counts = [0; 256];
+	for &byte in data {
+		counts[byte as usize] += 1;
+	}
+	counts
+}
+
+/// Returns the Shannon entropy of `data` in bits per byte, from 0 for constant data up to 8 for
+/// uniformly random data such as ciphertext.
+pub fn entropy(data: &[u8]) -> f64 {
+	entropy_of(&histogram(data), data.len())
+}
+
+fn entropy_of(histogram: &[usize; 256], len: usize) -> f64 {
+	if len == 0 {
+		return 0.0;
+	}
+	histogram
+		.iter()
+		.filter(|&&count| count > 0)
+		.map(|&count| {
+			let p = count as f64 / len as f64;
+			-p * p.log2()
+		})
+		.sum()
+}
+
+/// Returns the fraction of `data` that is printable ASCII, tabs and line breaks included, or
+/// 0 for empty data.
+pub fn printable_ratio(data: &[u8]) -> f64 {
+	printable_ratio_of(&histogram(data), data.len())
+}
+
+fn printable_ratio_of(histogram: &[usize; 256], len: usize) -> f64 {
+	if len == 0 {
+		return 0.0;
+	}
+	let printable: usize = (0..=255u8)
+		.filter(|byte| matches!(byte, b'\t' | b'\n' | b'\r' | b' '..=b'~'))
+		.map(|byte| histogram[byte as usize])
+		.sum();
+	printable as f64 / len as f64
+}
+
+/// Byte statistics of a block of data, such as the contents of a chunk.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ByteStats {
+	pub len: usize,
+	pub entropy: f64,
+	pub printable_ratio: f64,
+	pub histogram: [usize; 256],
+}
+
+impl ByteStats {
+	pub fn of(data: &[u8]) -> Self {
+		let histogram = histogram(data);
+		Self {
+			len: data.len(),
+			entropy: entropy_of(&histogram, data.len()),
+			printable_ratio: printable_ratio_of(&histogram, data.len()),
+			histogram,
+		}
+	}
+
+	/// Draws the histogram as 16 bars of 16 byte values each, scaled to the fullest bar.
+	pub fn sparkline(&self) -> String {
+		let bins: Vec<usize> = self.histogram.chunks(16).map(|bin| bin.iter().sum()).collect();
+		let max = bins.iter().copied().max().unwrap_or_default();
+		bins.iter()
+			.map(|&count| match count {
+				0 => ' ',
+				_ => BARS[(count * BARS.len()).div_ceil(max) - 1],
+			})
+			.collect()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_entropy() {
+		assert_eq!(entropy(b""), 0.0);
+		assert_eq!(entropy(b"aaaa"), 0.0);
+		assert_eq!(entropy(b"abab"), 1.0);
+		let all: Vec<u8> = (0..=255).collect();
+		assert_eq!(entropy(&all), 8.0);
+	}
+
+	#[test]
+	fn test_printable_ratio() {
+		assert_eq!(printable_ratio(b""), 0.0);
+		assert_eq!(printable_ratio(b"plain text\n"), 1.0);
+		assert_eq!(printable_ratio(b"ab\0\xff"), 0.5);
+	}
+
+	#[test]
+	fn test_byte_stats() {
+		let stats = ByteStats::of(b"aab");
+		assert_eq!(stats.len, 3);
+		assert_eq!(stats.histogram[b'a' as usize], 2);
+		assert_eq!(stats.printable_ratio, 1.0);
+		assert_eq!(stats.entropy, entropy(b"aab"));
+
+		let all: Vec<u8> = (0..=255).collect();
+		assert_eq!(ByteStats::of(&all).sparkline(), "█".repeat(16));
+		assert_eq!(ByteStats::of(b"").sparkline(), " ".repeat(16));
+		assert_eq!(ByteStats::of(b"\0\0\xff").sparkline(), "█              ▄");
+	}
+}
@@ -0,0 +1,491 @@
+//! Typed forms of common ancillary chunks: `tIME`, `pHYs`, `gAMA`, `sRGB`, `bKGD`, `tRNS`,
+//! and `cHRM`.
+
+use std::fmt;
+
+use crate::{chunk::Chunk, chunk_type::ChunkType, ihdr::ColorType, PngmeError, Result};
+
+fn invalid(chunk_type: ChunkType, reason: impl Into<String>) -> crate::Error {
+	PngmeError::invalid(format!("{} chunk", chunk_type), reason)
+}
+
+/// Returns the data of `chunk`, checking its type and, if given, its length.
+fn data_of(chunk: &Chunk, chunk_type: ChunkType, length: Option<usize>) -> Result<&[u8]> {
+	if chunk.chunk_type() != &chunk_type {
+		return Err(invalid(chunk_type, format!("got a {} chunk", chunk.chunk_type())));
+	}
+	match length {
+		Some(length) if chunk.data().len() != length => Err(invalid(
+			chunk_type,
+			format!("expected {} bytes, got {}", length, chunk.data().len()),
+		)),
+		_ => Ok(chunk.data()),
+	}
+}
+
+fn u16_at(data: &[u8], n: usize) -> u16 {
+	u16::from_be_bytes([data[n], data[n + 1]])
+}
+
+fn u32_at(data: &[u8], n: usize) -> u32 {
+	u32::from_be_bytes([data[n], data[n + 1], data[n + 2], data[n + 3]])
+}
+
+/// The `tIME` chunk: when the image was last modified, in UTC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Time {
+	pub year: u16,
+	pub month: u8,
+	pub day: u8,
+	pub hour: u8,
+	pub minute: u8,
+	/// Up to 60, to allow for leap seconds.
+	pub second: u8,
+}
+
+impl Time {
+	pub const TYPE: ChunkType = ChunkType::tIME;
+
+	/// Encodes the time as a raw [`Chunk`].
+	pub fn to_chunk(&self) -> Chunk {
+		let mut data = self.year.to_be_bytes().to_vec();
+		data.extend_from_slice(&[self.month, self.day, self.hour, self.minute, self.second]);
+		Chunk::new(Self::TYPE, data)
+	}
+}
+
+impl TryFrom<&Chunk> for Time {
+	type Error = crate::Error;
+	fn try_from(chunk: &Chunk) -> Result<Self> {
+		let data = data_of(chunk, Self::TYPE, Some(7))?;
+		let time = Self {
+			year: u16_at(data, 0),
+			month: data[2],
+			day: data[3],
+			hour: data[4],
+			minute: data[5],
+			second: data[6],
+		};
+		if !(1..=12).contains(&time.month)
+			|| !(1..=31).contains(&time.day)
+			|| time.hour > 23
+			|| time.minute > 59
+			|| time.second > 60
+		{
+			return Err(invalid(Self::TYPE, format!("{} is not a valid time", time)));
+		}
+		Ok(time)
+	}
+}
+
+impl From<Time> for Chunk {
+	fn from(time: Time) -> Self {
+		time.to_chunk()
+	}
+}
+
+impl fmt::Display for Time {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(
+			f,
+			"{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+			self.year, self.month, self.day, self.hour, self.minute, self.second
+		)
+	}
+}
+
+/// The unit of [`PhysicalDimensions`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PixelUnit {
+	/// Only the aspect ratio is known.
+	Unknown = 0,
+	Meter = 1,
+}
+
+/// The `pHYs` chunk: pixels per unit along each axis.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PhysicalDimensions {
+	pub x: u32,
+	pub y: u32,
+	pub unit: PixelUnit,
+}
+
+impl PhysicalDimensions {
+	pub const TYPE: ChunkType = ChunkType::pHYs;
+
+	/// Returns the horizontal and vertical resolution in dots per inch, if the unit is known.
+	pub fn dpi(&self) -> Option<(f64, f64)> {
+		const METERS_PER_INCH: f64 = 0.0254;
+		(self.unit == PixelUnit::Meter)
+			.then_some((self.x as f64 * METERS_PER_INCH, self.y as f64 * METERS_PER_INCH))
+	}
+
+	/// Encodes the dimensions as a raw [`Chunk`].
+	pub fn to_chunk(&self) -> Chunk {
+		let mut data = self.x.to_be_bytes().to_vec();
+		data.extend_from_slice(&self.y.to_be_bytes());
+		data.push(self.unit as u8);
+		Chunk::new(Self::TYPE, data)
+	}
+}
+
+impl TryFrom<&Chunk> for PhysicalDimensions {
+	type Error = crate::Error;
+	fn try_from(chunk: &Chunk) -> Result<Self> {
+		let data = data_of(chunk, Self::TYPE, Some(9))?;
+		let unit = match data[8] {
+			0 => PixelUnit::Unknown,
+			1 => PixelUnit::Meter,
+			unit => return Err(invalid(Self::TYPE, format!("unknown unit {}", unit))),
+		};
+		Ok(Self { x: u32_at(data, 0), y: u32_at(data, 4), unit })
+	}
+}
+
+impl From<PhysicalDimensions> for Chunk {
+	fn from(dimensions: PhysicalDimensions) -> Self {
+		dimensions.to_chunk()
+	}
+}
+
+impl fmt::Display for PhysicalDimensions {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match (self.dpi(), self.x == self.y) {
+			(Some((dpi, _)), true) => write!(f, "{} px/m ({:.0} dpi)", self.x, dpi),
+			(Some((x, y)), false) => {
+				write!(f, "{}x{} px/m ({:.0}x{:.0} dpi)", self.x, self.y, x, y)
+			}
+			(None, _) => write!(f, "aspect ratio {}:{}", self.x, self.y),
+		}
+	}
+}
+
+/// The `gAMA` chunk: image gamma times 100000.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Gamma {
+	pub scaled: u32,
+}
+
+impl Gamma {
+	pub const TYPE: ChunkType = ChunkType::gAMA;
+
+	pub fn value(&self) -> f64 {
+		self.scaled as f64 / 100_000.0
+	}
+
+	/// Encodes the gamma as a raw [`Chunk`].
+	pub fn to_chunk(&self) -> Chunk {
+		Chunk::new(Self::TYPE, self.scaled.to_be_bytes().to_vec())
+	}
+}
+
+impl TryFrom<&Chunk> for Gamma {
+	type Error = crate::Error;
+	fn try_from(chunk: &Chunk) -> Result<Self> {
+		let data = data_of(chunk, Self::TYPE, Some(4))?;
+		Ok(Self { scaled: u32_at(data, 0) })
+	}
+}
+
+impl From<Gamma> for Chunk {
+	fn from(gamma: Gamma) -> Self {
+		gamma.to_chunk()
+	}
+}
+
+impl fmt::Display for Gamma {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "gamma {}", self.value())
+	}
+}
+
+/// The `sRGB` chunk: the image uses the sRGB color space with this rendering intent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenderingIntent {
+	Perceptual = 0,
+	RelativeColorimetric = 1,
+	Saturation = 2,
+	AbsoluteColorimetric = 3,
+}
+
+impl RenderingIntent {
+	pub const TYPE: ChunkType = ChunkType::sRGB;
+
+	/// Encodes the rendering intent as a raw `sRGB` [`Chunk`].
+	pub fn to_chunk(&self) -> Chunk {
+		Chunk::new(Self::TYPE, vec![*self as u8])
+	}
+}
+
+impl TryFrom<&Chunk> for RenderingIntent {
+	type Error = crate::Error;
+	fn try_from(chunk: &Chunk) -> Result<Self> {
+		match data_of(chunk, Self::TYPE, Some(1))?[0] {
+			0 => Ok(Self::Perceptual),
+			1 => Ok(Self::RelativeColorimetric),
+			2 => Ok(Self::Saturation),
+			3 => Ok(Self::AbsoluteColorimetric),
+			intent => Err(invalid(Self::TYPE, format!("unknown rendering intent {}", intent))),
+		}
+	}
+}
+
+impl From<RenderingIntent> for Chunk {
+	fn from(intent: RenderingIntent) -> Self {
+		intent.to_chunk()
+	}
+}
+
+impl fmt::Display for RenderingIntent {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(match self {
+			Self::Perceptual => "perceptual",
+			Self::RelativeColorimetric => "relative colorimetric",
+			Self::Saturation => "saturation",
+			Self::AbsoluteColorimetric => "absolute colorimetric",
+		})
+	}
+}
+
+/// The `bKGD` chunk: the preferred background color.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Background {
+	Gray(u16),
+	Rgb(u16, u16, u16),
+	PaletteIndex(u8),
+}
+
+impl Background {
+	pub const TYPE: ChunkType = ChunkType::bKGD;
+
+	/// Encodes the background as a raw [`Chunk`].
+	pub fn to_chunk(&self) -> Chunk {
+		let data = match *self {
+			Self::Gray(gray) => gray.to_be_bytes().to_vec(),
+			Self::Rgb(r, g, b) => [r, g, b].iter().flat_map(|c| c.to_be_bytes()).collect(),
+			Self::PaletteIndex(index) => vec![index],
+		};
+		Chunk::new(Self::TYPE, data)
+	}
+}
+
+/// The layout is told apart by length, which is unambiguous for `bKGD`.
+impl TryFrom<&Chunk> for Background {
+	type Error = crate::Error;
+	fn try_from(chunk: &Chunk) -> Result<Self> {
+		let data = data_of(chunk, Self::TYPE, None)?;
+		match data.len() {
+			1 => Ok(Self::PaletteIndex(data[0])),
+			2 => Ok(Self::Gray(u16_at(data, 0))),
+			6 => Ok(Self::Rgb(u16_at(data, 0), u16_at(data, 2), u16_at(data, 4))),
+			len => Err(invalid(Self::TYPE, format!("unexpected length {}", len))),
+		}
+	}
+}
+
+impl From<Background> for Chunk {
+	fn from(background: Background) -> Self {
+		background.to_chunk()
+	}
+}
+
+impl fmt::Display for Background {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Gray(gray) => write!(f, "background gray {}", gray),
+			Self::Rgb(r, g, b) => write!(f, "background RGB ({}, {}, {})", r, g, b),
+			Self::PaletteIndex(index) => write!(f, "background palette entry {}", index),
+		}
+	}
+}
+
+/// The `tRNS` chunk: a single transparent color, or alpha values for palette entries.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Transparency {
+	Gray(u16),
+	Rgb(u16, u16, u16),
+	Palette(Vec<u8>),
+}
+
+impl Transparency {
+	pub const TYPE: ChunkType = ChunkType::tRNS;
+
+	/// Decodes a `tRNS` chunk for an image of the given color type.
+	pub fn decode(chunk: &Chunk, color_type: ColorType) -> Result<Self> {
+		let data = data_of(chunk, Self::TYPE, None)?;
+		match (color_type, data.len()) {
+			(ColorType::Grayscale, 2) => Ok(Self::Gray(u16_at(data, 0))),
+			(ColorType::Rgb, 6) => Ok(Self::Rgb(u16_at(data, 0), u16_at(data, 2), u16_at(data, 4))),
+			(ColorType::Indexed, len) if len <= 256 => Ok(Self::Palette(data.to_vec())),
+			(color_type, len) => Err(invalid(
+				Self::TYPE,
+				format!("{} bytes are not valid for {} images", len, color_type),
+			)),
+		}
+	}
+
+	/// Encodes the transparency as a raw [`Chunk`].
+	pub fn to_chunk(&self) -> Chunk {
+		let data = match self {
+			Self::Gray(gray) => gray.to_be_bytes().to_vec(),
+			Self::Rgb(r, g, b) => [r, g, b].iter().flat_map(|c| c.to_be_bytes()).collect(),
+			Self::Palette(alphas) => alphas.clone(),
+		};
+		Chunk::new(Self::TYPE, data)
+	}
+}
+
+/// Without the color type the layout is guessed from the length: 2 bytes are taken as a gray
+/// level and 6 as an RGB color. Use [`Transparency::decode`] when the header is at hand.
+impl TryFrom<&Chunk> for Transparency {
+	type Error = crate::Error;
+	fn try_from(chunk: &Chunk) -> Result<Self> {
+		let color_type = match data_of(chunk, Self::TYPE, None)?.len() {
+			2 => ColorType::Grayscale,
+			6 => ColorType::Rgb,
+			_ => ColorType::Indexed,
+		};
+		Self::decode(chunk, color_type)
+	}
+}
+
+impl From<Transparency> for Chunk {
+	fn from(transparency: Transparency) -> Self {
+		transparency.to_chunk()
+	}
+}
+
+impl fmt::Display for Transparency {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Gray(gray) => write!(f, "transparent gray {}", gray),
+			Self::Rgb(r, g, b) => write!(f, "transparent RGB ({}, {}, {})", r, g, b),
+			Self::Palette(alphas) => write!(f, "alpha for {} palette entries", alphas.len()),
+		}
+	}
+}
+
+/// The `cHRM` chunk: CIE 1931 x, y chromaticities times 100000.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Chromaticities {
+	pub white: (u32, u32),
+	pub red: (u32, u32),
+	pub green: (u32, u32),
+	pub blue: (u32, u32),
+}
+
+impl Chromaticities {
+	pub const TYPE: ChunkType = ChunkType::cHRM;
+
+	/// Encodes the chromaticities as a raw [`Chunk`].
+	pub fn to_chunk(&self) -> Chunk {
+		let points = [self.white, self.red, self.green, self.blue];
+		let data = points.iter().flat_map(|&(x, y)| [x, y]).flat_map(u32::to_be_bytes).collect();
+		Chunk::new(Self::TYPE, data)
+	}
+}
+
+impl TryFrom<&Chunk> for Chromaticities {
+	type Error = crate::Error;
+	fn try_from(chunk: &Chunk) -> Result<Self> {
+		let data = data_of(chunk, Self::TYPE, Some(32))?;
+		let point = |n: usize| (u32_at(data, n * 8), u32_at(data, n * 8 + 4));
+		Ok(Self { white: point(0), red: point(1), green: point(2), blue: point(3) })
+	}
+}
+
+impl From<Chromaticities> for Chunk {
+	fn from(chromaticities: Chromaticities) -> Self {
+		chromaticities.to_chunk()
+	}
+}
+
+impl fmt::Display for Chromaticities {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let point = |(x, y): (u32, u32)| format!("({}, {})", x as f64 / 1e5, y as f64 / 1e5);
+		write!(
+			f,
+			"white {}, red {}, green {}, blue {}",
+			point(self.white),
+			point(self.red),
+			point(self.green),
+			point(self.blue)
+		)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_time() {
+		let time = Time { year: 2024, month: 5, day: 1, hour: 12, minute: 0, second: 0 };
+		let chunk: Chunk = time.into();
+		assert_eq!(chunk.data(), [0x07, 0xe8, 5, 1, 12, 0, 0]);
+		assert_eq!(Time::try_from(&chunk).unwrap(), time);
+		assert_eq!(time.to_string(), "2024-05-01 12:00:00");
+
+		let bad = Time { month: 13, ..time };
+		assert!(Time::try_from(&bad.to_chunk()).is_err());
+	}
+
+	#[test]
+	fn test_physical_dimensions() {
+		let phys = PhysicalDimensions { x: 2835, y: 2835, unit: PixelUnit::Meter };
+		assert_eq!(PhysicalDimensions::try_from(&phys.to_chunk()).unwrap(), phys);
+		assert_eq!(phys.to_string(), "2835 px/m (72 dpi)");
+		let ratio = PhysicalDimensions { x: 1, y: 2, unit: PixelUnit::Unknown };
+		assert_eq!(ratio.dpi(), None);
+		assert_eq!(ratio.to_string(), "aspect ratio 1:2");
+	}
+
+	#[test]
+	fn test_gamma() {
+		let gamma = Gamma { scaled: 45455 };
+		assert_eq!(Gamma::try_from(&gamma.to_chunk()).unwrap(), gamma);
+		assert_eq!(gamma.value(), 0.45455);
+	}
+
+	#[test]
+	fn test_rendering_intent() {
+		let intent = RenderingIntent::Saturation;
+		assert_eq!(RenderingIntent::try_from(&intent.to_chunk()).unwrap(), intent);
+		assert!(RenderingIntent::try_from(&Chunk::new(ChunkType::sRGB, vec![4])).is_err());
+	}
+
+	#[test]
+	fn test_background() {
+		for background in
+			[Background::Gray(7), Background::Rgb(1, 2, 3), Background::PaletteIndex(9)]
+		{
+			assert_eq!(Background::try_from(&background.to_chunk()).unwrap(), background);
+		}
+	}
+
+	#[test]
+	fn test_transparency() {
+		let palette = Transparency::Palette(vec![0, 128]);
+		let chunk = palette.to_chunk();
+		assert_eq!(Transparency::try_from(&chunk).unwrap(), Transparency::Gray(128));
+		assert_eq!(Transparency::decode(&chunk, ColorType::Indexed).unwrap(), palette);
+		assert!(Transparency::decode(&chunk, ColorType::Rgba).is_err());
+	}
+
+	#[test]
+	fn test_chromaticities() {
+		let chrm = Chromaticities {
+			white: (31270, 32900),
+			red: (64000, 33000),
+			green: (30000, 60000),
+			blue: (15000, 6000),
+		};
+		assert_eq!(Chromaticities::try_from(&chrm.to_chunk()).unwrap(), chrm);
+	}
+
+	#[test]
+	fn test_wrong_type() {
+		let chunk = Gamma { scaled: 1 }.to_chunk();
+		assert!(Time::try_from(&chunk).is_err());
+	}
+}
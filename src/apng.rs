@@ -0,0 +1,646 @@
+//! Animated PNG (APNG) chunks: `acTL`, `fcTL`, and `fdAT`.
+//!
+//! An `acTL` chunk before the first `IDAT` marks a PNG as animated. Each frame is described by
+//! an `fcTL` chunk followed by its image data: the ordinary `IDAT` chunks for a frame that is
+//! also the default image, and `fdAT` chunks otherwise. `fcTL` and `fdAT` chunks share a
+//! single sequence, numbered from zero.
+//!
+//! [`split`] turns each frame into a standalone PNG holding the frame region as stored, before
+//! any blending or disposal, and [`build`] does the reverse for full-size frames.
+
+use std::{fmt, time::Duration};
+
+use crate::{chunk::Chunk, chunk_type::ChunkType, ihdr::IhdrData, png::Png, PngmeError, Result};
+
+fn invalid(chunk_type: ChunkType, reason: impl Into<String>) -> crate::Error {
+	PngmeError::invalid(format!("{} chunk", chunk_type), reason)
+}
+
+/// Returns the data of `chunk`, checking its type and minimum length.
+fn data_of(chunk: &Chunk, chunk_type: ChunkType, min_length: usize) -> Result<&[u8]> {
+	if chunk.chunk_type() != &chunk_type {
+		return Err(invalid(chunk_type, format!("got a {} chunk", chunk.chunk_type())));
+	}
+	if chunk.data().len() < min_length {
+		let reason = format!("expected at least {} bytes, got {}", min_length, chunk.data().len());
+		return Err(invalid(chunk_type, reason));
+	}
+	Ok(chunk.data())
+}
+
+fn u16_at(data: &[u8], n: usize) -> u16 {
+	u16::from_be_bytes([data[n], data[n + 1]])
+}
+
+fn u32_at(data: &[u8], n: usize) -> u32 {
+	u32::from_be_bytes([data[n], data[n + 1], data[n + 2], data[n + 3]])
+}
+
+/// The `acTL` chunk: how many frames the animation has and how often it plays.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AnimationControl {
+	pub num_frames: u32,
+	/// Zero means the animation loops forever.
+	pub num_plays: u32,
+}
+
+impl AnimationControl {
+	pub const TYPE: ChunkType = ChunkType::acTL;
+
+	pub fn loops_forever(&self) -> bool {
+		self.num_plays == 0
+	}
+
+	/// Encodes the animation control as a raw [`Chunk`].
+	pub fn to_chunk(&self) -> Chunk {
+		let mut data = self.num_frames.to_be_bytes().to_vec();
+		data.extend_from_slice(&self.num_plays.to_be_bytes());
+		Chunk::new(Self::TYPE, data)
+	}
+}
+
+impl TryFrom<&Chunk> for AnimationControl {
+	type Error = crate::Error;
+	fn try_from(chunk: &Chunk) -> Result<Self> {
+		let data = data_of(chunk, Self::TYPE, 8)?;
+		let control = Self { num_frames: u32_at(data, 0), num_plays: u32_at(data, 4) };
+		if control.num_frames == 0 {
+			return Err(invalid(Self::TYPE, "an animation needs at least one frame"));
+		}
+		Ok(control)
+	}
+}
+
+impl From<AnimationControl> for Chunk {
+	fn from(control: AnimationControl) -> Self {
+		control.to_chunk()
+	}
+}
+
+impl fmt::Display for AnimationControl {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let s = if self.num_frames == 1 { "" } else { "s" };
+		write!(f, "{} frame{}, ", self.num_frames, s)?;
+		match self.num_plays {
+			0 => f.write_str("loops forever"),
+			1 => f.write_str("plays once"),
+			n => write!(f, "plays {} times", n),
+		}
+	}
+}
+
+/// What happens to the frame region once the frame has been shown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DisposeOp {
+	/// The region is left as it is.
+	None = 0,
+	/// The region is cleared to fully transparent black.
+	Background = 1,
+	/// The region is reverted to what it was before the frame.
+	Previous = 2,
+}
+
+/// How the frame is combined with the output buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlendOp {
+	/// The frame replaces the region, alpha included.
+	Source = 0,
+	/// The frame is alpha-composited over the region.
+	Over = 1,
+}
+
+/// The `fcTL` chunk: size, position, timing, and compositing of one frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameControl {
+	pub sequence_number: u32,
+	pub width: u32,
+	pub height: u32,
+	pub x_offset: u32,
+	pub y_offset: u32,
+	pub delay_num: u16,
+	/// Zero stands for 100, making the delay a number of hundredths of a second.
+	pub delay_den: u16,
+	pub dispose_op: DisposeOp,
+	pub blend_op: BlendOp,
+}
+
+impl FrameControl {
+	pub const TYPE: ChunkType = ChunkType::fcTL;
+
+	/// How long the frame is shown.
+	pub fn delay(&self) -> Duration {
+		let den = if self.delay_den == 0 { 100 } else { self.delay_den };
+		Duration::from_secs_f64(self.delay_num as f64 / den as f64)
+	}
+
+	/// Encodes the frame control as a raw [`Chunk`].
+	pub fn to_chunk(&self) -> Chunk {
+		let mut data = Vec::with_capacity(26);
+		for n in [self.sequence_number, self.width, self.height, self.x_offset, self.y_offset] {
+			data.extend_from_slice(&n.to_be_bytes());
+		}
+		data.extend_from_slice(&self.delay_num.to_be_bytes());
+		data.extend_from_slice(&self.delay_den.to_be_bytes());
+		data.extend_from_slice(&[self.dispose_op as u8, self.blend_op as u8]);
+		Chunk::new(Self::TYPE, data)
+	}
+}
+
+impl TryFrom<&Chunk> for FrameControl {
+	type Error = crate::Error;
+	fn try_from(chunk: &Chunk) -> Result<Self> {
+		let data = data_of(chunk, Self::TYPE, 26)?;
+		let dispose_op = match data[24] {
+			0 => DisposeOp::None,
+			1 => DisposeOp::Background,
+			2 => DisposeOp::Previous,
+			op => return Err(invalid(Self::TYPE, format!("unknown dispose op {}", op))),
+		};
+		let blend_op = match data[25] {
+			0 => BlendOp::Source,
+			1 => BlendOp::Over,
+			op => return Err(invalid(Self::TYPE, format!("unknown blend op {}", op))),
+		};
+		let frame = Self {
+			sequence_number: u32_at(data, 0),
+			width: u32_at(data, 4),
+			height: u32_at(data, 8),
+			x_offset: u32_at(data, 12),
+			y_offset: u32_at(data, 16),
+			delay_num: u16_at(data, 20),
+			delay_den: u16_at(data, 22),
+			dispose_op,
+			blend_op,
+		};
+		if frame.width == 0 || frame.height == 0 {
+			return Err(invalid(Self::TYPE, "frame dimensions must be positive"));
+		}
+		Ok(frame)
+	}
+}
+
+impl From<FrameControl> for Chunk {
+	fn from(frame: FrameControl) -> Self {
+		frame.to_chunk()
+	}
+}
+
+impl fmt::Display for FrameControl {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(
+			f,
+			"frame {}x{} at ({}, {}), {} ms",
+			self.width,
+			self.height,
+			self.x_offset,
+			self.y_offset,
+			self.delay().as_millis()
+		)
+	}
+}
+
+/// The `fdAT` chunk: a piece of frame image data, laid out like `IDAT` data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrameData {
+	pub sequence_number: u32,
+	pub data: Vec<u8>,
+}
+
+impl FrameData {
+	pub const TYPE: ChunkType = ChunkType::fdAT;
+
+	/// Encodes the frame data as a raw [`Chunk`].
+	pub fn to_chunk(&self) -> Chunk {
+		let mut data = self.sequence_number.to_be_bytes().to_vec();
+		data.extend_from_slice(&self.data);
+		Chunk::new(Self::TYPE, data)
+	}
+}
+
+impl TryFrom<&Chunk> for FrameData {
+	type Error = crate::Error;
+	fn try_from(chunk: &Chunk) -> Result<Self> {
+		let data = data_of(chunk, Self::TYPE, 4)?;
+		Ok(Self { sequence_number: u32_at(data, 0), data: data[4..].to_vec() })
+	}
+}
+
+impl From<FrameData> for Chunk {
+	fn from(frame_data: FrameData) -> Self {
+		frame_data.to_chunk()
+	}
+}
+
+/// The animation of an APNG.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Animation {
+	pub control: AnimationControl,
+	/// The frames, in the order they are shown.
+	pub frames: Vec<FrameControl>,
+	/// Whether the default image, stored in `IDAT`, is the first frame.
+	pub default_image_is_first_frame: bool,
+}
+
+impl Animation {
+	/// Reads the animation chunks of `png`, or returns `None` if it is not an APNG.
+	pub fn of(png: &Png) -> Result<Option<Self>> {
+		let Some(chunk) = png.chunk_by_type("acTL") else {
+			return Ok(None);
+		};
+		let control = AnimationControl::try_from(chunk)?;
+		let chunks = png.chunks();
+		let frames = chunks
+			.iter()
+			.filter(|chunk| chunk.chunk_type() == &FrameControl::TYPE)
+			.map(FrameControl::try_from)
+			.collect::<Result<Vec<_>>>()?;
+		let first_fctl = chunks.iter().position(|c| c.chunk_type() == &FrameControl::TYPE);
+		let first_idat = chunks.iter().position(|c| c.chunk_type() == &ChunkType::IDAT);
+		let default_image_is_first_frame = match (first_fctl, first_idat) {
+			(Some(fctl), Some(idat)) => fctl < idat,
+			_ => false,
+		};
+		Ok(Some(Self { control, frames, default_image_is_first_frame }))
+	}
+
+	/// The time one play of the animation takes.
+	pub fn duration(&self) -> Duration {
+		self.frames.iter().map(FrameControl::delay).sum()
+	}
+}
+
+impl fmt::Display for Animation {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "APNG, {}", self.control)
+	}
+}
+
+fn invalid_animation(reason: impl Into<String>) -> crate::Error {
+	PngmeError::invalid("animation", reason)
+}
+
+/// Splits an APNG into one PNG per frame. Each frame gets an `IHDR` with the frame size, an
+/// `oFFs` chunk with its offset if that is not zero, its image data as `IDAT`, and copies of
+/// the ancillary chunks of the animation.
+pub fn split(png: &Png) -> Result<Vec<Png>> {
+	let animation = Animation::of(png)?.ok_or_else(|| invalid_animation("not an APNG"))?;
+	let header = png.header()?;
+	let first_idat = png
+		.chunks()
+		.iter()
+		.position(|chunk| chunk.chunk_type() == &ChunkType::IDAT)
+		.ok_or_else(|| invalid_animation("missing IDAT chunk"))?;
+
+	let mut frames: Vec<(FrameControl, Vec<Vec<u8>>)> = Vec::new();
+	let mut sequence_number = 0;
+	let mut check_sequence = |n: u32| {
+		if n != sequence_number {
+			let reason = format!("expected sequence number {}, got {}", sequence_number, n);
+			return Err(invalid_animation(reason));
+		}
+		sequence_number += 1;
+		Ok(())
+	};
+	for chunk in png.chunks() {
+		match &chunk.chunk_type().bytes() {
+			b"fcTL" => {
+				let frame = FrameControl::try_from(chunk)?;
+				check_sequence(frame.sequence_number)?;
+				frames.push((frame, Vec::new()));
+			}
+			b"fdAT" => {
+				let frame_data = FrameData::try_from(chunk)?;
+				check_sequence(frame_data.sequence_number)?;
+				let (_, data) = frames
+					.last_mut()
+					.ok_or_else(|| invalid_animation("fdAT chunk before any fcTL"))?;
+				data.push(frame_data.data);
+			}
+			b"IDAT" => {
+				if let Some((_, data)) = frames.last_mut() {
+					data.push(chunk.data().to_vec());
+				}
+			}
+			_ => {}
+		}
+	}
+	if frames.len() != animation.control.num_frames as usize {
+		let reason = format!(
+			"acTL announces {} frames, found {}",
+			animation.control.num_frames,
+			frames.len()
+		);
+		return Err(invalid_animation(reason));
+	}
+
+	frames
+		.into_iter()
+		.map(|(frame, data)| {
+			if frame.x_offset.saturating_add(frame.width) > header.width
+				|| frame.y_offset.saturating_add(frame.height) > header.height
+			{
+				return Err(invalid_animation(format!(
+					"frame {} does not fit in the image",
+					frame.sequence_number
+				)));
+			}
+			if data.is_empty() {
+				let reason = format!("frame {} has no image data", frame.sequence_number);
+				return Err(invalid_animation(reason));
+			}
+			frame_png(png, &header, first_idat, &frame, data)
+		})
+		.collect()
+}
+
+/// Builds the standalone PNG for one frame, given its image data.
+fn frame_png(
+	png: &Png,
+	header: &IhdrData,
+	first_idat: usize,
+	frame: &FrameControl,
+	data: Vec<Vec<u8>>,
+) -> Result<Png> {
+	let copy = |chunk: &Chunk| -> Result<Chunk> {
+		Ok(Chunk::new(chunk.chunk_type().bytes().try_into()?, chunk.data().to_vec()))
+	};
+	let is_shared = |chunk: &&Chunk| {
+		!matches!(
+			&chunk.chunk_type().bytes(),
+			b"IHDR" | b"IDAT" | b"IEND" | b"acTL" | b"fcTL" | b"fdAT" | b"oFFs"
+		)
+	};
+	let mut chunks =
+		vec![IhdrData { width: frame.width, height: frame.height, ..*header }.to_chunk()];
+	for chunk in png.chunks()[..first_idat].iter().filter(is_shared) {
+		chunks.push(copy(chunk)?);
+	}
+	// The frame offset is relative to the image, which may itself be offset in pixels.
+	let (mut x, mut y) = (frame.x_offset as i64, frame.y_offset as i64);
+	match png.chunk_by_type("oFFs").map(Chunk::data) {
+		Some(data) if data.len() == 9 && data[8] == 0 => {
+			x += i32::from_be_bytes(data[0..4].try_into()?) as i64;
+			y += i32::from_be_bytes(data[4..8].try_into()?) as i64;
+		}
+		Some(_) => return Err(invalid_animation("oFFs chunk is not in pixels")),
+		None => {}
+	}
+	if (x, y) != (0, 0) {
+		let mut offsets = i32::try_from(x)?.to_be_bytes().to_vec();
+		offsets.extend_from_slice(&i32::try_from(y)?.to_be_bytes());
+		// The unit is pixels.
+		offsets.push(0);
+		chunks.push(Chunk::new(ChunkType::oFFs, offsets));
+	}
+	chunks.extend(data.into_iter().map(|data| Chunk::new(ChunkType::IDAT, data)));
+	for chunk in png.chunks()[first_idat..].iter().filter(is_shared) {
+		chunks.push(copy(chunk)?);
+	}
+	chunks.push(Chunk::new(ChunkType::IEND, Vec::new()));
+	Ok(Png::from_chunks(chunks))
+}
+
+/// Packs PNGs of equal dimensions and pixel format into an APNG whose default image is the
+/// first frame. Every frame covers the whole image and is shown for `delay`; zero `num_plays`
+/// loops forever. Ancillary chunks are taken from the first frame.
+pub fn build(frames: &[Png], delay: Duration, num_plays: u32) -> Result<Png> {
+	let (first, rest) = frames.split_first().ok_or_else(|| invalid_animation("no frames"))?;
+	let header = first.header()?;
+	let palette = first.chunk_by_type("PLTE").map(Chunk::data);
+	for (n, frame) in frames.iter().enumerate() {
+		if frame.is_apng() {
+			return Err(invalid_animation(format!("frame {} is already animated", n + 1)));
+		}
+		if frame.header()? != header {
+			let reason = format!("frame {} differs from the first in size or pixel format", n + 1);
+			return Err(invalid_animation(reason));
+		}
+		if frame.chunk_by_type("PLTE").map(Chunk::data) != palette {
+			return Err(invalid_animation(format!("frame {} has a different palette", n + 1)));
+		}
+	}
+	let (delay_num, delay_den) = delay_fraction(delay)?;
+	let full_frame = |sequence_number| FrameControl {
+		sequence_number,
+		width: header.width,
+		height: header.height,
+		x_offset: 0,
+		y_offset: 0,
+		delay_num,
+		delay_den,
+		dispose_op: DisposeOp::None,
+		blend_op: BlendOp::Source,
+	};
+	let mut next_sequence_number = 0..;
+
+	let copy = |chunk: &Chunk| -> Result<Chunk> {
+		Ok(Chunk::new(chunk.chunk_type().bytes().try_into()?, chunk.data().to_vec()))
+	};
+	let mut chunks = Vec::new();
+	let first_idat = first
+		.chunks()
+		.iter()
+		.position(|chunk| chunk.chunk_type() == &ChunkType::IDAT)
+		.ok_or_else(|| invalid_animation("frame 1 has no IDAT chunk"))?;
+	for chunk in &first.chunks()[..first_idat] {
+		chunks.push(copy(chunk)?);
+	}
+	let num_frames = u32::try_from(frames.len())?;
+	chunks.push(AnimationControl { num_frames, num_plays }.to_chunk());
+	chunks.push(full_frame(next_sequence_number.next().unwrap()).to_chunk());
+	let mut tail = Vec::new();
+	for chunk in &first.chunks()[first_idat..] {
+		match &chunk.chunk_type().bytes() {
+			b"IDAT" => chunks.push(copy(chunk)?),
+			b"IEND" => {}
+			_ => tail.push(copy(chunk)?),
+		}
+	}
+	for frame in rest {
+		chunks.push(full_frame(next_sequence_number.next().unwrap()).to_chunk());
+		for chunk in frame.chunks().iter().filter(|chunk| chunk.chunk_type() == &ChunkType::IDAT) {
+			let sequence_number = next_sequence_number.next().unwrap();
+			chunks.push(FrameData { sequence_number, data: chunk.data().to_vec() }.to_chunk());
+		}
+	}
+	chunks.extend(tail);
+	chunks.push(Chunk::new(ChunkType::IEND, Vec::new()));
+	Ok(Png::from_chunks(chunks))
+}
+
+/// Expresses `delay` as the fraction of a second stored in `fcTL`, in the finest unit that fits.
+fn delay_fraction(delay: Duration) -> Result<(u16, u16)> {
+	let millis = delay.as_millis();
+	let fraction = if let Ok(num) = u16::try_from(millis) {
+		(num, 1000)
+	} else if let Ok(num) = u16::try_from(millis / 10) {
+		(num, 100)
+	} else {
+		(u16::try_from(delay.as_secs()).map_err(|_| invalid_animation("delay is too long"))?, 1)
+	};
+	Ok(fraction)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn frame(sequence_number: u32, delay_num: u16, delay_den: u16) -> FrameControl {
+		FrameControl {
+			sequence_number,
+			width: 4,
+			height: 3,
+			x_offset: 0,
+			y_offset: 0,
+			delay_num,
+			delay_den,
+			dispose_op: DisposeOp::Background,
+			blend_op: BlendOp::Over,
+		}
+	}
+
+	#[test]
+	fn test_round_trips() {
+		let control = AnimationControl { num_frames: 24, num_plays: 0 };
+		assert_eq!(AnimationControl::try_from(&control.to_chunk()).unwrap(), control);
+		let frame = frame(3, 1, 10);
+		assert_eq!(frame.to_chunk().data().len(), 26);
+		assert_eq!(FrameControl::try_from(&frame.to_chunk()).unwrap(), frame);
+		let frame_data = FrameData { sequence_number: 4, data: b"zlib".to_vec() };
+		assert_eq!(FrameData::try_from(&frame_data.to_chunk()).unwrap(), frame_data);
+	}
+
+	#[test]
+	fn test_invalid_chunks() {
+		let chunk = |chunk_type: ChunkType, data: &[u8]| Chunk::new(chunk_type, data.to_vec());
+		assert!(AnimationControl::try_from(&chunk(ChunkType::acTL, &[0; 8])).is_err());
+		assert!(AnimationControl::try_from(&chunk(ChunkType::acTL, &[0; 4])).is_err());
+		let mut data = frame(0, 1, 10).to_chunk().data().to_vec();
+		data[24] = 3;
+		assert!(FrameControl::try_from(&chunk(ChunkType::fcTL, &data)).is_err());
+		assert!(FrameData::try_from(&chunk(ChunkType::fdAT, &[0; 3])).is_err());
+	}
+
+	#[test]
+	fn test_delay() {
+		assert_eq!(frame(0, 1, 10).delay(), Duration::from_millis(100));
+		assert_eq!(frame(0, 5, 0).delay(), Duration::from_millis(50));
+	}
+
+	#[test]
+	fn test_split() {
+		let empty = |chunk_type: ChunkType| Chunk::new(chunk_type, Vec::new());
+		let header = IhdrData {
+			width: 4,
+			height: 3,
+			bit_depth: 8,
+			color_type: crate::ihdr::ColorType::Rgba,
+			compression_method: 0,
+			filter_method: 0,
+			interlace_method: 0,
+		};
+		let mut second = frame(1, 1, 10);
+		(second.width, second.height, second.x_offset, second.y_offset) = (2, 2, 1, 1);
+		let chunks = vec![
+			header.to_chunk(),
+			AnimationControl { num_frames: 2, num_plays: 0 }.to_chunk(),
+			Chunk::new(ChunkType::gAMA, 45455u32.to_be_bytes().to_vec()),
+			frame(0, 1, 10).to_chunk(),
+			Chunk::new(ChunkType::IDAT, b"first".to_vec()),
+			second.to_chunk(),
+			FrameData { sequence_number: 2, data: b"sec".to_vec() }.to_chunk(),
+			FrameData { sequence_number: 3, data: b"ond".to_vec() }.to_chunk(),
+			empty(ChunkType::IEND),
+		];
+		let frames = split(&Png::from_chunks(chunks)).unwrap();
+		assert_eq!(frames.len(), 2);
+
+		let types = |png: &Png| -> Vec<String> {
+			png.chunks().iter().map(|chunk| chunk.chunk_type().to_string()).collect()
+		};
+		assert_eq!(types(&frames[0]), ["IHDR", "gAMA", "IDAT", "IEND"]);
+		assert_eq!(types(&frames[1]), ["IHDR", "gAMA", "oFFs", "IDAT", "IDAT", "IEND"]);
+		assert_eq!(frames[0].header().unwrap(), header);
+		let second_header = frames[1].header().unwrap();
+		assert_eq!((second_header.width, second_header.height), (2, 2));
+		assert_eq!(frames[1].chunk_by_type("oFFs").unwrap().data(), [0, 0, 0, 1, 0, 0, 0, 1, 0]);
+		assert_eq!(frames[1].chunk_by_type("IDAT").unwrap().data(), b"sec");
+	}
+
+	#[test]
+	fn test_split_bad_sequence() {
+		let chunks = vec![
+			Chunk::new(ChunkType::IHDR, vec![0, 0, 0, 4, 0, 0, 0, 3, 8, 6, 0, 0, 0]),
+			AnimationControl { num_frames: 1, num_plays: 0 }.to_chunk(),
+			frame(1, 1, 10).to_chunk(),
+			Chunk::new(ChunkType::IDAT, Vec::new()),
+			Chunk::new(ChunkType::IEND, Vec::new()),
+		];
+		assert!(split(&Png::from_chunks(chunks)).is_err());
+	}
+
+	#[test]
+	fn test_build_and_split() {
+		let still = |data: &[u8]| {
+			Png::from_chunks(vec![
+				Chunk::new(ChunkType::IHDR, vec![0, 0, 0, 4, 0, 0, 0, 3, 8, 6, 0, 0, 0]),
+				Chunk::new(ChunkType::tEXt, b"Author\0me".to_vec()),
+				Chunk::new(ChunkType::IDAT, data.to_vec()),
+				Chunk::new(ChunkType::IEND, Vec::new()),
+			])
+		};
+		let frames = [still(b"one"), still(b"two"), still(b"three")];
+		let png = build(&frames, Duration::from_millis(100), 0).unwrap();
+		let animation = Animation::of(&png).unwrap().unwrap();
+		assert_eq!(animation.to_string(), "APNG, 3 frames, loops forever");
+		assert!(animation.default_image_is_first_frame);
+		assert!(animation.frames.iter().all(|frame| frame.delay() == Duration::from_millis(100)));
+
+		let split = split(&png).unwrap();
+		assert_eq!(split.len(), 3);
+		for (frame, data) in split.iter().zip([b"one".as_slice(), b"two", b"three"]) {
+			assert_eq!(frame.chunk_by_type("IDAT").unwrap().data(), data);
+			assert!(frame.chunk_by_type("tEXt").is_some());
+		}
+	}
+
+	#[test]
+	fn test_build_mismatched_frames() {
+		let still = |width: u8| {
+			Png::from_chunks(vec![
+				Chunk::new(ChunkType::IHDR, vec![0, 0, 0, width, 0, 0, 0, 3, 8, 6, 0, 0, 0]),
+				Chunk::new(ChunkType::IDAT, Vec::new()),
+				Chunk::new(ChunkType::IEND, Vec::new()),
+			])
+		};
+		assert!(build(&[still(4), still(5)], Duration::from_millis(100), 0).is_err());
+		assert!(build(&[], Duration::from_millis(100), 0).is_err());
+	}
+
+	#[test]
+	fn test_delay_fraction() {
+		assert_eq!(delay_fraction(Duration::from_millis(100)).unwrap(), (100, 1000));
+		assert_eq!(delay_fraction(Duration::from_secs(70)).unwrap(), (7000, 100));
+		assert_eq!(delay_fraction(Duration::from_secs(1000)).unwrap(), (1000, 1));
+	}
+
+	#[test]
+	fn test_animation() {
+		let empty = |chunk_type: ChunkType| Chunk::new(chunk_type, Vec::new());
+		let mut png = Png::from_chunks(vec![empty(ChunkType::IHDR), empty(ChunkType::IEND)]);
+		assert!(!png.is_apng());
+		assert_eq!(Animation::of(&png).unwrap(), None);
+
+		png.insert_chunk(AnimationControl { num_frames: 2, num_plays: 0 }.to_chunk());
+		png.insert_chunk(frame(0, 1, 10).to_chunk());
+		png.insert_chunk(empty(ChunkType::IDAT));
+		png.insert_chunk(frame(1, 1, 5).to_chunk());
+		png.insert_chunk(FrameData { sequence_number: 2, data: Vec::new() }.to_chunk());
+		assert!(png.is_apng());
+		let animation = Animation::of(&png).unwrap().unwrap();
+		assert_eq!(animation.to_string(), "APNG, 2 frames, loops forever");
+		assert_eq!(animation.frames.len(), 2);
+		assert!(animation.default_image_is_first_frame);
+		assert_eq!(animation.duration(), Duration::from_millis(300));
+	}
+}
@@ -0,0 +1,76 @@
+//! Payloads stashed after the `IEND` chunk, leaving the chunk list untouched.
+//!
+//! Each payload is framed as `MAGIC | tag (4 bytes) | length (u32) | data`, and frames follow
+//! one another in the PNG's trailer. The tag is a chunk type, so payloads are addressed the
+//! same way as chunk-embedded ones.
+
+use std::mem;
+
+use crate::{chunk_type::ChunkType, PngmeError, Result};
+
+const MAGIC: &[u8; 4] = b"PMEA";
+const HEADER_LEN: usize = MAGIC.len() + mem::size_of::<[u32; 2]>();
+
+/// The largest payload a single frame can hold.
+pub const MAX_PAYLOAD_LEN: usize = u32::MAX as usize;
+
+/// Frames `payload` under `tag`, ready to be appended to a trailer.
+pub fn frame(tag: &ChunkType, payload: &[u8]) -> Result<Vec<u8>> {
+	let mut frame = Vec::with_capacity(HEADER_LEN + payload.len());
+	frame.extend_from_slice(MAGIC);
+	frame.extend_from_slice(&tag.bytes());
+	frame.extend_from_slice(&u32::try_from(payload.len())?.to_be_bytes());
+	frame.extend_from_slice(payload);
+	Ok(frame)
+}
+
+/// Splits a trailer into its framed payloads, failing if it holds anything else.
+pub fn frames(trailer: &[u8]) -> Result<Vec<(ChunkType, &[u8])>> {
+	let mut frames = Vec::new();
+	let mut offset = 0;
+	while offset < trailer.len() {
+		let rest = &trailer[offset..];
+		if rest.len() < HEADER_LEN || !rest.starts_with(MAGIC) {
+			return Err(PngmeError::InvalidTrailer { offset });
+		}
+		let tag = ChunkType::try_from(<[u8; 4]>::try_from(&rest[4..8])?)?;
+		let len = u32::from_be_bytes(rest[8..12].try_into()?) as usize;
+		let Some(payload) = rest[HEADER_LEN..].get(..len) else {
+			return Err(PngmeError::InvalidTrailer { offset });
+		};
+		frames.push((tag, payload));
+		offset += HEADER_LEN + len;
+	}
+	Ok(frames)
+}
+
+/// Returns the first payload framed under `tag`, if any.
+pub fn find<'a>(trailer: &'a [u8], tag: &ChunkType) -> Result<Option<&'a [u8]>> {
+	Ok(frames(trailer)?.into_iter().find(|(t, _)| t == tag).map(|(_, payload)| payload))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_round_trip() {
+		let (a, b) = ("ruSt".parse().unwrap(), "teSt".parse().unwrap());
+		let mut trailer = frame(&a, b"first").unwrap();
+		trailer.extend(frame(&b, b"second").unwrap());
+		trailer.extend(frame(&a, b"third").unwrap());
+
+		assert_eq!(frames(&trailer).unwrap().len(), 3);
+		assert_eq!(find(&trailer, &a).unwrap(), Some(b"first".as_slice()));
+		assert_eq!(find(&trailer, &b).unwrap(), Some(b"second".as_slice()));
+		assert_eq!(find(&trailer, &"noNe".parse().unwrap()).unwrap(), None);
+		assert!(frames(&[]).unwrap().is_empty());
+	}
+
+	#[test]
+	fn test_invalid_trailer() {
+		assert!(frames(b"not a frame at all").is_err());
+		let frame = frame(&"ruSt".parse().unwrap(), b"payload").unwrap();
+		assert!(frames(&frame[..frame.len() - 1]).is_err());
+	}
+}
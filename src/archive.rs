@@ -0,0 +1,266 @@
+//! Directory archives, for storing a whole directory as one payload.
+//!
+//! An archive is `MAGIC` followed by its entries, each laid out as
+//! `kind (1 byte) | mode (u32) | path length (u32) | path | data length (u64) | data`, with
+//! integers stored big-endian. Paths are relative and `/`-separated UTF-8 without `.` or `..`
+//! components, and a directory comes before everything in it. The mode holds the Unix
+//! permission bits.
+
+use std::{
+	fs, io, mem,
+	path::{Path, PathBuf},
+};
+
+use crate::{chunk_type::ChunkType, PngmeError, Result};
+
+const MAGIC: &[u8; 4] = b"PMAR";
+
+/// The chunk type archives are stored in unless another one is given.
+pub const CHUNK_TYPE: ChunkType = ChunkType::pmAr;
+
+fn invalid(reason: impl Into<String>) -> crate::Error {
+	PngmeError::invalid("archive", reason)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryKind {
+	File = 0,
+	Directory = 1,
+}
+
+/// A file or directory in an archive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+	/// The path relative to the archived directory, with `/` separators.
+	pub path: String,
+	pub kind: EntryKind,
+	/// The Unix permission bits.
+	pub mode: u32,
+	/// The contents of a file; empty for a directory.
+	pub data: Vec<u8>,
+}
+
+/// Returns `true` if `data` starts like an archive.
+pub fn is_archive(data: &[u8]) -> bool {
+	data.starts_with(MAGIC)
+}
+
+/// Reads every file and directory under `dir`, in name order. Returns the entries and the paths
+/// that were left out because they are neither files nor directories, such as symlinks.
+pub fn read_dir(dir: &Path) -> Result<(Vec<Entry>, Vec<PathBuf>)> {
+	let mut entries = Vec::new();
+	let mut skipped = Vec::new();
+	read_dir_into(dir, "", &mut entries, &mut skipped)?;
+	Ok((entries, skipped))
+}
+
+fn read_dir_into(
+	dir: &Path,
+	prefix: &str,
+	entries: &mut Vec<Entry>,
+	skipped: &mut Vec<PathBuf>,
+) -> Result<()> {
+	let mut children: Vec<_> = fs::read_dir(dir)?.collect::<io::Result<_>>()?;
+	children.sort_by_key(|child| child.file_name());
+	for child in children {
+		let name = child.file_name();
+		let Some(name) = name.to_str() else {
+			return Err(invalid(format!("{} is not a UTF-8 path", child.path().display())));
+		};
+		let path = format!("{}{}", prefix, name);
+		let file_type = child.file_type()?;
+		let metadata = child.metadata()?;
+		if file_type.is_dir() {
+			entries.push(Entry {
+				path: path.clone(),
+				kind: EntryKind::Directory,
+				mode: mode(&metadata),
+				data: Vec::new(),
+			});
+			read_dir_into(&child.path(), &format!("{}/", path), entries, skipped)?;
+		} else if file_type.is_file() {
+			let data = fs::read(child.path())?;
+			entries.push(Entry { path, kind: EntryKind::File, mode: mode(&metadata), data });
+		} else {
+			skipped.push(child.path());
+		}
+	}
+	Ok(())
+}
+
+#[cfg(unix)]
+fn mode(metadata: &fs::Metadata) -> u32 {
+	use std::os::unix::fs::PermissionsExt;
+	metadata.permissions().mode() & 0o7777
+}
+
+#[cfg(not(unix))]
+fn mode(metadata: &fs::Metadata) -> u32 {
+	match (metadata.is_dir(), metadata.permissions().readonly()) {
+		(true, false) => 0o755,
+		(true, true) => 0o555,
+		(false, false) => 0o644,
+		(false, true) => 0o444,
+	}
+}
+
+/// Encodes entries as an archive.
+pub fn to_bytes(entries: &[Entry]) -> Result<Vec<u8>> {
+	let mut bytes = MAGIC.to_vec();
+	for entry in entries {
+		check_path(&entry.path)?;
+		bytes.push(entry.kind as u8);
+		bytes.extend_from_slice(&entry.mode.to_be_bytes());
+		bytes.extend_from_slice(&u32::try_from(entry.path.len())?.to_be_bytes());
+		bytes.extend_from_slice(entry.path.as_bytes());
+		bytes.extend_from_slice(&(entry.data.len() as u64).to_be_bytes());
+		bytes.extend_from_slice(&entry.data);
+	}
+	Ok(bytes)
+}
+
+/// Decodes an archive, checking that every path stays inside the directory it is extracted to.
+pub fn parse(archive: &[u8]) -> Result<Vec<Entry>> {
+	let mut rest = archive.strip_prefix(MAGIC).ok_or_else(|| invalid("missing the header"))?;
+	let mut entries = Vec::new();
+	while !rest.is_empty() {
+		let kind = match take(&mut rest, 1)?[0] {
+			0 => EntryKind::File,
+			1 => EntryKind::Directory,
+			kind => return Err(invalid(format!("unknown entry kind {}", kind))),
+		};
+		let mode = u32::from_be_bytes(take(&mut rest, mem::size_of::<u32>())?.try_into()?);
+		let path_len = u32::from_be_bytes(take(&mut rest, mem::size_of::<u32>())?.try_into()?);
+		let path = String::from_utf8(take(&mut rest, path_len as usize)?.to_vec())?;
+		check_path(&path)?;
+		let data_len = u64::from_be_bytes(take(&mut rest, mem::size_of::<u64>())?.try_into()?);
+		let data = take(&mut rest, usize::try_from(data_len)?)?.to_vec();
+		if kind == EntryKind::Directory && !data.is_empty() {
+			return Err(invalid(format!("directory {} has contents", path)));
+		}
+		entries.push(Entry { path, kind, mode, data });
+	}
+	Ok(entries)
+}
+
+/// Splits the next `len` bytes off `rest`.
+fn take<'a>(rest: &mut &'a [u8], len: usize) -> Result<&'a [u8]> {
+	if rest.len() < len {
+		return Err(invalid("cut off inside an entry"));
+	}
+	let (taken, after) = rest.split_at(len);
+	*rest = after;
+	Ok(taken)
+}
+
+/// Rejects paths that are absolute or could escape the directory things are extracted to.
+fn check_path(path: &str) -> Result<()> {
+	let bad = path.split('/').any(|component| {
+		matches!(component, "" | "." | "..") || component.contains(['\\', '\0', ':'])
+	});
+	if bad {
+		return Err(invalid(format!("unsafe path {:?}", path)));
+	}
+	Ok(())
+}
+
+/// Writes the entries under `dir`, creating it if needed and replacing files that exist.
+/// Directory permissions are set last, so that read-only directories can still be filled.
+pub fn extract(entries: &[Entry], dir: &Path) -> Result<()> {
+	fs::create_dir_all(dir)?;
+	for entry in entries {
+		let path = entry_path(dir, entry);
+		match entry.kind {
+			EntryKind::Directory => fs::create_dir_all(&path)?,
+			EntryKind::File => {
+				if let Some(parent) = path.parent() {
+					fs::create_dir_all(parent)?;
+				}
+				fs::write(&path, &entry.data)?;
+				set_mode(&path, entry.mode)?;
+			}
+		}
+	}
+	for entry in entries.iter().rev().filter(|entry| entry.kind == EntryKind::Directory) {
+		set_mode(&entry_path(dir, entry), entry.mode)?;
+	}
+	Ok(())
+}
+
+/// Where an entry goes when extracted under `dir`.
+pub fn entry_path(dir: &Path, entry: &Entry) -> PathBuf {
+	entry.path.split('/').fold(dir.to_owned(), |path, component| path.join(component))
+}
+
+#[cfg(unix)]
+fn set_mode(path: &Path, mode: u32) -> Result<()> {
+	use std::os::unix::fs::PermissionsExt;
+	Ok(fs::set_permissions(path, fs::Permissions::from_mode(mode))?)
+}
+
+#[cfg(not(unix))]
+fn set_mode(path: &Path, mode: u32) -> Result<()> {
+	let mut permissions = fs::metadata(path)?.permissions();
+	permissions.set_readonly(mode & 0o200 == 0);
+	Ok(fs::set_permissions(path, permissions)?)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn entry(path: &str, kind: EntryKind, data: &[u8]) -> Entry {
+		let mode = if kind == EntryKind::Directory { 0o755 } else { 0o640 };
+		Entry { path: path.to_owned(), kind, mode, data: data.to_vec() }
+	}
+
+	#[test]
+	fn test_round_trip() {
+		let entries = vec![
+			entry("a.txt", EntryKind::File, b"hello"),
+			entry("sub", EntryKind::Directory, b""),
+			entry("sub/b.bin", EntryKind::File, &[0, 1, 2]),
+			entry("sub/empty", EntryKind::File, b""),
+		];
+		let bytes = to_bytes(&entries).unwrap();
+		assert!(is_archive(&bytes));
+		assert_eq!(parse(&bytes).unwrap(), entries);
+		assert_eq!(parse(MAGIC).unwrap(), []);
+		assert!(parse(&bytes[..bytes.len() - 1]).is_err());
+	}
+
+	#[test]
+	fn test_unsafe_paths() {
+		for path in ["../up", "/etc/passwd", "a//b", "a/./b", "C:x", "a\\..\\b", ""] {
+			assert!(to_bytes(&[entry(path, EntryKind::File, b"")]).is_err(), "{}", path);
+			let mut bytes = to_bytes(&[entry("x", EntryKind::File, b"")]).unwrap();
+			// Swap in the unsafe path by hand, since to_bytes refuses it.
+			bytes.truncate(MAGIC.len() + 5);
+			bytes.extend_from_slice(&(path.len() as u32).to_be_bytes());
+			bytes.extend_from_slice(path.as_bytes());
+			bytes.extend_from_slice(&0u64.to_be_bytes());
+			assert!(parse(&bytes).is_err(), "{}", path);
+		}
+	}
+
+	#[test]
+	fn test_read_and_extract() {
+		let root = std::env::temp_dir().join(format!("pngme-archive-{}", std::process::id()));
+		let _ = fs::remove_dir_all(&root);
+		let source = root.join("source");
+		fs::create_dir_all(source.join("sub")).unwrap();
+		fs::write(source.join("b.txt"), "bee").unwrap();
+		fs::write(source.join("sub").join("a.txt"), "ay").unwrap();
+
+		let (entries, skipped) = read_dir(&source).unwrap();
+		assert!(skipped.is_empty());
+		let paths: Vec<&str> = entries.iter().map(|entry| entry.path.as_str()).collect();
+		assert_eq!(paths, ["b.txt", "sub", "sub/a.txt"]);
+
+		let target = root.join("target");
+		extract(&parse(&to_bytes(&entries).unwrap()).unwrap(), &target).unwrap();
+		assert_eq!(fs::read_to_string(target.join("sub").join("a.txt")).unwrap(), "ay");
+		assert_eq!(read_dir(&target).unwrap().0, entries);
+		fs::remove_dir_all(&root).unwrap();
+	}
+}
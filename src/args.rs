@@ -0,0 +1,1421 @@
+use std::{
+	ffi::{OsStr, OsString},
+	mem,
+	num::ParseIntError,
+	path::PathBuf,
+	process,
+	time::Duration,
+};
+
+use clap::{
+	builder::{PossibleValue, StringValueParser, TypedValueParser},
+	error::ErrorKind,
+	parser::ValueSource,
+	Arg, ArgAction, ArgMatches, Args, Command, FromArgMatches, Parser, Subcommand, ValueEnum,
+};
+use clap_complete::Shell;
+use pngme::{walk::Pattern, ChunkType};
+
+use crate::{commands::is_stdio, config, remote};
+
+/// Hide messages in PNG files and inspect their chunks
+#[derive(Parser)]
+#[command(author, version, about, long_about = None)]
+pub struct Cli {
+	#[command(subcommand)]
+	pub command: Commands,
+	/// Show progress bars even when stderr is not a terminal
+	#[arg(long, global = true)]
+	pub progress: bool,
+	/// Show only errors: no progress bars, warnings, or status messages
+	#[arg(short, long, global = true, conflicts_with_all = ["progress", "verbose"])]
+	pub quiet: bool,
+	/// Log what is read, checked, and decided along the way; twice for more detail
+	#[arg(short, long, global = true, action = ArgAction::Count)]
+	pub verbose: u8,
+	/// How messages on stderr are written
+	#[arg(long, global = true, value_enum, default_value_t = LogFormat::Text)]
+	pub log_format: LogFormat,
+	/// Write files in place rather than through a temporary file renamed over them, for
+	/// filesystems that cannot rename atomically; lets encode append to a file in place
+	#[arg(long, global = true)]
+	pub no_atomic: bool,
+	/// Copy each file to FILE.bak, or with the given suffix, before replacing it; `restore`
+	/// puts it back
+	#[arg(
+		long,
+		global = true,
+		value_name = "SUFFIX",
+		num_args = 0..=1,
+		require_equals = true,
+		default_missing_value = ".bak"
+	)]
+	pub backup: Option<String>,
+	/// Report the chunks that would be added, removed, or changed and the size of each file
+	/// that would be written, without writing anything
+	#[arg(long, global = true)]
+	pub dry_run: bool,
+	/// Replace existing files given with `-o` and similar options, let encode add a chunk of a
+	/// type the file already has, and let pack replace an archive
+	#[arg(short, long, global = true)]
+	pub force: bool,
+	/// When to color output; `auto` colors terminals unless `NO_COLOR` is set
+	#[arg(long, global = true, value_enum, default_value_t = ColorChoice::Auto)]
+	pub color: ColorChoice,
+	/// Read defaults for options from FILE rather than ~/.config/pngme/config.toml
+	// Once parsed, the file that was actually read, if any.
+	#[arg(long, global = true, value_name = "FILE")]
+	pub config: Option<PathBuf>,
+	/// Apply the settings of [profile.NAME] in the configuration file over the others
+	#[arg(long, global = true, value_name = "NAME")]
+	pub profile: Option<String>,
+	/// Fail rather than download more than this from a URL given for a file, as a number of
+	/// bytes with an optional KiB, MiB, or GiB suffix
+	#[arg(long, global = true, value_name = "SIZE", value_parser = parse_bytes, default_value = "64MiB")]
+	pub max_bytes: u64,
+	/// What a command that takes a file name was given when it was several files, or a
+	/// directory or glob pattern; see [`many_files`].
+	#[arg(skip)]
+	pub files: Option<WalkArgs>,
+}
+
+impl Cli {
+	/// Parses `argv` like [`Parser::parse_from`], with the defaults from the configuration
+	/// file, but exits with pngme's usage error status.
+	pub fn parse_or_exit<T: Into<OsString> + Clone>(argv: impl IntoIterator<Item = T>) -> Self {
+		let argv: Vec<OsString> = argv.into_iter().map(Into::into).collect();
+		let (command, config) = config::command(&argv).unwrap_or_else(|error| {
+			eprintln!("error: {}", error);
+			process::exit(crate::exit::USAGE.into())
+		});
+		let mut cli = Self::try_parse_with(command, argv).unwrap_or_else(|error| {
+			// Help and version requests are "errors" printed to stdout.
+			let _ = error.print();
+			process::exit(if error.use_stderr() { crate::exit::USAGE.into() } else { 0 })
+		});
+		cli.config = config;
+		cli
+	}
+
+	/// Parses `argv` with `command`, the interface [`config::command`] returns.
+	pub fn try_parse_with(command: Command, argv: Vec<OsString>) -> Result<Self, clap::Error> {
+		let default_chunk_type = command
+			.find_subcommand("encode")
+			.and_then(|encode| encode.get_arguments().find(|arg| arg.get_id() == "chunk_type"))
+			.and_then(|arg| arg.get_default_values().first())
+			.map(|value| value.to_string_lossy().into_owned());
+		let matches = match command.clone().try_get_matches_from(&argv) {
+			Err(error) if error.kind() == ErrorKind::MissingRequiredArgument => {
+				config::with_defaults_required(command, argv, error)?
+			}
+			result => result?,
+		};
+		let mut cli = Self::from_arg_matches(&matches)?;
+		let mut found = &matches;
+		while let Some((_, subcommand)) = found.subcommand() {
+			found = subcommand;
+		}
+		cli.files = many(found);
+		if let Commands::Encode(args) = &mut cli.command {
+			let encode = matches.subcommand_matches("encode").expect("encode was parsed");
+			if encode.value_source("chunk_type") == Some(ValueSource::DefaultValue) {
+				args.chunk_type = None;
+			}
+			args.default_chunk_type = default_chunk_type;
+		}
+		Ok(cli)
+	}
+}
+
+/// The commands that take a file name, but only work on one file, interactively.
+const ONE_FILE: [&str; 3] = ["edit", "shell", "tui"];
+
+/// Lets every command that takes a file name also take a directory or a glob pattern, and
+/// several of them where the file name is its only positional argument, with the options of
+/// [`WalkArgs`]. The command is then run on each file, as [`Cli::files`] tells.
+pub fn many_files(mut command: Command) -> Command {
+	if command.has_subcommands() {
+		for subcommand in command.get_subcommands_mut() {
+			*subcommand = many_files(mem::take(subcommand));
+		}
+		return command;
+	}
+	let takes_file = command.get_positionals().any(|arg| arg.get_id() == "filename");
+	if !takes_file || ONE_FILE.contains(&command.get_name()) {
+		return command;
+	}
+	let only_positional = command.get_positionals().count() == 1;
+	let heading = "Files";
+	// Not `mut_arg`, which would move the file name after the other positional arguments.
+	command
+		.mut_args(|arg| {
+			if arg.get_id() != "filename" {
+				return arg;
+			}
+			let help = arg.get_help().map(ToString::to_string).unwrap_or_default();
+			match only_positional {
+				true => arg.num_args(1..).help(format!(
+					"{}. Several files, directories, and glob patterns are taken too",
+					help
+				)),
+				false => arg.help(format!("{}. A directory or glob pattern is taken too", help)),
+			}
+		})
+		.arg(
+			Arg::new("recursive")
+				.short('r')
+				.long("recursive")
+				.action(ArgAction::SetTrue)
+				.help_heading(heading)
+				.help("Search subdirectories of the directories given"),
+		)
+		.arg(
+			Arg::new("include")
+				.long("include")
+				.value_name("PATTERN")
+				.action(ArgAction::Append)
+				.default_value("*.[pP][nN][gG]")
+				.help_heading(heading)
+				.help(
+					"Only take the files in directories that match this pattern (may be repeated)",
+				),
+		)
+		.arg(
+			Arg::new("exclude")
+				.long("exclude")
+				.value_name("PATTERN")
+				.action(ArgAction::Append)
+				.help_heading(heading)
+				.help("Skip files that match this pattern (may be repeated)"),
+		)
+}
+
+/// The files the command of `matches` runs on, unless it was given a single file.
+fn many(matches: &ArgMatches) -> Option<WalkArgs> {
+	let recursive = matches.try_get_one::<bool>("recursive").ok().flatten().copied()?;
+	let paths: Vec<PathBuf> = matches.try_get_many("filename").ok()??.cloned().collect();
+	let searched = |path: &PathBuf| {
+		!is_stdio(path)
+			&& !remote::is_url(path)
+			&& (path.is_dir() || !path.exists() && Pattern::is_pattern(&path.to_string_lossy()))
+	};
+	if paths.len() == 1 && !recursive && !searched(&paths[0]) {
+		return None;
+	}
+	let patterns =
+		|id: &str| matches.get_many::<String>(id).into_iter().flatten().cloned().collect();
+	Some(WalkArgs { paths, recursive, include: patterns("include"), exclude: patterns("exclude") })
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum LogFormat {
+	Text,
+	/// A JSON object with the level, target, and message on each line
+	Json,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ColorChoice {
+	Auto,
+	Always,
+	Never,
+}
+
+#[derive(Subcommand)]
+pub enum Commands {
+	/// Hide a message in a PNG file
+	Encode(EncodeArgs),
+	/// Print a message hidden in a PNG file
+	Decode(DecodeArgs),
+	/// List the payloads stored in a PNG file without printing their contents
+	List(InfoArgs),
+	/// Remove chunks from a PNG file
+	Remove(RemoveArgs),
+	/// Print the chunks of a PNG file
+	Print(PrintArgs),
+	/// Show the data of a chunk as a hex dump
+	Dump(DumpArgs),
+	/// Browse the chunks of a PNG file interactively, deleting, extracting, or editing them
+	Tui(TuiArgs),
+	/// Edit the text of a chunk in $VISUAL or $EDITOR
+	Edit(EditArgs),
+	/// Print image dimensions and properties
+	Info(InfoArgs),
+	/// Validate the structure of a PNG file
+	Check(CheckArgs),
+	/// Look for signs of hidden data, most suspicious first
+	Scan(InfoArgs),
+	/// Report how large a message each hiding method can store in a PNG file
+	Capacity(CapacityArgs),
+	/// Sign the chunks of a given type with an Ed25519 key
+	Sign(SignArgs),
+	/// Check the signature added by `sign`
+	VerifySig(VerifySigArgs),
+	/// Remove ancillary chunks such as metadata, keeping the critical ones
+	Strip(StripArgs),
+	/// Losslessly shrink a PNG by recompressing its image data
+	Optimize(OptimizeArgs),
+	/// Rewrite a PNG so that the same image, metadata, and payloads always give the same bytes
+	Canonicalize(CanonicalizeArgs),
+	/// Hash the critical chunks or the pixels, so that files differing only in metadata match
+	Hash(HashArgs),
+	/// Show, extract, or strip data appended after IEND
+	Trailer(TrailerArgs),
+	/// Fix bad CRCs, a missing IEND, trailing data, and a cut-off final chunk
+	Repair(RepairArgs),
+	/// Undo the last change to a file by putting back the copy made with `--backup`
+	Restore(RestoreArgs),
+	/// Extract the PNG files embedded anywhere in another file
+	Carve(CarveArgs),
+	/// Copy chunks of a given type from one PNG file into another
+	Copy(CopyArgs),
+	/// Compare the chunks of two PNG files
+	Diff(DiffArgs),
+	/// Check that two PNG files decode to the same pixels, however they are stored
+	SameImage(SameImageArgs),
+	/// Dump every chunk to a JSON file
+	Export(ExportArgs),
+	/// Rebuild a PNG from a JSON file written by `export`
+	Import(ImportArgs),
+	/// Write a small PNG of a single color, to hide messages in when there is no image at hand
+	Create(CreateArgs),
+	/// Store a directory, with its files and their permissions, in a PNG file
+	Pack(PackArgs),
+	/// Restore the directory stored with `pack`
+	Unpack(UnpackArgs),
+	/// Check the SHA-256 of every payload, and that the payloads in the manifest are intact
+	Verify(InfoArgs),
+	/// Read and write standard tEXt, zTXt, and iTXt chunks
+	Text {
+		#[command(subcommand)]
+		command: TextCommands,
+	},
+	/// Show, remove, or replace EXIF metadata stored in the eXIf chunk
+	Exif {
+		#[command(subcommand)]
+		command: ExifCommands,
+	},
+	/// Extract or embed the ICC color profile stored in the iCCP chunk
+	Icc {
+		#[command(subcommand)]
+		command: IccCommands,
+	},
+	/// Read, write, or remove the XMP metadata packet
+	Xmp {
+		#[command(subcommand)]
+		command: XmpCommands,
+	},
+	/// Work with animated PNGs
+	Apng {
+		#[command(subcommand)]
+		command: ApngCommands,
+	},
+	/// Inspect the compressed image data
+	Idat {
+		#[command(subcommand)]
+		command: IdatCommands,
+	},
+	/// Run another command on many files, directories, and glob patterns
+	Batch(BatchArgs),
+	/// Find PNGs that are the same except for their metadata, and optionally hard link them
+	Dedupe(DedupeArgs),
+	/// Print a shell completion script
+	Completions(CompletionsArgs),
+	/// Print the man page, or write one for every command
+	Man(ManArgs),
+	/// Open a prompt to print, encode, and remove chunks in memory, writing the file once on
+	/// `save`
+	Shell(ShellArgs),
+	/// Run a script of commands on PNG files, writing each file once if every command succeeds
+	/// on every file
+	Run(RunArgs),
+	/// Run a command on each PNG added to or changed in a directory, as it happens
+	Watch(WatchArgs),
+	/// Strip volatile metadata from a PNG for git, or put it back, as a clean or smudge filter
+	///
+	/// Volatile metadata is the tIME chunk and text chunks such as Software and date:modify,
+	/// which tools rewrite on every save. Set up the filter with
+	///
+	///     git config filter.pngme.clean 'pngme git-filter --clean %f'
+	///     git config filter.pngme.smudge 'pngme git-filter --smudge %f'
+	///     echo '*.png filter=pngme' >> .gitattributes
+	///
+	/// and add *.pngmeta to .gitignore.
+	#[command(verbatim_doc_comment)]
+	GitFilter(GitFilterArgs),
+	/// Print the chunks of a PNG as text for `git diff`, as a textconv driver, leaving out the
+	/// volatile metadata that git-filter strips
+	GitTextconv(GitTextconvArgs),
+	/// Remove EXIF, XMP, tIME, and text naming the author or tool from PNGs, as a pre-commit
+	/// hook, reporting what was removed from each file
+	Scrub(ScrubArgs),
+}
+
+impl Commands {
+	/// The file the command works on, for the commands that take one file name.
+	pub fn filename_mut(&mut self) -> Option<&mut PathBuf> {
+		Some(match self {
+			Self::Encode(args) => &mut args.filename,
+			Self::Decode(args) => &mut args.filename,
+			Self::List(args)
+			| Self::Info(args)
+			| Self::Scan(args)
+			| Self::Verify(args)
+			| Self::Idat { command: IdatCommands::Info(args) } => &mut args.filename,
+			Self::Remove(args) => &mut args.filename,
+			Self::Print(args) => &mut args.filename,
+			Self::Dump(args) => &mut args.filename,
+			Self::Tui(args) => &mut args.filename,
+			Self::Edit(args) => &mut args.filename,
+			Self::Check(args) => &mut args.filename,
+			Self::Capacity(args) => &mut args.filename,
+			Self::Sign(args) => &mut args.filename,
+			Self::VerifySig(args) => &mut args.filename,
+			Self::Strip(args) => &mut args.filename,
+			Self::Optimize(args) => &mut args.filename,
+			Self::Canonicalize(args) => &mut args.filename,
+			Self::Trailer(args) => &mut args.filename,
+			Self::Repair(args) => &mut args.filename,
+			Self::Restore(args) => &mut args.filename,
+			Self::Carve(args) => &mut args.filename,
+			Self::Export(args) => &mut args.filename,
+			Self::Import(args) => &mut args.filename,
+			Self::Create(args) => &mut args.filename,
+			Self::Pack(args) => &mut args.filename,
+			Self::Unpack(args) => &mut args.filename,
+			Self::Text { command: TextCommands::Set(args) } => &mut args.filename,
+			Self::Text { command: TextCommands::Get(args) } => &mut args.filename,
+			Self::Exif { command: ExifCommands::Show(args) } => &mut args.filename,
+			Self::Exif { command: ExifCommands::Strip(args) } => &mut args.filename,
+			Self::Exif { command: ExifCommands::Import(args) } => &mut args.filename,
+			Self::Icc { command: IccCommands::Extract(args) } => &mut args.filename,
+			Self::Icc { command: IccCommands::Embed(args) } => &mut args.filename,
+			Self::Xmp { command: XmpCommands::Get(args) } => &mut args.filename,
+			Self::Xmp { command: XmpCommands::Set(args) } => &mut args.filename,
+			Self::Xmp { command: XmpCommands::Strip(args) } => &mut args.filename,
+			Self::Apng { command: ApngCommands::Split(args) } => &mut args.filename,
+			Self::Shell(args) => &mut args.filename,
+			Self::GitTextconv(args) => &mut args.filename,
+			Self::Hash(_)
+			| Self::Copy(_)
+			| Self::Diff(_)
+			| Self::SameImage(_)
+			| Self::Apng { command: ApngCommands::Build(_) }
+			| Self::Batch(_)
+			| Self::Dedupe(_)
+			| Self::Completions(_)
+			| Self::Man(_)
+			| Self::Run(_)
+			| Self::Watch(_)
+			| Self::GitFilter(_)
+			| Self::Scrub(_) => return None,
+		})
+	}
+}
+
+#[derive(Subcommand)]
+pub enum TextCommands {
+	/// Set a keyword, replacing any existing text chunks with the same keyword
+	Set(TextSetArgs),
+	/// Print the text for a keyword, or every keyword and its text
+	Get(TextGetArgs),
+}
+
+#[derive(Subcommand)]
+pub enum ExifCommands {
+	/// List the EXIF tags, GPS location included
+	Show(ExifShowArgs),
+	/// Remove the eXIf chunk
+	Strip(ExifStripArgs),
+	/// Replace the eXIf chunk with the contents of a raw EXIF file
+	Import(ExifImportArgs),
+}
+
+#[derive(Subcommand)]
+pub enum IccCommands {
+	/// Write the embedded ICC profile to a file
+	Extract(IccExtractArgs),
+	/// Embed an ICC profile, replacing any existing one
+	Embed(IccEmbedArgs),
+}
+
+#[derive(Subcommand)]
+pub enum XmpCommands {
+	/// Print the XMP packet, indented for reading
+	Get(XmpGetArgs),
+	/// Store an XMP packet, replacing any existing one
+	Set(XmpSetArgs),
+	/// Remove the XMP packet
+	Strip(XmpStripArgs),
+}
+
+#[derive(Subcommand)]
+pub enum ApngCommands {
+	/// Write each frame of an animation as a standalone PNG
+	Split(ApngSplitArgs),
+	/// Pack PNGs of equal dimensions into an animation
+	Build(ApngBuildArgs),
+}
+
+#[derive(Subcommand)]
+pub enum IdatCommands {
+	/// Report sizes, compression ratio, and scanline filters of the image data
+	Info(InfoArgs),
+}
+
+/// Where `encode` hides the message.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Method {
+	/// In a chunk of the given type
+	Chunk,
+	/// In the least significant bits of the pixels, tagged with the chunk type
+	Lsb,
+}
+
+/// How `encode` and `pack` compress the payload before storing it.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Compress {
+	None,
+	/// zlib, fast to decode everywhere
+	Deflate,
+	/// Zstandard, usually smaller
+	Zstd,
+}
+
+/// The hash function of `hash`.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum HashAlgorithm {
+	Sha256,
+	Sha512,
+}
+
+/// A column of `print` output.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Column {
+	/// Position of the chunk in the file, counting from 0
+	Index,
+	/// Byte offset of the chunk in the file
+	Offset,
+	Type,
+	/// What the chunk type is for, if it is a standard one
+	Description,
+	/// Length of the chunk data in bytes
+	Length,
+	/// The stored CRC, and whether it matches the chunk
+	Crc,
+	/// `C` or `A` for critical or ancillary, then `P` if private and `S` if safe to copy
+	Flags,
+	/// The start of the data as text, or the decoded contents with `--decode`
+	Preview,
+}
+
+/// How `print` shows chunk data that is not plain UTF-8 text.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Render {
+	/// Skip chunks that are not valid UTF-8
+	Strict,
+	/// Replace bytes that are not valid UTF-8 with U+FFFD
+	Lossy,
+	/// Write control characters and bytes that are not valid UTF-8 as `\xNN`
+	Escaped,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+	Text,
+	Json,
+}
+
+/// Output formats of `print`, which can also imitate pngcheck.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum PrintFormat {
+	Text,
+	Json,
+	/// A line of any problems and an OK or ERROR summary, like `pngcheck`
+	Pngcheck,
+	/// A line for each chunk and a summary, like `pngcheck -v`
+	PngcheckVerbose,
+}
+
+#[derive(Args)]
+pub struct EncodeArgs {
+	/// PNG file to modify, or `-` for stdin/stdout
+	pub filename: PathBuf,
+	/// May be left out with `--key`, which defaults to pmKy chunks, `--random-type`, or a
+	/// chunk type set in the configuration file
+	#[arg(value_parser = ChunkTypeParser, hide_possible_values = true)]
+	pub chunk_type: Option<String>,
+	/// Required unless given with `--input-file`
+	pub message: Option<String>,
+	// The chunk type from the configuration file. It cannot be the default of `chunk_type`,
+	// which a lone message would take the place of.
+	#[arg(skip)]
+	pub default_chunk_type: Option<String>,
+	/// Store the message in a chunk of a random private type instead, printing the type
+	#[arg(long, conflicts_with = "compressed_text")]
+	pub random_type: bool,
+	/// Seed for `--random-type`, to get the same type for the same input file
+	#[arg(long, requires = "random_type")]
+	pub seed: Option<u64>,
+	/// Embed the contents of this file instead of a message (`-` for stdin)
+	#[arg(long, conflicts_with = "message")]
+	pub input_file: Option<PathBuf>,
+	/// Write the result here instead of overwriting the input file (`-` for stdout)
+	#[arg(short, long)]
+	pub output: Option<PathBuf>,
+	/// Store the message as a standard compressed zTXt chunk under this keyword; the chunk type
+	/// must be zTXt
+	#[arg(long, value_name = "KEYWORD", conflicts_with_all = ["input_file", "encrypt"])]
+	pub compressed_text: Option<String>,
+	/// Split messages larger than this many bytes across several chunks
+	#[arg(long, default_value_t = 1 << 20)]
+	pub chunk_size: usize,
+	/// Encrypt the message with a passphrase
+	#[arg(long)]
+	pub encrypt: bool,
+	/// Passphrase for `--encrypt`; falls back to `PNGME_PASSWORD`, then a prompt
+	#[arg(long, requires = "encrypt")]
+	pub passphrase: Option<String>,
+	/// Encrypt the message to this age public key (`age1...`) instead; repeat for several
+	/// recipients, any of whom can decrypt it
+	#[arg(long, value_name = "PUBLIC_KEY", conflicts_with_all = ["compressed_text", "encrypt"])]
+	pub recipient: Vec<String>,
+	/// Append the message after the IEND chunk instead of storing it in a chunk; the chunk
+	/// type serves as its tag
+	#[arg(long, conflicts_with_all = ["compressed_text", "before", "after", "index"])]
+	pub after_iend: bool,
+	/// How to hide the message
+	#[arg(long, value_enum, default_value_t = Method::Chunk)]
+	pub method: Method,
+	/// Compress the message before encrypting and storing it
+	#[arg(long, value_enum, default_value_t = Compress::None, conflicts_with = "compressed_text")]
+	pub compress: Compress,
+	/// Store the message under this key, replacing any earlier message with the same key and
+	/// chunk type but keeping messages under other keys
+	#[arg(long, conflicts_with = "compressed_text")]
+	pub key: Option<String>,
+	/// Store the bare message without the pngme envelope header, as older versions did
+	#[arg(long, conflicts_with_all = ["compressed_text", "compress", "recipient", "key"])]
+	pub raw: bool,
+	/// Add Reed–Solomon parity data, this many bytes per byte of payload (up to 1), so that
+	/// decode can repair damaged bytes; damage inside chunks is repaired despite their CRCs
+	#[arg(long, value_name = "RATIO", conflicts_with_all = ["compressed_text", "raw"])]
+	pub ecc: Option<f64>,
+	/// Add the chunk even if the file already has one of the same type
+	#[arg(long, conflicts_with_all = ["replace", "upsert"])]
+	pub append_duplicate: bool,
+	/// Replace the chunks of this type, the message taking the place of the first of them;
+	/// fails if there are none
+	#[arg(long, conflicts_with_all = ["key", "after_iend", "method", "random_type", "upsert"])]
+	pub replace: bool,
+	/// Like `--replace`, but add the message as usual if there is no chunk of this type
+	#[arg(long, conflicts_with_all = ["key", "after_iend", "method", "random_type"])]
+	pub upsert: bool,
+	#[command(flatten)]
+	pub position: PositionArgs,
+}
+
+/// Where to insert new chunks; without any of these they go to the latest position the PNG
+/// chunk ordering rules allow.
+#[derive(Args)]
+#[group(multiple = false)]
+pub struct PositionArgs {
+	/// Insert before the first chunk of this type
+	#[arg(long, value_name = "TYPE")]
+	pub before: Option<String>,
+	/// Insert after the last chunk of this type
+	#[arg(long, value_name = "TYPE")]
+	pub after: Option<String>,
+	/// Insert at this position in the chunk list, counting from 0
+	#[arg(long)]
+	pub index: Option<usize>,
+}
+
+#[derive(Args)]
+pub struct DecodeArgs {
+	/// PNG file to read, `-` for stdin, or an http:// or https:// URL
+	pub filename: PathBuf,
+	/// Chunk type holding the message; without it every chunk is searched for a pngme payload
+	#[arg(value_parser = ChunkTypeParser, hide_possible_values = true)]
+	pub chunk_type: Option<String>,
+	/// Read the message stored with `encode --key`
+	#[arg(long)]
+	pub key: Option<String>,
+	/// Write the raw message to this file instead of printing it (`-` for stdout)
+	#[arg(long)]
+	pub output_file: Option<PathBuf>,
+	/// Output format
+	#[arg(long, value_enum, default_value_t = Format::Text)]
+	pub format: Format,
+	#[command(flatten)]
+	pub decryption: DecryptArgs,
+	/// Read a message stored with `encode --after-iend`
+	#[arg(long)]
+	pub after_iend: bool,
+	/// Where the message was hidden
+	#[arg(long, value_enum, default_value_t = Method::Chunk, conflicts_with = "after_iend")]
+	pub method: Method,
+	/// Read chunks with bad CRCs instead of failing, warning about each one
+	#[arg(long)]
+	pub ignore_crc: bool,
+	/// Read the Nth matching message, counting from 0, instead of the first
+	#[arg(long)]
+	pub index: Option<usize>,
+	/// Print every matching message
+	#[arg(long, conflicts_with_all = ["index", "output_file"])]
+	pub all: bool,
+}
+
+/// How to decrypt and check a payload.
+#[derive(Args)]
+pub struct DecryptArgs {
+	/// Decrypt a message encoded with `--encrypt`
+	#[arg(long)]
+	pub decrypt: bool,
+	/// Passphrase for `--decrypt`; falls back to `PNGME_PASSWORD`, then a prompt
+	#[arg(long, requires = "decrypt")]
+	pub passphrase: Option<String>,
+	/// Decrypt a message encoded with `--recipient` using the keys in this age identity file
+	#[arg(long, conflicts_with = "decrypt")]
+	pub identity: Option<PathBuf>,
+	/// Fail unless the payload has a SHA-256 to check it against; a stored SHA-256 that does
+	/// not match fails either way
+	#[arg(long)]
+	pub verify: bool,
+}
+
+#[derive(Args)]
+pub struct DumpArgs {
+	/// PNG file to read, or `-` for stdin
+	pub filename: PathBuf,
+	#[arg(value_parser = ChunkTypeParser, hide_possible_values = true)]
+	pub chunk_type: String,
+	/// Dump the Nth chunk of the type, counting from 0
+	#[arg(long, default_value_t = 0)]
+	pub index: usize,
+}
+
+#[derive(Args)]
+pub struct EditArgs {
+	/// PNG file to modify
+	pub filename: PathBuf,
+	#[arg(value_parser = ChunkTypeParser, hide_possible_values = true)]
+	pub chunk_type: String,
+	/// Edit the Nth chunk of the type, counting from 0
+	#[arg(long, default_value_t = 0)]
+	pub index: usize,
+}
+
+#[derive(Args)]
+pub struct TuiArgs {
+	/// PNG file to browse, which saving overwrites
+	pub filename: PathBuf,
+}
+
+#[derive(Args)]
+pub struct ShellArgs {
+	/// PNG file to edit, which saving overwrites
+	pub filename: PathBuf,
+}
+
+#[derive(Args)]
+pub struct RunArgs {
+	/// File of commands, one per line, given like `shell` takes them
+	pub script: PathBuf,
+	/// PNG files to run the script on
+	#[arg(required = true)]
+	pub filenames: Vec<PathBuf>,
+}
+
+#[derive(Args)]
+pub struct RemoveArgs {
+	/// PNG file to modify, or `-` for stdin/stdout
+	pub filename: PathBuf,
+	#[arg(value_parser = ChunkTypeParser, hide_possible_values = true)]
+	pub chunk_type: String,
+	/// Remove every chunk of the type instead of only the first
+	#[arg(long, conflicts_with_all = ["index", "offset"])]
+	pub all: bool,
+	/// Remove the Nth chunk of the type, counting from 0
+	#[arg(long, conflicts_with = "offset")]
+	pub index: Option<usize>,
+	/// Remove the chunk of the type starting at this byte offset, decimal or `0x`-prefixed hex
+	#[arg(long, value_parser = parse_offset)]
+	pub offset: Option<u64>,
+	/// Write the result here instead of overwriting the input file (`-` for stdout)
+	#[arg(short, long)]
+	pub output: Option<PathBuf>,
+}
+
+/// Parses chunk type arguments as plain strings, offering the registered chunk types and
+/// pngme's own to shell completions.
+#[derive(Clone)]
+struct ChunkTypeParser;
+
+/// pngme's private chunk types, which are not registered.
+const PNGME_CHUNK_TYPES: [(&str, &str); 4] = [
+	("pmKy", "pngme keyed payload"),
+	("pmAr", "pngme archive"),
+	("pmMf", "pngme manifest"),
+	("siGN", "pngme signature"),
+];
+
+impl TypedValueParser for ChunkTypeParser {
+	type Value = String;
+
+	fn parse_ref(
+		&self,
+		cmd: &clap::Command,
+		arg: Option<&clap::Arg>,
+		value: &OsStr,
+	) -> Result<String, clap::Error> {
+		StringValueParser::new().parse_ref(cmd, arg, value)
+	}
+
+	fn possible_values(&self) -> Option<Box<dyn Iterator<Item = PossibleValue> + '_>> {
+		let types = ChunkType::registered().chain(PNGME_CHUNK_TYPES);
+		Some(Box::new(types.map(|(name, description)| PossibleValue::new(name).help(description))))
+	}
+}
+
+fn parse_offset(s: &str) -> Result<u64, ParseIntError> {
+	match s.strip_prefix("0x") {
+		Some(hex) => u64::from_str_radix(hex, 16),
+		None => s.parse(),
+	}
+}
+
+#[derive(Args)]
+pub struct PrintArgs {
+	/// PNG file to read, `-` for stdin, or an http:// or https:// URL
+	pub filename: PathBuf,
+	/// Columns to show, separated by commas
+	#[arg(
+		long,
+		value_enum,
+		value_delimiter = ',',
+		default_value = "index,offset,type,length,crc,flags,preview"
+	)]
+	pub columns: Vec<Column>,
+	/// Show the decoded contents of known chunk types instead of their raw data
+	#[arg(long)]
+	pub decode: bool,
+	/// Add the entropy, printable ratio, and byte histogram of each chunk, listing binary
+	/// chunks too
+	#[arg(long)]
+	pub analysis: bool,
+	/// Follow each chunk with a hex dump of its data, listing binary chunks and image data too
+	#[arg(long)]
+	pub hex: bool,
+	/// How to show chunk data that is not plain text
+	#[arg(long, value_enum, default_value_t = Render::Strict)]
+	pub render: Render,
+	/// Output format
+	#[arg(long, value_enum, default_value_t = PrintFormat::Text)]
+	pub format: PrintFormat,
+	/// Read chunks with bad CRCs instead of failing, warning about each one
+	#[arg(long)]
+	pub ignore_crc: bool,
+	/// Map the file into memory and read only the chunks that are printed, skipping image data
+	/// unless analyzing it. Only safe on files nothing else is writing: a file truncated
+	/// meanwhile crashes pngme
+	#[arg(long)]
+	pub mmap: bool,
+}
+
+#[derive(Args)]
+pub struct TextSetArgs {
+	/// PNG file to modify, or `-` for stdin/stdout
+	pub filename: PathBuf,
+	pub keyword: String,
+	pub value: String,
+	/// Compress the text, producing a zTXt or compressed iTXt chunk
+	#[arg(long)]
+	pub compress: bool,
+	/// Produce an iTXt chunk even if the text is representable in Latin-1
+	#[arg(long)]
+	pub international: bool,
+	/// Language tag of the text, such as `en-GB`; implies `--international`
+	#[arg(long)]
+	pub language: Option<String>,
+	/// Keyword translated into the text's language; implies `--international`
+	#[arg(long)]
+	pub translated_keyword: Option<String>,
+	/// Write the result here instead of overwriting the input file (`-` for stdout)
+	#[arg(short, long)]
+	pub output: Option<PathBuf>,
+}
+
+#[derive(Args)]
+pub struct TextGetArgs {
+	/// PNG file to read, or `-` for stdin
+	pub filename: PathBuf,
+	pub keyword: Option<String>,
+}
+
+#[derive(Args)]
+pub struct ExifShowArgs {
+	/// PNG file to read, or `-` for stdin
+	pub filename: PathBuf,
+}
+
+#[derive(Args)]
+pub struct ExifStripArgs {
+	/// PNG file to modify, or `-` for stdin/stdout
+	pub filename: PathBuf,
+	/// Write the result here instead of overwriting the input file (`-` for stdout)
+	#[arg(short, long)]
+	pub output: Option<PathBuf>,
+}
+
+#[derive(Args)]
+pub struct ExifImportArgs {
+	/// PNG file to modify, or `-` for stdin/stdout
+	pub filename: PathBuf,
+	/// Raw EXIF data, with or without the `Exif\0\0` marker (`-` for stdin)
+	pub exif: PathBuf,
+	/// Write the result here instead of overwriting the input file (`-` for stdout)
+	#[arg(short, long)]
+	pub output: Option<PathBuf>,
+}
+
+#[derive(Args)]
+pub struct IccExtractArgs {
+	/// PNG file to read, or `-` for stdin
+	pub filename: PathBuf,
+	/// Where to write the profile (defaults to stdout)
+	#[arg(short, long, default_value = "-")]
+	pub output: PathBuf,
+}
+
+#[derive(Args)]
+pub struct IccEmbedArgs {
+	/// PNG file to modify, or `-` for stdin/stdout
+	pub filename: PathBuf,
+	/// ICC profile to embed (`-` for stdin)
+	pub profile: PathBuf,
+	/// Profile name stored in the chunk
+	#[arg(long, default_value = "ICC profile")]
+	pub name: String,
+	/// Write the result here instead of overwriting the input file (`-` for stdout)
+	#[arg(short, long)]
+	pub output: Option<PathBuf>,
+}
+
+#[derive(Args)]
+pub struct XmpGetArgs {
+	/// PNG file to read, or `-` for stdin
+	pub filename: PathBuf,
+	/// Print the packet exactly as stored
+	#[arg(long)]
+	pub raw: bool,
+}
+
+#[derive(Args)]
+pub struct XmpSetArgs {
+	/// PNG file to modify, or `-` for stdin/stdout
+	pub filename: PathBuf,
+	/// File holding the XMP packet (`-` for stdin)
+	pub packet: PathBuf,
+	/// Write the result here instead of overwriting the input file (`-` for stdout)
+	#[arg(short, long)]
+	pub output: Option<PathBuf>,
+}
+
+#[derive(Args)]
+pub struct XmpStripArgs {
+	/// PNG file to modify, or `-` for stdin/stdout
+	pub filename: PathBuf,
+	/// Write the result here instead of overwriting the input file (`-` for stdout)
+	#[arg(short, long)]
+	pub output: Option<PathBuf>,
+}
+
+#[derive(Args)]
+pub struct ApngSplitArgs {
+	/// APNG file to read, or `-` for stdin
+	pub filename: PathBuf,
+	/// Directory to write the frames to, as NAME-001.png, NAME-002.png, ...
+	#[arg(short, long, default_value = ".")]
+	pub output: PathBuf,
+}
+
+#[derive(Args)]
+pub struct ApngBuildArgs {
+	/// Frames in the order they are shown
+	#[arg(required = true)]
+	pub frames: Vec<PathBuf>,
+	/// Where to write the animation (`-` for stdout)
+	#[arg(short, long)]
+	pub output: PathBuf,
+	/// How long each frame is shown, such as `100ms` or `1.5s`; plain numbers are milliseconds
+	#[arg(long, default_value = "100ms", value_parser = parse_delay)]
+	pub delay: Duration,
+	/// How many times the animation plays, 0 for forever
+	#[arg(long, default_value_t = 0)]
+	pub loops: u32,
+}
+
+fn parse_delay(s: &str) -> Result<Duration, String> {
+	let (number, seconds_per_unit) = match s.strip_suffix("ms") {
+		Some(millis) => (millis, 0.001),
+		None => s.strip_suffix('s').map_or((s, 0.001), |secs| (secs, 1.0)),
+	};
+	let number: f64 = number.trim().parse().map_err(|_| format!("invalid delay {:?}", s))?;
+	Duration::try_from_secs_f64(number * seconds_per_unit).map_err(|e| e.to_string())
+}
+
+#[derive(Args)]
+pub struct CreateArgs {
+	/// PNG file to write, or `-` for stdout
+	pub filename: PathBuf,
+	/// Image size in pixels
+	#[arg(long, value_name = "WIDTHxHEIGHT", value_parser = parse_size, default_value = "1x1")]
+	pub size: (u32, u32),
+	/// Color of every pixel, as `#RRGGBB`, or `#RRGGBBAA` for an image with an alpha channel
+	// Not `--color`, which is the global option for colored output.
+	#[arg(long, value_name = "COLOR", value_parser = parse_color, default_value = "#00000000")]
+	pub fill: Color,
+	/// Store this message in the new file, in a chunk of the type given with `--chunk-type`
+	#[arg(long, requires = "chunk_type")]
+	pub message: Option<String>,
+	/// Chunk type for `--message`
+	#[arg(long, requires = "message", value_parser = ChunkTypeParser, hide_possible_values = true)]
+	pub chunk_type: Option<String>,
+}
+
+#[derive(Args)]
+pub struct PackArgs {
+	/// Directory to store
+	pub dir: PathBuf,
+	/// PNG file to modify, or `-` for stdin/stdout
+	pub filename: PathBuf,
+	/// Write the result here instead of overwriting the PNG file (`-` for stdout)
+	#[arg(short, long)]
+	pub output: Option<PathBuf>,
+	/// Chunk type to store the archive in
+	#[arg(long, default_value = "pmAr", value_parser = ChunkTypeParser, hide_possible_values = true)]
+	pub chunk_type: String,
+	/// Split archives larger than this many bytes across several chunks
+	#[arg(long, default_value_t = 1 << 20)]
+	pub chunk_size: usize,
+	/// Compress the archive before encrypting and storing it
+	#[arg(long, value_enum, default_value_t = Compress::None)]
+	pub compress: Compress,
+	/// Encrypt the archive with a passphrase
+	#[arg(long)]
+	pub encrypt: bool,
+	/// Passphrase for `--encrypt`; falls back to `PNGME_PASSWORD`, then a prompt
+	#[arg(long, requires = "encrypt")]
+	pub passphrase: Option<String>,
+	/// Encrypt the archive to this age public key (`age1...`) instead; repeat for several
+	/// recipients
+	#[arg(long, value_name = "PUBLIC_KEY", conflicts_with = "encrypt")]
+	pub recipient: Vec<String>,
+}
+
+#[derive(Args)]
+pub struct UnpackArgs {
+	/// PNG file to read, or `-` for stdin
+	pub filename: PathBuf,
+	/// Directory to restore the files into, created if needed
+	#[arg(short, long, default_value = ".")]
+	pub output: PathBuf,
+	/// Chunk type the archive is stored in; by default the one the manifest lists, or pmAr
+	#[arg(long, value_parser = ChunkTypeParser, hide_possible_values = true)]
+	pub chunk_type: Option<String>,
+	/// Restore the directory of this name in the manifest, when several are stored
+	#[arg(long, conflicts_with = "chunk_type")]
+	pub name: Option<String>,
+	#[command(flatten)]
+	pub decryption: DecryptArgs,
+	/// List the files and directories instead of restoring them
+	#[arg(long)]
+	pub list: bool,
+}
+
+/// The samples of an RGB or RGBA color.
+#[derive(Clone)]
+pub struct Color(pub Vec<u8>);
+
+fn parse_size(s: &str) -> Result<(u32, u32), String> {
+	let invalid = || format!("invalid size {:?}, expected WIDTHxHEIGHT such as 16x16", s);
+	let (width, height) = s.split_once(['x', 'X']).ok_or_else(invalid)?;
+	let width: u32 = width.trim().parse().map_err(|_| invalid())?;
+	let height: u32 = height.trim().parse().map_err(|_| invalid())?;
+	if width == 0 || height == 0 {
+		return Err("the width and height must be at least 1".to_owned());
+	}
+	Ok((width, height))
+}
+
+fn parse_bytes(s: &str) -> Result<u64, String> {
+	let invalid = || format!("invalid size {:?}, expected a number of bytes such as 10MiB", s);
+	let (number, unit) = [("KiB", 1 << 10), ("MiB", 1 << 20), ("GiB", 1 << 30)]
+		.into_iter()
+		.find_map(|(suffix, unit)| s.strip_suffix(suffix).map(|number| (number, unit)))
+		.unwrap_or((s.strip_suffix('B').unwrap_or(s), 1));
+	let number: u64 = number.trim().parse().map_err(|_| invalid())?;
+	number.checked_mul(unit).ok_or_else(invalid)
+}
+
+fn parse_color(s: &str) -> Result<Color, String> {
+	let invalid = || format!("invalid color {:?}, expected #RRGGBB or #RRGGBBAA", s);
+	let hex = s.strip_prefix('#').unwrap_or(s);
+	if !matches!(hex.len(), 6 | 8) || !hex.is_ascii() {
+		return Err(invalid());
+	}
+	(0..hex.len())
+		.step_by(2)
+		.map(|n| u8::from_str_radix(&hex[n..n + 2], 16).map_err(|_| invalid()))
+		.collect::<Result<_, _>>()
+		.map(Color)
+}
+
+#[derive(Args)]
+pub struct InfoArgs {
+	/// PNG file to read, `-` for stdin, or an http:// or https:// URL
+	pub filename: PathBuf,
+	/// Output format
+	#[arg(long, value_enum, default_value_t = Format::Text)]
+	pub format: Format,
+}
+
+#[derive(Args)]
+pub struct CheckArgs {
+	/// PNG file to check, `-` for stdin, or an http:// or https:// URL
+	pub filename: PathBuf,
+}
+
+#[derive(Args)]
+pub struct CapacityArgs {
+	/// PNG file to read, or `-` for stdin
+	pub filename: PathBuf,
+	/// Account for the overhead of `encode --encrypt`
+	#[arg(long)]
+	pub encrypt: bool,
+	/// Account for `encode --raw`, which stores the message without the envelope header
+	#[arg(long)]
+	pub raw: bool,
+	/// Output format
+	#[arg(long, value_enum, default_value_t = Format::Text)]
+	pub format: Format,
+}
+
+#[derive(Args)]
+pub struct SignArgs {
+	/// PNG file to modify, or `-` for stdin/stdout
+	pub filename: PathBuf,
+	/// Type of the payload chunks to sign
+	#[arg(value_parser = ChunkTypeParser, hide_possible_values = true)]
+	pub chunk_type: String,
+	/// Ed25519 private key in PKCS#8 PEM format
+	#[arg(long)]
+	pub key: PathBuf,
+	/// Also sign the critical chunks, binding the payload to this image
+	#[arg(long)]
+	pub critical: bool,
+	/// Write the result here instead of overwriting the input file (`-` for stdout)
+	#[arg(short, long)]
+	pub output: Option<PathBuf>,
+}
+
+#[derive(Args)]
+pub struct VerifySigArgs {
+	/// PNG file to read, or `-` for stdin
+	pub filename: PathBuf,
+	/// Type of the signed payload chunks
+	#[arg(value_parser = ChunkTypeParser, hide_possible_values = true)]
+	pub chunk_type: String,
+	/// Ed25519 public key in PEM format; a private key works too
+	#[arg(long)]
+	pub key: PathBuf,
+}
+
+#[derive(Args)]
+pub struct RepairArgs {
+	/// PNG file to repair, or `-` for stdin/stdout
+	pub filename: PathBuf,
+	/// Write the result here instead of overwriting the input file (`-` for stdout)
+	#[arg(short, long)]
+	pub output: Option<PathBuf>,
+	/// Leave wrong CRCs as they are
+	#[arg(long)]
+	pub keep_crcs: bool,
+	/// Do not append a missing IEND chunk
+	#[arg(long)]
+	pub no_iend: bool,
+	/// Keep any data after IEND
+	#[arg(long)]
+	pub keep_trailer: bool,
+	/// Keep a cut-off or unparseable final chunk
+	#[arg(long)]
+	pub keep_partial: bool,
+}
+
+#[derive(Args)]
+pub struct RestoreArgs {
+	/// File to restore
+	pub filename: PathBuf,
+	/// The suffix given to `--backup`
+	#[arg(long, default_value = ".bak")]
+	pub suffix: String,
+}
+
+#[derive(Args)]
+pub struct BatchArgs {
+	#[command(flatten)]
+	pub walk: WalkArgs,
+	/// Stop starting new files once the command fails on one
+	#[arg(long)]
+	pub fail_fast: bool,
+	/// How many files to process at once; 0 uses one job per CPU
+	#[arg(short, long, default_value_t = 1)]
+	pub jobs: usize,
+	/// The command to run and its arguments, leaving out the file name, after `--`
+	#[arg(last = true, required = true)]
+	pub command: Vec<OsString>,
+}
+
+#[derive(Args)]
+pub struct GitFilterArgs {
+	/// Read a PNG from stdin and write it to stdout without its volatile metadata, which is
+	/// kept in the sidecar
+	#[arg(long, required_unless_present = "smudge", conflicts_with = "smudge")]
+	pub clean: bool,
+	/// Read a PNG from stdin and write it to stdout with the volatile metadata from the sidecar
+	/// put back, if the sidecar was made for it
+	#[arg(long)]
+	pub smudge: bool,
+	/// Path of the file in the work tree, which git gives as %f; its sidecar is FILE.pngmeta
+	pub file: Option<PathBuf>,
+	/// Keep the volatile metadata in this file instead
+	#[arg(long, value_name = "FILE")]
+	pub sidecar: Option<PathBuf>,
+}
+
+#[derive(Args)]
+pub struct GitTextconvArgs {
+	/// PNG file to show
+	pub filename: PathBuf,
+}
+
+#[derive(Args)]
+pub struct ScrubArgs {
+	#[command(flatten)]
+	pub walk: WalkArgs,
+	/// Keep chunks of this type, or text chunks with this keyword, such as `tIME` or `Author`
+	/// (may be repeated)
+	#[arg(long, value_name = "TYPE|KEYWORD")]
+	pub keep: Vec<String>,
+	/// Change nothing, but fail if anything would be removed
+	#[arg(long)]
+	pub check: bool,
+}
+
+#[derive(Args)]
+pub struct WatchArgs {
+	/// Directory to watch until interrupted
+	pub dir: PathBuf,
+	/// The command to run on each file and its arguments, leaving out the file name, such as
+	/// 'strip --keep tRNS'; with --dry-run, it only reports what it would change
+	#[arg(long, value_name = "COMMAND")]
+	pub on_add: String,
+	/// Watch subdirectories of the directory too
+	#[arg(short, long)]
+	pub recursive: bool,
+	/// Only take the files that match this pattern (may be repeated)
+	#[arg(long, default_value = "*.[pP][nN][gG]")]
+	pub include: Vec<String>,
+	/// Skip files that match this pattern (may be repeated)
+	#[arg(long)]
+	pub exclude: Vec<String>,
+	/// How long a file must go unchanged before the command runs on it, so that it is not run
+	/// on a file still being written
+	#[arg(long, value_name = "DELAY", default_value = "500ms", value_parser = parse_delay)]
+	pub debounce: Duration,
+}
+
+/// Which files a command over many files takes.
+#[derive(Args)]
+pub struct WalkArgs {
+	/// Files, directories, and glob patterns such as `photos/**/*.png`
+	#[arg(required = true)]
+	pub paths: Vec<PathBuf>,
+	/// Search subdirectories of the directories given
+	#[arg(short, long)]
+	pub recursive: bool,
+	/// Only take the files in directories that match this pattern (may be repeated)
+	#[arg(long, default_value = "*.[pP][nN][gG]")]
+	pub include: Vec<String>,
+	/// Skip files that match this pattern (may be repeated)
+	#[arg(long)]
+	pub exclude: Vec<String>,
+}
+
+#[derive(Args)]
+pub struct DedupeArgs {
+	#[command(flatten)]
+	pub walk: WalkArgs,
+	/// Compare the decoded pixels instead of the critical chunks, so that differently
+	/// compressed copies of an image count as duplicates too
+	#[arg(long)]
+	pub pixels_only: bool,
+	/// Replace every duplicate with a hard link to the first file of its group, losing its
+	/// metadata and payloads
+	#[arg(long)]
+	pub link: bool,
+	/// How many files to hash at once; 0 uses one job per CPU
+	#[arg(short, long, default_value_t = 0)]
+	pub jobs: usize,
+	/// Output format
+	#[arg(long, value_enum, default_value_t = Format::Text)]
+	pub format: Format,
+}
+
+#[derive(Args)]
+pub struct CarveArgs {
+	/// File to search, of any format, or `-` for stdin
+	pub filename: PathBuf,
+	/// Directory to write the PNGs to, as NAME-OFFSET.png with the offset in hex
+	#[arg(short, long, default_value = ".")]
+	pub output: PathBuf,
+}
+
+#[derive(Args)]
+pub struct StripArgs {
+	/// PNG file to modify, or `-` for stdin/stdout
+	pub filename: PathBuf,
+	/// Keep ancillary chunks of these types
+	#[arg(long, value_name = "TYPES", value_delimiter = ',')]
+	pub keep: Vec<String>,
+	/// Remove only ancillary chunks of these types
+	#[arg(long, value_name = "TYPES", value_delimiter = ',')]
+	pub only: Vec<String>,
+	/// Write the result here instead of overwriting the input file (`-` for stdout)
+	#[arg(short, long)]
+	pub output: Option<PathBuf>,
+}
+
+#[derive(Args)]
+pub struct OptimizeArgs {
+	/// PNG file to modify, or `-` for stdin/stdout
+	pub filename: PathBuf,
+	/// zlib compression level
+	#[arg(long, default_value_t = 9, value_parser = clap::value_parser!(u32).range(0..=9))]
+	pub level: u32,
+	/// Try every scanline filter strategy and keep the smallest result
+	#[arg(long)]
+	pub try_filters: bool,
+	/// Also remove textual chunks, tIME, and eXIf
+	#[arg(long)]
+	pub strip_metadata: bool,
+	/// Write the result here instead of overwriting the input file (`-` for stdout)
+	#[arg(short, long)]
+	pub output: Option<PathBuf>,
+}
+
+#[derive(Args)]
+pub struct CanonicalizeArgs {
+	/// PNG file to modify, or `-` for stdin/stdout
+	pub filename: PathBuf,
+	/// Only report whether the file is canonical, failing if it is not
+	#[arg(long, conflicts_with = "output")]
+	pub check: bool,
+	/// Write the result here instead of overwriting the input file (`-` for stdout)
+	#[arg(short, long)]
+	pub output: Option<PathBuf>,
+}
+
+#[derive(Args)]
+pub struct HashArgs {
+	/// PNG files to hash, or `-` for stdin
+	#[arg(required = true)]
+	pub filenames: Vec<PathBuf>,
+	#[arg(long, value_enum, default_value_t = HashAlgorithm::Sha256)]
+	pub algo: HashAlgorithm,
+	/// Hash the decoded pixels instead of the critical chunks, so that differently compressed
+	/// copies of an image match too
+	#[arg(long)]
+	pub pixels_only: bool,
+}
+
+#[derive(Args)]
+pub struct TrailerArgs {
+	/// PNG file to read, or `-` for stdin
+	pub filename: PathBuf,
+	/// Write the trailing data to this file (`-` for stdout)
+	#[arg(long, conflicts_with = "strip")]
+	pub extract: Option<PathBuf>,
+	/// Remove the trailing data from the file
+	#[arg(long)]
+	pub strip: bool,
+	/// With `--strip`, write the result here instead of overwriting the input file
+	#[arg(short, long, requires = "strip")]
+	pub output: Option<PathBuf>,
+}
+
+#[derive(Args)]
+pub struct ExportArgs {
+	/// PNG file to read, or `-` for stdin
+	pub filename: PathBuf,
+	/// JSON file to write, or `-` for stdout
+	pub json: PathBuf,
+}
+
+#[derive(Args)]
+pub struct ImportArgs {
+	/// JSON file to read, or `-` for stdin
+	pub json: PathBuf,
+	/// PNG file to write, or `-` for stdout
+	pub filename: PathBuf,
+}
+
+#[derive(Args)]
+pub struct DiffArgs {
+	pub old: PathBuf,
+	pub new: PathBuf,
+	/// Show which bytes changed in modified chunks
+	#[arg(long)]
+	pub bytes: bool,
+}
+
+#[derive(Args)]
+pub struct SameImageArgs {
+	pub first: PathBuf,
+	pub second: PathBuf,
+}
+
+#[derive(Args)]
+pub struct CopyArgs {
+	/// PNG file to copy chunks from, or `-` for stdin
+	#[arg(long)]
+	pub from: PathBuf,
+	/// PNG file to copy chunks into
+	#[arg(long)]
+	pub to: PathBuf,
+	#[arg(long = "type", value_parser = ChunkTypeParser, hide_possible_values = true)]
+	pub chunk_type: String,
+	/// Copy every matching chunk instead of only the first
+	#[arg(long)]
+	pub all: bool,
+	/// Copy the chunks even if their type is not safe to copy
+	// Not `--force`, which is the global option for replacing existing files.
+	#[arg(long)]
+	pub allow_unsafe: bool,
+	/// Write the result here instead of overwriting the destination file (`-` for stdout)
+	#[arg(short, long)]
+	pub output: Option<PathBuf>,
+}
+
+#[derive(Args)]
+pub struct CompletionsArgs {
+	pub shell: Shell,
+}
+
+#[derive(Args)]
+pub struct ManArgs {
+	/// Directory to write pngme.1 and a pngme-COMMAND.1 page for each command to
+	#[arg(long)]
+	pub out: Option<PathBuf>,
+}
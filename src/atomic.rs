@@ -0,0 +1,246 @@
+//! Writing files so that a crash never leaves one half-written.
+//!
+//! Output goes to `<file>.tmp.XXXX` next to the file, which is synced and then renamed over
+//! it, so the file is either intact or complete. `--no-atomic` writes the file directly, for
+//! filesystems where renaming over a file is not possible or not atomic.
+//!
+//! With `--backup`, the file being replaced is first copied to `<file>.bak`, or whatever suffix
+//! was given, for `pngme restore` to put back. With `--dry-run`, nothing is written at all.
+
+use std::{
+	collections::hash_map::RandomState,
+	fs::{self, File, OpenOptions},
+	hash::{BuildHasher, Hasher},
+	io::{self, Write},
+	path::{Path, PathBuf},
+	sync::{
+		atomic::{AtomicBool, Ordering},
+		OnceLock,
+	},
+};
+
+use crate::args::Cli;
+
+static ENABLED: AtomicBool = AtomicBool::new(true);
+static DRY_RUN: AtomicBool = AtomicBool::new(false);
+static FORCE: AtomicBool = AtomicBool::new(false);
+/// The suffix of backup files, if they are made.
+static BACKUP: OnceLock<String> = OnceLock::new();
+
+/// Decides once how files are written from the global options.
+pub fn init(cli: &Cli) {
+	ENABLED.store(!cli.no_atomic, Ordering::Relaxed);
+	DRY_RUN.store(cli.dry_run, Ordering::Relaxed);
+	FORCE.store(cli.force, Ordering::Relaxed);
+	if let Some(suffix) = cli.backup.clone() {
+		let _ = BACKUP.set(suffix);
+	}
+}
+
+/// Whether files are written atomically, as opposed to changed in place.
+pub fn is_enabled() -> bool {
+	ENABLED.load(Ordering::Relaxed)
+}
+
+/// Whether commands only report what they would write.
+pub fn is_dry_run() -> bool {
+	DRY_RUN.load(Ordering::Relaxed)
+}
+
+/// Whether existing files other than a command's input may be replaced.
+pub fn is_forced() -> bool {
+	FORCE.load(Ordering::Relaxed)
+}
+
+/// Where the backup of the file at `path` goes.
+pub fn backup_path(path: &Path, suffix: &str) -> PathBuf {
+	let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_owned());
+	let mut name = path.file_name().unwrap_or_default().to_owned();
+	name.push(suffix);
+	path.with_file_name(name)
+}
+
+/// Copies the file at `path` to its backup with `--backup`, unless there is no file yet.
+pub fn back_up(path: &Path) -> io::Result<()> {
+	match BACKUP.get() {
+		Some(suffix) if path.is_file() => {
+			let backup = backup_path(path, suffix);
+			log::debug!("backing up {} to {}", path.display(), backup.display());
+			fs::copy(path, backup).map(drop)
+		}
+		_ => Ok(()),
+	}
+}
+
+/// Calls `create` with `<file>.tmp.XXXX` paths next to the file at `path` until one does not
+/// exist yet, returning what it made along with the path.
+fn with_temp_path<T>(
+	path: &Path,
+	mut create: impl FnMut(&Path) -> io::Result<T>,
+) -> io::Result<(T, PathBuf)> {
+	let name = path.file_name().ok_or_else(|| {
+		io::Error::new(io::ErrorKind::InvalidInput, format!("{} is not a file", path.display()))
+	})?;
+	let mut attempts = 0;
+	loop {
+		let suffix = RandomState::new().build_hasher().finish() as u16;
+		let mut temp_name = name.to_owned();
+		temp_name.push(format!(".tmp.{:04x}", suffix));
+		let temp = path.with_file_name(temp_name);
+		match create(&temp) {
+			Ok(made) => return Ok((made, temp)),
+			Err(error) if error.kind() == io::ErrorKind::AlreadyExists && attempts < 16 => {
+				attempts += 1;
+			}
+			Err(error) => return Err(error),
+		}
+	}
+}
+
+/// Replaces the file at `path` with a hard link to `original`, through a temporary link that
+/// is renamed over it.
+pub fn replace_with_link(original: &Path, path: &Path) -> io::Result<()> {
+	back_up(path)?;
+	if !is_enabled() {
+		fs::remove_file(path)?;
+		return fs::hard_link(original, path);
+	}
+	let ((), temp) = with_temp_path(path, |temp| fs::hard_link(original, temp))?;
+	fs::rename(&temp, path).inspect_err(|_| {
+		let _ = fs::remove_file(&temp);
+	})
+}
+
+/// A file being written, which only replaces the file at its path once committed.
+pub struct AtomicFile {
+	file: File,
+	/// The temporary file, unless writing atomically is disabled.
+	temp: Option<PathBuf>,
+	path: PathBuf,
+}
+
+impl AtomicFile {
+	pub fn create(path: &Path) -> io::Result<Self> {
+		if !is_enabled() {
+			back_up(path)?;
+			return Ok(Self { file: File::create(path)?, temp: None, path: path.to_owned() });
+		}
+		// Replace the file a symlink points to, not the symlink.
+		let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_owned());
+		let (file, temp) = with_temp_path(&path, |temp| {
+			OpenOptions::new().write(true).create_new(true).open(temp)
+		})?;
+		if let Ok(metadata) = fs::metadata(&path) {
+			fs::set_permissions(&temp, metadata.permissions())?;
+		}
+		log::debug!("writing {} through {}", path.display(), temp.display());
+		Ok(Self { file, temp: Some(temp), path })
+	}
+
+	/// Syncs the file to disk and moves it into place.
+	pub fn commit(mut self) -> io::Result<()> {
+		self.file.sync_all()?;
+		let Some(temp) = self.temp.take() else { return Ok(()) };
+		if let Err(error) = back_up(&self.path).and_then(|()| fs::rename(&temp, &self.path)) {
+			let _ = fs::remove_file(&temp);
+			return Err(error);
+		}
+		// The rename itself is only durable once the directory is synced.
+		#[cfg(unix)]
+		if let Some(dir) = self.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+			File::open(dir)?.sync_all()?;
+		}
+		Ok(())
+	}
+}
+
+impl Write for AtomicFile {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		self.file.write(buf)
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		self.file.flush()
+	}
+}
+
+/// Removes the temporary file of a write that failed or was abandoned.
+impl Drop for AtomicFile {
+	fn drop(&mut self) {
+		if let Some(temp) = &self.temp {
+			let _ = fs::remove_file(temp);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::env;
+
+	use super::*;
+
+	/// An empty directory for the files of the test `name`.
+	fn dir(name: &str) -> PathBuf {
+		let dir = env::temp_dir().join(format!("pngme-atomic-{}-{}", name, std::process::id()));
+		let _ = fs::remove_dir_all(&dir);
+		fs::create_dir(&dir).unwrap();
+		dir
+	}
+
+	fn names(dir: &Path) -> Vec<String> {
+		let mut names: Vec<String> = fs::read_dir(dir)
+			.unwrap()
+			.map(|entry| entry.unwrap().file_name().into_string().unwrap())
+			.collect();
+		names.sort();
+		names
+	}
+
+	#[test]
+	fn test_commit() {
+		let dir = dir("commit");
+		let path = dir.join("a.png");
+		fs::write(&path, "old").unwrap();
+		let mut file = AtomicFile::create(&path).unwrap();
+		file.write_all(b"new").unwrap();
+		assert_eq!(fs::read(&path).unwrap(), b"old");
+		assert_eq!(names(&dir).len(), 2);
+		file.commit().unwrap();
+		assert_eq!(fs::read(&path).unwrap(), b"new");
+		assert_eq!(names(&dir), ["a.png"]);
+
+		let new = dir.join("b.png");
+		AtomicFile::create(&new).unwrap().commit().unwrap();
+		assert_eq!(names(&dir), ["a.png", "b.png"]);
+		fs::remove_dir_all(dir).unwrap();
+	}
+
+	#[test]
+	fn test_drop() {
+		let dir = dir("drop");
+		let path = dir.join("a.png");
+		fs::write(&path, "old").unwrap();
+		let mut file = AtomicFile::create(&path).unwrap();
+		file.write_all(b"new").unwrap();
+		drop(file);
+		assert_eq!(fs::read(&path).unwrap(), b"old");
+		assert_eq!(names(&dir), ["a.png"]);
+		fs::remove_dir_all(dir).unwrap();
+	}
+
+	#[cfg(unix)]
+	#[test]
+	fn test_permissions() {
+		use std::os::unix::fs::PermissionsExt;
+
+		let dir = dir("permissions");
+		let path = dir.join("a.png");
+		fs::write(&path, "old").unwrap();
+		fs::set_permissions(&path, fs::Permissions::from_mode(0o640)).unwrap();
+		let mut file = AtomicFile::create(&path).unwrap();
+		file.write_all(b"new").unwrap();
+		file.commit().unwrap();
+		assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o640);
+		fs::remove_dir_all(dir).unwrap();
+	}
+}
@@ -0,0 +1,224 @@
+//! Building PNGs chunk by chunk.
+//!
+//! ```
+//! use pngme::{ihdr::ColorType, IhdrData, PngBuilder};
+//!
+//! let header = IhdrData {
+//!     width: 1,
+//!     height: 1,
+//!     bit_depth: 8,
+//!     color_type: ColorType::Indexed,
+//!     compression_method: 0,
+//!     filter_method: 0,
+//!     interlace_method: 0,
+//! };
+//! let png = PngBuilder::new()
+//!     .header(header)
+//!     .palette(&[[255, 0, 0]])
+//!     .text("Author", "me")
+//!     .idat(vec![0x78, 0x9c, 0x63, 0x60, 0x00, 0x00, 0x00, 0x02, 0x00, 0x01])
+//!     .build()?;
+//! assert_eq!(png.chunks().len(), 5);
+//! # Ok::<(), pngme::Error>(())
+//! ```
+
+use crate::{
+	chunk::Chunk,
+	chunk_type::ChunkType,
+	ihdr::{ColorType, IhdrData},
+	ordering,
+	png::Png,
+	text::TextChunk,
+	Error, PngmeError, Result,
+};
+
+fn invalid(reason: impl Into<String>) -> Error {
+	PngmeError::invalid("PNG", reason)
+}
+
+/// Assembles a [`Png`] from its header, image data, and any other chunks.
+///
+/// Chunks are kept in the order they are added, between `IHDR` and an `IEND` that
+/// [`build`](PngBuilder::build) adds. Nothing is checked until then: `build` fails if the header
+/// or image data is missing, if the palette does not suit the color type, or if a chunk is
+/// somewhere the specification does not allow.
+#[derive(Debug, Default)]
+pub struct PngBuilder {
+	header: Option<IhdrData>,
+	chunks: Vec<Chunk>,
+	/// The first error from a method that could not make its chunk.
+	error: Option<Error>,
+}
+
+impl PngBuilder {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Sets the image header, replacing any set before.
+	pub fn header(mut self, header: IhdrData) -> Self {
+		self.header = Some(header);
+		self
+	}
+
+	/// Adds a `PLTE` chunk with the given RGB entries.
+	pub fn palette(self, entries: &[[u8; 3]]) -> Self {
+		let data = entries.iter().flatten().copied().collect();
+		self.chunk(Chunk::new(ChunkType::PLTE, data))
+	}
+
+	/// Adds an `IDAT` chunk holding (part of) the zlib stream of filtered scanlines.
+	pub fn idat(self, compressed: Vec<u8>) -> Self {
+		self.chunk(Chunk::new(ChunkType::IDAT, compressed))
+	}
+
+	/// Adds a `tEXt` chunk.
+	pub fn text(mut self, keyword: &str, text: &str) -> Self {
+		match TextChunk::new(keyword, text) {
+			Ok(text) => self.chunk(text.to_chunk()),
+			Err(error) => {
+				self.error.get_or_insert(error);
+				self
+			}
+		}
+	}
+
+	/// Adds any chunk.
+	pub fn chunk(mut self, chunk: Chunk) -> Self {
+		self.chunks.push(chunk);
+		self
+	}
+
+	/// Checks the chunks and puts them together.
+	pub fn build(self) -> Result<Png> {
+		if let Some(error) = self.error {
+			return Err(error);
+		}
+		let header = self.header.ok_or_else(|| invalid("no header was set"))?;
+		// Goes through the same checks as a header that was read.
+		let ihdr = header.to_chunk();
+		IhdrData::try_from(&ihdr)?;
+
+		let count = |chunk_type: &ChunkType| {
+			self.chunks.iter().filter(|chunk| chunk.chunk_type() == chunk_type).count()
+		};
+		if count(&ChunkType::IDAT) == 0 {
+			return Err(invalid("no image data was added"));
+		}
+		match (count(&ChunkType::PLTE), header.color_type) {
+			(0, ColorType::Indexed) => return Err(invalid("indexed images need a palette")),
+			(0 | 1, _) => {}
+			(_, _) => return Err(invalid("there is more than one palette")),
+		}
+		if let Some(palette) =
+			self.chunks.iter().find(|chunk| chunk.chunk_type() == &ChunkType::PLTE)
+		{
+			if matches!(header.color_type, ColorType::Grayscale | ColorType::GrayscaleAlpha) {
+				return Err(invalid(format!("{} images cannot have a palette", header.color_type)));
+			}
+			let entries = palette.data().len() / 3;
+			let limit = match header.color_type {
+				ColorType::Indexed => 1 << header.bit_depth.min(8),
+				_ => 256,
+			};
+			if entries == 0 || entries > limit {
+				return Err(invalid(format!(
+					"a palette of {} entries is not allowed, it needs 1 to {}",
+					entries, limit
+				)));
+			}
+		}
+
+		let mut chunks = Vec::with_capacity(self.chunks.len() + 2);
+		chunks.push(ihdr);
+		chunks.extend(self.chunks);
+		chunks.push(Chunk::new(ChunkType::IEND, Vec::new()));
+		let png = Png::from_chunks(chunks);
+		let mut misplaced: Vec<String> = ordering::misplaced_chunks(&png)
+			.into_iter()
+			.map(|n| png.chunks()[n].chunk_type().to_string())
+			.collect();
+		misplaced.dedup();
+		if !misplaced.is_empty() {
+			return Err(invalid(format!("chunks out of order: {}", misplaced.join(", "))));
+		}
+		Ok(png)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn header(color_type: ColorType) -> IhdrData {
+		IhdrData {
+			width: 1,
+			height: 1,
+			bit_depth: 8,
+			color_type,
+			compression_method: 0,
+			filter_method: 0,
+			interlace_method: 0,
+		}
+	}
+
+	fn types(png: &Png) -> Vec<String> {
+		png.chunks().iter().map(|chunk| chunk.chunk_type().to_string()).collect()
+	}
+
+	#[test]
+	fn test_build() {
+		let png = PngBuilder::new()
+			.header(header(ColorType::Rgb))
+			.text("Author", "me")
+			.idat(vec![1])
+			.idat(vec![2])
+			.chunk(Chunk::new("ruSt".parse().unwrap(), b"hi".to_vec()))
+			.build()
+			.unwrap();
+		assert_eq!(types(&png), ["IHDR", "tEXt", "IDAT", "IDAT", "ruSt", "IEND"]);
+		assert_eq!(png.header().unwrap(), header(ColorType::Rgb));
+	}
+
+	#[test]
+	fn test_required_chunks() {
+		let error = PngBuilder::new().idat(vec![1]).build().unwrap_err();
+		assert!(error.to_string().contains("no header"), "{}", error);
+		let error = PngBuilder::new().header(header(ColorType::Rgb)).build().unwrap_err();
+		assert!(error.to_string().contains("no image data"), "{}", error);
+		let error = PngBuilder::new().header(header(ColorType::Indexed)).idat(vec![1]).build();
+		assert!(error.unwrap_err().to_string().contains("need a palette"));
+	}
+
+	#[test]
+	fn test_invalid_chunks() {
+		let mut bad_header = header(ColorType::Rgb);
+		bad_header.bit_depth = 3;
+		assert!(PngBuilder::new().header(bad_header).idat(vec![1]).build().is_err());
+
+		let gray = PngBuilder::new().header(header(ColorType::Grayscale));
+		assert!(gray.palette(&[[0, 0, 0]]).idat(vec![1]).build().is_err());
+		let mut indexed = header(ColorType::Indexed);
+		indexed.bit_depth = 1;
+		let three = PngBuilder::new().header(indexed).palette(&[[0, 0, 0]; 3]);
+		assert!(three.idat(vec![1]).build().is_err());
+
+		let text = PngBuilder::new().header(header(ColorType::Rgb)).text("", "no keyword");
+		assert!(text.idat(vec![1]).build().is_err());
+	}
+
+	#[test]
+	fn test_ordering() {
+		let late_palette =
+			PngBuilder::new().header(header(ColorType::Indexed)).idat(vec![1]).palette(&[[0; 3]]);
+		let error = late_palette.build().unwrap_err();
+		assert!(error.to_string().contains("PLTE"), "{}", error);
+
+		let split = PngBuilder::new()
+			.header(header(ColorType::Rgb))
+			.idat(vec![1])
+			.text("Author", "me")
+			.idat(vec![2]);
+		assert!(split.build().is_err());
+	}
+}
@@ -0,0 +1,159 @@
+//! Rewriting PNGs into a canonical form, so that files with the same image, metadata, and
+//! payloads come out byte for byte the same.
+//!
+//! The image data is recompressed into a single `IDAT` chunk, `tIME` is dropped, textual
+//! chunks are stored uncompressed, as `tEXt` when Latin-1 can hold them and as `iTXt`
+//! otherwise, and the other chunks are sorted by where the specification places them, then by
+//! type and contents. The trailer is kept as it is.
+
+use crate::{
+	chunk::Chunk,
+	chunk_type::ChunkType,
+	idat,
+	ordering::Placement,
+	png::Png,
+	text::{ItxtChunk, TextChunk, TextualChunk},
+	zlib, PngmeError, Result,
+};
+
+/// The zlib compression level the image data is recompressed at.
+const LEVEL: u32 = 9;
+
+/// Rewrites `png` in its canonical form. Animated PNGs are refused, since their frame chunks
+/// have to stay in the order they were written.
+pub fn canonicalize(png: &mut Png) -> Result<()> {
+	if png.is_apng() {
+		return Err(PngmeError::invalid(
+			"PNG",
+			"animated PNGs cannot be canonicalized, their frames would be reordered",
+		));
+	}
+	let data = idat::decompressed_data(png)?;
+	png.replace_image_data(zlib::compress_with_level(&data, LEVEL), false);
+	png.retain_chunks(|chunk| chunk.chunk_type() != &ChunkType::tIME);
+	for n in 0..png.chunks().len() {
+		let Ok(text) = TextualChunk::try_from(&png.chunks()[n]) else {
+			continue;
+		};
+		let canonical = canonical_text(&text)?;
+		if canonical != png.chunks()[n] {
+			png.remove_chunk_at(n);
+			png.insert_chunk_at(n, canonical);
+		}
+	}
+	png.sort_chunks_by(|a, b| {
+		rank(a.chunk_type())
+			.cmp(&rank(b.chunk_type()))
+			.then_with(|| a.chunk_type().bytes().cmp(&b.chunk_type().bytes()))
+			.then_with(|| a.data().cmp(b.data()))
+	});
+	Ok(())
+}
+
+/// Returns `true` if `png` is already in its canonical form.
+pub fn is_canonical(png: &Png) -> Result<bool> {
+	let mut canonical = png.clone();
+	canonicalize(&mut canonical)?;
+	Ok(canonical.chunks() == png.chunks())
+}
+
+/// Where chunks of a type go relative to the others. Chunks that may go anywhere are put after
+/// the image data, where new chunks are added by default.
+fn rank(chunk_type: &ChunkType) -> u8 {
+	match Placement::of(chunk_type) {
+		Placement::First => 0,
+		Placement::BeforePalette => 1,
+		Placement::Palette => 2,
+		Placement::AfterPalette => 3,
+		Placement::BeforeImageData => 4,
+		Placement::ImageData => 5,
+		Placement::Anywhere => 6,
+		Placement::Last => 7,
+	}
+}
+
+/// Stores the text uncompressed, in a `tEXt` chunk unless it needs what only `iTXt` has.
+fn canonical_text(text: &TextualChunk) -> Result<Chunk> {
+	let (language_tag, translated_keyword) = match text {
+		TextualChunk::Itxt(itxt) => (itxt.language_tag(), itxt.translated_keyword()),
+		_ => ("", ""),
+	};
+	if language_tag.is_empty() && translated_keyword.is_empty() {
+		if let Ok(latin1) = TextChunk::new(text.keyword(), text.text()) {
+			return Ok(latin1.to_chunk());
+		}
+	}
+	let itxt =
+		ItxtChunk::new(text.keyword(), language_tag, translated_keyword, text.text(), false)?;
+	Ok(itxt.to_chunk())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		ihdr::{ColorType, IhdrData},
+		text::ZtxtChunk,
+		PngBuilder,
+	};
+
+	fn header() -> IhdrData {
+		IhdrData {
+			width: 2,
+			height: 1,
+			bit_depth: 8,
+			color_type: ColorType::Grayscale,
+			compression_method: 0,
+			filter_method: 0,
+			interlace_method: 0,
+		}
+	}
+
+	fn types(png: &Png) -> Vec<String> {
+		png.chunks().iter().map(|chunk| chunk.chunk_type().to_string()).collect()
+	}
+
+	#[test]
+	fn test_canonicalize() {
+		let compressed = zlib::compress_with_level(&[0, 10, 20], 1);
+		let (first, second) = compressed.split_at(4);
+		let png = PngBuilder::new()
+			.header(header())
+			.chunk(Chunk::new(ChunkType::tIME, vec![7, 234, 1, 1, 0, 0, 0]))
+			.chunk(Chunk::new(ChunkType::gAMA, 45455u32.to_be_bytes().to_vec()))
+			.text("Title", "z")
+			.chunk(ZtxtChunk::new("Author", "me").unwrap().to_chunk())
+			.idat(first.to_vec())
+			.idat(second.to_vec())
+			.chunk(ItxtChunk::new("Comment", "", "", "ünïcødé ✓", true).unwrap().to_chunk())
+			.chunk(Chunk::new("ruSt".parse().unwrap(), b"payload".to_vec()))
+			.build()
+			.unwrap();
+
+		let mut canonical = png.clone();
+		canonicalize(&mut canonical).unwrap();
+		assert_eq!(
+			types(&canonical),
+			["IHDR", "gAMA", "IDAT", "iTXt", "ruSt", "tEXt", "tEXt", "IEND"]
+		);
+		let texts: Vec<_> = canonical.chunks()[5..7]
+			.iter()
+			.map(|chunk| TextualChunk::try_from(chunk).unwrap().keyword().to_owned())
+			.collect();
+		assert_eq!(texts, ["Author", "Title"]);
+		let itxt = ItxtChunk::try_from(&canonical.chunks()[3]).unwrap();
+		assert!(!itxt.is_compressed());
+		assert_eq!(idat::decompressed_data(&canonical).unwrap(), [0, 10, 20]);
+		assert!(is_canonical(&canonical).unwrap());
+		assert!(!is_canonical(&png).unwrap());
+
+		// The same chunks in another order come out the same.
+		let (idat, mut others): (Vec<Chunk>, Vec<Chunk>) =
+			png.chunks().iter().cloned().partition(|chunk| chunk.chunk_type() == &ChunkType::IDAT);
+		others.reverse();
+		others.extend(idat);
+		let mut shuffled = Png::from_chunks(others);
+		canonicalize(&mut shuffled).unwrap();
+		assert_eq!(shuffled.as_bytes(), canonical.as_bytes());
+	}
+}
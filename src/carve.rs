@@ -0,0 +1,114 @@
+//! Recovering PNG files embedded in arbitrary data, such as disk images, archives, or other
+//! PNGs.
+//!
+//! Every PNG signature is a candidate. A candidate is only carved out if it starts with an
+//! `IHDR` chunk and every chunk up to and including `IEND` has a valid type and CRC, which
+//! rules out signatures that merely occur by chance. Candidates may overlap, so PNGs hidden
+//! inside carved PNGs are found as well.
+
+use crate::{
+	chunk::{compute_crc, Chunk},
+	chunk_type::ChunkType,
+	png::Png,
+};
+
+/// A PNG found inside a larger piece of data.
+pub struct Carved {
+	/// Where the PNG signature starts.
+	pub offset: usize,
+	/// How many bytes the PNG takes up, through the end of `IEND`.
+	pub len: usize,
+	pub png: Png,
+}
+
+/// Finds every complete PNG in `data`, in order of offset.
+pub fn carve(data: &[u8]) -> Vec<Carved> {
+	let signature = &Png::STANDARD_HEADER;
+	(0..data.len().saturating_sub(signature.len() - 1))
+		.filter(|&offset| data[offset..].starts_with(signature))
+		.filter_map(|offset| {
+			let len = png_length(&data[offset..])?;
+			let png = Png::try_from(&data[offset..offset + len]).ok()?;
+			Some(Carved { offset, len, png })
+		})
+		.collect()
+}
+
+/// Walks the chunks of the PNG at the start of `data`, returning its length if it is complete.
+fn png_length(data: &[u8]) -> Option<usize> {
+	let mut offset = Png::STANDARD_HEADER.len();
+	loop {
+		let header = data.get(offset..offset + 8)?;
+		let length = u32::from_be_bytes(header[..4].try_into().unwrap());
+		if length > Chunk::MAX_LENGTH {
+			return None;
+		}
+		let chunk_type = ChunkType::try_from(<[u8; 4]>::try_from(&header[4..]).unwrap()).ok()?;
+		if offset == Png::STANDARD_HEADER.len() && chunk_type != ChunkType::IHDR {
+			return None;
+		}
+		let end = offset + 12 + length as usize;
+		let chunk = data.get(offset..end)?;
+		let crc = u32::from_be_bytes(chunk[chunk.len() - 4..].try_into().unwrap());
+		if compute_crc(&chunk[4..chunk.len() - 4]) != crc {
+			return None;
+		}
+		if chunk_type == ChunkType::IEND {
+			return Some(end);
+		}
+		offset = end;
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn testing_png(payload: &[u8]) -> Vec<u8> {
+		Png::from_chunks(vec![
+			Chunk::new(ChunkType::IHDR, vec![0, 0, 0, 1, 0, 0, 0, 1, 8, 0, 0, 0, 0]),
+			Chunk::new("ruSt".parse().unwrap(), payload.to_vec()),
+			Chunk::new(ChunkType::IEND, Vec::new()),
+		])
+		.as_bytes()
+	}
+
+	#[test]
+	fn test_carve() {
+		let first = testing_png(b"first");
+		let second = testing_png(b"second");
+		let mut blob = b"garbage".to_vec();
+		blob.extend(&first);
+		blob.extend(b"more garbage");
+		blob.extend(&second);
+		blob.extend(b"trailing");
+
+		let carved = carve(&blob);
+		let found: Vec<_> = carved.iter().map(|c| (c.offset, c.len)).collect();
+		assert_eq!(found, [(7, first.len()), (7 + first.len() + 12, second.len())]);
+		assert_eq!(carved[1].png.chunk_by_type("ruSt").unwrap().data(), b"second");
+	}
+
+	#[test]
+	fn test_nested() {
+		let inner = testing_png(b"inner");
+		let outer = testing_png(&inner);
+		let carved = carve(&outer);
+		assert_eq!(carved.len(), 2);
+		assert_eq!(carved[0].len, outer.len());
+		assert_eq!(carved[1].len, inner.len());
+	}
+
+	#[test]
+	fn test_rejects_broken_candidates() {
+		let png = testing_png(b"payload");
+		let mut corrupted = png.clone();
+		corrupted[png.len() - 20] ^= 1;
+		let truncated = &png[..png.len() - 1];
+		let mut blob = Png::STANDARD_HEADER.to_vec();
+		blob.extend(&corrupted);
+		blob.extend(truncated);
+		assert!(carve(&blob).is_empty());
+		assert!(carve(b"").is_empty());
+	}
+}
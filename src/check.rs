@@ -0,0 +1,233 @@
+//! Structural validation of PNG files.
+//!
+//! Unlike [`Png::try_from`], checking does not stop at the first problem: it walks the raw
+//! bytes and reports everything it can find.
+
+use std::fmt;
+
+use crate::{
+	analysis,
+	chunk::{compute_crc, Chunk},
+	chunk_type::ChunkType,
+	ihdr::{ColorType, IhdrData},
+	ordering,
+	png::{FileKind, Png},
+};
+
+/// A single problem found in a file, located by the byte offset where it starts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Problem {
+	pub offset: u64,
+	pub message: String,
+}
+
+impl fmt::Display for Problem {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{:#010x}: {}", self.offset, self.message)
+	}
+}
+
+/// Checks the signature, chunk framing, CRCs, and critical chunk layout of the PNG in `bytes`.
+/// An empty result means the file is structurally valid.
+pub fn check(bytes: &[u8]) -> Vec<Problem> {
+	let mut problems = Vec::new();
+	let mut problem =
+		|offset: usize, message: String| problems.push(Problem { offset: offset as u64, message });
+
+	if !bytes.starts_with(&Png::STANDARD_HEADER) {
+		let message = match Png::sniff(bytes) {
+			FileKind::Unknown => "missing or damaged PNG signature".to_owned(),
+			FileKind::Empty | FileKind::TruncatedPng => {
+				"the file ends inside the PNG signature".to_owned()
+			}
+			kind => format!("missing or damaged PNG signature; this is {}", kind),
+		};
+		problem(0, message);
+	}
+
+	let mut chunks = Vec::new();
+	let mut offsets = Vec::new();
+	let mut offset = Png::STANDARD_HEADER.len().min(bytes.len());
+	while offset < bytes.len() {
+		let rest = &bytes[offset..];
+		if rest.len() < 12 {
+			problem(offset, format!("{} trailing bytes are too short to be a chunk", rest.len()));
+			break;
+		}
+		let length = u32::from_be_bytes(rest[0..4].try_into().unwrap());
+		let type_bytes: [u8; 4] = rest[4..8].try_into().unwrap();
+		let Ok(chunk_type) = ChunkType::try_from(type_bytes) else {
+			problem(
+				offset,
+				format!("invalid chunk type {:?}", String::from_utf8_lossy(&type_bytes)),
+			);
+			break;
+		};
+		if length > Chunk::MAX_LENGTH {
+			problem(offset, format!("{} chunk length {} exceeds 2^31 - 1", chunk_type, length));
+			break;
+		}
+		let end = 12 + length as usize;
+		if rest.len() < end {
+			problem(
+				offset,
+				format!(
+					"{} chunk claims {} bytes of data but only {} remain",
+					chunk_type,
+					length,
+					rest.len() - 12
+				),
+			);
+			break;
+		}
+		if !chunk_type.is_reserved_bit_valid() {
+			problem(offset, format!("{} chunk has the reserved bit set", chunk_type));
+		}
+		let stored = u32::from_be_bytes(rest[end - 4..end].try_into().unwrap());
+		let computed = compute_crc(&rest[4..end - 4]);
+		if stored != computed {
+			problem(
+				offset,
+				format!(
+					"{} chunk CRC is 0x{:08x}, expected 0x{:08x}",
+					chunk_type, stored, computed
+				),
+			);
+		}
+
+		let is_iend = chunk_type == ChunkType::IEND;
+		chunks.push(Chunk::new(chunk_type, rest[8..end - 4].to_vec()));
+		offsets.push(offset);
+		offset += end;
+		if is_iend && offset < bytes.len() {
+			let trailer = &bytes[offset..];
+			problem(
+				offset,
+				format!(
+					"{} bytes of trailing data after IEND (entropy {:.2} bits/byte)",
+					trailer.len(),
+					analysis::entropy(trailer)
+				),
+			);
+			break;
+		}
+	}
+
+	let count = |name: &[u8; 4]| chunks.iter().filter(|c| &c.chunk_type().bytes() == name).count();
+	for name in ["IHDR", "IDAT", "IEND"] {
+		if count(&name.as_bytes().try_into().unwrap()) == 0 {
+			problem(offset, format!("missing {} chunk", name));
+		}
+	}
+	for (n, chunk) in chunks.iter().enumerate() {
+		let name = chunk.chunk_type().bytes();
+		let seen_before = chunks[..n].iter().any(|c| c.chunk_type().bytes() == name);
+		if matches!(&name, b"IHDR" | b"PLTE" | b"IEND" | b"iCCP") && seen_before {
+			problem(offsets[n], format!("duplicate {} chunk", chunk.chunk_type()));
+		}
+	}
+
+	if let Some(ihdr) = chunks.first().and_then(|chunk| IhdrData::try_from(chunk).ok()) {
+		let has_palette = count(b"PLTE") > 0;
+		if ihdr.color_type == ColorType::Indexed && !has_palette {
+			problem(offsets[0], "indexed image has no PLTE chunk".to_owned());
+		}
+		if matches!(ihdr.color_type, ColorType::Grayscale | ColorType::GrayscaleAlpha)
+			&& has_palette
+		{
+			problem(offsets[0], format!("{} image must not have a PLTE chunk", ihdr.color_type));
+		}
+	} else if let Some(first) = chunks.first().filter(|c| c.chunk_type().bytes() == IhdrData::TYPE)
+	{
+		if let Err(e) = IhdrData::try_from(first) {
+			problem(offsets[0], e.to_string());
+		}
+	}
+
+	let png = Png::from_chunks(chunks);
+	for n in ordering::misplaced_chunks(&png) {
+		problem(offsets[n], format!("{} chunk is out of order", png.chunks()[n].chunk_type()));
+	}
+
+	problems.sort_by_key(|problem| problem.offset);
+	problems
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::ihdr::ColorType;
+
+	fn ihdr() -> Chunk {
+		IhdrData {
+			width: 1,
+			height: 1,
+			bit_depth: 8,
+			color_type: ColorType::Rgb,
+			compression_method: 0,
+			filter_method: 0,
+			interlace_method: 0,
+		}
+		.to_chunk()
+	}
+
+	fn chunk(chunk_type: &str) -> Chunk {
+		Chunk::new(chunk_type.parse().unwrap(), b"data".to_vec())
+	}
+
+	fn file(chunks: Vec<Chunk>) -> Vec<u8> {
+		Png::from_chunks(chunks).as_bytes()
+	}
+
+	#[test]
+	fn test_valid_file() {
+		assert!(check(&file(vec![ihdr(), chunk("IDAT"), chunk("IEND")])).is_empty());
+	}
+
+	#[test]
+	fn test_bad_signature() {
+		let mut bytes = file(vec![ihdr(), chunk("IDAT"), chunk("IEND")]);
+		bytes[1] = b'X';
+		assert_eq!(check(&bytes)[0].offset, 0);
+	}
+
+	#[test]
+	fn test_bad_crc() {
+		let mut bytes = file(vec![ihdr(), chunk("IDAT"), chunk("IEND")]);
+		bytes[33 + 8] ^= 1;
+		let problems = check(&bytes);
+		assert_eq!(problems.len(), 1);
+		assert_eq!(problems[0].offset, 33);
+		assert!(problems[0].message.contains("CRC"));
+	}
+
+	#[test]
+	fn test_truncated_chunk() {
+		let bytes = file(vec![ihdr(), chunk("IDAT"), chunk("IEND")]);
+		let problems = check(&bytes[..bytes.len() - 18]);
+		assert!(problems.iter().any(|p| p.offset == 33 && p.message.contains("only")));
+		assert!(problems.iter().any(|p| p.message == "missing IEND chunk"));
+	}
+
+	#[test]
+	fn test_trailing_data() {
+		let mut bytes = file(vec![ihdr(), chunk("IDAT"), chunk("IEND")]);
+		let len = bytes.len();
+		bytes.extend_from_slice(b"abcc");
+		assert_eq!(
+			check(&bytes),
+			[Problem {
+				offset: len as u64,
+				message: "4 bytes of trailing data after IEND (entropy 1.50 bits/byte)".into()
+			}]
+		);
+	}
+
+	#[test]
+	fn test_layout_problems() {
+		let problems = check(&file(vec![chunk("IDAT"), ihdr(), ihdr(), chunk("IEND")]));
+		let messages: Vec<&str> = problems.iter().map(|p| p.message.as_str()).collect();
+		assert!(messages.contains(&"duplicate IHDR chunk"));
+		assert!(messages.contains(&"IHDR chunk is out of order"));
+	}
+}
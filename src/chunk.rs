@@ -1,38 +1,89 @@
 use core::fmt;
-use std::{mem, str, sync::OnceLock};
-
-use crate::{chunk_type::ChunkType, Error, Result};
-
+use std::{
+	hash::{Hash, Hasher},
+	io::{self, Read, Write},
+	mem, str,
+	sync::OnceLock,
+};
+
+use crate::{
+	chunk_type::ChunkType,
+	hexdump::{self, HexDump},
+	Error, PngmeError, Result,
+};
+
+/// A single PNG chunk: its type, data, and CRC.
+///
+/// Chunks compare and hash by their type, data, and CRC; where they were read from does not
+/// matter.
+#[derive(Clone)]
 pub struct Chunk {
 	chunk_type: ChunkType,
 	data: Vec<u8>,
 	crc: u32,
+	offset: Option<u64>,
 }
 
 impl Chunk {
-	pub fn new(chunk_type: ChunkType, data: Vec<u8>) -> Self {
-		let mut bytes = Vec::with_capacity(chunk_type.bytes().len() + data.len());
-		bytes.extend_from_slice(&chunk_type.bytes());
-		bytes.extend_from_slice(&data);
-		let crc = compute_crc(&bytes);
+	/// The largest chunk data length the PNG specification allows.
+	pub const MAX_LENGTH: u32 = i32::MAX as u32;
 
-		Self { chunk_type, data, crc }
+	/// Creates a chunk of the given type, computing its CRC.
+	pub fn new(chunk_type: ChunkType, data: Vec<u8>) -> Self {
+		let crc = chunk_crc(&chunk_type, &data);
+		Self::with_crc(chunk_type, data, crc)
+	}
+	/// Creates a chunk with a CRC that was read along with it, without computing it again.
+	fn with_crc(chunk_type: ChunkType, data: Vec<u8>, crc: u32) -> Self {
+		Self { chunk_type, data, crc, offset: None }
 	}
+	/// Returns the length of the chunk data in bytes.
 	pub fn length(&self) -> u32 {
 		self.data.len() as u32
 	}
+	/// Returns the chunk type.
 	pub fn chunk_type(&self) -> &ChunkType {
 		&self.chunk_type
 	}
+	/// Returns the chunk data.
 	pub fn data(&self) -> &[u8] {
 		&self.data
 	}
+	/// Returns the CRC of the chunk: computed for chunks created in memory, as stored in the
+	/// file for parsed ones.
 	pub fn crc(&self) -> u32 {
 		self.crc
 	}
+	/// Returns `true` if the stored CRC matches the chunk type and data.
+	pub fn has_valid_crc(&self) -> bool {
+		self.computed_crc() == self.crc
+	}
+	fn computed_crc(&self) -> u32 {
+		chunk_crc(&self.chunk_type, &self.data)
+	}
+	/// Returns the byte offset of the chunk in the file it was parsed from, or `None` if the
+	/// chunk was created in memory.
+	pub fn offset(&self) -> Option<u64> {
+		self.offset
+	}
+	pub(crate) fn with_offset(self, offset: u64) -> Self {
+		Self { offset: Some(offset), ..self }
+	}
+	/// Returns the chunk data as a string, failing if it is not valid UTF-8.
 	pub fn data_as_string(&self) -> Result<String> {
 		Ok(str::from_utf8(self.data())?.to_owned())
 	}
+	/// Returns the chunk data as a string, replacing bytes that are not valid UTF-8 with
+	/// U+FFFD.
+	pub fn data_as_string_lossy(&self) -> String {
+		String::from_utf8_lossy(self.data()).into_owned()
+	}
+	/// Returns the chunk data as a string with control characters and bytes that are not valid
+	/// UTF-8 written as `\xNN`; see [`hexdump::escape`].
+	pub fn data_as_string_escaped(&self) -> String {
+		hexdump::escape(self.data())
+	}
+	/// Serializes the chunk as length, type, data, and CRC.
 	pub fn as_bytes(&self) -> Vec<u8> {
 		(self.length().to_be_bytes().iter())
 			.chain(self.chunk_type.bytes().iter())
@@ -41,13 +92,161 @@ impl Chunk {
 			.copied()
 			.collect()
 	}
+	/// Parses a chunk like [`Chunk::try_from`], but keeps the stored CRC instead of failing
+	/// when it does not match; see [`Chunk::has_valid_crc`].
+	pub fn try_from_lenient(bytes: &[u8]) -> Result<Self> {
+		ChunkRef::try_from_lenient(bytes).map(ChunkRef::into_chunk)
+	}
+	/// Reads the next chunk from `reader`, or returns `None` if the input is exhausted.
+	pub fn from_reader(reader: impl Read) -> Result<Option<Self>> {
+		Self::read_bytes(reader)?.map(|bytes| Self::try_from(bytes.as_slice())).transpose()
+	}
+	/// Reads the next chunk from `reader` like [`Chunk::from_reader`], tolerating a bad CRC.
+	pub fn from_reader_lenient(reader: impl Read) -> Result<Option<Self>> {
+		Self::read_bytes(reader)?.map(|bytes| Self::try_from_lenient(&bytes)).transpose()
+	}
+	/// Reads the bytes of the next chunk, fewer than its length says if the input ends early.
+	pub(crate) fn read_bytes(mut reader: impl Read) -> Result<Option<Vec<u8>>> {
+		let mut bytes = Vec::new();
+		(&mut reader).take(mem::size_of::<u32>() as u64).read_to_end(&mut bytes)?;
+		// A partial length is left for the parser to report, as it would be in a slice.
+		let Ok(len) = <[u8; 4]>::try_from(bytes.as_slice()) else {
+			return Ok((!bytes.is_empty()).then_some(bytes));
+		};
+
+		let rest = u32::from_be_bytes(len) as u64 + mem::size_of::<[u32; 2]>() as u64;
+		reader.take(rest).read_to_end(&mut bytes)?;
+		Ok(Some(bytes))
+	}
+	/// Writes the chunk as length, type, data, and CRC.
+	pub fn write_to(&self, mut writer: impl Write) -> io::Result<()> {
+		writer.write_all(&self.length().to_be_bytes())?;
+		writer.write_all(&self.chunk_type.bytes())?;
+		writer.write_all(&self.data)?;
+		writer.write_all(&self.crc().to_be_bytes())
+	}
+}
+
+/// A chunk parsed in place: its data borrows from the buffer it was parsed from.
+///
+/// Parsing a [`Png`](crate::Png) from a buffer goes through `ChunkRef`s, so each chunk's data
+/// is copied once, when it is turned into a [`Chunk`], and its CRC is checked once.
+#[derive(Clone)]
+pub struct ChunkRef<'a> {
+	chunk_type: ChunkType,
+	data: &'a [u8],
+	crc: u32,
+	offset: Option<u64>,
+}
+
+impl<'a> ChunkRef<'a> {
+	/// Returns the length of the chunk data in bytes.
+	pub fn length(&self) -> u32 {
+		self.data.len() as u32
+	}
+	/// Returns the chunk type.
+	pub fn chunk_type(&self) -> &ChunkType {
+		&self.chunk_type
+	}
+	/// Returns the chunk data.
+	pub fn data(&self) -> &'a [u8] {
+		self.data
+	}
+	/// Returns the CRC stored in the file.
+	pub fn crc(&self) -> u32 {
+		self.crc
+	}
+	/// Returns `true` if the stored CRC matches the chunk type and data.
+	pub fn has_valid_crc(&self) -> bool {
+		chunk_crc(&self.chunk_type, self.data) == self.crc
+	}
+	/// Fails with [`PngmeError::CrcMismatch`] unless [`ChunkRef::has_valid_crc`].
+	pub fn verify_crc(&self) -> Result<()> {
+		let computed = chunk_crc(&self.chunk_type, self.data);
+		log::trace!("{} chunk CRC {:08x}, computed {:08x}", self.chunk_type, self.crc, computed);
+		if computed != self.crc {
+			return Err(PngmeError::CrcMismatch {
+				expected: self.crc,
+				computed,
+				offset: self.offset,
+			});
+		}
+		Ok(())
+	}
+	/// Returns the byte offset of the chunk in the file it was parsed from, if known.
+	pub fn offset(&self) -> Option<u64> {
+		self.offset
+	}
+	pub(crate) fn with_offset(self, offset: u64) -> Self {
+		Self { offset: Some(offset), ..self }
+	}
+	/// Returns the chunk data as a string, failing if it is not valid UTF-8.
+	pub fn data_as_str(&self) -> Result<&'a str> {
+		Ok(str::from_utf8(self.data)?)
+	}
+	/// Copies the chunk into an owned [`Chunk`], keeping its stored CRC and offset.
+	pub fn into_chunk(self) -> Chunk {
+		Chunk {
+			offset: self.offset,
+			..Chunk::with_crc(self.chunk_type, self.data.to_vec(), self.crc)
+		}
+	}
+	/// Parses a chunk like [`ChunkRef::try_from`], but keeps the stored CRC instead of failing
+	/// when it does not match; see [`ChunkRef::has_valid_crc`].
+	pub fn try_from_lenient(bytes: &'a [u8]) -> Result<Self> {
+		let Some((len, bytes)) = bytes.split_first_chunk::<4>() else {
+			return Err(PngmeError::invalid("chunk", "cut off inside its length"));
+		};
+		let len = u32::from_be_bytes(*len);
+
+		if len as usize + mem::size_of::<(u32, u32)>() != bytes.len() {
+			return Err(PngmeError::InvalidChunkLength {
+				expected: len,
+				received: bytes.len().saturating_sub(mem::size_of::<(u32, u32)>()) as u32,
+			});
+		}
+
+		let (bytes, crc) = bytes.split_at(bytes.len() - 4);
+		let (chunk_type, data) = bytes.split_at(4);
+		let chunk_type = ChunkType::try_from(<[u8; 4]>::try_from(chunk_type)?)?;
+		let crc = u32::from_be_bytes(crc.try_into()?);
+
+		Ok(Self { chunk_type, data, crc, offset: None })
+	}
 }
 
-fn compute_crc(bytes: &[u8]) -> u32 {
-	static CRC_TABLE: OnceLock<[u32; 256]> = OnceLock::new();
-	let crc_table = CRC_TABLE.get_or_init(|| {
-		let mut buf = [0; 256];
-		buf.iter_mut().enumerate().for_each(|(n, x)| {
+impl<'a> TryFrom<&'a [u8]> for ChunkRef<'a> {
+	type Error = Error;
+	fn try_from(bytes: &'a [u8]) -> Result<Self> {
+		let chunk = Self::try_from_lenient(bytes)?;
+		chunk.verify_crc()?;
+		Ok(chunk)
+	}
+}
+
+impl<'a> From<ChunkRef<'a>> for Chunk {
+	fn from(chunk: ChunkRef<'a>) -> Self {
+		chunk.into_chunk()
+	}
+}
+
+/// The CRC of a chunk, which covers its type and data.
+fn chunk_crc(chunk_type: &ChunkType, data: &[u8]) -> u32 {
+	update_crc(update_crc(0xffffffff, &chunk_type.bytes()), data) ^ 0xffffffff
+}
+
+pub(crate) fn compute_crc(bytes: &[u8]) -> u32 {
+	update_crc(0xffffffff, bytes) ^ 0xffffffff
+}
+
+/// Feeds `bytes` into a running CRC, eight bytes at a time ("slicing-by-8"): table `k` gives
+/// the CRC contribution of a byte followed by `k` zero bytes, so eight lookups stand in for
+/// eight rounds of the byte-at-a-time loop.
+fn update_crc(mut crc: u32, bytes: &[u8]) -> u32 {
+	static CRC_TABLES: OnceLock<[[u32; 256]; 8]> = OnceLock::new();
+	let tables = CRC_TABLES.get_or_init(|| {
+		let mut tables = [[0; 256]; 8];
+		tables[0].iter_mut().enumerate().for_each(|(n, x)| {
 			let mut c = n as u32;
 			for _ in 0..8 {
 				if (c & 1) != 0 {
@@ -58,71 +257,113 @@ fn compute_crc(bytes: &[u8]) -> u32 {
 			}
 			*x = c;
 		});
-		buf
+		for k in 1..tables.len() {
+			for n in 0..256 {
+				let previous = tables[k - 1][n];
+				tables[k][n] = (previous >> 8) ^ tables[0][(previous & 0xff) as usize];
+			}
+		}
+		tables
 	});
-
-	let mut crc = 0xffffffff;
-	for byte in bytes {
-		crc = crc_table[((crc ^ *byte as u32) & 0xff) as usize] ^ (crc >> 8);
+	let lookup = |k: usize, word: u32, shift: u32| tables[k][((word >> shift) & 0xff) as usize];
+
+	let mut words = bytes.chunks_exact(8);
+	for word in &mut words {
+		let (low, high) = word.split_at(4);
+		let low = crc ^ u32::from_le_bytes(low.try_into().unwrap());
+		let high = u32::from_le_bytes(high.try_into().unwrap());
+		crc = lookup(7, low, 0)
+			^ lookup(6, low, 8)
+			^ lookup(5, low, 16)
+			^ lookup(4, low, 24)
+			^ lookup(3, high, 0)
+			^ lookup(2, high, 8)
+			^ lookup(1, high, 16)
+			^ lookup(0, high, 24);
 	}
-	crc ^ 0xffffffff
+	for byte in words.remainder() {
+		crc = tables[0][((crc ^ *byte as u32) & 0xff) as usize] ^ (crc >> 8);
+	}
+	crc
 }
 
-#[derive(Debug)]
-struct InvalidChunkLength {
-	expected: u32,
-	received: u32,
+impl TryFrom<&[u8]> for Chunk {
+	type Error = Error;
+	fn try_from(bytes: &[u8]) -> Result<Self> {
+		ChunkRef::try_from(bytes).map(Self::from)
+	}
 }
-impl std::error::Error for InvalidChunkLength {}
-impl fmt::Display for InvalidChunkLength {
+
+/// Shows the data as text, or as a hex dump when it is not UTF-8.
+impl fmt::Display for Chunk {
 	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-		write!(f, "Invalid chunk length: expected {}, got {}", self.expected, self.received)
+		match str::from_utf8(self.data()) {
+			Ok(text) => f.write_str(text),
+			Err(_) => HexDump::new(self.data(), 0).fmt(f),
+		}
 	}
 }
 
-#[derive(Debug)]
-struct InvalidChunkCrc {
-	expected: u32,
-	computed: u32,
+impl PartialEq for Chunk {
+	fn eq(&self, other: &Self) -> bool {
+		self.chunk_type == other.chunk_type && self.crc == other.crc && self.data == other.data
+	}
 }
-impl std::error::Error for InvalidChunkCrc {}
-impl fmt::Display for InvalidChunkCrc {
-	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-		write!(f, "Invalid chunk crc: expected 0x{:x}, got 0x{:x}", self.expected, self.computed)
+
+impl Eq for Chunk {}
+
+impl Hash for Chunk {
+	fn hash<H: Hasher>(&self, state: &mut H) {
+		self.chunk_type.hash(state);
+		self.data.hash(state);
+		self.crc.hash(state);
 	}
 }
 
-impl TryFrom<&[u8]> for Chunk {
-	type Error = Error;
-	fn try_from(bytes: &[u8]) -> Result<Self> {
-		let (len, bytes) = bytes.split_at(4);
-		let len = u32::from_be_bytes(len.try_into()?);
+impl fmt::Debug for Chunk {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		debug_chunk(f, "Chunk", &self.chunk_type, &self.data, self.crc, self.offset)
+	}
+}
 
-		if len as usize + mem::size_of::<(u32, u32)>() != bytes.len() {
-			return Err(InvalidChunkLength {
-				expected: len,
-				received: (bytes.len() - mem::size_of::<(u32, u32)>()) as u32,
-			}
-			.into());
-		}
+impl fmt::Debug for ChunkRef<'_> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		debug_chunk(f, "ChunkRef", &self.chunk_type, self.data, self.crc, self.offset)
+	}
+}
 
-		let (bytes, crc) = bytes.split_at(bytes.len() - 4);
-		let computed_crc = compute_crc(bytes);
-		let crc = u32::from_be_bytes(crc.try_into()?);
-		if computed_crc != crc {
-			return Err(InvalidChunkCrc { expected: crc, computed: computed_crc }.into());
-		}
+fn debug_chunk(
+	f: &mut fmt::Formatter<'_>,
+	name: &str,
+	chunk_type: &ChunkType,
+	data: &[u8],
+	crc: u32,
+	offset: Option<u64>,
+) -> fmt::Result {
+	f.debug_struct(name)
+		.field("chunk_type", chunk_type)
+		.field("data", &DataPreview(data))
+		.field("crc", &format_args!("{:#010x}", crc))
+		.field("offset", &offset)
+		.finish()
+}
 
-		let (chunk_type, chunk_data) = bytes.split_at(4);
-		let chunk_type = ChunkType::try_from(<[u8; 4]>::try_from(chunk_type)?)?;
+/// Formats bytes for `Debug` as an escaped byte string, cut short after the first
+/// [`DataPreview::LEN`] bytes so that printing an image does not print all of its pixels.
+pub(crate) struct DataPreview<'a>(pub(crate) &'a [u8]);
 
-		Ok(Self::new(chunk_type, chunk_data.to_owned()))
-	}
+impl DataPreview<'_> {
+	const LEN: usize = 32;
 }
 
-impl fmt::Display for Chunk {
+impl fmt::Debug for DataPreview<'_> {
 	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-		f.write_str(&self.data_as_string().map_err(|_| fmt::Error)?)
+		let shown = &self.0[..self.0.len().min(Self::LEN)];
+		write!(f, "b\"{}\"", shown.escape_ascii())?;
+		if shown.len() < self.0.len() {
+			write!(f, "... ({} bytes)", self.0.len())?;
+		}
+		Ok(())
 	}
 }
 
@@ -178,6 +419,11 @@ mod tests {
 		let chunk_string = chunk.data_as_string().unwrap();
 		let expected_chunk_string = String::from("This is where your secret message will be!");
 		assert_eq!(chunk_string, expected_chunk_string);
+		assert_eq!(chunk.to_string(), expected_chunk_string);
+		let binary = Chunk::new(ChunkType::IDAT, vec![0x78, 0x9c, 0xff]);
+		assert_eq!(binary.to_string(), format!("00000000: 789c ff{:34}x..", ""));
+		assert_eq!(binary.data_as_string_lossy(), "x\u{fffd}\u{fffd}");
+		assert_eq!(binary.data_as_string_escaped(), "x\\x9c\\xff");
 	}
 
 	#[test]
@@ -234,6 +480,94 @@ mod tests {
 		assert!(chunk.is_err());
 	}
 
+	#[test]
+	fn test_lenient_chunk_from_bytes() {
+		let mut chunk_data = testing_chunk().as_bytes();
+		let last = chunk_data.len() - 1;
+		chunk_data[last] ^= 1;
+
+		let chunk = Chunk::try_from_lenient(chunk_data.as_ref()).unwrap();
+		assert!(!chunk.has_valid_crc());
+		assert_eq!(chunk.crc(), 2882656334 ^ 1);
+		assert_eq!(chunk.data(), testing_chunk().data());
+		assert_eq!(chunk.as_bytes(), chunk_data);
+		assert!(testing_chunk().has_valid_crc());
+	}
+
+	/// The CRC as the PNG specification defines it, one bit at a time.
+	fn reference_crc(bytes: &[u8]) -> u32 {
+		let mut crc = 0xffffffff;
+		for byte in bytes {
+			crc ^= *byte as u32;
+			for _ in 0..8 {
+				crc = if crc & 1 != 0 { 0xedb88320 ^ (crc >> 1) } else { crc >> 1 };
+			}
+		}
+		crc ^ 0xffffffff
+	}
+
+	#[test]
+	fn test_compute_crc() {
+		assert_eq!(compute_crc(b""), 0);
+		assert_eq!(compute_crc(b"123456789"), 0xcbf43926);
+
+		let bytes: Vec<u8> = (0..1000u32).map(|i| (i * 37 + i / 7) as u8).collect();
+		for start in 0..9 {
+			for len in [0, 1, 7, 8, 9, 15, 16, 17, 100, 991 - start] {
+				let bytes = &bytes[start..start + len];
+				assert_eq!(compute_crc(bytes), reference_crc(bytes), "{} bytes at {}", len, start);
+			}
+		}
+		let (head, tail) = bytes.split_at(333);
+		let crc = update_crc(update_crc(0xffffffff, head), tail) ^ 0xffffffff;
+		assert_eq!(crc, reference_crc(&bytes));
+	}
+
+	#[test]
+	fn test_chunk_ref() {
+		let bytes = testing_chunk().as_bytes();
+		let chunk = ChunkRef::try_from(bytes.as_slice()).unwrap();
+		assert_eq!(chunk.length(), 42);
+		assert_eq!(chunk.data_as_str().unwrap(), "This is where your secret message will be!");
+		assert!(std::ptr::eq(chunk.data(), &bytes[8..50]));
+
+		let owned = chunk.into_chunk();
+		assert_eq!(owned.crc(), 2882656334);
+		assert_eq!(owned.as_bytes(), bytes);
+
+		let mut corrupted = bytes.clone();
+		corrupted[10] ^= 1;
+		assert!(ChunkRef::try_from(corrupted.as_slice()).is_err());
+		assert!(!ChunkRef::try_from_lenient(corrupted.as_slice()).unwrap().has_valid_crc());
+	}
+
+	#[test]
+	fn test_chunk_from_reader() {
+		let bytes = testing_chunk().as_bytes();
+		let mut reader = bytes.as_slice();
+
+		let chunk = Chunk::from_reader(&mut reader).unwrap().unwrap();
+		assert_eq!(chunk.chunk_type().to_string(), String::from("RuSt"));
+		assert_eq!(chunk.crc(), 2882656334);
+
+		assert!(Chunk::from_reader(&mut reader).unwrap().is_none());
+	}
+
+	#[test]
+	fn test_truncated_chunk_from_reader() {
+		let bytes = testing_chunk().as_bytes();
+		let chunk = Chunk::from_reader(&bytes[..bytes.len() - 10]);
+		assert!(chunk.is_err());
+	}
+
+	#[test]
+	fn test_chunk_write_to() {
+		let chunk = testing_chunk();
+		let mut bytes = Vec::new();
+		chunk.write_to(&mut bytes).unwrap();
+		assert_eq!(bytes, chunk.as_bytes());
+	}
+
 	#[test]
 	pub fn test_chunk_trait_impls() {
 		let data_length: u32 = 42;
@@ -254,4 +588,31 @@ mod tests {
 
 		let _chunk_string = format!("{}", chunk);
 	}
+
+	#[test]
+	fn test_chunk_equality_ignores_offset() {
+		use std::collections::HashSet;
+
+		let chunk = testing_chunk();
+		let moved = chunk.clone().with_offset(100);
+		assert_eq!(chunk, moved);
+		let other = Chunk::new(ChunkType::from_str("RuSt").unwrap(), b"other".to_vec());
+		assert_ne!(chunk, other);
+		let set: HashSet<Chunk> = [chunk, moved, other].into_iter().collect();
+		assert_eq!(set.len(), 2);
+	}
+
+	#[test]
+	fn test_chunk_debug() {
+		let chunk = Chunk::new(ChunkType::from_str("teXt").unwrap(), b"Title\0hi".to_vec());
+		assert_eq!(
+			format!("{:?}", chunk),
+			"Chunk { chunk_type: ChunkType(\"teXt\"), data: b\"Title\\x00hi\", crc: 0x".to_owned()
+				+ &format!("{:08x}", chunk.crc())
+				+ ", offset: None }",
+		);
+		let long = Chunk::new(ChunkType::IDAT, vec![b'a'; 1000]);
+		let debug = format!("{:?}", long);
+		assert!(debug.contains(&format!("b\"{}\"... (1000 bytes)", "a".repeat(32))));
+	}
 }
@@ -3,47 +3,150 @@ use std::{
 	str::{self, FromStr},
 };
 
-#[derive(Debug, PartialEq, Eq)]
+use crate::PngmeError;
+
+/// A four-byte PNG chunk type code, such as `IHDR` or `RuSt`.
+///
+/// The case of each byte carries a property bit as described in the PNG specification.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
 pub struct ChunkType {
 	bytes: [u8; 4],
 }
 
-#[allow(dead_code)]
+/// Registered public chunk types and what they hold, from the PNG specification and its
+/// registered extensions.
+const REGISTRY: &[([u8; 4], &str)] = &[
+	(*b"IHDR", "Image header"),
+	(*b"PLTE", "Palette"),
+	(*b"IDAT", "Image data"),
+	(*b"IEND", "Image trailer"),
+	(*b"acTL", "Animation control"),
+	(*b"bKGD", "Background color"),
+	(*b"cHRM", "Primary chromaticities and white point"),
+	(*b"cICP", "Coding-independent code points"),
+	(*b"cLLI", "Content light level information"),
+	(*b"eXIf", "Exif metadata"),
+	(*b"fcTL", "Frame control"),
+	(*b"fdAT", "Frame data"),
+	(*b"gAMA", "Image gamma"),
+	(*b"hIST", "Image histogram"),
+	(*b"iCCP", "Embedded ICC profile"),
+	(*b"iTXt", "International textual data"),
+	(*b"mDCV", "Mastering display color volume"),
+	(*b"pHYs", "Physical pixel dimensions"),
+	(*b"sBIT", "Significant bits"),
+	(*b"sPLT", "Suggested palette"),
+	(*b"sRGB", "Standard RGB color space"),
+	(*b"tEXt", "Textual data"),
+	(*b"tIME", "Image last-modification time"),
+	(*b"tRNS", "Transparency"),
+	(*b"zTXt", "Compressed textual data"),
+	(*b"gIFg", "GIF graphic control extension"),
+	(*b"gIFx", "GIF application extension"),
+	(*b"oFFs", "Image offset"),
+	(*b"pCAL", "Pixel calibration"),
+	(*b"sCAL", "Physical scale"),
+	(*b"sTER", "Stereo image indicator"),
+];
+
+#[allow(non_upper_case_globals)]
 impl ChunkType {
+	pub const IHDR: Self = Self { bytes: *b"IHDR" };
+	pub const PLTE: Self = Self { bytes: *b"PLTE" };
+	pub const IDAT: Self = Self { bytes: *b"IDAT" };
+	pub const IEND: Self = Self { bytes: *b"IEND" };
+	pub const acTL: Self = Self { bytes: *b"acTL" };
+	pub const bKGD: Self = Self { bytes: *b"bKGD" };
+	pub const cHRM: Self = Self { bytes: *b"cHRM" };
+	pub const cICP: Self = Self { bytes: *b"cICP" };
+	pub const cLLI: Self = Self { bytes: *b"cLLI" };
+	pub const eXIf: Self = Self { bytes: *b"eXIf" };
+	pub const fcTL: Self = Self { bytes: *b"fcTL" };
+	pub const fdAT: Self = Self { bytes: *b"fdAT" };
+	pub const gAMA: Self = Self { bytes: *b"gAMA" };
+	pub const hIST: Self = Self { bytes: *b"hIST" };
+	pub const iCCP: Self = Self { bytes: *b"iCCP" };
+	pub const iTXt: Self = Self { bytes: *b"iTXt" };
+	pub const mDCV: Self = Self { bytes: *b"mDCV" };
+	pub const oFFs: Self = Self { bytes: *b"oFFs" };
+	pub const pHYs: Self = Self { bytes: *b"pHYs" };
+	pub const sBIT: Self = Self { bytes: *b"sBIT" };
+	pub const sPLT: Self = Self { bytes: *b"sPLT" };
+	pub const sRGB: Self = Self { bytes: *b"sRGB" };
+	pub const tEXt: Self = Self { bytes: *b"tEXt" };
+	pub const tIME: Self = Self { bytes: *b"tIME" };
+	pub const tRNS: Self = Self { bytes: *b"tRNS" };
+	pub const zTXt: Self = Self { bytes: *b"zTXt" };
+	/// pngme's private chunk for keyed payloads, see [`crate::envelope`].
+	pub const pmKy: Self = Self { bytes: *b"pmKy" };
+	/// pngme's private chunk for directory archives, see [`crate::archive`].
+	pub const pmAr: Self = Self { bytes: *b"pmAr" };
+	/// pngme's private manifest chunk, see [`crate::manifest`].
+	pub const pmMf: Self = Self { bytes: *b"pmMf" };
+	/// pngme's private signature chunk, see [`crate::signature`].
+	pub const siGN: Self = Self { bytes: *b"siGN" };
+
+	/// Returns the raw bytes of the chunk type.
 	pub fn bytes(&self) -> [u8; 4] {
 		self.bytes
 	}
 
+	/// Returns `true` if the chunk type is valid, i.e. its reserved bit is unset.
 	pub fn is_valid(&self) -> bool {
 		self.is_reserved_bit_valid()
 	}
 
+	/// Returns `true` if the chunk is critical (uppercase first byte).
 	pub fn is_critical(&self) -> bool {
 		(self.bytes[0] & 0x20) == 0
 	}
 
+	/// Returns `true` if the chunk type is public (uppercase second byte).
 	pub fn is_public(&self) -> bool {
 		(self.bytes[1] & 0x20) == 0
 	}
 
+	/// Returns `true` if the reserved bit is unset (uppercase third byte).
 	pub fn is_reserved_bit_valid(&self) -> bool {
 		(self.bytes[2] & 0x20) == 0
 	}
 
+	/// Returns `true` if the chunk is safe to copy (lowercase fourth byte).
 	pub fn is_safe_to_copy(&self) -> bool {
 		(self.bytes[3] & 0x20) != 0
 	}
-}
 
-#[derive(Debug)]
-struct InvalidChunkTypeBytes {
-	bytes: [u8; 4],
-}
+	/// Returns `true` if this is a registered public chunk type.
+	pub fn is_standard(&self) -> bool {
+		self.description().is_some()
+	}
 
-impl std::error::Error for InvalidChunkTypeBytes {}
-impl fmt::Display for InvalidChunkTypeBytes {
-	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-		write!(f, "Invalid chunk type bytes: {:?}", self.bytes)
+	/// Returns a short human-readable description of a registered chunk type.
+	pub fn description(&self) -> Option<&'static str> {
+		REGISTRY.iter().find(|(bytes, _)| *bytes == self.bytes).map(|&(_, description)| description)
+	}
+
+	/// Returns the name and description of every registered chunk type.
+	pub fn registered() -> impl Iterator<Item = (&'static str, &'static str)> {
+		REGISTRY.iter().map(|(bytes, description)| {
+			(str::from_utf8(bytes).expect("registered types are ASCII"), *description)
+		})
+	}
+
+	/// Returns a pseudo-random ancillary, private, safe-to-copy chunk type such as `qxTk`;
+	/// the same seed always gives the same type.
+	pub fn random(seed: u64) -> Self {
+		// One round of SplitMix64 mixes nearby seeds into unrelated types.
+		let mut z = seed.wrapping_add(0x9e3779b97f4a7c15);
+		z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+		z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+		z ^= z >> 31;
+		let mut bytes = [0; 4];
+		for (n, byte) in bytes.iter_mut().enumerate() {
+			let letter = b'a' + ((z >> (16 * n)) % 26) as u8;
+			*byte = if n == 2 { letter.to_ascii_uppercase() } else { letter };
+		}
+		Self { bytes }
 	}
 }
 
@@ -53,7 +156,7 @@ impl TryFrom<[u8; 4]> for ChunkType {
 		if bytes.iter().all(u8::is_ascii_alphabetic) {
 			Ok(Self { bytes })
 		} else {
-			Err(InvalidChunkTypeBytes { bytes }.into())
+			Err(PngmeError::InvalidChunkType { bytes })
 		}
 	}
 }
@@ -71,12 +174,33 @@ impl fmt::Display for ChunkType {
 	}
 }
 
+/// Shows the type as its four letters, `ChunkType("IHDR")`, rather than as bytes.
+impl fmt::Debug for ChunkType {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_tuple("ChunkType").field(&format_args!("\"{}\"", self)).finish()
+	}
+}
+
 #[cfg(test)]
 mod tests {
 	use std::{convert::TryFrom, str::FromStr};
 
 	use super::*;
 
+	#[test]
+	pub fn test_random_chunk_type() {
+		for seed in 0..1000 {
+			let chunk = ChunkType::random(seed);
+			assert!(chunk.is_valid());
+			assert!(!chunk.is_critical());
+			assert!(!chunk.is_public());
+			assert!(chunk.is_safe_to_copy());
+			assert!(chunk.bytes().iter().all(u8::is_ascii_alphabetic));
+		}
+		assert_eq!(ChunkType::random(42), ChunkType::random(42));
+		assert_ne!(ChunkType::random(42), ChunkType::random(43));
+	}
+
 	#[test]
 	pub fn test_chunk_type_from_bytes() {
 		let expected = [82, 117, 83, 116];
@@ -168,4 +292,19 @@ mod tests {
 		let _chunk_string = format!("{}", chunk_type_1);
 		let _are_chunks_equal = chunk_type_1 == chunk_type_2;
 	}
+
+	#[test]
+	pub fn test_standard_chunk_types() {
+		assert_eq!(ChunkType::IHDR, ChunkType::from_str("IHDR").unwrap());
+		assert_eq!(ChunkType::tEXt.to_string(), "tEXt");
+		assert!(ChunkType::IDAT.is_critical());
+		assert!(!ChunkType::tIME.is_safe_to_copy());
+		assert!(ChunkType::zTXt.is_standard());
+		assert!(ChunkType::from_str("oFFs").unwrap().is_standard());
+		assert!(!ChunkType::from_str("RuSt").unwrap().is_standard());
+		assert_eq!(ChunkType::gAMA.description(), Some("Image gamma"));
+		assert_eq!(ChunkType::from_str("ruSt").unwrap().description(), None);
+		assert!(ChunkType::registered().any(|(name, _)| name == "eXIf"));
+		assert!(ChunkType::registered().all(|(name, _)| name.parse::<ChunkType>().is_ok()));
+	}
 }
@@ -0,0 +1,2833 @@
+use std::{
+	borrow::Cow,
+	collections::{hash_map::RandomState, BTreeMap, HashMap, HashSet},
+	env,
+	ffi::OsString,
+	fs::{self, File, OpenOptions},
+	hash::{BuildHasher, Hasher},
+	io::{self, BufReader, BufWriter, IsTerminal, Read, Seek, SeekFrom, Write},
+	iter, mem,
+	path::{Path, PathBuf},
+	process::Command,
+	slice, str,
+	sync::{
+		atomic::{AtomicBool, Ordering},
+		mpsc::{self, RecvTimeoutError},
+		Mutex,
+	},
+	thread,
+	time::Instant,
+};
+
+use base64::prelude::*;
+use clap::CommandFactory;
+use clap_mangen::Man;
+use memmap2::Mmap;
+use notify::{
+	event::{EventKind, ModifyKind},
+	RecursiveMode, Watcher,
+};
+use pngme::{
+	analysis::{self, ByteStats},
+	apng::{self, Animation},
+	appended,
+	archive::{self, EntryKind},
+	canonical, carve, check,
+	content_hash::{self, Algorithm, Scope},
+	crypto,
+	decoder::DecoderRegistry,
+	diff::{self, ChunkChange},
+	ecc,
+	envelope::{self, Compression, Encryption, Envelope},
+	exif::ExifChunk,
+	fragment,
+	hexdump::{self, HexDump},
+	icc::IccProfile,
+	idat::{self, FilterStrategy, IdatStats, FILTER_NAMES},
+	ihdr::ColorType,
+	inventory::{self, Integrity, Location},
+	keyring::{self, Keyring},
+	manifest::{self, Manifest},
+	metadata,
+	optimize::{self, OptimizeOptions},
+	ordering,
+	repair::{self, RepairOptions},
+	scan, signature, stego,
+	text::{ItxtChunk, TextChunk, TextualChunk, ZtxtChunk},
+	walk::{self, WalkOptions},
+	xmp, Chunk, ChunkRef, ChunkType, IhdrData, Png, PngBuilder, PngRef, PngmeError,
+	PreservationPolicy, Result,
+};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+
+use crate::{
+	args::{
+		many_files, ApngBuildArgs, ApngSplitArgs, BatchArgs, CanonicalizeArgs, CapacityArgs,
+		CarveArgs, CheckArgs, Cli, Column, Commands, CompletionsArgs, Compress, CopyArgs,
+		CreateArgs, DecodeArgs, DecryptArgs, DedupeArgs, DiffArgs, DumpArgs, EditArgs, EncodeArgs,
+		ExifImportArgs, ExifShowArgs, ExifStripArgs, ExportArgs, Format, GitFilterArgs,
+		GitTextconvArgs, HashAlgorithm, HashArgs, IccEmbedArgs, IccExtractArgs, ImportArgs,
+		InfoArgs, ManArgs, Method, OptimizeArgs, PackArgs, PositionArgs, PrintArgs, PrintFormat,
+		RemoveArgs, Render, RepairArgs, RestoreArgs, RunArgs, SameImageArgs, ScrubArgs, ShellArgs,
+		SignArgs, StripArgs, TextGetArgs, TextSetArgs, TrailerArgs, TuiArgs, UnpackArgs,
+		VerifySigArgs, WalkArgs, WatchArgs, XmpGetArgs, XmpSetArgs, XmpStripArgs,
+	},
+	atomic::{self, AtomicFile},
+	credentials,
+	output::{self, out, outln, Style},
+	progress::{self, ProgressReader},
+	remote, shell, tui,
+};
+
+pub fn encode(mut args: EncodeArgs) -> Result<()> {
+	if (args.key.is_some() || args.random_type || args.default_chunk_type.is_some())
+		&& args.message.is_none()
+		&& args.input_file.is_none()
+	{
+		// The chunk type is optional then, so a lone argument is the message.
+		args.message = args.chunk_type.take();
+	}
+	if args.message.is_none() && args.input_file.is_none() {
+		return Err(usage("give a message to hide, or --input-file"));
+	}
+	let parity = args.ecc.map(ecc::parity_for).transpose()?;
+	let mut png = read_png(&args.filename)?;
+	// Whether chunks already in the file are replaced, which rules out appending in place.
+	let mut replaced = false;
+	let chunk_type = if args.random_type {
+		if args.chunk_type.is_some() {
+			return Err(usage("--random-type picks the chunk type, leave it out"));
+		}
+		let chunk_type = unused_random_chunk_type(&png, args.seed);
+		log::info!("Chunk type: {}", chunk_type);
+		chunk_type
+	} else {
+		let chunk_type = args.chunk_type.take().or(args.default_chunk_type.take());
+		if chunk_type.is_none() && args.key.is_none() {
+			return Err(usage("give a chunk type, or --key or --random-type to leave it out"));
+		}
+		payload_chunk_type(chunk_type.as_deref())?
+	};
+	// The manifest entry for the message, unless it is a standard text chunk.
+	let mut added = None;
+	let chunks = if let Some(keyword) = &args.compressed_text {
+		if args.method == Method::Lsb {
+			return Err(usage(
+				"--compressed-text stores a zTXt chunk and cannot be used with --method lsb",
+			));
+		}
+		if chunk_type.bytes() != ZtxtChunk::TYPE {
+			return Err(usage("--compressed-text requires the zTXt chunk type"));
+		}
+		let text = args.message.as_deref().unwrap_or_default();
+		vec![ZtxtChunk::new(keyword, text)?.to_chunk()]
+	} else {
+		let data = match (args.message, &args.input_file) {
+			(Some(message), _) => message.into_bytes(),
+			(None, Some(path)) => {
+				let mut data = Vec::new();
+				open_input(path)?.read_to_end(&mut data)?;
+				data
+			}
+			(None, None) => return Err(usage("No message given")),
+		};
+		let (mut data, compression, encryption) =
+			seal(data, args.compress, args.encrypt, args.passphrase, &args.recipient)?;
+		if !args.raw {
+			let binary = args.input_file.is_some();
+			let key = args.key.clone();
+			data = Envelope { binary, compression, encryption, key, ..Envelope::new(data) }
+				.to_bytes()?;
+		}
+		if let Some(parity) = parity {
+			data = ecc::protect(&data, parity)?;
+		}
+		let name = args
+			.input_file
+			.as_deref()
+			.filter(|path| !is_stdio(path))
+			.and_then(Path::file_name)
+			.map(|name| name.to_string_lossy().into_owned());
+		let entry = |location, fragments| manifest::Entry {
+			name: name.clone(),
+			..manifest::Entry::new(location, chunk_type, args.key.clone(), &data, fragments)
+		};
+		if args.method == Method::Lsb {
+			let position = &args.position;
+			if args.after_iend
+				|| position.before.is_some()
+				|| position.after.is_some()
+				|| position.index.is_some()
+			{
+				return Err(usage(
+					"--method lsb hides the message in the pixels, not at a position",
+				));
+			}
+			stego::embed(&mut png, &chunk_type, &data)?;
+			manifest::update(&mut png, Some(entry(Location::Lsb, 1)))?;
+			return write_png(&png, output_path(args.output.as_ref(), &args.filename)?);
+		}
+		if args.after_iend {
+			let mut trailer = png.take_trailer();
+			if let Some(key) = &args.key {
+				let mut kept = Vec::new();
+				for (tag, payload) in appended::frames(&trailer)? {
+					if tag != chunk_type || !Envelope::has_key(payload, key) {
+						kept.extend(appended::frame(&tag, payload)?);
+					}
+				}
+				trailer = kept;
+			}
+			trailer.extend(appended::frame(&chunk_type, &data)?);
+			png.set_trailer(trailer);
+			manifest::update(&mut png, Some(entry(Location::AfterIend, 1)))?;
+			return write_png(&png, output_path(args.output.as_ref(), &args.filename)?);
+		}
+		if let Some(key) = &args.key {
+			if data.len() > args.chunk_size {
+				return Err(usage("Messages stored with --key must fit in one chunk"));
+			}
+			let count = png.chunks().len();
+			png.retain_chunks(|chunk| {
+				chunk.chunk_type() != &chunk_type || !Envelope::has_key(chunk.data(), key)
+			});
+			replaced = png.chunks().len() != count;
+		}
+		let chunks: Vec<Chunk> = if data.len() > args.chunk_size {
+			fragment::split(&data, args.chunk_size)?
+				.into_iter()
+				.map(|fragment| Chunk::new(chunk_type, fragment))
+				.collect()
+		} else {
+			vec![Chunk::new(chunk_type, data.clone())]
+		};
+		added = Some(entry(Location::Chunk, chunks.len()));
+		chunks
+	};
+
+	let chunk_type = chunks[0].chunk_type();
+	let existing = png.chunks().iter().position(|chunk| chunk.chunk_type() == chunk_type);
+	match existing {
+		Some(index) if args.replace || args.upsert => {
+			let chunk_type = chunk_type.to_string();
+			let mut chunks = chunks.into_iter();
+			png.replace_chunk(&chunk_type, chunks.next().expect("a message has a chunk"))?;
+			// Any later chunks of the type hold the rest of a split message, or are duplicates.
+			let chunk_type: ChunkType = chunk_type.parse()?;
+			let mut first = true;
+			png.retain_chunks(|chunk| chunk.chunk_type() != &chunk_type || mem::take(&mut first));
+			for (n, chunk) in chunks.enumerate() {
+				png.insert_chunk_at(index + 1 + n, chunk);
+			}
+			manifest::update(&mut png, added)?;
+			return write_png(&png, output_path(args.output.as_ref(), &args.filename)?);
+		}
+		None if args.replace => {
+			return Err(PngmeError::ChunkNotFound { chunk_type: chunk_type.bytes().try_into()? });
+		}
+		_ => {}
+	}
+	if existing.is_some()
+		&& args.key.is_none()
+		&& args.compressed_text.is_none()
+		&& !args.append_duplicate
+		&& !atomic::is_forced()
+	{
+		return Err(usage(format!(
+			"The file already has a {} chunk, which decode would find first; pass --replace \
+			 to replace it or --append-duplicate to add another",
+			chunk_type
+		)));
+	}
+	let position = insert_position(&png, chunk_type, &args.position)?;
+	if !ordering::is_legal_position(&png, chunk_type, position) {
+		log::warn!(
+			"inserting {} at index {} breaks the PNG chunk ordering rules",
+			chunk_type,
+			position
+		);
+	}
+	let output = output_path(args.output.as_ref(), &args.filename)?;
+	let iend = png.chunks().last().filter(|last| last.chunk_type() == &ChunkType::IEND);
+	let in_place = iend.and_then(Chunk::offset).filter(|_| {
+		!replaced
+			&& !chunk_type.is_critical()
+			&& position + 1 == png.chunks().len()
+			&& output == &args.filename
+			&& !atomic::is_enabled()
+			&& !atomic::is_dry_run()
+			&& is_unchanged_file(&png, output)
+	});
+	let manifest_before = png.chunk_by_type(&manifest::CHUNK_TYPE.to_string()).cloned();
+	log::debug!("inserting {} {} chunks at index {}", chunks.len(), chunk_type, position);
+	for (n, chunk) in chunks.iter().enumerate() {
+		png.insert_chunk_at(position + n, chunk.clone());
+	}
+	manifest::update(&mut png, added)?;
+	let manifest_after = png.chunk_by_type(&manifest::CHUNK_TYPE.to_string());
+	if let Some(iend_offset) = in_place.filter(|_| manifest_after == manifest_before.as_ref()) {
+		log::debug!("appending in place over IEND at {:#010x}", iend_offset);
+		return append_in_place(&png, iend_offset, &chunks, output);
+	}
+	write_png(&png, output)
+}
+
+/// Compresses and then encrypts a payload as asked, returning it with what was done to it for
+/// its envelope.
+fn seal(
+	data: Vec<u8>,
+	compress: Compress,
+	encrypt: bool,
+	passphrase: Option<String>,
+	recipients: &[String],
+) -> Result<(Vec<u8>, Compression, Encryption)> {
+	let compression = match compress {
+		Compress::None => Compression::None,
+		Compress::Deflate => Compression::Deflate,
+		Compress::Zstd => Compression::Zstd,
+	};
+	let data = compression.compress(&data)?;
+	if encrypt {
+		let data = crypto::encrypt(&data, &credentials::for_encryption(passphrase)?)?;
+		Ok((data, compression, Encryption::Passphrase))
+	} else if !recipients.is_empty() {
+		let recipients: Vec<_> =
+			recipients.iter().map(|r| keyring::parse_recipient(r)).collect::<Result<_>>()?;
+		Ok((keyring::encrypt(&data, &recipients)?, compression, Encryption::Recipients))
+	} else {
+		Ok((data, compression, Encryption::None))
+	}
+}
+
+/// Whether `path` is a file still as long as `png` when it was read from it.
+fn is_unchanged_file(png: &Png, path: &Path) -> bool {
+	let len = trailer_offset(png) + png.trailer().len() as u64;
+	!is_stdio(path)
+		&& !shell::is_open(path)
+		&& fs::metadata(path).is_ok_and(|metadata| metadata.len() == len)
+}
+
+/// Inserts `chunks` before the `IEND` chunk at `iend_offset` of the file `png` was read from
+/// by overwriting the file from there on, which for a small payload is much less writing than
+/// the whole file. A crash meanwhile leaves the file without `IEND`, so this is only done with
+/// `--no-atomic`.
+fn append_in_place(png: &Png, iend_offset: u64, chunks: &[Chunk], path: &Path) -> Result<()> {
+	atomic::back_up(path)?;
+	let mut file = OpenOptions::new().write(true).open(path)?;
+	file.seek(SeekFrom::Start(iend_offset))?;
+	let mut output = BufWriter::new(&mut file);
+	for chunk in chunks.iter().chain(png.chunks().last()) {
+		chunk.write_to(&mut output)?;
+	}
+	output.write_all(png.trailer())?;
+	output.flush()?;
+	drop(output);
+	let len = file.stream_position()?;
+	file.set_len(len)?;
+	Ok(())
+}
+
+/// Parses the chunk type of a payload, defaulting to the one for keyed payloads.
+fn payload_chunk_type(chunk_type: Option<&str>) -> Result<ChunkType> {
+	match chunk_type {
+		Some(chunk_type) => chunk_type.parse(),
+		None => Ok(ChunkType::try_from(envelope::KEYED_CHUNK_TYPE.bytes())?),
+	}
+}
+
+/// Picks a random private chunk type that `png` does not use yet, reproducibly with a seed.
+fn unused_random_chunk_type(png: &Png, seed: Option<u64>) -> ChunkType {
+	let seed = seed.unwrap_or_else(|| RandomState::new().build_hasher().finish());
+	(seed..)
+		.map(ChunkType::random)
+		.find(|chunk_type| {
+			chunk_type != &envelope::KEYED_CHUNK_TYPE
+				&& png.chunks().iter().all(|chunk| chunk.chunk_type() != chunk_type)
+		})
+		.expect("a PNG cannot use every chunk type")
+}
+
+/// Resolves the requested position, defaulting to a legal one for `chunk_type`.
+fn insert_position(png: &Png, chunk_type: &ChunkType, args: &PositionArgs) -> Result<usize> {
+	if let Some(before) = &args.before {
+		let chunk_type: ChunkType = before.parse()?;
+		let first = png.chunks().iter().position(|chunk| chunk.chunk_type() == &chunk_type);
+		first.ok_or(PngmeError::ChunkNotFound { chunk_type })
+	} else if let Some(after) = &args.after {
+		let chunk_type: ChunkType = after.parse()?;
+		let last = png.chunks().iter().rposition(|chunk| chunk.chunk_type() == &chunk_type);
+		Ok(last.ok_or(PngmeError::ChunkNotFound { chunk_type })? + 1)
+	} else if let Some(index) = args.index {
+		if index > png.chunks().len() {
+			return Err(usage(format!("Index {} is past the last chunk", index)));
+		}
+		Ok(index)
+	} else {
+		Ok(ordering::default_position(png, chunk_type))
+	}
+}
+
+pub fn decode(args: DecodeArgs) -> Result<()> {
+	let png = if args.ignore_crc {
+		read_damaged_png(&args.filename)?
+	} else {
+		read_protected_png(&args.filename)?
+	};
+	let chunk_type: Option<ChunkType> = args.chunk_type.as_deref().map(str::parse).transpose()?;
+	// Without a chunk type, anything holding a payload envelope qualifies.
+	let wanted = |found: &ChunkType, data: &[u8]| {
+		chunk_type.as_ref().is_none_or(|chunk_type| chunk_type == found)
+			&& match &args.key {
+				Some(key) => Envelope::has_key(data, key),
+				None => chunk_type.is_some() || Envelope::is_envelope(data),
+			}
+	};
+	let missing = || {
+		let message = match (&args.key, &chunk_type) {
+			(Some(key), _) => format!("No message stored under the key {:?}", key),
+			(None, Some(chunk_type)) => format!("No {} message", chunk_type),
+			(None, None) => "No pngme message".to_owned(),
+		};
+		if args.after_iend {
+			not_found(message + " after IEND")
+		} else if args.method == Method::Lsb {
+			not_found(message + " in the pixels")
+		} else {
+			not_found(message)
+		}
+	};
+	// Every matching payload, with a description of where it came from.
+	let mut found = Vec::new();
+	if args.after_iend {
+		for (tag, payload) in appended::frames(png.trailer())? {
+			if wanted(&tag, payload) {
+				let json = json!({ "chunk_type": tag.to_string(), "after_iend": true });
+				found.push((payload.to_vec(), json));
+			}
+		}
+	} else if args.method == Method::Lsb {
+		if let Some((tag, payload)) = stego::find(&png)?.filter(|(tag, data)| wanted(tag, data)) {
+			found.push((payload, json!({ "chunk_type": tag.to_string(), "method": "lsb" })));
+		}
+	} else {
+		let mut reassembled: Vec<&ChunkType> = Vec::new();
+		for chunk in png.chunks() {
+			let found_type = chunk.chunk_type();
+			if chunk_type.as_ref().is_some_and(|chunk_type| chunk_type != found_type) {
+				continue;
+			}
+			let payload = if fragment::is_fragment(chunk.data()) {
+				if reassembled.contains(&found_type) {
+					continue;
+				}
+				reassembled.push(found_type);
+				let fragments = png
+					.chunks_by_type(&found_type.to_string())
+					.map(Chunk::data)
+					.filter(|data| fragment::is_fragment(data));
+				match fragment::reassemble(fragments) {
+					Ok(payload) => payload,
+					Err(e) if chunk_type.is_some() => return Err(e),
+					Err(_) => continue,
+				}
+			} else {
+				readable_data(chunk).into_owned()
+			};
+			if wanted(found_type, &payload) {
+				found.push((payload, chunk_json(chunk)));
+			}
+		}
+	}
+	if args.all && !found.is_empty() {
+		return output_messages(&args, found);
+	}
+	let index = args.index.unwrap_or(0);
+	let count = found.len();
+	if let Some((payload, json)) = found.into_iter().nth(index) {
+		return output_message(&args, payload, json);
+	}
+	match chunk_type {
+		_ if count > 0 => Err(not_found(format!(
+			"No message at index {}, there {}",
+			index,
+			if count == 1 { "is 1".to_owned() } else { format!("are {}", count) }
+		))),
+		Some(chunk_type)
+			if !args.after_iend
+				&& args.method != Method::Lsb
+				&& args.key.is_none()
+				&& png.chunk_by_type(&chunk_type.to_string()).is_none() =>
+		{
+			Err(PngmeError::ChunkNotFound { chunk_type })
+		}
+		_ => Err(missing()),
+	}
+}
+
+/// Prints every message for `decode --all`, one per line or as a JSON array.
+fn output_messages(args: &DecodeArgs, found: Vec<(Vec<u8>, Value)>) -> Result<()> {
+	let mut messages = Vec::new();
+	for (payload, json) in found {
+		messages.push(open_message(args, payload, json)?);
+	}
+	if args.format == Format::Json {
+		let messages = messages.into_iter().map(|(data, mut json)| {
+			json["message"] = message_json(&data);
+			json
+		});
+		outln!("{:#}", Value::from(messages.collect::<Vec<_>>()));
+		return Ok(());
+	}
+	for (data, _) in messages {
+		match String::from_utf8(data) {
+			Ok(message) => outln!("{}", message),
+			Err(_) => {
+				return Err(usage("A message is not valid UTF-8, use --index to pick another"))
+			}
+		}
+	}
+	Ok(())
+}
+
+/// Opens the payload like [`open_message`], and prints or saves the message; in JSON output
+/// it is added to `json`, which describes where it came from.
+fn output_message(args: &DecodeArgs, payload: Vec<u8>, json: Value) -> Result<()> {
+	let (data, mut json) = open_message(args, payload, json)?;
+	match &args.output_file {
+		Some(path) => {
+			refuse_overwrite(path)?;
+			let mut output = open_output(path)?;
+			output.write_all(&data)?;
+			output.finish()?;
+		}
+		None if args.format == Format::Json => {
+			json["message"] = message_json(&data);
+			outln!("{:#}", json);
+		}
+		None => match String::from_utf8(data) {
+			Ok(message) => outln!("{}", message),
+			Err(_) => {
+				return Err(usage("Message is not valid UTF-8, use --output-file to save it"))
+			}
+		},
+	}
+	Ok(())
+}
+
+fn message_json(data: &[u8]) -> Value {
+	json!({ "utf8": str::from_utf8(data).is_ok(), "data": BASE64_STANDARD.encode(data) })
+}
+
+/// Unwraps the decoded payload, decrypting and decompressing it, and describes its envelope
+/// in `json`. Payloads without an envelope are treated as raw messages.
+fn open_message(args: &DecodeArgs, payload: Vec<u8>, mut json: Value) -> Result<(Vec<u8>, Value)> {
+	let payload = if ecc::is_protected(&payload) {
+		let (payload, corrected) = ecc::correct(&payload)?;
+		if corrected > 0 {
+			log::info!(
+				"Corrected {} damaged byte{}",
+				corrected,
+				if corrected == 1 { "" } else { "s" }
+			);
+		}
+		json["corrected"] = corrected.into();
+		payload
+	} else {
+		payload
+	};
+	let (data, envelope) = open_payload(&args.decryption, payload)?;
+	if let Some(envelope) = envelope {
+		json["envelope"] = json!({
+			"binary": envelope.binary,
+			"compression": envelope.compression.name(),
+			"encryption": envelope.encryption.name(),
+			"key": envelope.key,
+		});
+	}
+	Ok((data, json))
+}
+
+/// Undoes the encryption and compression of a payload, returning it with its envelope, if it
+/// has one, without the body.
+fn open_payload(args: &DecryptArgs, payload: Vec<u8>) -> Result<(Vec<u8>, Option<Envelope>)> {
+	let (payload, compression, envelope) = match Envelope::parse(&payload)? {
+		Some(envelope) if args.verify && !envelope.hashed => {
+			return Err(PngmeError::invalid(
+				"payload",
+				"it was stored without a SHA-256, so it cannot be verified",
+			));
+		}
+		Some(mut envelope) => {
+			match envelope.encryption {
+				Encryption::Passphrase if !args.decrypt => {
+					return Err(usage("Message is encrypted, use --decrypt to read it"));
+				}
+				Encryption::Recipients if args.identity.is_none() => {
+					return Err(usage(
+						"Message is encrypted to age recipients, use --identity to read it",
+					));
+				}
+				Encryption::Recipients => {}
+				_ if args.identity.is_some() => {
+					return Err(usage("Message is not encrypted to age recipients"));
+				}
+				_ => {}
+			}
+			let body = mem::take(&mut envelope.body);
+			(body, envelope.compression, Some(envelope))
+		}
+		None if args.identity.is_some() => {
+			return Err(usage("Message is not encrypted to age recipients"));
+		}
+		None if args.verify => {
+			return Err(PngmeError::invalid(
+				"payload",
+				"it has no envelope, so there is no SHA-256 to verify it against",
+			));
+		}
+		None => (payload, Compression::None, None),
+	};
+	let data = if let Some(path) = &args.identity {
+		keyring::decrypt(&payload, &Keyring::parse(&fs::read_to_string(path)?)?)?
+	} else if args.decrypt {
+		crypto::decrypt(&payload, &credentials::for_decryption(args.passphrase.clone())?)?
+	} else if crypto::is_encrypted(&payload) {
+		return Err(usage("Message is encrypted, use --decrypt to read it"));
+	} else {
+		payload
+	};
+	Ok((compression.decompress(&data)?, envelope))
+}
+
+pub fn list(args: InfoArgs) -> Result<()> {
+	let png = read_png(&args.filename)?;
+	let payloads = inventory::list(&png);
+	for problem in manifest_problems(&png)? {
+		log::warn!("{}", problem);
+	}
+	if args.format == Format::Json {
+		let payloads: Vec<_> = payloads
+			.iter()
+			.map(|payload| {
+				json!({
+					"method": payload.location.name(),
+					"chunk_type": payload.chunk_type,
+					"key": payload.key,
+					"name": payload.name,
+					"size": payload.size,
+					"fragments": payload.fragments,
+					"envelope": payload.enveloped,
+					"compression": payload.compression.name(),
+					"encryption": payload.encryption.name(),
+				})
+			})
+			.collect();
+		outln!("{:#}", Value::from(payloads));
+		return Ok(());
+	}
+	if payloads.is_empty() {
+		log::info!("No payloads found");
+		return Ok(());
+	}
+	// Names only come from a manifest, so most files do without the column.
+	let named = payloads.iter().any(|payload| payload.name.is_some());
+	outln!("METHOD\tTYPE\tKEY\tSIZE\tCOMPRESSION\tENCRYPTION{}", if named { "\tNAME" } else { "" });
+	for payload in &payloads {
+		let mut size = format!("{} bytes", payload.size);
+		if payload.fragments > 1 {
+			size += &format!(" in {} chunks", payload.fragments);
+		}
+		let encryption = match payload.encryption {
+			Encryption::None => "-",
+			encryption => encryption.name(),
+		};
+		out!(
+			"{}\t{}\t{}\t{}\t{}\t{}",
+			payload.location.name(),
+			payload.chunk_type,
+			payload.key.as_deref().unwrap_or("-"),
+			size,
+			payload.compression.name(),
+			encryption,
+		);
+		if named {
+			out!("\t{}", payload.name.as_deref().unwrap_or("-"));
+		}
+		outln!();
+	}
+	Ok(())
+}
+
+/// Describes the payloads the manifest lists that are missing or changed.
+fn manifest_problems(png: &Png) -> Result<Vec<String>> {
+	let Some(manifest) = Manifest::read(png)? else {
+		return Ok(Vec::new());
+	};
+	let problems = manifest.verify(png).into_iter().map(|(entry, problem)| {
+		let what = match (&entry.name, &entry.key) {
+			(Some(name), _) => name.clone(),
+			(None, Some(key)) => format!("the {} payload under {:?}", entry.chunk_type, key),
+			(None, None) => format!("the {} payload", entry.chunk_type),
+		};
+		let problem = match problem {
+			manifest::Problem::Missing => "is missing",
+			manifest::Problem::Modified => "has changed since it was stored",
+		};
+		format!("{} listed in the manifest {}", what, problem)
+	});
+	Ok(problems.collect())
+}
+
+pub fn verify(args: InfoArgs) -> Result<()> {
+	let png = read_png(&args.filename)?;
+	let payloads = inventory::list(&png);
+	let problems = manifest_problems(&png)?;
+	let failed =
+		payloads.iter().filter(|payload| matches!(payload.integrity, Integrity::Failed(_))).count()
+			+ problems.len();
+	if args.format == Format::Json {
+		let payloads: Vec<_> = payloads
+			.iter()
+			.map(|payload| {
+				let error = match &payload.integrity {
+					Integrity::Failed(error) => Some(error),
+					_ => None,
+				};
+				json!({
+					"method": payload.location.name(),
+					"chunk_type": payload.chunk_type,
+					"key": payload.key,
+					"integrity": payload.integrity.name(),
+					"error": error,
+				})
+			})
+			.collect();
+		outln!("{:#}", json!({ "payloads": payloads, "manifest": problems }));
+	} else {
+		if payloads.is_empty() {
+			log::info!("No payloads found");
+		}
+		for payload in &payloads {
+			let status = match &payload.integrity {
+				Integrity::Unchecked => "not checked, it has no envelope".to_owned(),
+				Integrity::Checksum => "OK, but only a CRC-32 was stored".to_owned(),
+				Integrity::Verified => "OK".to_owned(),
+				Integrity::Failed(error) => format!("FAILED: {}", error),
+			};
+			out!("{} {}", payload.location.name(), payload.chunk_type);
+			if let Some(key) = &payload.key {
+				out!(" {:?}", key);
+			}
+			outln!(": {}", status);
+		}
+		for problem in &problems {
+			outln!("FAILED: {}", problem);
+		}
+	}
+	match failed {
+		0 => Ok(()),
+		1 => Err(PngmeError::invalid("payloads", "1 problem found")),
+		n => Err(PngmeError::invalid("payloads", format!("{} problems found", n))),
+	}
+}
+
+pub fn remove(args: RemoveArgs) -> Result<()> {
+	let mut png = read_png(&args.filename)?;
+	let chunk_type: ChunkType = args.chunk_type.parse()?;
+	if args.all {
+		if png.remove_chunks(&args.chunk_type).is_empty() {
+			return Err(PngmeError::ChunkNotFound { chunk_type });
+		}
+	} else if let Some(n) = args.index {
+		let (position, _) = png
+			.chunks()
+			.iter()
+			.enumerate()
+			.filter(|(_, chunk)| chunk.chunk_type() == &chunk_type)
+			.nth(n)
+			.ok_or_else(|| {
+				not_found(format!("There are fewer than {} {} chunks", n + 1, chunk_type))
+			})?;
+		png.remove_chunk_at(position);
+	} else if let Some(offset) = args.offset {
+		let position = png
+			.chunks_with_offsets()
+			.position(|(o, chunk)| o == offset && chunk.chunk_type() == &chunk_type)
+			.ok_or_else(|| not_found(format!("No {} chunk at offset {}", chunk_type, offset)))?;
+		png.remove_chunk_at(position);
+	} else {
+		png.remove_chunk(&args.chunk_type)?;
+	}
+	manifest::update(&mut png, None)?;
+	write_png(&png, output_path(args.output.as_ref(), &args.filename)?)
+}
+
+pub fn dump(args: DumpArgs) -> Result<()> {
+	let png = read_png(&args.filename)?;
+	let chunk_type: ChunkType = args.chunk_type.parse()?;
+	let (offset, chunk) = png
+		.chunks_with_offsets()
+		.filter(|(_, chunk)| chunk.chunk_type() == &chunk_type)
+		.nth(args.index)
+		.ok_or_else(|| match args.index {
+			0 => PngmeError::ChunkNotFound { chunk_type },
+			n => not_found(format!("There are fewer than {} {} chunks", n + 1, chunk_type)),
+		})?;
+	if chunk.length() > 0 {
+		outln!("{}", HexDump::new(chunk.data(), offset + 8));
+	}
+	Ok(())
+}
+
+pub fn tui(args: TuiArgs) -> Result<()> {
+	if is_stdio(&args.filename) {
+		return Err(usage("the chunk browser reads keys from stdin, give it a file rather than -"));
+	}
+	if !io::stdin().is_terminal() || !io::stdout().is_terminal() {
+		return Err(usage("the chunk browser needs a terminal"));
+	}
+	let png = read_png(&args.filename)?;
+	let name = args.filename.display().to_string();
+	let save = |png: &Png| write_png(png, &args.filename);
+	let extract = |path: &Path, data: &[u8]| {
+		if is_stdio(path) {
+			return Err(usage("extract to a file, stdout is the screen"));
+		}
+		refuse_overwrite(path)?;
+		let mut output = open_output(path)?;
+		output.write_all(data)?;
+		output.finish()
+	};
+	tui::browse(png, &name, save, extract)
+}
+
+pub fn shell(args: ShellArgs, run: fn(Commands) -> Result<()>) -> Result<()> {
+	if is_stdio(&args.filename) {
+		return Err(usage("the shell reads commands from stdin, give it a file rather than -"));
+	}
+	let png = read_png(&args.filename)?;
+	shell::run(&args.filename, png, run, |png| write_png(png, &args.filename))
+}
+
+pub fn run_script(args: RunArgs, run: fn(Commands) -> Result<()>) -> Result<()> {
+	let mut files = Vec::new();
+	for path in args.filenames {
+		if is_stdio(&path) {
+			return Err(usage("a script runs on files, not on -"));
+		}
+		if files.iter().any(|(file, _)| file == &path) {
+			return Err(usage(format!("{} is given more than once", path.display())));
+		}
+		let png = read_png(&path)?;
+		files.push((path, png));
+	}
+	shell::run_script(&args.script, files, run, write_pngs)
+}
+
+pub fn edit(args: EditArgs) -> Result<()> {
+	if is_stdio(&args.filename) {
+		return Err(usage("the editor needs a file to write the changes back to, not -"));
+	}
+	let original = fs::read(&args.filename)?;
+	let mut png = Png::from_reader(&original[..])?;
+	png.set_preservation_policy(PreservationPolicy::Warn);
+	let chunk_type: ChunkType = args.chunk_type.parse()?;
+	let (index, chunk) = (png.chunks().iter().enumerate())
+		.filter(|(_, chunk)| chunk.chunk_type() == &chunk_type)
+		.nth(args.index)
+		.ok_or_else(|| match args.index {
+			0 => PngmeError::ChunkNotFound { chunk_type },
+			n => not_found(format!("There are fewer than {} {} chunks", n + 1, chunk_type)),
+		})?;
+	let textual = TextualChunk::try_from(chunk).ok();
+	let text = match &textual {
+		Some(textual) => textual.text(),
+		None => str::from_utf8(chunk.data()).map_err(|_| {
+			usage(format!("the {} chunk holds binary data, only text can be edited", chunk_type))
+		})?,
+	};
+	let (mut file, path) = create_edit_file(&chunk_type)?;
+	file.write_all(text.as_bytes())?;
+	drop(file);
+	run_editor(&path)?;
+	let mut edited = fs::read_to_string(&path)
+		.map_err(|error| format!("cannot read the edited text in {}: {}", path.display(), error))?;
+	// Editors end the last line with a newline the text did not have.
+	if !text.ends_with('\n') && edited.ends_with('\n') {
+		edited.pop();
+	}
+	if edited == text {
+		fs::remove_file(&path)?;
+		log::info!("No changes to the {} chunk", chunk_type);
+		return Ok(());
+	}
+	let new_chunk = match &textual {
+		Some(textual) => textual.with_text(&edited)?.to_chunk(),
+		None => Chunk::new(chunk_type, edited.into_bytes()),
+	};
+	// Someone else writing the file meanwhile would lose their changes to ours.
+	if fs::read(&args.filename).ok().as_ref() != Some(&original) {
+		return Err(format!(
+			"{} changed while the chunk was being edited, the edited text is kept in {}",
+			args.filename.display(),
+			path.display()
+		)
+		.into());
+	}
+	png.remove_chunk_at(index);
+	png.insert_chunk_at(index, new_chunk);
+	write_png(&png, &args.filename)?;
+	fs::remove_file(&path)?;
+	log::info!("Edited the {} chunk", chunk_type);
+	Ok(())
+}
+
+/// Creates a file in the temporary directory to hold the text being edited.
+fn create_edit_file(chunk_type: &ChunkType) -> Result<(File, PathBuf)> {
+	let mut attempts = 0;
+	loop {
+		let suffix = RandomState::new().build_hasher().finish() as u16;
+		let path = env::temp_dir().join(format!("pngme-{}-{:04x}.txt", chunk_type, suffix));
+		match OpenOptions::new().write(true).create_new(true).open(&path) {
+			Ok(file) => return Ok((file, path)),
+			Err(error) if error.kind() == io::ErrorKind::AlreadyExists && attempts < 16 => {
+				attempts += 1;
+			}
+			Err(error) => return Err(error.into()),
+		}
+	}
+}
+
+/// Opens `path` in `$VISUAL` or `$EDITOR`, or `vi` without either, and waits for it to exit.
+fn run_editor(path: &Path) -> Result<()> {
+	let editor = (env::var("VISUAL").ok().filter(|editor| !editor.trim().is_empty()))
+		.or_else(|| env::var("EDITOR").ok().filter(|editor| !editor.trim().is_empty()))
+		.unwrap_or_else(|| "vi".to_owned());
+	// The editor may come with arguments of its own, such as `code --wait`.
+	let mut words = editor.split_whitespace();
+	let program = words.next().expect("the editor is not blank");
+	let status = Command::new(program).args(words).arg(path).status().map_err(|error| {
+		// Nothing was edited, so there is no text to keep.
+		let _ = fs::remove_file(path);
+		format!("cannot run the editor {}: {}", program, error)
+	})?;
+	if !status.success() {
+		return Err(format!(
+			"the editor {} failed ({}), the text is kept in {}",
+			program,
+			status,
+			path.display()
+		)
+		.into());
+	}
+	Ok(())
+}
+
+pub fn print(args: PrintArgs) -> Result<()> {
+	match args.format {
+		PrintFormat::Pngcheck => return print_pngcheck(&args.filename, false),
+		PrintFormat::PngcheckVerbose => return print_pngcheck(&args.filename, true),
+		PrintFormat::Text | PrintFormat::Json => {}
+	}
+	let map = args.mmap.then(|| map_input(&args.filename)).transpose()?;
+	let png = match &map {
+		// Text output skips image data, which the mapped chunks then never read.
+		Some(map) if args.format == PrintFormat::Text => {
+			return print_mapped(&args, Png::from_mmap(map)?)
+		}
+		Some(map) => {
+			let png = Png::from_mmap(map)?;
+			for chunk in png.chunks() {
+				check_mapped_crc(chunk, args.ignore_crc)?;
+			}
+			png.into_png()
+		}
+		None if args.ignore_crc => read_damaged_png(&args.filename)?,
+		None => read_png(&args.filename)?,
+	};
+	let decoders = DecoderRegistry::default();
+	let decode = |chunk: &Chunk| if args.decode { decoders.decode(chunk) } else { None };
+	if args.format == PrintFormat::Json {
+		let chunks: Vec<_> = png
+			.chunks()
+			.iter()
+			.map(|chunk| {
+				let mut json = chunk_json(chunk);
+				if let Some(decoded) = decode(chunk) {
+					let fields: serde_json::Map<_, _> = decoded
+						.fields
+						.into_iter()
+						.map(|(name, value)| (name, value.into()))
+						.collect();
+					json["decoded"] = json!({ "summary": decoded.summary, "fields": fields });
+				}
+				if args.analysis {
+					let stats = ByteStats::of(chunk.data());
+					json["analysis"] = json!({
+						"entropy": stats.entropy,
+						"printable_ratio": stats.printable_ratio,
+						"histogram": stats.histogram.to_vec(),
+					});
+				}
+				json
+			})
+			.collect();
+		outln!("{:#}", Value::from(chunks));
+		return Ok(());
+	}
+	let mut rows: Vec<_> = (png.chunks_with_offsets().enumerate())
+		.map(|(index, (offset, chunk))| chunk_row(&args, &decoders, index, offset, chunk))
+		.collect();
+	rows.extend(trailer_row(&args, png.trailer(), trailer_offset(&png)));
+	print_table(&args, &rows);
+	Ok(())
+}
+
+/// Prints the chunks of a mapped PNG like `print`, never touching image data unless it is
+/// analyzed or dumped.
+fn print_mapped(args: &PrintArgs, png: PngRef) -> Result<()> {
+	let decoders = DecoderRegistry::default();
+	let trailer = png.trailer();
+	let trailer_offset = png.trailer_offset();
+	let mut rows = Vec::new();
+	for (index, chunk) in png.into_chunks().into_iter().enumerate() {
+		let offset = chunk.offset().unwrap_or_default();
+		let image_data = [ChunkType::IDAT, ChunkType::fdAT].contains(chunk.chunk_type());
+		if image_data && !args.analysis && !args.hex {
+			rows.push(ChunkRow {
+				index: Some(index),
+				offset,
+				chunk_type: Some(*chunk.chunk_type()),
+				length: chunk.length() as usize,
+				crc: Some((chunk.crc(), None)),
+				preview: String::new(),
+				dump: None,
+			});
+			continue;
+		}
+		check_mapped_crc(&chunk, args.ignore_crc)?;
+		rows.push(chunk_row(args, &decoders, index, offset, &chunk.into_chunk()));
+	}
+	rows.extend(trailer_row(args, trailer, trailer_offset));
+	print_table(args, &rows);
+	Ok(())
+}
+
+/// Checks the file like `check` and prints the result the way pngcheck does, with `verbose` like
+/// `pngcheck -v`. Chunk offsets are those of the chunk types, as pngcheck gives them.
+fn print_pngcheck(path: &Path, verbose: bool) -> Result<()> {
+	let name = path.display();
+	let mut bytes = Vec::new();
+	open_input(path)?.read_to_end(&mut bytes)?;
+	let mut problems: Vec<_> =
+		check::check(&bytes).into_iter().map(|problem| problem.message).collect();
+	let png = Png::from_reader_lenient(bytes.as_slice()).map_err(|e| e.to_string());
+	let header =
+		png.as_ref().map_err(Clone::clone).and_then(|png| png.header().map_err(|e| e.to_string()));
+	if let (Err(e), true) = (&header, problems.is_empty()) {
+		problems.push(e.clone());
+	}
+	if verbose {
+		outln!("File: {} ({} bytes)", name, bytes.len());
+		for problem in &problems {
+			outln!("  {}", problem);
+		}
+		if let Ok(png) = &png {
+			let mut image_data = false;
+			for (offset, chunk) in png.chunks_with_offsets() {
+				let first_idat = chunk.chunk_type() == &ChunkType::IDAT && !image_data;
+				image_data |= first_idat;
+				print_pngcheck_chunk(png, offset, chunk, first_idat);
+			}
+		}
+	} else {
+		for problem in &problems {
+			outln!("{}  {}", name, problem);
+		}
+	}
+	let (Ok(header), Ok(png), true) = (header, &png, problems.is_empty()) else {
+		if verbose {
+			outln!("ERRORS DETECTED in {}", name);
+		} else {
+			outln!("ERROR: {}", name);
+		}
+		let count = problems.len();
+		let noun = if count == 1 { "problem" } else { "problems" };
+		return Err(PngmeError::invalid("PNG file", format!("{} {} found", count, noun)));
+	};
+	let raw = header.height as f64 * idat::row_length(&header, header.width) as f64;
+	let compression = 100.0 * (1.0 - bytes.len() as f64 / raw);
+	if verbose {
+		outln!(
+			"No errors detected in {} ({} chunks, {:.1}% compression).",
+			name,
+			png.chunks().len(),
+			compression
+		);
+	} else {
+		let format = pngcheck_format(png, &header);
+		outln!(
+			"OK: {} ({}x{}, {}, {:.1}%).",
+			name,
+			header.width,
+			header.height,
+			format,
+			compression
+		);
+	}
+	Ok(())
+}
+
+fn print_pngcheck_chunk(png: &Png, offset: u64, chunk: &Chunk, first_idat: bool) {
+	let chunk_type = chunk.chunk_type();
+	out!("  chunk {} at offset {:#07x}, length {}", chunk_type, offset + 4, chunk.length());
+	if let Ok(text) = TextualChunk::try_from(chunk) {
+		out!(", keyword: {}", text.keyword());
+	}
+	outln!();
+	match chunk.data() {
+		_ if chunk_type == &ChunkType::IHDR => {
+			if let Ok(header) = png.header() {
+				let format = pngcheck_format(png, &header);
+				outln!("    {} x {} image, {}", header.width, header.height, format);
+			}
+		}
+		data if chunk_type == &ChunkType::PLTE => outln!("    {} palette entries", data.len() / 3),
+		[cmf, flg, ..] if first_idat => {
+			let window = match 1u32 << ((cmf >> 4) + 8) {
+				bytes if bytes >= 1024 => format!("{}K", bytes / 1024),
+				bytes => format!("{}-byte", bytes),
+			};
+			let level = ["superfast", "fast", "default", "maximum"][(flg >> 6) as usize];
+			outln!("    zlib: deflated, {} window, {} compression", window, level);
+		}
+		_ => {}
+	}
+}
+
+/// Describes the pixel format in pngcheck's words, such as `32-bit RGB+alpha, non-interlaced`.
+fn pngcheck_format(png: &Png, header: &IhdrData) -> String {
+	let color = match header.color_type {
+		ColorType::Grayscale => "grayscale",
+		ColorType::Rgb => "RGB",
+		ColorType::Indexed => "palette",
+		ColorType::GrayscaleAlpha => "grayscale+alpha",
+		ColorType::Rgba => "RGB+alpha",
+	};
+	let transparency = if png.chunk_by_type("tRNS").is_some() { "+trns" } else { "" };
+	let interlacing = if header.is_interlaced() { "interlaced" } else { "non-interlaced" };
+	format!("{}-bit {}{}, {}", header.bits_per_pixel(), color, transparency, interlacing)
+}
+
+/// Characters of chunk data shown in the preview column.
+const PREVIEW_LENGTH: usize = 40;
+
+/// A line of `print` output: a chunk, or the trailer when there is no chunk type.
+struct ChunkRow {
+	index: Option<usize>,
+	offset: u64,
+	chunk_type: Option<ChunkType>,
+	length: usize,
+	/// The stored CRC and whether it matches, if it was checked.
+	crc: Option<(u32, Option<bool>)>,
+	preview: String,
+	dump: Option<String>,
+}
+
+fn chunk_row(
+	args: &PrintArgs,
+	decoders: &DecoderRegistry,
+	index: usize,
+	offset: u64,
+	chunk: &Chunk,
+) -> ChunkRow {
+	let decoded = if args.decode { decoders.decode(chunk) } else { None };
+	let mut preview = match decoded {
+		Some(decoded) => decoded.summary,
+		None => {
+			let data = readable_data(chunk);
+			let text = match (args.render, str::from_utf8(&data)) {
+				(Render::Lossy, _) => String::from_utf8_lossy(&data).into_owned(),
+				(Render::Escaped, _) => hexdump::escape(&data),
+				(Render::Strict, Ok(string)) => string.to_owned(),
+				(Render::Strict, Err(_)) => String::new(),
+			};
+			preview(&text)
+		}
+	};
+	if args.analysis {
+		let stats = describe_stats(&ByteStats::of(chunk.data()));
+		preview = if preview.is_empty() { stats } else { format!("{}  {}", preview, stats) };
+	}
+	ChunkRow {
+		index: Some(index),
+		offset,
+		chunk_type: Some(*chunk.chunk_type()),
+		length: chunk.data().len(),
+		crc: Some((chunk.crc(), Some(chunk.has_valid_crc()))),
+		preview,
+		dump: (args.hex && chunk.length() > 0)
+			.then(|| HexDump::new(chunk.data(), offset + 8).to_string()),
+	}
+}
+
+fn trailer_row(args: &PrintArgs, trailer: &[u8], offset: u64) -> Option<ChunkRow> {
+	if trailer.is_empty() {
+		return None;
+	}
+	let preview = if args.analysis {
+		describe_stats(&ByteStats::of(trailer))
+	} else {
+		describe_trailer(trailer)
+	};
+	Some(ChunkRow {
+		index: None,
+		offset,
+		chunk_type: None,
+		length: trailer.len(),
+		crc: None,
+		preview,
+		dump: args.hex.then(|| HexDump::new(trailer, offset).to_string()),
+	})
+}
+
+/// Shortens `text` to [`PREVIEW_LENGTH`] characters on one line, control characters shown as
+/// `.`; escaped text has none left.
+fn preview(text: &str) -> String {
+	let mut preview: String =
+		text.chars().take(PREVIEW_LENGTH).map(|c| if c.is_control() { '.' } else { c }).collect();
+	if text.chars().nth(PREVIEW_LENGTH).is_some() {
+		preview.pop();
+		preview.push('…');
+	}
+	preview
+}
+
+/// Returns `C` or `A` for a critical or ancillary chunk, then `P` for a private one and `S` for
+/// one that is safe to copy, with `-` for the others.
+fn chunk_flags(chunk_type: &ChunkType) -> String {
+	[
+		if chunk_type.is_critical() { 'C' } else { 'A' },
+		if chunk_type.is_public() { '-' } else { 'P' },
+		if chunk_type.is_safe_to_copy() { 'S' } else { '-' },
+	]
+	.into_iter()
+	.collect()
+}
+
+/// Prints the rows as a table of the selected columns, each hex dump under its row.
+fn print_table(args: &PrintArgs, rows: &[ChunkRow]) {
+	let cell = |row: &ChunkRow, column: Column| match column {
+		Column::Index => row.index.map(|index| index.to_string()).unwrap_or_default(),
+		Column::Offset => format!("{:#010x}", row.offset),
+		Column::Type => row.chunk_type.map_or("(trailer)".to_owned(), |t| t.to_string()),
+		Column::Description => {
+			row.chunk_type.and_then(|t| t.description()).unwrap_or_default().to_owned()
+		}
+		Column::Length => row.length.to_string(),
+		Column::Crc => match row.crc {
+			Some((crc, Some(true))) => format!("{:08x} ok", crc),
+			Some((crc, Some(false))) => format!("{:08x} bad", crc),
+			Some((crc, None)) => format!("{:08x}", crc),
+			None => String::new(),
+		},
+		Column::Flags => row.chunk_type.as_ref().map(chunk_flags).unwrap_or_default(),
+		Column::Preview => row.preview.clone(),
+	};
+	let heading = |column: Column| match column {
+		Column::Index => "#",
+		Column::Offset => "OFFSET",
+		Column::Type => "TYPE",
+		Column::Description => "DESCRIPTION",
+		Column::Length => "LENGTH",
+		Column::Crc => "CRC",
+		Column::Flags => "FLAGS",
+		Column::Preview => "PREVIEW",
+	};
+	let cells: Vec<Vec<String>> = rows
+		.iter()
+		.map(|row| args.columns.iter().map(|&column| cell(row, column)).collect())
+		.collect();
+	let widths: Vec<usize> = (args.columns.iter().enumerate())
+		.map(|(n, &column)| {
+			let cells = cells.iter().map(|row| row[n].chars().count());
+			cells.chain([heading(column).len()]).max().unwrap_or_default()
+		})
+		.collect();
+	// Chunk types are colored by class and bad CRCs as errors; padding goes outside the color.
+	let style = |row: &ChunkRow, column: Column| match (column, row.crc) {
+		(Column::Type, _) => row.chunk_type.as_ref().map(Style::of),
+		(Column::Crc, Some((_, Some(false)))) => Some(Style::Error),
+		_ => None,
+	};
+	let print_line = |line: Vec<String>, row: Option<&ChunkRow>| {
+		let last = line.len().saturating_sub(1);
+		let padded: Vec<String> = (line.into_iter().enumerate())
+			.map(|(n, cell)| {
+				let column = args.columns[n];
+				let padding = widths[n] - cell.chars().count();
+				let cell = match row.and_then(|row| style(row, column)) {
+					Some(style) => output::paint(style, cell).to_string(),
+					None => cell,
+				};
+				match column {
+					Column::Index | Column::Length => format!("{:padding$}{}", "", cell),
+					_ if n == last => cell,
+					_ => format!("{}{:padding$}", cell, ""),
+				}
+			})
+			.collect();
+		outln!("{}", padded.join("  ").trim_end());
+	};
+	print_line(args.columns.iter().map(|&column| heading(column).to_owned()).collect(), None);
+	for (row, line) in rows.iter().zip(cells) {
+		print_line(line, Some(row));
+		if let Some(dump) = &row.dump {
+			outln!("{}", dump);
+		}
+	}
+}
+
+pub fn strip(args: StripArgs) -> Result<()> {
+	let parse =
+		|types: &[String]| types.iter().map(|t| t.parse()).collect::<Result<Vec<ChunkType>>>();
+	let (keep, only) = (parse(&args.keep)?, parse(&args.only)?);
+	let mut png = read_png(&args.filename)?;
+	let before = png.as_bytes().len();
+	let mut removed = 0;
+	png.retain_chunks(|chunk| {
+		let chunk_type = chunk.chunk_type();
+		let strip = !chunk_type.is_critical()
+			&& !keep.contains(chunk_type)
+			&& (only.is_empty() || only.contains(chunk_type));
+		removed += strip as usize;
+		!strip
+	});
+	let saved = before - png.as_bytes().len();
+	log::info!("Removed {} chunks, saving {} bytes", removed, saved);
+	write_png(&png, output_path(args.output.as_ref(), &args.filename)?)
+}
+
+pub fn optimize(args: OptimizeArgs) -> Result<()> {
+	let mut png = read_png(&args.filename)?;
+	let before = png.as_bytes().len();
+	let options = OptimizeOptions {
+		level: args.level,
+		try_filters: args.try_filters,
+		strip_metadata: args.strip_metadata,
+	};
+	optimize::optimize(&mut png, &options)?;
+	let after = png.as_bytes().len();
+	log::info!("Optimized {} to {} bytes, saving {} bytes", before, after, before - after);
+	write_png(&png, output_path(args.output.as_ref(), &args.filename)?)
+}
+
+pub fn canonicalize(args: CanonicalizeArgs) -> Result<()> {
+	let mut png = read_png(&args.filename)?;
+	if args.check {
+		if !canonical::is_canonical(&png)? {
+			return Err(PngmeError::invalid("PNG", "it is not in canonical form"));
+		}
+		outln!("{}: canonical", args.filename.display());
+		return Ok(());
+	}
+	let before = png.as_bytes().len();
+	canonical::canonicalize(&mut png)?;
+	log::info!("Canonicalized {} bytes to {} bytes", before, png.as_bytes().len());
+	write_png(&png, output_path(args.output.as_ref(), &args.filename)?)
+}
+
+pub fn hash(args: HashArgs) -> Result<()> {
+	let algorithm = match args.algo {
+		HashAlgorithm::Sha256 => Algorithm::Sha256,
+		HashAlgorithm::Sha512 => Algorithm::Sha512,
+	};
+	let scope = if args.pixels_only { Scope::Pixels } else { Scope::Critical };
+	for path in &args.filenames {
+		let hash = content_hash::hash(&read_png(path)?, algorithm, scope)?;
+		let hex: String = hash.iter().map(|byte| format!("{:02x}", byte)).collect();
+		outln!("{}  {}", hex, path.display());
+	}
+	Ok(())
+}
+
+pub fn trailer(args: TrailerArgs) -> Result<()> {
+	let mut png = read_png(&args.filename)?;
+	if let Some(path) = &args.extract {
+		refuse_overwrite(path)?;
+		let mut output = open_output(path)?;
+		output.write_all(png.trailer())?;
+		output.finish()?;
+	} else if args.strip {
+		let trailer = png.take_trailer();
+		log::info!("Stripped {} bytes", trailer.len());
+		write_png(&png, output_path(args.output.as_ref(), &args.filename)?)?;
+	} else if png.trailer().is_empty() {
+		outln!("No data after IEND");
+	} else {
+		outln!("{:#010x}\t{}", trailer_offset(&png), describe_trailer(png.trailer()));
+	}
+	Ok(())
+}
+
+fn trailer_offset(png: &Png) -> u64 {
+	png.chunks_with_offsets().last().map_or(Png::STANDARD_HEADER.len() as u64, |(offset, chunk)| {
+		offset + chunk.length() as u64 + 12
+	})
+}
+
+fn describe_trailer(trailer: &[u8]) -> String {
+	format!("{} bytes, entropy {:.2} bits/byte", trailer.len(), analysis::entropy(trailer))
+}
+
+pub fn info(args: InfoArgs) -> Result<()> {
+	let png = read_png(&args.filename)?;
+	let header = png.header()?;
+	let animation = if png.is_apng() { Animation::of(&png)? } else { None };
+	if args.format == Format::Json {
+		let json = json!({
+			"width": header.width,
+			"height": header.height,
+			"bit_depth": header.bit_depth,
+			"color_type": header.color_type.to_string(),
+			"interlaced": header.is_interlaced(),
+			"chunks": png.chunks().len(),
+			"animation": animation.map(|animation| json!({
+				"frames": animation.control.num_frames,
+				"plays": animation.control.num_plays,
+				"delays_ms": animation.frames.iter().map(|frame| frame.delay().as_millis() as u64).collect::<Vec<_>>(),
+			})),
+		});
+		outln!("{:#}", json);
+		return Ok(());
+	}
+	outln!("Dimensions: {}x{}", header.width, header.height);
+	outln!("Bit depth:  {}", header.bit_depth);
+	outln!("Color type: {}", header.color_type);
+	outln!("Interlaced: {}", if header.is_interlaced() { "Adam7" } else { "no" });
+	outln!("Chunks:     {}", png.chunks().len());
+	if let Some(animation) = animation {
+		outln!("Animation:  {}", animation);
+	}
+	Ok(())
+}
+
+pub fn check(args: CheckArgs) -> Result<()> {
+	let mut bytes = Vec::new();
+	open_input(&args.filename)?.read_to_end(&mut bytes)?;
+	let problems = check::check(&bytes);
+	for problem in &problems {
+		outln!("{}", problem);
+	}
+	match problems.len() {
+		0 => {
+			outln!("{}: OK", args.filename.display());
+			Ok(())
+		}
+		1 => Err(PngmeError::invalid("PNG file", "1 problem found")),
+		n => Err(PngmeError::invalid("PNG file", format!("{} problems found", n))),
+	}
+}
+
+pub fn scan(args: InfoArgs) -> Result<()> {
+	let png = read_png(&args.filename)?;
+	let findings = scan::scan(&png);
+	if args.format == Format::Json {
+		let findings: Vec<_> = findings
+			.iter()
+			.map(|finding| {
+				json!({
+					"severity": finding.severity.name(),
+					"offset": finding.offset,
+					"message": finding.message,
+				})
+			})
+			.collect();
+		outln!("{:#}", Value::from(findings));
+		return Ok(());
+	}
+	for finding in &findings {
+		outln!("{}", finding);
+	}
+	if findings.is_empty() {
+		log::info!("Nothing suspicious found");
+	}
+	Ok(())
+}
+
+pub fn capacity(args: CapacityArgs) -> Result<()> {
+	let png = read_png(&args.filename)?;
+	let overhead = if args.encrypt { crypto::OVERHEAD } else { 0 }
+		+ if args.raw { 0 } else { envelope::OVERHEAD };
+	let chunk = Chunk::MAX_LENGTH as usize - overhead;
+	let after_iend = appended::MAX_PAYLOAD_LEN - overhead;
+	let lsb = stego::capacity(&png).map(|capacity| capacity.saturating_sub(overhead));
+	if args.format == Format::Json {
+		let json = json!({
+			"chunk": chunk,
+			"after_iend": after_iend,
+			"lsb": lsb.as_ref().ok(),
+		});
+		outln!("{:#}", json);
+		return Ok(());
+	}
+	outln!("chunk\t{} bytes per chunk, more when split across chunks", chunk);
+	outln!("after-iend\t{} bytes", after_iend);
+	match lsb {
+		Ok(capacity) => outln!("lsb\t{} bytes", capacity),
+		Err(e) => outln!("lsb\tunavailable: {}", e),
+	}
+	Ok(())
+}
+
+pub fn sign(args: SignArgs) -> Result<()> {
+	let key = signature::signing_key_from_pem(&fs::read_to_string(&args.key)?)?;
+	let chunk_type: ChunkType = args.chunk_type.parse()?;
+	let mut png = read_png(&args.filename)?;
+	signature::sign(&mut png, &chunk_type, &key, args.critical)?;
+	write_png(&png, output_path(args.output.as_ref(), &args.filename)?)
+}
+
+pub fn verify_sig(args: VerifySigArgs) -> Result<()> {
+	let key = signature::verifying_key_from_pem(&fs::read_to_string(&args.key)?)?;
+	let chunk_type: ChunkType = args.chunk_type.parse()?;
+	let png = read_png(&args.filename)?;
+	if !signature::verify(&png, &chunk_type, &key)? {
+		return Err(PngmeError::invalid(
+			format!("signature over {}", chunk_type),
+			"it does not match the chunks or the key",
+		));
+	}
+	outln!("Good signature over {}", chunk_type);
+	Ok(())
+}
+
+pub fn repair(args: RepairArgs) -> Result<()> {
+	let mut bytes = Vec::new();
+	open_input(&args.filename)?.read_to_end(&mut bytes)?;
+	let options = RepairOptions {
+		fix_crcs: !args.keep_crcs,
+		add_iend: !args.no_iend,
+		strip_trailer: !args.keep_trailer,
+		truncate_partial: !args.keep_partial,
+	};
+	// The output is checked first so that no fixes are reported for a file that is not written.
+	let mut output = open_output(output_path(args.output.as_ref(), &args.filename)?)?;
+	let (repaired, fixes) = repair::repair(&bytes, options)?;
+	for fix in &fixes {
+		log::info!("{}", fix);
+	}
+	if fixes.is_empty() {
+		log::info!("Nothing to repair");
+	}
+	output.write_all(&repaired)?;
+	output.finish()?;
+	Ok(())
+}
+
+pub fn restore(args: RestoreArgs) -> Result<()> {
+	let backup = atomic::backup_path(&args.filename, &args.suffix);
+	if !backup.is_file() {
+		return Err(not_found(format!(
+			"No backup of {} at {}",
+			args.filename.display(),
+			backup.display()
+		)));
+	}
+	let path = fs::canonicalize(&args.filename).unwrap_or(args.filename);
+	if atomic::is_dry_run() {
+		outln!("Would restore {} from {}", path.display(), backup.display());
+		return Ok(());
+	}
+	fs::rename(&backup, &path)?;
+	log::info!("Restored {} from {}", path.display(), backup.display());
+	Ok(())
+}
+
+pub fn carve(args: CarveArgs) -> Result<()> {
+	let mut data = Vec::new();
+	open_input(&args.filename)?.read_to_end(&mut data)?;
+	let name = match args.filename.file_stem() {
+		Some(stem) if !is_stdio(&args.filename) => stem.to_string_lossy().into_owned(),
+		_ => "carved".to_owned(),
+	};
+	let found = carve::carve(&data);
+	if found.is_empty() {
+		log::info!("No PNG files found");
+		return Ok(());
+	}
+	if !atomic::is_dry_run() {
+		fs::create_dir_all(&args.output)?;
+	}
+	for carved in &found {
+		// The original bytes are written as they are rather than re-encoded.
+		let path = args.output.join(format!("{}-{:08x}.png", name, carved.offset));
+		refuse_overwrite(&path)?;
+		let mut output = open_output(&path)?;
+		output.write_all(&data[carved.offset..carved.offset + carved.len])?;
+		output.finish()?;
+		outln!("{}\t{:#010x}\t{} bytes", path.display(), carved.offset, carved.len);
+	}
+	Ok(())
+}
+
+pub fn copy(args: CopyArgs) -> Result<()> {
+	let chunk_type: ChunkType = args.chunk_type.parse()?;
+	if !chunk_type.is_safe_to_copy() && !args.allow_unsafe {
+		return Err(usage(format!(
+			"{} chunks are not safe to copy, use --allow-unsafe to copy anyway",
+			chunk_type
+		)));
+	}
+	let (from, mut to) = (read_png(&args.from)?, read_png(&args.to)?);
+	let matching = from.chunks().iter().filter(|chunk| chunk.chunk_type() == &chunk_type);
+	let mut copied = 0;
+	for chunk in matching.take(if args.all { usize::MAX } else { 1 }) {
+		to.insert_chunk(chunk.clone());
+		copied += 1;
+	}
+	if copied == 0 {
+		return Err(not_found(format!("No {} chunk found in {}", chunk_type, args.from.display())));
+	}
+	write_png(&to, output_path(args.output.as_ref(), &args.to)?)
+}
+
+pub fn diff(args: DiffArgs) -> Result<()> {
+	let (old, new) = (read_png(&args.old)?, read_png(&args.new)?);
+	print_changes(&old, &new, args.bytes);
+	Ok(())
+}
+
+pub fn same_image(args: SameImageArgs) -> Result<()> {
+	let (first, second) = (read_png(&args.first)?, read_png(&args.second)?);
+	match image_difference(&first, &second)? {
+		Some(difference) => Err(format!("The images differ: {}", difference).into()),
+		None => {
+			outln!("Same image");
+			Ok(())
+		}
+	}
+}
+
+/// Decodes both images and describes the first way in which they differ, if any.
+fn image_difference(first: &Png, second: &Png) -> Result<Option<String>> {
+	let (a, b) = (first.header()?, second.header()?);
+	if (a.width, a.height) != (b.width, b.height) {
+		return Ok(Some(format!("{}x{} and {}x{} pixels", a.width, a.height, b.width, b.height)));
+	}
+	if (a.color_type, a.bit_depth) != (b.color_type, b.bit_depth) {
+		return Ok(Some(format!(
+			"{} at {} bits and {} at {} bits",
+			a.color_type, a.bit_depth, b.color_type, b.bit_depth
+		)));
+	}
+	let palette = |png: &Png| png.chunk_by_type("PLTE").map(|chunk| chunk.data().to_vec());
+	if a.color_type == ColorType::Indexed && palette(first) != palette(second) {
+		return Ok(Some("the palettes differ".to_owned()));
+	}
+	let decode = |png: &Png, header: &IhdrData| -> Result<Vec<u8>> {
+		let mut data = idat::decompressed_data(png)?;
+		idat::unfilter(header, &mut data)?;
+		idat::pixels(header, &data)
+	};
+	let size = a.bits_per_pixel().div_ceil(8);
+	let (first, second) = (decode(first, &a)?, decode(second, &b)?);
+	let mut differing =
+		first.chunks(size).zip(second.chunks(size)).enumerate().filter(|(_, (x, y))| x != y);
+	let Some((index, _)) = differing.next() else {
+		return Ok(None);
+	};
+	let (x, y) = (index % a.width as usize, index / a.width as usize);
+	let count = 1 + differing.count();
+	let (noun, verb) = if count == 1 { ("pixel", "differs") } else { ("pixels", "differ") };
+	Ok(Some(format!("{} {} {}, the first at ({}, {})", count, noun, verb, x, y)))
+}
+
+/// Prints the chunks added, removed, and modified from `old` to `new`, and with `bytes` the
+/// byte ranges that changed in each modified chunk.
+fn print_changes(old: &Png, new: &Png, bytes: bool) {
+	let describe = |chunk: &Chunk| {
+		format!("{} (length {}, crc 0x{:08x})", chunk.chunk_type(), chunk.length(), chunk.crc())
+	};
+	for change in diff::diff(old, new) {
+		match change {
+			ChunkChange::Added { new_index } => {
+				let line = format!("+ [{}] {}", new_index, describe(&new.chunks()[new_index]));
+				outln!("{}", output::paint(Style::Added, line))
+			}
+			ChunkChange::Removed { old_index } => {
+				let line = format!("- [{}] {}", old_index, describe(&old.chunks()[old_index]));
+				outln!("{}", output::paint(Style::Removed, line))
+			}
+			ChunkChange::Modified { old_index, new_index } => {
+				let (old_chunk, new_chunk) = (&old.chunks()[old_index], &new.chunks()[new_index]);
+				let line = format!(
+					"~ [{} -> {}] {} -> {}",
+					old_index,
+					new_index,
+					describe(old_chunk),
+					describe(new_chunk)
+				);
+				outln!("{}", output::paint(Style::Modified, line));
+				if bytes {
+					for range in diff::byte_differences(old_chunk.data(), new_chunk.data()) {
+						let old_bytes = old_chunk.data().get(range.clone()).unwrap_or_default();
+						let new_bytes = new_chunk.data().get(range.clone()).unwrap_or_default();
+						outln!("\tbytes {:?}: {:02x?} -> {:02x?}", range, old_bytes, new_bytes);
+					}
+				}
+			}
+		}
+	}
+}
+
+pub fn export(args: ExportArgs) -> Result<()> {
+	let png = read_png(&args.filename)?;
+	refuse_overwrite(&args.json)?;
+	let mut output = open_output(&args.json)?;
+	serde_json::to_writer_pretty(&mut output, &png)?;
+	writeln!(output)?;
+	output.finish()?;
+	Ok(())
+}
+
+pub fn import(args: ImportArgs) -> Result<()> {
+	let png: Png = serde_json::from_reader(BufReader::new(open_input(&args.json)?))?;
+	write_png(&png, &args.filename)
+}
+
+pub fn create(args: CreateArgs) -> Result<()> {
+	let (width, height) = args.size;
+	let samples = args.fill.0;
+	let header = IhdrData {
+		width,
+		height,
+		bit_depth: 8,
+		color_type: if samples.len() == 4 { ColorType::Rgba } else { ColorType::Rgb },
+		compression_method: 0,
+		filter_method: 0,
+		interlace_method: 0,
+	};
+	let row: Vec<u8> = iter::once(0).chain(samples.repeat(width as usize)).collect();
+	let compressed =
+		idat::compress(&header, row.repeat(height as usize), FilterStrategy::Adaptive)?;
+	let mut builder = PngBuilder::new().header(header).idat(compressed);
+	if let (Some(message), Some(chunk_type)) = (args.message, args.chunk_type) {
+		let envelope = Envelope::new(message.into_bytes());
+		builder = builder.chunk(Chunk::new(chunk_type.parse()?, envelope.to_bytes()?));
+	}
+	refuse_overwrite(&args.filename)?;
+	write_png(&builder.build()?, &args.filename)
+}
+
+pub fn pack(args: PackArgs) -> Result<()> {
+	let mut png = read_png(&args.filename)?;
+	let chunk_type: ChunkType = args.chunk_type.parse()?;
+	let (entries, skipped) = archive::read_dir(&args.dir)?;
+	for path in skipped {
+		log::warn!("skipping {}, which is neither a file nor a directory", path.display());
+	}
+	let (body, compression, encryption) = seal(
+		archive::to_bytes(&entries)?,
+		args.compress,
+		args.encrypt,
+		args.passphrase,
+		&args.recipient,
+	)?;
+	let data =
+		Envelope { binary: true, compression, encryption, ..Envelope::new(body) }.to_bytes()?;
+	if png.chunk_by_type(&args.chunk_type).is_some() {
+		// The fragments of two archives of the same type could not be told apart.
+		if !atomic::is_forced() {
+			return Err(usage(format!(
+				"The file already has a {} chunk, pass --force to replace it",
+				chunk_type
+			)));
+		}
+		png.remove_chunks(&args.chunk_type);
+	}
+	let mut entry = manifest::Entry {
+		name: dir_name(&args.dir),
+		archive: true,
+		..manifest::Entry::new(Location::Chunk, chunk_type, None, &data, 1)
+	};
+	let fragments = if data.len() > args.chunk_size {
+		fragment::split(&data, args.chunk_size)?
+	} else {
+		vec![data]
+	};
+	entry.fragments = fragments.len() as u32;
+	for fragment in fragments {
+		let position = ordering::default_position(&png, &chunk_type);
+		png.insert_chunk_at(position, Chunk::new(chunk_type, fragment));
+	}
+	manifest::update(&mut png, Some(entry))?;
+	write_png(&png, output_path(args.output.as_ref(), &args.filename)?)
+}
+
+/// The name of a directory given on the command line, even if it is `.`.
+fn dir_name(dir: &Path) -> Option<String> {
+	let dir = fs::canonicalize(dir).unwrap_or_else(|_| dir.to_owned());
+	dir.file_name().map(|name| name.to_string_lossy().into_owned())
+}
+
+pub fn unpack(args: UnpackArgs) -> Result<()> {
+	let png = read_png(&args.filename)?;
+	let chunk_type = archive_chunk_type(&png, &args)?;
+	let type_name = chunk_type.to_string();
+	let Some(first) = png.chunk_by_type(&type_name) else {
+		return Err(PngmeError::ChunkNotFound { chunk_type });
+	};
+	let payload = if fragment::is_fragment(first.data()) {
+		let fragments = png
+			.chunks_by_type(&type_name)
+			.map(Chunk::data)
+			.filter(|data| fragment::is_fragment(data));
+		fragment::reassemble(fragments)?
+	} else {
+		first.data().to_vec()
+	};
+	let (data, _) = open_payload(&args.decryption, payload)?;
+	if !archive::is_archive(&data) {
+		return Err(PngmeError::invalid(
+			format!("{} chunk", chunk_type),
+			"it does not hold a directory stored with pack",
+		));
+	}
+	let entries = archive::parse(&data)?;
+	if args.list {
+		for entry in &entries {
+			match entry.kind {
+				EntryKind::Directory => outln!("{:04o} {:>10} {}/", entry.mode, "", entry.path),
+				EntryKind::File => {
+					outln!("{:04o} {:>10} {}", entry.mode, entry.data.len(), entry.path)
+				}
+			}
+		}
+		return Ok(());
+	}
+	for entry in entries.iter().filter(|entry| entry.kind == EntryKind::File) {
+		refuse_overwrite(&archive::entry_path(&args.output, entry))?;
+	}
+	if atomic::is_dry_run() {
+		let bytes: usize = entries.iter().map(|entry| entry.data.len()).sum();
+		outln!(
+			"Would restore {} files and directories, {} bytes, to {}",
+			entries.len(),
+			bytes,
+			args.output.display()
+		);
+		return Ok(());
+	}
+	archive::extract(&entries, &args.output)
+}
+
+/// The chunk type of the archive to unpack: the one given, or the one the manifest lists.
+fn archive_chunk_type(png: &Png, args: &UnpackArgs) -> Result<ChunkType> {
+	if let Some(chunk_type) = &args.chunk_type {
+		return chunk_type.parse();
+	}
+	let manifest = Manifest::read(png)?.unwrap_or_default();
+	let archives: Vec<_> = manifest
+		.entries
+		.iter()
+		.filter(|entry| entry.archive && entry.location == Location::Chunk)
+		.filter(|entry| args.name.is_none() || entry.name == args.name)
+		.collect();
+	match (&archives[..], &args.name) {
+		([], Some(name)) => Err(not_found(format!("No directory named {} is stored", name))),
+		([], None) => Ok(archive::CHUNK_TYPE),
+		([entry], _) => Ok(entry.chunk_type),
+		(archives, _) => {
+			let names: Vec<_> =
+				archives.iter().map(|entry| entry.name.as_deref().unwrap_or("?")).collect();
+			Err(usage(format!(
+				"Several directories are stored, pick one with --name: {}",
+				names.join(", ")
+			)))
+		}
+	}
+}
+
+pub fn text_set(args: TextSetArgs) -> Result<()> {
+	let mut png = read_png(&args.filename)?;
+	let is_latin1 = args.value.chars().all(|c| u8::try_from(c).is_ok());
+	let text = if args.international
+		|| args.language.is_some()
+		|| args.translated_keyword.is_some()
+		|| !is_latin1
+	{
+		TextualChunk::Itxt(ItxtChunk::new(
+			&args.keyword,
+			args.language.as_deref().unwrap_or_default(),
+			args.translated_keyword.as_deref().unwrap_or_default(),
+			&args.value,
+			args.compress,
+		)?)
+	} else if args.compress {
+		TextualChunk::Ztxt(ZtxtChunk::new(&args.keyword, &args.value)?)
+	} else {
+		TextualChunk::Text(TextChunk::new(&args.keyword, &args.value)?)
+	};
+	png.retain_chunks(|chunk| {
+		TextualChunk::try_from(chunk).map_or(true, |existing| existing.keyword() != text.keyword())
+	});
+	png.insert_chunk(text.to_chunk());
+	write_png(&png, output_path(args.output.as_ref(), &args.filename)?)
+}
+
+pub fn text_get(args: TextGetArgs) -> Result<()> {
+	let png = read_png(&args.filename)?;
+	let texts = png.chunks().iter().filter_map(|chunk| TextualChunk::try_from(chunk).ok());
+	match &args.keyword {
+		Some(keyword) => texts
+			.filter(|text| text.keyword() == keyword)
+			.for_each(|text| outln!("{}", text.text())),
+		None => texts.for_each(|text| outln!("{}\t{}", text.keyword(), text.text())),
+	}
+	Ok(())
+}
+
+pub fn exif_show(args: ExifShowArgs) -> Result<()> {
+	let png = read_png(&args.filename)?;
+	let chunk = png
+		.chunk_by_type("eXIf")
+		.ok_or(PngmeError::ChunkNotFound { chunk_type: ChunkType::eXIf })?;
+	for tag in ExifChunk::try_from(chunk)?.tags()? {
+		outln!("{}", tag);
+	}
+	Ok(())
+}
+
+pub fn exif_strip(args: ExifStripArgs) -> Result<()> {
+	let mut png = read_png(&args.filename)?;
+	if png.remove_chunks("eXIf").is_empty() {
+		return Err(PngmeError::ChunkNotFound { chunk_type: ChunkType::eXIf });
+	}
+	write_png(&png, output_path(args.output.as_ref(), &args.filename)?)
+}
+
+pub fn exif_import(args: ExifImportArgs) -> Result<()> {
+	let mut data = Vec::new();
+	open_input(&args.exif)?.read_to_end(&mut data)?;
+	let exif = ExifChunk::new(data)?;
+	let mut png = read_png(&args.filename)?;
+	png.remove_chunks("eXIf");
+	png.insert_chunk(exif.to_chunk());
+	write_png(&png, output_path(args.output.as_ref(), &args.filename)?)
+}
+
+pub fn icc_extract(args: IccExtractArgs) -> Result<()> {
+	let png = read_png(&args.filename)?;
+	let profile =
+		IccProfile::find(&png)?.ok_or(PngmeError::ChunkNotFound { chunk_type: ChunkType::iCCP })?;
+	refuse_overwrite(&args.output)?;
+	let mut output = open_output(&args.output)?;
+	output.write_all(profile.profile())?;
+	output.finish()?;
+	Ok(())
+}
+
+pub fn icc_embed(args: IccEmbedArgs) -> Result<()> {
+	let mut profile = Vec::new();
+	open_input(&args.profile)?.read_to_end(&mut profile)?;
+	let profile = IccProfile::new(&args.name, profile)?;
+	let mut png = read_png(&args.filename)?;
+	png.remove_chunks("iCCP");
+	if png.chunk_by_type("sRGB").is_some() {
+		log::warn!("the sRGB chunk takes precedence over the embedded profile");
+	}
+	png.insert_chunk(profile.to_chunk());
+	write_png(&png, output_path(args.output.as_ref(), &args.filename)?)
+}
+
+pub fn xmp_get(args: XmpGetArgs) -> Result<()> {
+	let png = read_png(&args.filename)?;
+	let packet = xmp::get(&png).ok_or_else(|| not_found("No XMP metadata found"))?;
+	if args.raw {
+		outln!("{}", packet);
+	} else {
+		out!("{}", xmp::pretty_print(&packet));
+	}
+	Ok(())
+}
+
+pub fn xmp_set(args: XmpSetArgs) -> Result<()> {
+	let mut packet = String::new();
+	open_input(&args.packet)?.read_to_string(&mut packet)?;
+	let mut png = read_png(&args.filename)?;
+	xmp::set(&mut png, &packet)?;
+	write_png(&png, output_path(args.output.as_ref(), &args.filename)?)
+}
+
+pub fn xmp_strip(args: XmpStripArgs) -> Result<()> {
+	let mut png = read_png(&args.filename)?;
+	if !xmp::strip(&mut png) {
+		return Err(not_found("No XMP metadata found"));
+	}
+	write_png(&png, output_path(args.output.as_ref(), &args.filename)?)
+}
+
+pub fn apng_split(args: ApngSplitArgs) -> Result<()> {
+	let png = read_png(&args.filename)?;
+	let frames = apng::split(&png)?;
+	let name = match args.filename.file_stem() {
+		Some(stem) if !is_stdio(&args.filename) => stem.to_string_lossy().into_owned(),
+		_ => "frame".to_owned(),
+	};
+	if !atomic::is_dry_run() {
+		fs::create_dir_all(&args.output)?;
+	}
+	for (n, frame) in frames.iter().enumerate() {
+		let path = args.output.join(format!("{}-{:03}.png", name, n + 1));
+		refuse_overwrite(&path)?;
+		write_png(frame, &path)?;
+		outln!("{}", path.display());
+	}
+	Ok(())
+}
+
+pub fn apng_build(args: ApngBuildArgs) -> Result<()> {
+	let frames = args.frames.iter().map(|path| read_png(path)).collect::<Result<Vec<_>>>()?;
+	refuse_overwrite(&args.output)?;
+	write_png(&apng::build(&frames, args.delay, args.loops)?, &args.output)
+}
+
+pub fn idat_info(args: InfoArgs) -> Result<()> {
+	let stats = IdatStats::of(&read_png(&args.filename)?)?;
+	if args.format == Format::Json {
+		let filters: serde_json::Map<_, _> = FILTER_NAMES
+			.iter()
+			.zip(stats.filters)
+			.map(|(&name, count)| (name.into(), count.into()))
+			.collect();
+		let json = json!({
+			"chunks": stats.chunks,
+			"compressed_size": stats.compressed_size,
+			"decompressed_size": stats.decompressed_size,
+			"expected_size": stats.expected_size,
+			"compression_ratio": stats.compression_ratio(),
+			"filters": filters,
+			"invalid_filters": stats.invalid_filters,
+		});
+		outln!("{:#}", json);
+	} else {
+		outln!("{}", stats);
+	}
+	Ok(())
+}
+
+/// Expands the paths given to a command over many files.
+fn walk_files(args: &WalkArgs) -> Result<Vec<PathBuf>> {
+	let options = WalkOptions {
+		recursive: args.recursive,
+		include: args.include.iter().map(|p| p.parse()).collect::<Result<_>>()?,
+		exclude: args.exclude.iter().map(|p| p.parse()).collect::<Result<_>>()?,
+	};
+	// URLs are passed through like files, and not taken for patterns.
+	let mut files = Vec::new();
+	for path in &args.paths {
+		match remote::is_url(path) {
+			true => files.push(path.clone()),
+			false => files.extend(walk::expand(slice::from_ref(path), &options)?),
+		}
+	}
+	let mut seen = HashSet::new();
+	files.retain(|file| seen.insert(file.clone()));
+	Ok(files)
+}
+
+/// The number of worker threads for `--jobs`, where 0 means one per CPU.
+fn job_count(jobs: usize) -> usize {
+	match jobs {
+		0 => thread::available_parallelism().map_or(1, |n| n.get()),
+		jobs => jobs,
+	}
+}
+
+/// How many words of `command` name the (sub)command that `runner` runs on each file.
+fn command_depth(command: &[OsString], runner: &str) -> Result<usize> {
+	let mut found = Cli::command();
+	let mut depth = 0;
+	while let Some(sub) = command.get(depth).and_then(|name| found.find_subcommand(name)) {
+		found = sub.clone();
+		depth += 1;
+	}
+	match found.get_name() {
+		_ if depth == 0 => Err(usage(format!("Unknown command {:?}", command[0]))),
+		name if name == runner => Err(usage(format!("{} cannot run itself", runner))),
+		name if found.has_subcommands() => Err(usage(format!("{} needs a subcommand", name))),
+		_ => Ok(depth),
+	}
+}
+
+/// The arguments that run `command`, named by its first `depth` words, on `file`.
+fn file_command(command: &[OsString], depth: usize, file: &Path) -> Vec<OsString> {
+	// The file name goes right after the (sub)command name, where every command expects it.
+	let mut argv = vec![OsString::from("pngme")];
+	argv.extend(command[..depth].iter().cloned());
+	argv.push(file.into());
+	argv.extend(command[depth..].iter().cloned());
+	argv
+}
+
+/// Parses `argv` into a command on the one file it names, for `runner` to run.
+fn one_file_command(argv: Vec<OsString>, runner: &str) -> Result<Commands> {
+	match Cli::parse_or_exit(argv) {
+		Cli { files: Some(_), .. } => {
+			Err(usage(format!("{} runs the command on one file at a time", runner)))
+		}
+		cli => Ok(cli.command),
+	}
+}
+
+pub fn batch(args: BatchArgs, run: fn(Commands) -> Result<()>) -> Result<()> {
+	let files = walk_files(&args.walk)?;
+	let depth = command_depth(&args.command, "batch")?;
+	// Parsed up front so that a mistake in the arguments stops the batch before it starts.
+	let commands = files
+		.iter()
+		.map(|file| one_file_command(file_command(&args.command, depth, file), "batch"))
+		.collect::<Result<Vec<_>>>()?;
+	run_each(&files, commands, job_count(args.jobs), args.fail_fast, run)
+}
+
+/// Runs the command of `argv`, which was given `files` in place of its file name, on each file
+/// they name, as `batch` would.
+pub fn each_file(
+	files: &WalkArgs,
+	argv: &[OsString],
+	run: fn(Commands) -> Result<()>,
+) -> Result<()> {
+	let files = walk_files(files)?;
+	let mut commands = files
+		.iter()
+		.map(|file| {
+			let mut command = Cli::parse_or_exit(argv).command;
+			*command.filename_mut().expect("the command takes a file name") = file.clone();
+			command
+		})
+		.collect::<Vec<_>>();
+	// A pattern that names a single file runs the command as if the file had been given.
+	match commands.len() {
+		1 => run(commands.pop().expect("there is a command")),
+		_ => run_each(&files, commands, 1, false, run),
+	}
+}
+
+/// Runs each of `commands` on the file of `files` at the same position, `jobs` at a time,
+/// printing their output in file order and a summary of how many failed.
+fn run_each(
+	files: &[PathBuf],
+	commands: Vec<Commands>,
+	jobs: usize,
+	fail_fast: bool,
+	run: fn(Commands) -> Result<()>,
+) -> Result<()> {
+	// Workers take the next file as they finish one; the results are printed in file order.
+	let commands = Mutex::new(commands.into_iter().enumerate());
+	let stop = AtomicBool::new(false);
+	let (sender, receiver) = mpsc::channel();
+	let (mut succeeded, mut failed) = (0, 0);
+	thread::scope(|scope| {
+		for _ in 0..jobs.min(files.len()) {
+			let sender = sender.clone();
+			let (commands, stop) = (&commands, &stop);
+			scope.spawn(move || {
+				while !stop.load(Ordering::Relaxed) {
+					let Some((n, command)) = commands.lock().unwrap().next() else { break };
+					let (result, output) =
+						output::capture(|| run(command).map_err(|e| e.to_string()));
+					if result.is_err() && fail_fast {
+						stop.store(true, Ordering::Relaxed);
+					}
+					if sender.send((n, result, output)).is_err() {
+						break;
+					}
+				}
+			});
+		}
+		drop(sender);
+
+		let mut bar = progress::Bar::files(files.len());
+		let mut pending = BTreeMap::new();
+		let mut next = 0;
+		for (n, result, output) in receiver {
+			pending.insert(n, (result, output));
+			while let Some((result, output)) = pending.remove(&next) {
+				let file = &files[next];
+				if let Some(bar) = &mut bar {
+					bar.clear();
+				}
+				if files.len() > 1 {
+					outln!("==> {} <==", file.display());
+				}
+				io::stdout().write_all(&output)?;
+				match result {
+					Ok(()) => succeeded += 1,
+					Err(error) => {
+						log::error!("{}: {}", file.display(), error);
+						failed += 1;
+					}
+				}
+				if let Some(bar) = &mut bar {
+					bar.advance(1);
+				}
+				next += 1;
+			}
+		}
+		Ok::<_, io::Error>(())
+	})?;
+	log::info!("{} succeeded, {} failed", succeeded, failed);
+	if failed == 0 {
+		Ok(())
+	} else {
+		Err(format!("{} of {} files failed", failed, succeeded + failed).into())
+	}
+}
+
+pub fn watch(args: WatchArgs, run: fn(Commands) -> Result<()>) -> Result<()> {
+	let command: Vec<OsString> =
+		shell::split_words(&args.on_add)?.into_iter().map(OsString::from).collect();
+	if command.is_empty() {
+		return Err(usage("--on-add needs a command"));
+	}
+	let depth = command_depth(&command, "watch")?;
+	// Parsed once up front so that a mistake in the arguments stops watching before it starts.
+	one_file_command(file_command(&command, depth, &args.dir), "watch")?;
+	let options = WalkOptions {
+		recursive: args.recursive,
+		include: args.include.iter().map(|p| p.parse()).collect::<Result<_>>()?,
+		exclude: args.exclude.iter().map(|p| p.parse()).collect::<Result<_>>()?,
+	};
+	let watch_error = |error: notify::Error| PngmeError::Other(Box::new(error));
+	let (sender, receiver) = mpsc::channel();
+	let mut watcher = notify::recommended_watcher(sender).map_err(watch_error)?;
+	let mode = if args.recursive { RecursiveMode::Recursive } else { RecursiveMode::NonRecursive };
+	watcher.watch(&args.dir, mode).map_err(watch_error)?;
+	log::info!("Watching {} for changes", args.dir.display());
+
+	// When each file last changed, waiting for it to settle.
+	let mut pending: BTreeMap<PathBuf, Instant> = BTreeMap::new();
+	// The length and modification time of each file once the command ran on it, so that the
+	// command writing it does not set it off again.
+	let stamp = |metadata: &fs::Metadata| (metadata.len(), metadata.modified().ok());
+	let mut done = HashMap::new();
+	loop {
+		let settled = pending.values().min().map(|&changed| changed + args.debounce);
+		let received = match settled {
+			Some(settled) => {
+				receiver.recv_timeout(settled.saturating_duration_since(Instant::now()))
+			}
+			None => receiver.recv().map_err(RecvTimeoutError::from),
+		};
+		match received {
+			Ok(Ok(event)) => {
+				let changed = matches!(
+					event.kind,
+					EventKind::Create(_)
+						| EventKind::Modify(
+							ModifyKind::Any | ModifyKind::Data(_) | ModifyKind::Name(_)
+						)
+				);
+				for path in event.paths.into_iter().filter(|path| changed && options.picks(path)) {
+					pending.insert(path, Instant::now());
+				}
+			}
+			Ok(Err(error)) => log::warn!("{}", error),
+			Err(RecvTimeoutError::Timeout) => {}
+			Err(RecvTimeoutError::Disconnected) => return Ok(()),
+		}
+		let now = Instant::now();
+		let ready: Vec<PathBuf> = (pending.iter())
+			.filter(|(_, &changed)| now.duration_since(changed) >= args.debounce)
+			.map(|(path, _)| path.clone())
+			.collect();
+		for path in ready {
+			pending.remove(&path);
+			// Removed or renamed away since.
+			let Ok(metadata) = fs::metadata(&path) else { continue };
+			if !metadata.is_file() || done.get(&path) == Some(&stamp(&metadata)) {
+				continue;
+			}
+			log::info!("Running on {}", path.display());
+			if let Err(error) =
+				run(Cli::parse_or_exit(file_command(&command, depth, &path)).command)
+			{
+				log::error!("{}: {}", path.display(), error);
+			}
+			if let Ok(metadata) = fs::metadata(&path) {
+				done.insert(path, stamp(&metadata));
+			}
+		}
+	}
+}
+
+pub fn git_filter(args: GitFilterArgs) -> Result<()> {
+	let sidecar = match (args.sidecar, args.file) {
+		(Some(sidecar), _) => Some(sidecar),
+		(None, Some(file)) => {
+			let mut name = file.into_os_string();
+			name.push(".pngmeta");
+			Some(PathBuf::from(name))
+		}
+		(None, None) => None,
+	};
+	let mut input = Vec::new();
+	io::stdin().lock().read_to_end(&mut input)?;
+	// Whatever is not a PNG, such as a Git LFS pointer, goes through as it is.
+	let png = match Png::try_from(input.as_slice()) {
+		Ok(png) => png,
+		Err(error) => {
+			log::warn!("passing the input through unchanged, it is not a PNG: {}", error);
+			return write_stdout(&input);
+		}
+	};
+	if args.clean {
+		clean(png, sidecar.as_deref())
+	} else {
+		smudge(png, &input, sidecar.as_deref())
+	}
+}
+
+/// Writes `png` without its volatile metadata, keeping the chunks and where they were in
+/// `sidecar`, or removing the sidecar if there are none.
+fn clean(mut png: Png, sidecar: Option<&Path>) -> Result<()> {
+	let mut removed = Vec::new();
+	let mut index = 0;
+	png.retain_chunks(|chunk| {
+		let volatile = metadata::is_volatile(chunk);
+		if volatile {
+			removed.push(json!({ "index": index, "chunk": chunk }));
+		}
+		index += 1;
+		!volatile
+	});
+	let cleaned = png.as_bytes();
+	match sidecar {
+		Some(sidecar) if !removed.is_empty() => {
+			let json = json!({ "sha256": sha256_hex(&cleaned), "chunks": removed });
+			let mut output = open_output(sidecar)?;
+			writeln!(output, "{:#}", json)?;
+			output.finish()?;
+		}
+		Some(sidecar) if sidecar.exists() && !atomic::is_dry_run() => fs::remove_file(sidecar)?,
+		_ => {}
+	}
+	write_stdout(&cleaned)
+}
+
+/// Writes `png` with the chunks kept in `sidecar` put back where they were, if the sidecar is
+/// for the cleaned file `input` is.
+fn smudge(mut png: Png, input: &[u8], sidecar: Option<&Path>) -> Result<()> {
+	let json: Option<Value> = match sidecar {
+		Some(sidecar) => match fs::read(sidecar) {
+			Ok(text) => Some(serde_json::from_slice(&text)?),
+			Err(error) if error.kind() == io::ErrorKind::NotFound => None,
+			Err(error) => return Err(error.into()),
+		},
+		None => None,
+	};
+	let Some(json) = json.filter(|json| json["sha256"] == sha256_hex(input)) else {
+		log::debug!("no sidecar for this file, writing it as it is");
+		return write_stdout(input);
+	};
+	let chunks = json["chunks"].as_array().map_or(&[][..], Vec::as_slice);
+	for entry in chunks {
+		let index =
+			entry["index"].as_u64().ok_or_else(|| usage("sidecar chunk without an index"))?;
+		let chunk: Chunk = serde_json::from_value(entry["chunk"].clone())?;
+		if index as usize > png.chunks().len() {
+			return Err(usage(format!("sidecar chunk index {} is past the chunks", index)));
+		}
+		png.insert_chunk_at(index as usize, chunk);
+	}
+	write_stdout(&png.as_bytes())
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+	Sha256::digest(data).iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn write_stdout(data: &[u8]) -> Result<()> {
+	let mut output = open_output(Path::new("-"))?;
+	output.write_all(data)?;
+	output.finish()
+}
+
+pub fn git_textconv(args: GitTextconvArgs) -> Result<()> {
+	let png = read_damaged_png(&args.filename)?;
+	let decoders = DecoderRegistry::default();
+	// Offsets and indices are left out, so that adding a chunk changes only its own line.
+	for chunk in png.chunks().iter().filter(|chunk| !metadata::is_volatile(chunk)) {
+		let summary = match TextualChunk::try_from(chunk) {
+			Ok(text) => format!("{}: {}", text.keyword(), hexdump::escape(text.text().as_bytes())),
+			Err(_) => decoders.decode(chunk).map(|decoded| decoded.summary).unwrap_or_default(),
+		};
+		let line = format!(
+			"{} {} bytes crc {:08x} {}",
+			chunk.chunk_type(),
+			chunk.length(),
+			chunk.crc(),
+			summary
+		);
+		outln!("{}", line.trim_end());
+	}
+	if !png.trailer().is_empty() {
+		outln!("trailer {} bytes", png.trailer().len());
+	}
+	Ok(())
+}
+
+pub fn scrub(args: ScrubArgs) -> Result<()> {
+	let files = walk_files(&args.walk)?;
+	let kept = |chunk: &Chunk| {
+		let keyword = TextualChunk::try_from(chunk).ok().map(|text| text.keyword().to_string());
+		(args.keep.iter())
+			.any(|keep| *keep == chunk.chunk_type().to_string() || Some(keep) == keyword.as_ref())
+	};
+	let (mut private, mut failed) = (0, 0);
+	for path in &files {
+		let result = read_png(path).and_then(|mut png| {
+			let mut removed = Vec::new();
+			png.retain_chunks(|chunk| match metadata::private_data(chunk) {
+				Some(what) if !kept(chunk) => {
+					removed.push(format!("{} ({})", chunk.chunk_type(), what));
+					false
+				}
+				_ => true,
+			});
+			if removed.is_empty() {
+				return Ok(());
+			}
+			private += 1;
+			if args.check {
+				outln!("{}: would remove {}", path.display(), removed.join(", "));
+				return Ok(());
+			}
+			write_png(&png, path)?;
+			outln!("{}: removed {}", path.display(), removed.join(", "));
+			Ok(())
+		});
+		if let Err(error) = result {
+			log::error!("{}: {}", path.display(), error);
+			failed += 1;
+		}
+	}
+	if failed > 0 {
+		return Err(format!("{} of {} files failed", failed, files.len()).into());
+	}
+	if args.check && private > 0 {
+		return Err(format!("{} of {} files have private metadata", private, files.len()).into());
+	}
+	Ok(())
+}
+
+pub fn dedupe(args: DedupeArgs) -> Result<()> {
+	let files = walk_files(&args.walk)?;
+	let scope = if args.pixels_only { Scope::Pixels } else { Scope::Critical };
+	let hash = |path: &PathBuf| content_hash::hash(&read_png(path)?, Algorithm::Sha256, scope);
+
+	// Workers take the next file as they finish one, like in `batch`.
+	let next = Mutex::new(files.iter().enumerate());
+	let mut hashes = vec![None; files.len()];
+	thread::scope(|scope| {
+		let (sender, receiver) = mpsc::channel();
+		for _ in 0..job_count(args.jobs).min(files.len()) {
+			let (sender, next) = (sender.clone(), &next);
+			scope.spawn(move || {
+				while let Some((n, path)) = next.lock().unwrap().next() {
+					if sender.send((n, hash(path))).is_err() {
+						break;
+					}
+				}
+			});
+		}
+		drop(sender);
+		let mut bar = progress::Bar::files(files.len());
+		for (n, result) in receiver {
+			match result {
+				Ok(hash) => hashes[n] = Some(hash),
+				Err(error) => {
+					if let Some(bar) = &mut bar {
+						bar.clear();
+					}
+					log::warn!("skipping {}: {}", files[n].display(), error);
+				}
+			}
+			if let Some(bar) = &mut bar {
+				bar.advance(1);
+			}
+		}
+	});
+
+	// Groups of duplicates, in the order of their first file.
+	let mut groups: Vec<(Vec<u8>, Vec<&PathBuf>)> = Vec::new();
+	let mut group_of: HashMap<Vec<u8>, usize> = HashMap::new();
+	for (path, hash) in files.iter().zip(hashes) {
+		let Some(hash) = hash else { continue };
+		match group_of.get(&hash) {
+			Some(&n) => groups[n].1.push(path),
+			None => {
+				group_of.insert(hash.clone(), groups.len());
+				groups.push((hash, vec![path]));
+			}
+		}
+	}
+	groups.retain(|(_, paths)| paths.len() > 1);
+
+	if args.format == Format::Json {
+		let groups: Vec<_> = groups
+			.iter()
+			.map(|(hash, paths)| {
+				let hex: String = hash.iter().map(|byte| format!("{:02x}", byte)).collect();
+				let paths: Vec<_> = paths.iter().map(|path| path.display().to_string()).collect();
+				json!({ "hash": hex, "files": paths })
+			})
+			.collect();
+		outln!("{:#}", Value::from(groups));
+	} else {
+		for (n, (_, paths)) in groups.iter().enumerate() {
+			if n > 0 {
+				outln!();
+			}
+			for path in paths {
+				outln!("{}", path.display());
+			}
+		}
+	}
+	let duplicates: usize = groups.iter().map(|(_, paths)| paths.len() - 1).sum();
+	log::info!("{} duplicates in {} groups", duplicates, groups.len());
+	if args.link {
+		for (_, paths) in &groups {
+			let (original, duplicates) = paths.split_first().unwrap();
+			for path in duplicates {
+				if is_same_file(original, path) {
+					continue;
+				}
+				if atomic::is_dry_run() {
+					outln!("Would link {} to {}", path.display(), original.display());
+					continue;
+				}
+				atomic::replace_with_link(original, path)?;
+				log::info!("Linked {} to {}", path.display(), original.display());
+			}
+		}
+	}
+	Ok(())
+}
+
+pub fn completions(args: CompletionsArgs) -> Result<()> {
+	let mut output = Vec::new();
+	clap_complete::generate(args.shell, &mut many_files(Cli::command()), "pngme", &mut output);
+	out!("{}", String::from_utf8_lossy(&output));
+	Ok(())
+}
+
+pub fn man(args: ManArgs) -> Result<()> {
+	let mut command = many_files(Cli::command());
+	// Building gives subcommands their display names, such as pngme-text-get.
+	command.build();
+	let Some(dir) = &args.out else {
+		let mut page = Vec::new();
+		Man::new(command).render(&mut page)?;
+		out!("{}", String::from_utf8_lossy(&page));
+		return Ok(());
+	};
+	if !atomic::is_dry_run() {
+		fs::create_dir_all(dir)?;
+	}
+	write_man_pages(&command, dir)
+}
+
+/// Writes the man page of `command` and those of its subcommands into `dir`.
+fn write_man_pages(command: &clap::Command, dir: &Path) -> Result<()> {
+	let name = command.get_display_name().unwrap_or(command.get_name());
+	let path = dir.join(format!("{}.1", name));
+	refuse_overwrite(&path)?;
+	let mut output = open_output(&path)?;
+	Man::new(command.clone()).render(&mut output)?;
+	output.finish()?;
+	outln!("{}", path.display());
+	for subcommand in command.get_subcommands().filter(|subcommand| subcommand.get_name() != "help")
+	{
+		write_man_pages(subcommand, dir)?;
+	}
+	Ok(())
+}
+
+/// Returns `true` if both paths name the same file, as hard links or otherwise.
+#[cfg(unix)]
+fn is_same_file(a: &Path, b: &Path) -> bool {
+	use std::os::unix::fs::MetadataExt;
+	match (fs::metadata(a), fs::metadata(b)) {
+		(Ok(a), Ok(b)) => (a.dev(), a.ino()) == (b.dev(), b.ino()),
+		_ => false,
+	}
+}
+
+#[cfg(not(unix))]
+fn is_same_file(a: &Path, b: &Path) -> bool {
+	matches!((fs::canonicalize(a), fs::canonicalize(b)), (Ok(a), Ok(b)) if a == b)
+}
+
+fn chunk_json(chunk: &Chunk) -> Value {
+	json!({
+		"chunk_type": chunk.chunk_type().to_string(),
+		"description": chunk.chunk_type().description(),
+		"length": chunk.length(),
+		"crc": chunk.crc(),
+		"crc_valid": chunk.has_valid_crc(),
+		"offset": chunk.offset(),
+		"utf8": str::from_utf8(chunk.data()).is_ok(),
+		"data": BASE64_STANDARD.encode(chunk.data()),
+	})
+}
+
+/// Summarizes byte statistics on one line, the histogram drawn as bars.
+fn describe_stats(stats: &ByteStats) -> String {
+	format!(
+		"entropy {:.2} bits/byte, {:.0}% printable, [{}]",
+		stats.entropy,
+		stats.printable_ratio * 100.0,
+		stats.sparkline()
+	)
+}
+
+/// zTXt chunks are shown inflated, as if they were tEXt.
+fn readable_data(chunk: &Chunk) -> Cow<'_, [u8]> {
+	match ZtxtChunk::try_from(chunk) {
+		Ok(ztxt) => Cow::Owned(format!("{}\0{}", ztxt.keyword(), ztxt.text()).into_bytes()),
+		Err(_) => Cow::Borrowed(chunk.data()),
+	}
+}
+
+fn read_png(path: &Path) -> Result<Png> {
+	let mut png = Png::from_reader(BufReader::new(open_input(path)?))?;
+	png.set_preservation_policy(PreservationPolicy::Warn);
+	Ok(png)
+}
+
+/// Checks the CRC of a chunk read through `--mmap`, or warns about it like
+/// [`read_damaged_png`] with `--ignore-crc`.
+fn check_mapped_crc(chunk: &ChunkRef, ignore_crc: bool) -> Result<()> {
+	match chunk.verify_crc() {
+		Err(PngmeError::CrcMismatch { offset: Some(offset), .. }) if ignore_crc => {
+			log::warn!("{} chunk at {:#010x} has a bad CRC", chunk.chunk_type(), offset);
+			Ok(())
+		}
+		result => result,
+	}
+}
+
+/// Reads a PNG keeping chunks with bad CRCs, warning about each of them.
+fn read_damaged_png(path: &Path) -> Result<Png> {
+	let png = Png::from_reader_lenient(BufReader::new(open_input(path)?))?;
+	for (offset, chunk) in png.chunks_with_offsets().filter(|(_, chunk)| !chunk.has_valid_crc()) {
+		log::warn!("{} chunk at {:#010x} has a bad CRC", chunk.chunk_type(), offset);
+	}
+	Ok(png)
+}
+
+/// Reads a PNG for `decode`, keeping chunks with bad CRCs only if their payload is protected by
+/// error correction, which can repair it.
+fn read_protected_png(path: &Path) -> Result<Png> {
+	let png = Png::from_reader_lenient(BufReader::new(open_input(path)?))?;
+	for (offset, chunk) in png.chunks_with_offsets().filter(|(_, chunk)| !chunk.has_valid_crc()) {
+		let chunk_type = chunk.chunk_type();
+		let protected = if fragment::is_fragment(chunk.data()) {
+			let fragments = png.chunks_by_type(&chunk_type.to_string()).map(Chunk::data);
+			fragment::reassemble(fragments).is_ok_and(|payload| ecc::is_protected(&payload))
+		} else {
+			ecc::is_protected(chunk.data())
+		};
+		if !protected {
+			return Err(PngmeError::CrcMismatch {
+				expected: chunk.crc(),
+				computed: Chunk::new(*chunk_type, chunk.data().to_vec()).crc(),
+				offset: Some(offset),
+			});
+		}
+		log::warn!(
+			"{} chunk at {:#010x} has a bad CRC, its error correction data may repair it",
+			chunk_type,
+			offset
+		);
+	}
+	Ok(png)
+}
+
+fn write_png(png: &Png, path: &Path) -> Result<()> {
+	stage_png(png, path)?.map_or(Ok(()), Output::finish)
+}
+
+/// Writes each PNG of `files` to its path, replacing none of the files before all of them were
+/// written.
+fn write_pngs(files: &[(PathBuf, Png)]) -> Result<()> {
+	let mut staged = Vec::new();
+	for (path, png) in files {
+		staged.extend(stage_png(png, path)?);
+	}
+	staged.into_iter().try_for_each(Output::finish)
+}
+
+/// Writes `png` for `path` all but replacing the file, which finishing the output returned
+/// does, or stores it in memory if `path` is open in the shell and returns `None`.
+fn stage_png(png: &Png, path: &Path) -> Result<Option<Output>> {
+	for chunk in png.flagged_chunks() {
+		log::warn!(
+			"{} chunk is not safe to copy and may be stale now that critical chunks changed",
+			chunk.chunk_type()
+		);
+	}
+	if shell::store(path, png) {
+		return Ok(None);
+	}
+	if atomic::is_dry_run() && !is_stdio(path) {
+		let old = File::open(path).ok().and_then(|file| Png::from_reader_lenient(file).ok());
+		if let Some(old) = old {
+			print_changes(&old, png, false);
+			if old.trailer() != png.trailer() {
+				let line =
+					format!("~ trailer: {} -> {} bytes", old.trailer().len(), png.trailer().len());
+				outln!("{}", output::paint(Style::Modified, line));
+			}
+		}
+	}
+	let mut output = BufWriter::new(open_output(path)?);
+	png.write_to(&mut output)?;
+	Ok(Some(output.into_inner().map_err(io::IntoInnerError::into_error)?))
+}
+
+/// The file to write: the input file, modified in place, or another file given with `-o`.
+fn output_path<'a>(output: Option<&'a PathBuf>, input: &'a PathBuf) -> Result<&'a PathBuf> {
+	match output {
+		Some(output) if output != input => {
+			refuse_overwrite(output)?;
+			Ok(output)
+		}
+		_ => Ok(input),
+	}
+}
+
+/// Fails if a command would replace an existing file other than its input, unless `--force`.
+fn refuse_overwrite(path: &Path) -> Result<()> {
+	if !is_stdio(path) && path.exists() && !atomic::is_forced() {
+		return Err(usage(format!(
+			"{} already exists, pass --force to replace it",
+			path.display()
+		)));
+	}
+	Ok(())
+}
+
+fn usage(message: impl Into<String>) -> PngmeError {
+	PngmeError::InvalidArgument(message.into())
+}
+
+fn not_found(message: impl Into<String>) -> PngmeError {
+	PngmeError::NotFound(message.into())
+}
+
+/// `-` stands for stdin.
+fn open_input(path: &Path) -> Result<Box<dyn Read>> {
+	if let Some(bytes) = shell::contents(path) {
+		Ok(Box::new(io::Cursor::new(bytes)))
+	} else if remote::is_url(path) {
+		remote::open(path)
+	} else if is_stdio(path) {
+		Ok(Box::new(ProgressReader::new(io::stdin().lock(), "stdin", 0)))
+	} else {
+		let label = path.file_name().map_or(path.to_string_lossy(), |name| name.to_string_lossy());
+		let file = File::open(path)?;
+		let total = file.metadata()?.len();
+		Ok(Box::new(ProgressReader::new(file, label, total)))
+	}
+}
+
+/// Maps the file at `path` into memory for `--mmap`.
+fn map_input(path: &Path) -> Result<Mmap> {
+	if is_stdio(path) {
+		return Err(usage("--mmap needs a file, not stdin"));
+	}
+	if remote::is_url(path) {
+		return Err(usage("--mmap needs a file, not a URL"));
+	}
+	if shell::is_open(path) {
+		return Err(usage("--mmap maps the file, which the shell has not saved to"));
+	}
+	let file = File::open(path)?;
+	// SAFETY: pngme only reads the map, but the map is only sound while nothing else writes the
+	// file: bytes may change under the parser, and on Unix reading pages past a truncation
+	// kills the process with SIGBUS rather than returning an error. Hence `--mmap` is opt-in.
+	Ok(unsafe { Mmap::map(&file)? })
+}
+
+/// `-` stands for stdout.
+fn open_output(path: &Path) -> Result<Output> {
+	if is_stdio(path) {
+		Ok(Output::Stdout(io::stdout().lock()))
+	} else if shell::is_active() {
+		Err(usage(format!("{} cannot be written while a PNG is edited in memory", path.display())))
+	} else if atomic::is_dry_run() {
+		Ok(Output::DryRun { path: path.to_owned(), len: 0 })
+	} else {
+		Ok(Output::File(AtomicFile::create(path)?))
+	}
+}
+
+/// Where [`open_output`] writes; nothing is written to a file until it is finished.
+enum Output {
+	Stdout(io::StdoutLock<'static>),
+	File(AtomicFile),
+	/// With `--dry-run`, counts the bytes that would be written to `path`.
+	DryRun {
+		path: PathBuf,
+		len: u64,
+	},
+}
+
+impl Output {
+	fn finish(self) -> Result<()> {
+		match self {
+			Self::Stdout(mut stdout) => stdout.flush()?,
+			Self::File(file) => file.commit()?,
+			Self::DryRun { path, len } => match fs::metadata(&path) {
+				Ok(metadata) => outln!(
+					"Would write {} bytes to {}, now {} bytes",
+					len,
+					path.display(),
+					metadata.len()
+				),
+				Err(_) => outln!("Would create {} with {} bytes", path.display(), len),
+			},
+		}
+		Ok(())
+	}
+}
+
+impl Write for Output {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		match self {
+			Self::Stdout(stdout) => stdout.write(buf),
+			Self::File(file) => file.write(buf),
+			Self::DryRun { len, .. } => {
+				*len += buf.len() as u64;
+				Ok(buf.len())
+			}
+		}
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		match self {
+			Self::Stdout(stdout) => stdout.flush(),
+			Self::File(file) => file.flush(),
+			Self::DryRun { .. } => Ok(()),
+		}
+	}
+}
+
+pub fn is_stdio(path: &Path) -> bool {
+	path.as_os_str() == "-"
+}
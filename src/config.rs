@@ -0,0 +1,324 @@
+//! Defaults read from a configuration file.
+//!
+//! `~/.config/pngme/config.toml`, or the file given with `--config`, sets defaults for options,
+//! which options given on the command line override. Settings are named like the options, with
+//! `chunk-type` for the chunk type commands take:
+//!
+//! ```toml
+//! chunk-type = "ruSt"
+//! compress = "zstd"
+//! backup = ".orig"
+//! color = "never"
+//!
+//! [print]
+//! format = "json"
+//! columns = ["index", "type", "length"]
+//! ```
+//!
+//! Settings at the top level apply to every command that has the option and takes the value,
+//! and those in a table named after a command, such as `[print]` or `[text.set]`, to that
+//! command only.
+//!
+//! Tables under `[profile]` hold settings that only apply when picked with `--profile`, over
+//! the others:
+//!
+//! ```toml
+//! [profile.secret]
+//! encrypt = true
+//! compress = "zstd"
+//! random-type = true
+//! ```
+
+use std::{
+	env,
+	ffi::OsString,
+	fs, io, mem,
+	path::{Path, PathBuf},
+};
+
+use clap::{
+	builder::Resettable,
+	error::{ContextKind, ContextValue},
+	parser::ValueSource,
+	Arg, ArgAction, ArgMatches, Command, CommandFactory,
+};
+use toml::{Table, Value};
+
+use crate::args::{self, Cli};
+
+/// Returns the command line interface with the defaults from the configuration file applied,
+/// and the path of the file, if one was read.
+pub fn command(argv: &[OsString]) -> Result<(Command, Option<PathBuf>), String> {
+	let mut command = args::many_files(Cli::command());
+	// Building fills in how flags parse their values, and copies the global options into every
+	// command, where defaults are then set one by one.
+	command.build();
+	let profile = option(argv, "--profile").map(|name| name.to_string_lossy().into_owned());
+	let Some((path, text)) = read(argv)? else {
+		return match profile {
+			Some(name) => Err(format!("no profile `{}`, there is no configuration file", name)),
+			None => Ok((command, None)),
+		};
+	};
+	let in_file = |error: String| format!("{}: {}", path.display(), error);
+	let mut table: Table = text.parse().map_err(|error| in_file(format!("{}", error)))?;
+	let profiles = table.remove("profile");
+	apply(&mut command, &table).map_err(in_file)?;
+	if let Some(name) = profile {
+		let settings = profiles
+			.as_ref()
+			.and_then(|profiles| profiles.get(&name))
+			.and_then(Value::as_table)
+			.ok_or_else(|| in_file(format!("no profile `{}`", name)))?;
+		apply(&mut command, settings)
+			.map_err(|error| in_file(format!("profile `{}`: {}", name, error)))?;
+	}
+	Ok((command, Some(path)))
+}
+
+/// Reads the file given with `--config`, or the default one if it exists.
+fn read(argv: &[OsString]) -> Result<Option<(PathBuf, String)>, String> {
+	let (path, explicit) = match option(argv, "--config") {
+		Some(path) => (path.into(), true),
+		None => match default_path() {
+			Some(path) => (path, false),
+			None => return Ok(None),
+		},
+	};
+	match fs::read_to_string(&path) {
+		Ok(text) => Ok(Some((path, text))),
+		Err(error) if error.kind() == io::ErrorKind::NotFound && !explicit => Ok(None),
+		Err(error) => Err(format!("{}: {}", path.display(), error)),
+	}
+}
+
+/// Finds the value of the option `name` among the arguments, which are only parsed once the
+/// configuration file has been read.
+fn option(argv: &[OsString], name: &str) -> Option<OsString> {
+	let mut found = None;
+	let mut args = argv.iter().skip(1).take_while(|arg| *arg != "--");
+	while let Some(arg) = args.next() {
+		if arg == name {
+			found = args.next().cloned();
+		} else if let Some(value) =
+			arg.to_str().and_then(|arg| arg.strip_prefix(name)?.strip_prefix('='))
+		{
+			found = Some(value.into());
+		}
+	}
+	found
+}
+
+/// `$XDG_CONFIG_HOME/pngme/config.toml`, where `XDG_CONFIG_HOME` defaults to `~/.config`.
+fn default_path() -> Option<PathBuf> {
+	let dir = env::var_os("XDG_CONFIG_HOME")
+		.filter(|dir| !dir.is_empty())
+		.map(PathBuf::from)
+		.or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+	Some(dir.join("pngme").join("config.toml"))
+}
+
+/// Makes the settings in `table` the defaults of `command` and its subcommands.
+fn apply(command: &mut Command, table: &Table) -> Result<(), String> {
+	for (key, value) in table {
+		if let Value::Table(table) = value {
+			let name = command.get_name().to_owned();
+			let subcommand = command
+				.find_subcommand_mut(key)
+				.ok_or_else(|| format!("`{}` has no command `{}`", name, key))?;
+			apply(subcommand, table)?;
+			continue;
+		}
+		let values = match value {
+			Value::Array(values) => values.iter().map(|value| scalar(key, value)).collect(),
+			value => scalar(key, value).map(|value| vec![value]),
+		}?;
+		match set_default(command, &key.replace('-', "_"), &values) {
+			Match::Set => {}
+			Match::InvalidValue => return Err(format!("invalid value for `{}`", key)),
+			Match::NoArgument => return Err(format!("no command takes `{}`", key)),
+		}
+	}
+	Ok(())
+}
+
+fn scalar(key: &str, value: &Value) -> Result<String, String> {
+	match value {
+		Value::String(value) => Ok(value.clone()),
+		Value::Integer(value) => Ok(value.to_string()),
+		Value::Float(value) => Ok(value.to_string()),
+		Value::Boolean(value) => Ok(value.to_string()),
+		_ => Err(format!("`{}` must be a string, number, boolean, or a list of them", key)),
+	}
+}
+
+/// How well a setting fits the arguments of a command and its subcommands, from worst to best.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Match {
+	NoArgument,
+	InvalidValue,
+	Set,
+}
+
+/// Sets the default of the argument `id` wherever it appears in `command` and its subcommands
+/// and takes `values`.
+fn set_default(command: &mut Command, id: &str, values: &[String]) -> Match {
+	// Of the positional arguments, only the chunk type comes last wherever it is required, and
+	// a required positional cannot be left out before another.
+	let settable = |arg: &Arg| arg.get_id() == id && (!arg.is_positional() || id == "chunk_type");
+	let fit = command.get_arguments().find(|arg| settable(arg)).map(|arg| takes(arg, values));
+	let mut found = match fit {
+		None => Match::NoArgument,
+		Some(false) => Match::InvalidValue,
+		Some(true) => {
+			// Defaults outlive the command, which is only built once.
+			let values: Vec<&'static str> =
+				values.iter().map(|value| &*Box::leak(value.clone().into_boxed_str())).collect();
+			*command = mem::take(command).mut_args(|arg| match settable(&arg) {
+				true => arg.default_values(values.clone()).required(false),
+				false => arg,
+			});
+			Match::Set
+		}
+	};
+	for subcommand in command.get_subcommands_mut() {
+		found = found.max(set_default(subcommand, id, values));
+	}
+	found
+}
+
+/// Parses `argv` with `command` once more after clap refused it with `error` for missing
+/// arguments, if the configuration file gave each of them a default.
+///
+/// Only options given on the command line count towards what others require, so `--passphrase`
+/// would be refused with `encrypt = true`. The arguments are parsed again with nothing required
+/// by another, and the missing ones are then checked to have a default other than `false`.
+pub fn with_defaults_required(
+	command: Command,
+	argv: Vec<OsString>,
+	error: clap::Error,
+) -> Result<ArgMatches, clap::Error> {
+	let Some(ContextValue::Strings(missing)) = error.get(ContextKind::InvalidArg) else {
+		return Err(error);
+	};
+	let mut command = without_requires(command);
+	let Ok(matches) = command.try_get_matches_from_mut(argv) else {
+		return Err(error);
+	};
+	// The missing arguments belong to the command run, the most nested one.
+	let (mut command, mut found) = (&command, &matches);
+	while let Some((name, subcommand)) = found.subcommand() {
+		command = command.find_subcommand(name).expect("the subcommand was parsed");
+		found = subcommand;
+	}
+	let defaulted = |name: &String| {
+		command.get_arguments().find(|arg| arg.to_string() == *name).is_some_and(|arg| {
+			let id = arg.get_id().as_str();
+			found.value_source(id) == Some(ValueSource::DefaultValue)
+				&& found.get_raw(id).is_some_and(|values| !values.eq(["false"]))
+		})
+	};
+	match missing.iter().all(defaulted) {
+		true => Ok(matches),
+		false => Err(error),
+	}
+}
+
+fn without_requires(command: Command) -> Command {
+	let mut command = command.mut_args(|arg| arg.requires(Resettable::Reset));
+	for subcommand in command.get_subcommands_mut() {
+		*subcommand = without_requires(mem::take(subcommand));
+	}
+	command
+}
+
+/// Whether `arg` takes `values`, as far as it lists the values it takes. Flags take `true` or
+/// `false`, and counted flags a count. Chunk types are listed for completion only, and any
+/// other is taken too.
+fn takes(arg: &Arg, values: &[String]) -> bool {
+	match arg.get_action() {
+		ArgAction::SetTrue | ArgAction::SetFalse => {
+			return matches!(values, [value] if value == "true" || value == "false");
+		}
+		ArgAction::Count => return matches!(values, [value] if value.parse::<u8>().is_ok()),
+		action if !action.takes_values() => return false,
+		_ => {}
+	}
+	let possible = arg.get_possible_values();
+	possible.is_empty()
+		|| arg.is_hide_possible_values_set()
+		|| values.iter().all(|value| possible.iter().any(|p| p.matches(value, false)))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::args::{Commands, Format, PrintFormat};
+
+	/// Parses `args` with the configuration file `text`, written under `name`.
+	fn parse(name: &str, text: &str, args: &[&str]) -> Result<Cli, String> {
+		let path = env::temp_dir().join(format!("pngme-{}-{}.toml", name, std::process::id()));
+		fs::write(&path, text).unwrap();
+		let mut argv: Vec<OsString> = vec!["pngme".into(), "--config".into(), path.clone().into()];
+		argv.extend(args.iter().map(OsString::from));
+		let result = command(&argv).and_then(|(command, _)| {
+			Cli::try_parse_with(command, argv).map_err(|error| error.to_string())
+		});
+		fs::remove_file(path).unwrap();
+		result
+	}
+
+	fn print_format(cli: Cli) -> PrintFormat {
+		match cli.command {
+			Commands::Print(args) => args.format,
+			_ => unreachable!("not a print command"),
+		}
+	}
+
+	#[test]
+	fn test_precedence() {
+		let text = "format = \"json\"\n[print]\nformat = \"pngcheck\"\n";
+		let print = parse("precedence", text, &["print", "a.png"]).map(print_format);
+		assert!(matches!(print, Ok(PrintFormat::Pngcheck)));
+		let info = parse("precedence", text, &["info", "a.png"]).unwrap();
+		assert!(matches!(info.command, Commands::Info(args) if args.format == Format::Json));
+		let given = parse("precedence", text, &["print", "a.png", "--format", "text"]);
+		assert!(matches!(given.map(print_format), Ok(PrintFormat::Text)));
+	}
+
+	#[test]
+	fn test_profiles() {
+		let text = "[print]\nformat = \"pngcheck\"\n[profile.json]\nformat = \"json\"\n";
+		let plain = parse("profiles", text, &["print", "a.png"]).map(print_format);
+		assert!(matches!(plain, Ok(PrintFormat::Pngcheck)));
+		let picked = parse("profiles", text, &["--profile", "json", "print", "a.png"]);
+		assert!(matches!(picked.map(print_format), Ok(PrintFormat::Json)));
+		let error = parse("profiles", text, &["--profile=nope", "print", "a.png"]).err().unwrap();
+		assert!(error.ends_with("no profile `nope`"), "{}", error);
+	}
+
+	#[test]
+	fn test_invalid_settings() {
+		let cases = [
+			("nope = 1", "no command takes `nope`"),
+			("format = \"xml\"", "invalid value for `format`"),
+			("[nope]\nformat = \"json\"", "`pngme` has no command `nope`"),
+		];
+		for (text, message) in cases {
+			let error = parse("invalid", text, &["print", "a.png"]).err().unwrap();
+			assert!(error.ends_with(message), "{}", error);
+		}
+	}
+
+	#[test]
+	fn test_requires_with_config() {
+		let args = ["encode", "a.png", "ruSt", "hi", "--passphrase", "secret"];
+		let error = parse("unrelated", "color = \"never\"", &args).err().unwrap();
+		assert!(error.contains("--encrypt"), "{}", error);
+		let cli = parse("encrypt", "encrypt = true", &args).unwrap();
+		assert!(matches!(cli.command, Commands::Encode(args) if args.encrypt));
+		assert!(parse("no-encrypt", "encrypt = false", &args).is_err());
+		// A default only stands in for what is required, not for what is missing otherwise.
+		assert!(parse("encrypt-only", "encrypt = true", &["encode", "--passphrase", "x"]).is_err());
+	}
+}
@@ -0,0 +1,142 @@
+//! Hashing what a PNG shows rather than how its file is laid out.
+//!
+//! [`Scope::Critical`] hashes the critical chunks, so that files differing only in metadata or
+//! payloads hash the same. The image data is hashed as one stream, however it is split into
+//! `IDAT` chunks. [`Scope::Pixels`] goes further and hashes the decoded scanlines, so that the
+//! same pixels compressed differently also hash the same; the image dimensions, format, and
+//! palette are hashed along with them. Scanlines are taken in the order they are stored, so an
+//! interlaced copy of an image hashes differently.
+
+use sha2::{Digest, Sha256, Sha512};
+
+use crate::{chunk_type::ChunkType, idat, ihdr::ColorType, png::Png, Result};
+
+/// The hash function.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm {
+	Sha256,
+	Sha512,
+}
+
+impl Algorithm {
+	/// Returns the lowercase name of the algorithm.
+	pub fn name(&self) -> &'static str {
+		match self {
+			Self::Sha256 => "sha256",
+			Self::Sha512 => "sha512",
+		}
+	}
+}
+
+/// What goes into the hash.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scope {
+	/// The critical chunks, with the image data as one stream.
+	Critical,
+	/// The header fields, palette, and unfiltered scanlines.
+	Pixels,
+}
+
+/// Hashes `png` with `algorithm`.
+pub fn hash(png: &Png, algorithm: Algorithm, scope: Scope) -> Result<Vec<u8>> {
+	match algorithm {
+		Algorithm::Sha256 => hash_with::<Sha256>(png, scope),
+		Algorithm::Sha512 => hash_with::<Sha512>(png, scope),
+	}
+}
+
+fn hash_with<D: Digest>(png: &Png, scope: Scope) -> Result<Vec<u8>> {
+	let mut hasher = D::new();
+	// Every field is prefixed with its length, so that no two inputs run together the same way.
+	let mut field = |name: &[u8], data: &[u8]| {
+		hasher.update(name);
+		hasher.update((data.len() as u64).to_be_bytes());
+		hasher.update(data);
+	};
+	match scope {
+		Scope::Critical => {
+			let mut image_data = false;
+			for chunk in png.chunks().iter().filter(|chunk| chunk.chunk_type().is_critical()) {
+				if chunk.chunk_type() != &ChunkType::IDAT {
+					field(&chunk.chunk_type().bytes(), chunk.data());
+				} else if !image_data {
+					field(b"IDAT", &idat::compressed_data(png));
+					image_data = true;
+				}
+			}
+		}
+		Scope::Pixels => {
+			let header = png.header()?;
+			let mut format = Vec::with_capacity(10);
+			format.extend_from_slice(&header.width.to_be_bytes());
+			format.extend_from_slice(&header.height.to_be_bytes());
+			format.extend_from_slice(&[header.bit_depth, header.color_type as u8]);
+			field(b"IHDR", &format);
+			if header.color_type == ColorType::Indexed {
+				field(b"PLTE", png.chunk_by_type("PLTE").map_or(&[], |chunk| chunk.data()));
+			}
+			let mut data = idat::decompressed_data(png)?;
+			idat::unfilter(&header, &mut data)?;
+			field(b"IDAT", &data);
+		}
+	}
+	Ok(hasher.finalize().to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{chunk::Chunk, idat::FilterStrategy, ihdr::IhdrData, zlib, PngBuilder};
+
+	fn header() -> IhdrData {
+		IhdrData {
+			width: 2,
+			height: 2,
+			bit_depth: 8,
+			color_type: ColorType::Grayscale,
+			compression_method: 0,
+			filter_method: 0,
+			interlace_method: 0,
+		}
+	}
+
+	fn png(compressed: &[u8], split: usize, text: &str) -> Png {
+		let (first, second) = compressed.split_at(split);
+		PngBuilder::new()
+			.header(header())
+			.text("Comment", text)
+			.idat(first.to_vec())
+			.idat(second.to_vec())
+			.chunk(Chunk::new("ruSt".parse().unwrap(), text.as_bytes().to_vec()))
+			.build()
+			.unwrap()
+	}
+
+	#[test]
+	fn test_critical() {
+		let compressed = zlib::compress(&[0, 1, 2, 0, 3, 4]);
+		let hash = |png: &Png| super::hash(png, Algorithm::Sha256, Scope::Critical).unwrap();
+		let a = png(&compressed, 2, "one");
+		assert_eq!(hash(&a), hash(&png(&compressed, 5, "two")));
+		assert_eq!(hash(&a).len(), 32);
+		assert_ne!(
+			hash(&a),
+			hash(&png(&zlib::compress_with_level(&[0, 1, 2, 0, 3, 4], 0), 2, "one"))
+		);
+		assert_eq!(super::hash(&a, Algorithm::Sha512, Scope::Critical).unwrap().len(), 64);
+	}
+
+	#[test]
+	fn test_pixels() {
+		let raw = vec![0, 1, 2, 0, 3, 4];
+		let mut filtered = raw.clone();
+		idat::filter(&header(), &mut filtered, FilterStrategy::Fixed(1)).unwrap();
+		assert_ne!(filtered, raw);
+
+		let hash = |png: &Png| super::hash(png, Algorithm::Sha256, Scope::Pixels).unwrap();
+		let plain = png(&zlib::compress(&raw), 3, "one");
+		let refiltered = png(&zlib::compress_with_level(&filtered, 1), 1, "two");
+		assert_eq!(hash(&plain), hash(&refiltered));
+		assert_ne!(hash(&plain), hash(&png(&zlib::compress(&[0, 1, 2, 0, 3, 5]), 3, "one")));
+	}
+}
@@ -0,0 +1,34 @@
+//! Resolving the passphrase for `--encrypt` and `--decrypt`.
+//!
+//! A passphrase given on the command line wins, then `PNGME_PASSWORD`, and as a last
+//! resort the user is prompted on the terminal without echo.
+
+use std::env;
+
+use pngme::{PngmeError, Result};
+
+const PASSWORD_VAR: &str = "PNGME_PASSWORD";
+
+/// Resolves the passphrase to encrypt with, asking twice if prompting.
+pub fn for_encryption(given: Option<String>) -> Result<String> {
+	if let Some(passphrase) = given.or_else(from_env) {
+		return Ok(passphrase);
+	}
+	let passphrase = rpassword::prompt_password("Passphrase: ")?;
+	if rpassword::prompt_password("Confirm passphrase: ")? != passphrase {
+		return Err(PngmeError::InvalidArgument("Passphrases do not match".into()));
+	}
+	Ok(passphrase)
+}
+
+/// Resolves the passphrase to decrypt with.
+pub fn for_decryption(given: Option<String>) -> Result<String> {
+	match given.or_else(from_env) {
+		Some(passphrase) => Ok(passphrase),
+		None => Ok(rpassword::prompt_password("Passphrase: ")?),
+	}
+}
+
+fn from_env() -> Option<String> {
+	env::var(PASSWORD_VAR).ok()
+}
@@ -0,0 +1,113 @@
+//! Passphrase-based payload encryption.
+//!
+//! An encrypted payload is laid out as
+//! `MAGIC | version (1 byte) | salt (16 bytes) | nonce (12 bytes) | ciphertext`,
+//! where the key is derived from the passphrase with Argon2id and the ciphertext is
+//! sealed with ChaCha20-Poly1305.
+
+use argon2::Argon2;
+use chacha20poly1305::{
+	aead::{rand_core::RngCore, Aead, AeadCore, KeyInit, OsRng},
+	ChaCha20Poly1305, Key, Nonce,
+};
+
+use crate::{PngmeError, Result};
+
+const MAGIC: &[u8; 4] = b"PMEC";
+const VERSION: u8 = 1;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+const HEADER_LEN: usize = MAGIC.len() + 1 + SALT_LEN + NONCE_LEN;
+const TAG_LEN: usize = 16;
+
+/// How many bytes encryption adds to a payload: the header and the authentication tag.
+pub const OVERHEAD: usize = HEADER_LEN + TAG_LEN;
+
+/// Returns `true` if `payload` starts with an encrypted payload header.
+pub fn is_encrypted(payload: &[u8]) -> bool {
+	payload.len() >= HEADER_LEN && payload.starts_with(MAGIC)
+}
+
+/// Encrypts `plaintext` with a key derived from `passphrase`, prepending the header.
+pub fn encrypt(plaintext: &[u8], passphrase: &str) -> Result<Vec<u8>> {
+	let mut salt = [0; SALT_LEN];
+	OsRng.fill_bytes(&mut salt);
+	let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+
+	let cipher = ChaCha20Poly1305::new(&derive_key(passphrase, &salt)?);
+	let ciphertext = cipher.encrypt(&nonce, plaintext).map_err(|e| e.to_string())?;
+
+	let mut payload = Vec::with_capacity(HEADER_LEN + ciphertext.len());
+	payload.extend_from_slice(MAGIC);
+	payload.push(VERSION);
+	payload.extend_from_slice(&salt);
+	payload.extend_from_slice(&nonce);
+	payload.extend_from_slice(&ciphertext);
+	Ok(payload)
+}
+
+/// Validates the header of `payload` and decrypts it with a key derived from `passphrase`.
+pub fn decrypt(payload: &[u8], passphrase: &str) -> Result<Vec<u8>> {
+	if !is_encrypted(payload) {
+		return Err(PngmeError::NotEncrypted);
+	}
+	let (version, rest) = payload[MAGIC.len()..].split_first().ok_or(PngmeError::NotEncrypted)?;
+	if *version != VERSION {
+		return Err(PngmeError::Unsupported(format!(
+			"Unsupported encrypted payload version: {}",
+			version
+		)));
+	}
+	let (salt, rest) = rest.split_at(SALT_LEN);
+	let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+
+	let cipher = ChaCha20Poly1305::new(&derive_key(passphrase, salt)?);
+	cipher.decrypt(Nonce::from_slice(nonce), ciphertext).map_err(|_| PngmeError::DecryptionFailed)
+}
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> Result<Key> {
+	let mut key = Key::default();
+	Argon2::default()
+		.hash_password_into(passphrase.as_bytes(), salt, &mut key)
+		.map_err(|e| e.to_string())?;
+	Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_round_trip() {
+		let payload = encrypt(b"secret message", "hunter2").unwrap();
+		assert!(is_encrypted(&payload));
+		assert_eq!(payload.len(), b"secret message".len() + OVERHEAD);
+		assert_eq!(decrypt(&payload, "hunter2").unwrap(), b"secret message");
+	}
+
+	#[test]
+	fn test_wrong_passphrase() {
+		let payload = encrypt(b"secret message", "hunter2").unwrap();
+		assert!(decrypt(&payload, "hunter3").is_err());
+	}
+
+	#[test]
+	fn test_tampered_payload() {
+		let mut payload = encrypt(b"secret message", "hunter2").unwrap();
+		*payload.last_mut().unwrap() ^= 1;
+		assert!(decrypt(&payload, "hunter2").is_err());
+	}
+
+	#[test]
+	fn test_not_encrypted() {
+		assert!(!is_encrypted(b"secret message"));
+		assert!(decrypt(b"secret message", "hunter2").is_err());
+	}
+
+	#[test]
+	fn test_unsupported_version() {
+		let mut payload = encrypt(b"secret message", "hunter2").unwrap();
+		payload[MAGIC.len()] = VERSION + 1;
+		assert!(decrypt(&payload, "hunter2").is_err());
+	}
+}
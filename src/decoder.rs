@@ -0,0 +1,226 @@
+//! Human-readable decoding of chunk contents.
+//!
+//! A [`DecoderRegistry`] maps chunk types to [`ChunkDecoder`]s. The default registry knows the
+//! standard textual chunks and the ancillary chunks in [`crate::ancillary`]; library users can
+//! register decoders for their own chunk types, including plain closures.
+
+use std::fmt;
+
+use crate::{
+	ancillary::{
+		Background, Chromaticities, Gamma, PhysicalDimensions, RenderingIntent, Time, Transparency,
+	},
+	apng::{AnimationControl, FrameControl},
+	chunk::Chunk,
+	chunk_type::ChunkType,
+	exif::ExifChunk,
+	icc::IccProfile,
+	signature::SignatureChunk,
+	text::{ItxtChunk, TextChunk, ZtxtChunk},
+};
+
+/// The decoded contents of a chunk: a one-line summary plus named fields.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodedChunk {
+	pub summary: String,
+	pub fields: Vec<(String, String)>,
+}
+
+impl DecodedChunk {
+	pub fn new(summary: impl Into<String>) -> Self {
+		Self { summary: summary.into(), fields: Vec::new() }
+	}
+
+	/// Adds a named field.
+	pub fn with_field(mut self, name: impl Into<String>, value: impl ToString) -> Self {
+		self.fields.push((name.into(), value.to_string()));
+		self
+	}
+}
+
+impl fmt::Display for DecodedChunk {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(&self.summary)
+	}
+}
+
+/// Turns a chunk into a [`DecodedChunk`], or `None` if its contents are not understood.
+pub trait ChunkDecoder {
+	fn decode(&self, chunk: &Chunk) -> Option<DecodedChunk>;
+}
+
+impl<F: Fn(&Chunk) -> Option<DecodedChunk>> ChunkDecoder for F {
+	fn decode(&self, chunk: &Chunk) -> Option<DecodedChunk> {
+		self(chunk)
+	}
+}
+
+/// A set of decoders, each responsible for one chunk type.
+pub struct DecoderRegistry {
+	decoders: Vec<(ChunkType, Box<dyn ChunkDecoder>)>,
+}
+
+impl DecoderRegistry {
+	/// Creates a registry without any decoders.
+	pub fn empty() -> Self {
+		Self { decoders: Vec::new() }
+	}
+
+	/// Registers `decoder` for chunks of `chunk_type`, replacing any earlier one.
+	pub fn register(&mut self, chunk_type: ChunkType, decoder: impl ChunkDecoder + 'static) {
+		self.decoders.retain(|(t, _)| t != &chunk_type);
+		self.decoders.push((chunk_type, Box::new(decoder)));
+	}
+
+	/// Decodes `chunk` with the decoder registered for its type.
+	pub fn decode(&self, chunk: &Chunk) -> Option<DecodedChunk> {
+		let (_, decoder) = self.decoders.iter().find(|(t, _)| t == chunk.chunk_type())?;
+		decoder.decode(chunk)
+	}
+}
+
+impl Default for DecoderRegistry {
+	/// Creates a registry with the built-in decoders.
+	fn default() -> Self {
+		let mut registry = Self::empty();
+		registry.register(ChunkType::tEXt, |chunk: &Chunk| {
+			let text = TextChunk::try_from(chunk).ok()?;
+			Some(decode_text(text.keyword(), text.text()))
+		});
+		registry.register(ChunkType::zTXt, |chunk: &Chunk| {
+			let text = ZtxtChunk::try_from(chunk).ok()?;
+			Some(decode_text(text.keyword(), text.text()).with_field("compressed", true))
+		});
+		registry.register(ChunkType::iTXt, |chunk: &Chunk| {
+			let text = ItxtChunk::try_from(chunk).ok()?;
+			Some(
+				decode_text(text.keyword(), text.text())
+					.with_field("compressed", text.is_compressed())
+					.with_field("language", text.language_tag())
+					.with_field("translated_keyword", text.translated_keyword()),
+			)
+		});
+		registry.register(ChunkType::tIME, |chunk: &Chunk| {
+			let time = Time::try_from(chunk).ok()?;
+			Some(DecodedChunk::new(format!("last modified {}", time)).with_field("time", time))
+		});
+		registry.register(ChunkType::pHYs, |chunk: &Chunk| {
+			let phys = PhysicalDimensions::try_from(chunk).ok()?;
+			Some(
+				DecodedChunk::new(phys.to_string())
+					.with_field("x", phys.x)
+					.with_field("y", phys.y)
+					.with_field("unit", phys.unit as u8),
+			)
+		});
+		registry.register(ChunkType::gAMA, |chunk: &Chunk| {
+			let gamma = Gamma::try_from(chunk).ok()?;
+			Some(DecodedChunk::new(gamma.to_string()).with_field("gamma", gamma.value()))
+		});
+		registry.register(ChunkType::sRGB, |chunk: &Chunk| {
+			let intent = RenderingIntent::try_from(chunk).ok()?;
+			Some(DecodedChunk::new(format!("sRGB, {} intent", intent)).with_field("intent", intent))
+		});
+		registry.register(ChunkType::bKGD, |chunk: &Chunk| {
+			Some(DecodedChunk::new(Background::try_from(chunk).ok()?.to_string()))
+		});
+		registry.register(ChunkType::tRNS, |chunk: &Chunk| {
+			Some(DecodedChunk::new(Transparency::try_from(chunk).ok()?.to_string()))
+		});
+		registry.register(ChunkType::cHRM, |chunk: &Chunk| {
+			Some(DecodedChunk::new(Chromaticities::try_from(chunk).ok()?.to_string()))
+		});
+		registry.register(ChunkType::acTL, |chunk: &Chunk| {
+			let control = AnimationControl::try_from(chunk).ok()?;
+			Some(
+				DecodedChunk::new(control.to_string())
+					.with_field("frames", control.num_frames)
+					.with_field("plays", control.num_plays),
+			)
+		});
+		registry.register(ChunkType::fcTL, |chunk: &Chunk| {
+			let frame = FrameControl::try_from(chunk).ok()?;
+			Some(
+				DecodedChunk::new(frame.to_string())
+					.with_field("sequence_number", frame.sequence_number)
+					.with_field("dispose_op", format!("{:?}", frame.dispose_op))
+					.with_field("blend_op", format!("{:?}", frame.blend_op)),
+			)
+		});
+		registry.register(ChunkType::iCCP, |chunk: &Chunk| {
+			let profile = IccProfile::try_from(chunk).ok()?;
+			Some(DecodedChunk::new(profile.to_string()).with_field("name", profile.name()))
+		});
+		registry.register(ChunkType::eXIf, |chunk: &Chunk| {
+			let tags = ExifChunk::try_from(chunk).ok()?.tags().ok()?;
+			let gps = if tags.iter().any(|tag| tag.is_gps()) { ", with GPS location" } else { "" };
+			let decoded = DecodedChunk::new(format!("Exif metadata, {} tags{}", tags.len(), gps));
+			Some(tags.iter().fold(decoded, |decoded, tag| {
+				let name = tag.name().map_or_else(|| format!("0x{:04x}", tag.tag), String::from);
+				decoded.with_field(name, &tag.value)
+			}))
+		});
+		registry.register(ChunkType::siGN, |chunk: &Chunk| {
+			let signature = SignatureChunk::try_from(chunk).ok()?;
+			Some(
+				DecodedChunk::new(signature.to_string())
+					.with_field("target", signature.target())
+					.with_field("covers_critical", signature.covers_critical()),
+			)
+		});
+		registry
+	}
+}
+
+fn decode_text(keyword: &str, text: &str) -> DecodedChunk {
+	DecodedChunk::new(format!("{}: {}", keyword, text))
+		.with_field("keyword", keyword)
+		.with_field("text", text)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn chunk(chunk_type: ChunkType, data: &[u8]) -> Chunk {
+		Chunk::new(chunk_type, data.to_vec())
+	}
+
+	#[test]
+	fn test_builtin_decoders() {
+		let registry = DecoderRegistry::default();
+		let decode = |chunk: Chunk| registry.decode(&chunk).map(|decoded| decoded.summary);
+
+		assert_eq!(decode(chunk(ChunkType::tEXt, b"Author\0me")).unwrap(), "Author: me");
+		assert_eq!(
+			decode(chunk(ChunkType::tIME, &[0x07, 0xe8, 5, 1, 12, 0, 0])).unwrap(),
+			"last modified 2024-05-01 12:00:00"
+		);
+		let phys = [0, 0, 0x0b, 0x13, 0, 0, 0x0b, 0x13, 1];
+		assert_eq!(decode(chunk(ChunkType::pHYs, &phys)).unwrap(), "2835 px/m (72 dpi)");
+		assert_eq!(
+			decode(chunk(ChunkType::gAMA, &45455u32.to_be_bytes())).unwrap(),
+			"gamma 0.45455"
+		);
+		assert_eq!(decode(chunk(ChunkType::gAMA, b"bad")), None);
+		assert_eq!(decode(chunk(ChunkType::IDAT, b"data")), None);
+	}
+
+	#[test]
+	fn test_custom_decoder() {
+		struct Upper;
+		impl ChunkDecoder for Upper {
+			fn decode(&self, chunk: &Chunk) -> Option<DecodedChunk> {
+				Some(DecodedChunk::new(String::from_utf8_lossy(chunk.data()).to_uppercase()))
+			}
+		}
+
+		let mut registry = DecoderRegistry::empty();
+		let rust: ChunkType = "ruSt".parse().unwrap();
+		registry.register(rust, Upper);
+		assert_eq!(registry.decode(&chunk("ruSt".parse().unwrap(), b"hi")).unwrap().summary, "HI");
+
+		registry.register("ruSt".parse().unwrap(), |_: &Chunk| None);
+		assert_eq!(registry.decode(&chunk("ruSt".parse().unwrap(), b"hi")), None);
+	}
+}
@@ -0,0 +1,163 @@
+//! Comparing the chunk sequences of two PNGs.
+
+use std::ops::Range;
+
+use similar::{Algorithm, DiffOp};
+
+use crate::{chunk::Chunk, png::Png};
+
+/// A difference between two chunk sequences. Indices refer to the chunk lists of the old and
+/// new PNGs respectively.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChunkChange {
+	Added {
+		new_index: usize,
+	},
+	Removed {
+		old_index: usize,
+	},
+	/// A chunk of the same type whose data changed.
+	Modified {
+		old_index: usize,
+		new_index: usize,
+	},
+}
+
+/// Computes the changes that turn `old` into `new`.
+///
+/// Chunks are aligned with as few removals and additions as possible, by Myers' algorithm in
+/// linear space; between two aligned chunks, a removal and an addition of the same chunk type
+/// are reported as a modification.
+pub fn diff(old: &Png, new: &Png) -> Vec<ChunkChange> {
+	let (old, new) = (old.chunks(), new.chunks());
+	let (old_keys, new_keys) = (keys(old), keys(new));
+
+	let mut changes = Vec::new();
+	let (mut removed, mut added) = (Vec::new(), Vec::new());
+	for op in similar::capture_diff_slices(Algorithm::Myers, &old_keys, &new_keys) {
+		match op {
+			DiffOp::Equal { .. } => pair_hunk(old, new, &mut removed, &mut added, &mut changes),
+			DiffOp::Delete { old_index, old_len, .. } => {
+				removed.extend(old_index..old_index + old_len)
+			}
+			DiffOp::Insert { new_index, new_len, .. } => {
+				added.extend(new_index..new_index + new_len)
+			}
+			DiffOp::Replace { old_index, old_len, new_index, new_len } => {
+				removed.extend(old_index..old_index + old_len);
+				added.extend(new_index..new_index + new_len);
+			}
+		}
+	}
+	pair_hunk(old, new, &mut removed, &mut added, &mut changes);
+	changes
+}
+
+/// What chunks are compared by: their type and data.
+fn keys(chunks: &[Chunk]) -> Vec<([u8; 4], &[u8])> {
+	chunks.iter().map(|chunk| (chunk.chunk_type().bytes(), chunk.data())).collect()
+}
+
+/// Turns the removals and additions between two matching chunks into changes, pairing up
+/// chunks of the same type as modifications.
+fn pair_hunk(
+	old: &[Chunk],
+	new: &[Chunk],
+	removed: &mut Vec<usize>,
+	added: &mut Vec<usize>,
+	changes: &mut Vec<ChunkChange>,
+) {
+	for old_index in removed.drain(..) {
+		let same_type =
+			added.iter().position(|&j| new[j].chunk_type() == old[old_index].chunk_type());
+		match same_type {
+			Some(n) => {
+				changes.push(ChunkChange::Modified { old_index, new_index: added.remove(n) })
+			}
+			None => changes.push(ChunkChange::Removed { old_index }),
+		}
+	}
+	changes.extend(added.drain(..).map(|new_index| ChunkChange::Added { new_index }));
+}
+
+/// Returns the ranges of byte positions at which `old` and `new` differ. Bytes past the end
+/// of the shorter slice count as different.
+pub fn byte_differences(old: &[u8], new: &[u8]) -> Vec<Range<usize>> {
+	let mut ranges: Vec<Range<usize>> = Vec::new();
+	for n in 0..old.len().max(new.len()) {
+		if old.get(n) == new.get(n) {
+			continue;
+		}
+		match ranges.last_mut() {
+			Some(range) if range.end == n => range.end += 1,
+			_ => ranges.push(n..n + 1),
+		}
+	}
+	ranges
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn chunk(chunk_type: &str, data: &str) -> Chunk {
+		Chunk::new(chunk_type.parse().unwrap(), data.as_bytes().to_vec())
+	}
+
+	fn png(chunks: &[(&str, &str)]) -> Png {
+		Png::from_chunks(chunks.iter().map(|(t, d)| chunk(t, d)).collect())
+	}
+
+	#[test]
+	fn test_identical() {
+		let a = png(&[("IHDR", "a"), ("tEXt", "b"), ("IEND", "")]);
+		assert!(diff(&a, &a).is_empty());
+	}
+
+	#[test]
+	fn test_added_and_removed() {
+		let a = png(&[("IHDR", "a"), ("tEXt", "b"), ("IEND", "")]);
+		let b = png(&[("IHDR", "a"), ("IDAT", "c"), ("IEND", "")]);
+		assert_eq!(
+			diff(&a, &b),
+			[ChunkChange::Removed { old_index: 1 }, ChunkChange::Added { new_index: 1 }]
+		);
+		assert_eq!(diff(&a, &png(&[])).len(), 3);
+	}
+
+	#[test]
+	fn test_modified() {
+		let a = png(&[("IHDR", "a"), ("tEXt", "b"), ("IEND", "")]);
+		let b = png(&[("IHDR", "a"), ("ruSt", "new"), ("tEXt", "c"), ("IEND", "")]);
+		assert_eq!(
+			diff(&a, &b),
+			[
+				ChunkChange::Modified { old_index: 1, new_index: 2 },
+				ChunkChange::Added { new_index: 1 }
+			]
+		);
+	}
+
+	#[test]
+	fn test_common_ends() {
+		let chunks: Vec<(&str, &str)> = (0..10_000).map(|_| ("tEXt", "same")).collect();
+		let (mut a, mut b) = (chunks.clone(), chunks);
+		a.insert(5000, ("zTXt", "old"));
+		b.insert(5000, ("zTXt", "new"));
+		b.push(("IEND", ""));
+		assert_eq!(
+			diff(&png(&a), &png(&b)),
+			[
+				ChunkChange::Modified { old_index: 5000, new_index: 5000 },
+				ChunkChange::Added { new_index: 10_001 }
+			]
+		);
+	}
+
+	#[test]
+	fn test_byte_differences() {
+		assert_eq!(byte_differences(b"abcdef", b"abXYeZ"), [2..4, 5..6]);
+		assert_eq!(byte_differences(b"abc", b"aXcde"), [1..2, 3..5]);
+		assert!(byte_differences(b"abc", b"abc").is_empty());
+	}
+}
@@ -0,0 +1,369 @@
+//! Reed–Solomon error correction, so that payloads survive a few damaged bytes.
+//!
+//! A protected payload is a header codeword followed by the body. The header codeword holds
+//! `MAGIC | parity (1 byte) | length (u32)` and [`HEADER_PARITY`] parity bytes of its own, so
+//! that it can be repaired before anything else is read. The payload is then cut into as few
+//! blocks as fit in 255-byte codewords with `parity` parity bytes each, all blocks the same
+//! size with the last one padded with zeros. The codewords are interleaved byte by byte, so
+//! that a run of damaged bytes is spread across all of them.
+//!
+//! The code works on bytes over GF(2⁸) with the polynomial `x⁸ + x⁴ + x³ + x² + 1`, and a
+//! codeword with `parity` parity bytes corrects up to `parity / 2` damaged bytes.
+
+use crate::{PngmeError, Result};
+
+const MAGIC: &[u8; 4] = b"PMRS";
+/// The longest codeword GF(2⁸) allows.
+const CODEWORD_LEN: usize = 255;
+const HEADER_DATA_LEN: usize = MAGIC.len() + 1 + 4;
+/// The parity bytes of the header codeword, enough to correct 8 damaged bytes.
+const HEADER_PARITY: usize = 16;
+const HEADER_LEN: usize = HEADER_DATA_LEN + HEADER_PARITY;
+
+/// The most parity bytes a codeword may have, leaving room for as many data bytes.
+pub const MAX_PARITY: u8 = 128;
+
+fn invalid(reason: impl Into<String>) -> crate::Error {
+	PngmeError::invalid("error correction", reason)
+}
+
+/// Logarithms and exponentials of GF(2⁸), to multiply and divide by table lookups.
+struct Tables {
+	exp: [u8; 2 * CODEWORD_LEN],
+	log: [u8; 256],
+}
+
+const TABLES: Tables = {
+	let mut tables = Tables { exp: [0; 2 * CODEWORD_LEN], log: [0; 256] };
+	let mut x: u16 = 1;
+	let mut n = 0;
+	while n < CODEWORD_LEN {
+		tables.exp[n] = x as u8;
+		tables.exp[n + CODEWORD_LEN] = x as u8;
+		tables.log[x as usize] = n as u8;
+		x <<= 1;
+		if x & 0x100 != 0 {
+			x ^= 0x11d;
+		}
+		n += 1;
+	}
+	tables
+};
+
+fn mul(a: u8, b: u8) -> u8 {
+	if a == 0 || b == 0 {
+		return 0;
+	}
+	TABLES.exp[TABLES.log[a as usize] as usize + TABLES.log[b as usize] as usize]
+}
+
+fn div(a: u8, b: u8) -> u8 {
+	debug_assert_ne!(b, 0);
+	if a == 0 {
+		return 0;
+	}
+	let log = TABLES.log[a as usize] as usize + CODEWORD_LEN - TABLES.log[b as usize] as usize;
+	TABLES.exp[log % CODEWORD_LEN]
+}
+
+/// Returns α to the power `n`.
+fn pow(n: usize) -> u8 {
+	TABLES.exp[n % CODEWORD_LEN]
+}
+
+/// Evaluates a polynomial with its highest-degree coefficient first.
+fn eval_high_first(poly: &[u8], x: u8) -> u8 {
+	poly.iter().fold(0, |acc, &coefficient| mul(acc, x) ^ coefficient)
+}
+
+/// Evaluates a polynomial with its lowest-degree coefficient first.
+fn eval_low_first(poly: &[u8], x: u8) -> u8 {
+	poly.iter().rev().fold(0, |acc, &coefficient| mul(acc, x) ^ coefficient)
+}
+
+/// The generator polynomial `(x - α⁰)(x - α¹)…(x - α^(parity - 1))`, highest degree first.
+fn generator(parity: usize) -> Vec<u8> {
+	let mut poly = vec![1];
+	for n in 0..parity {
+		let root = pow(n);
+		let mut next = vec![0; poly.len() + 1];
+		for (i, &coefficient) in poly.iter().enumerate() {
+			next[i] ^= coefficient;
+			next[i + 1] ^= mul(coefficient, root);
+		}
+		poly = next;
+	}
+	poly
+}
+
+/// Appends `parity` parity bytes to `data`, making a codeword.
+fn encode_block(data: &[u8], parity: usize) -> Vec<u8> {
+	let generator = generator(parity);
+	let mut codeword = data.to_vec();
+	codeword.resize(data.len() + parity, 0);
+	for n in 0..data.len() {
+		let coefficient = codeword[n];
+		if coefficient != 0 {
+			for (i, &g) in generator.iter().enumerate().skip(1) {
+				codeword[n + i] ^= mul(g, coefficient);
+			}
+		}
+	}
+	codeword[..data.len()].copy_from_slice(data);
+	codeword
+}
+
+/// Repairs a codeword in place, returning how many bytes were wrong, or `None` if there is
+/// more damage than the parity bytes can make up for.
+fn correct_block(codeword: &mut [u8], parity: usize) -> Option<usize> {
+	let syndromes: Vec<u8> = (0..parity).map(|n| eval_high_first(codeword, pow(n))).collect();
+	if syndromes.iter().all(|&s| s == 0) {
+		return Some(0);
+	}
+
+	// Berlekamp–Massey finds the error locator, whose roots are the inverses of the error
+	// positions. Both polynomials have their lowest-degree coefficient first.
+	let mut locator = vec![1];
+	let mut previous = vec![1];
+	let mut errors = 0;
+	let mut shift = 1;
+	let mut previous_discrepancy = 1;
+	for n in 0..parity {
+		let discrepancy = (1..=errors.min(locator.len() - 1))
+			.fold(syndromes[n], |acc, i| acc ^ mul(locator[i], syndromes[n - i]));
+		if discrepancy == 0 {
+			shift += 1;
+			continue;
+		}
+		let scale = div(discrepancy, previous_discrepancy);
+		let mut next = locator.clone();
+		next.resize(next.len().max(previous.len() + shift), 0);
+		for (i, &coefficient) in previous.iter().enumerate() {
+			next[i + shift] ^= mul(scale, coefficient);
+		}
+		if 2 * errors <= n {
+			previous = locator;
+			errors = n + 1 - errors;
+			previous_discrepancy = discrepancy;
+			shift = 1;
+		} else {
+			shift += 1;
+		}
+		locator = next;
+	}
+	locator.truncate(errors + 1);
+	if 2 * errors > parity {
+		return None;
+	}
+
+	// The error evaluator, syndromes times locator modulo x^parity.
+	let mut evaluator = vec![0; parity];
+	for (i, &s) in syndromes.iter().enumerate() {
+		for (j, &l) in locator.iter().enumerate().take(parity - i) {
+			evaluator[i + j] ^= mul(s, l);
+		}
+	}
+	// The formal derivative of the locator keeps the odd powers, one degree lower.
+	let derivative: Vec<u8> =
+		locator.iter().enumerate().skip(1).map(|(i, &l)| if i % 2 == 1 { l } else { 0 }).collect();
+
+	// Chien search for the positions, then Forney's formula for the error values.
+	let len = codeword.len();
+	let mut found = 0;
+	for (position, byte) in codeword.iter_mut().enumerate() {
+		let power = len - 1 - position;
+		let inverse = pow(CODEWORD_LEN - power % CODEWORD_LEN);
+		if eval_low_first(&locator, inverse) != 0 {
+			continue;
+		}
+		let denominator = eval_low_first(&derivative, inverse);
+		if denominator == 0 {
+			return None;
+		}
+		*byte ^= mul(pow(power), div(eval_low_first(&evaluator, inverse), denominator));
+		found += 1;
+	}
+	// A locator whose roots are not all in the codeword means the damage was misjudged.
+	if found != errors || (0..parity).any(|n| eval_high_first(codeword, pow(n)) != 0) {
+		return None;
+	}
+	Some(found)
+}
+
+/// Returns how many parity bytes each codeword gets for `ratio` parity bytes per data byte,
+/// failing if the ratio is not between 0 and 1 or too small to correct anything.
+pub fn parity_for(ratio: f64) -> Result<u8> {
+	if !(ratio > 0.0 && ratio <= 1.0) {
+		return Err(PngmeError::InvalidArgument(format!(
+			"The error correction ratio must be more than 0 and at most 1, got {}",
+			ratio
+		)));
+	}
+	let parity = (CODEWORD_LEN as f64 * ratio / (1.0 + ratio)).ceil() as u8;
+	if parity < 2 {
+		return Err(PngmeError::InvalidArgument(format!(
+			"An error correction ratio of {} is too small to correct anything",
+			ratio
+		)));
+	}
+	Ok(parity.min(MAX_PARITY))
+}
+
+/// How many blocks a payload of `len` bytes is cut into, and how many bytes each one holds.
+fn layout(len: usize, parity: usize) -> (usize, usize) {
+	let blocks = len.div_ceil(CODEWORD_LEN - parity);
+	(blocks, if blocks == 0 { 0 } else { len.div_ceil(blocks) })
+}
+
+/// Adds `parity` parity bytes to every codeword of `data`.
+pub fn protect(data: &[u8], parity: u8) -> Result<Vec<u8>> {
+	if !(2..=MAX_PARITY).contains(&parity) {
+		return Err(invalid(format!("{} parity bytes is not 2 to {}", parity, MAX_PARITY)));
+	}
+	let parity = parity as usize;
+	let mut header = MAGIC.to_vec();
+	header.push(parity as u8);
+	header.extend_from_slice(&u32::try_from(data.len())?.to_be_bytes());
+	let mut protected = encode_block(&header, HEADER_PARITY);
+
+	let (blocks, block_len) = layout(data.len(), parity);
+	let codewords: Vec<Vec<u8>> = (0..blocks)
+		.map(|n| {
+			let end = ((n + 1) * block_len).min(data.len());
+			let mut block = data[n * block_len..end].to_vec();
+			block.resize(block_len, 0);
+			encode_block(&block, parity)
+		})
+		.collect();
+	protected.reserve(blocks * (block_len + parity));
+	for n in 0..block_len + parity {
+		protected.extend(codewords.iter().map(|codeword| codeword[n]));
+	}
+	Ok(protected)
+}
+
+/// Repairs the header codeword, returning the parity, the payload length, and how many bytes
+/// were wrong.
+fn read_header(data: &[u8]) -> Option<(usize, usize, usize)> {
+	let mut header: [u8; HEADER_LEN] = data.get(..HEADER_LEN)?.try_into().ok()?;
+	let corrected = correct_block(&mut header, HEADER_PARITY)?;
+	if !header.starts_with(MAGIC) {
+		return None;
+	}
+	let parity = header[4] as usize;
+	if !(2..=MAX_PARITY as usize).contains(&parity) {
+		return None;
+	}
+	let len = u32::from_be_bytes(header[5..HEADER_DATA_LEN].try_into().ok()?) as usize;
+	Some((parity, len, corrected))
+}
+
+/// Returns `true` if `data` starts with a header written by [`protect`], even a damaged one.
+pub fn is_protected(data: &[u8]) -> bool {
+	read_header(data).is_some()
+}
+
+/// Repairs and unwraps a payload written by [`protect`], returning it with the number of
+/// bytes that were corrected.
+pub fn correct(data: &[u8]) -> Result<(Vec<u8>, usize)> {
+	let (parity, len, mut corrected) =
+		read_header(data).ok_or_else(|| invalid("the header is missing or beyond repair"))?;
+	let (blocks, block_len) = layout(len, parity);
+	let body = &data[HEADER_LEN..];
+	if body.len() != blocks * (block_len + parity) {
+		return Err(invalid(format!(
+			"expected {} bytes of codewords, got {}",
+			blocks * (block_len + parity),
+			body.len()
+		)));
+	}
+	let mut payload = Vec::with_capacity(blocks * block_len);
+	for n in 0..blocks {
+		let mut codeword: Vec<u8> = body.iter().skip(n).step_by(blocks).copied().collect();
+		corrected += correct_block(&mut codeword, parity).ok_or_else(|| {
+			invalid(format!("block {} has more damage than {} parity bytes can fix", n, parity))
+		})?;
+		payload.extend_from_slice(&codeword[..block_len]);
+	}
+	payload.truncate(len);
+	Ok((payload, corrected))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_block() {
+		let data = b"hello, reed-solomon";
+		let codeword = encode_block(data, 8);
+		assert_eq!(&codeword[..data.len()], data);
+		assert_eq!(correct_block(&mut codeword.clone(), 8), Some(0));
+
+		for errors in 1..=4 {
+			let mut damaged = codeword.clone();
+			for n in 0..errors {
+				damaged[n * 5] ^= 0x5a + n as u8;
+			}
+			assert_eq!(correct_block(&mut damaged, 8), Some(errors));
+			assert_eq!(damaged, codeword);
+		}
+		let mut damaged = codeword.clone();
+		for byte in &mut damaged[..5] {
+			*byte = !*byte;
+		}
+		correct_block(&mut damaged, 8);
+		assert_ne!(damaged, codeword);
+	}
+
+	#[test]
+	fn test_round_trip() {
+		let data: Vec<u8> = (0..1000u32).map(|n| (n * 7 % 251) as u8).collect();
+		for len in [0, 1, 200, 1000] {
+			let protected = protect(&data[..len], 32).unwrap();
+			assert!(is_protected(&protected));
+			assert_eq!(correct(&protected).unwrap(), (data[..len].to_vec(), 0));
+		}
+		assert!(!is_protected(b"not protected at all, just some bytes"));
+		assert!(protect(b"x", 1).is_err());
+	}
+
+	#[test]
+	fn test_not_encrypted() {
+		let protected = protect(&[0; 64], 16).unwrap();
+		assert!(!crate::crypto::is_encrypted(&protected));
+		let encrypted = crate::crypto::encrypt(&[0; 64], "secret").unwrap();
+		assert!(!is_protected(&encrypted));
+	}
+
+	#[test]
+	fn test_burst() {
+		let data: Vec<u8> = (0..2000u32).map(|n| (n % 256) as u8).collect();
+		let mut protected = protect(&data, parity_for(0.1).unwrap()).unwrap();
+		// Damage part of the header and a run of the body longer than one codeword could fix.
+		protected[1] ^= 0xff;
+		protected[3] ^= 0x01;
+		for byte in &mut protected[100..200] {
+			*byte = 0;
+		}
+		let (corrected, count) = correct(&protected).unwrap();
+		assert_eq!(corrected, data);
+		assert!(count >= 2, "{}", count);
+
+		for byte in &mut protected[HEADER_LEN..] {
+			*byte ^= 0x55;
+		}
+		assert!(correct(&protected).is_err());
+		assert!(correct(&protected[..protected.len() - 1]).is_err());
+	}
+
+	#[test]
+	fn test_parity_for() {
+		assert_eq!(parity_for(1.0).unwrap(), 128);
+		assert_eq!(parity_for(0.1).unwrap(), 24);
+		assert!(parity_for(0.0).is_err());
+		assert!(parity_for(0.001).is_err());
+		assert!(parity_for(1.5).is_err());
+		assert!(parity_for(f64::NAN).is_err());
+	}
+}
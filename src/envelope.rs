@@ -0,0 +1,379 @@
+//! The versioned payload envelope written by `encode`.
+//!
+//! An envelope is laid out as
+//! `MAGIC | version (1 byte) | flags (1 byte) | compression (1 byte) | encryption (1 byte) |
+//! length (u32) | checksum (u32) | body`, where the checksum is the CRC-32 of the body. The
+//! body is the payload after compression and then encryption, as recorded in the header, so
+//! decoders know which steps to undo. Payloads without the header are raw payloads written
+//! by earlier versions.
+//!
+//! With [`FLAG_HASHED`] set, the header is followed by the SHA-256 of the body (32 bytes),
+//! which catches a body edited after it was stored even when its CRCs were recomputed.
+//!
+//! An envelope may also be wrapped in error correction codewords by [`ecc::protect`], which
+//! [`Envelope::parse`] repairs and unwraps before reading it.
+//!
+//! With [`FLAG_KEYED`] set, the digest (if any) is followed by
+//! `key length (1 byte) | key (UTF-8)` before the body, so that several payloads of the same
+//! chunk type can be told apart.
+
+use std::mem;
+
+use sha2::{Digest, Sha256};
+
+use crate::{chunk::compute_crc, chunk_type::ChunkType, ecc, zlib, PngmeError, Result};
+
+const MAGIC: &[u8; 4] = b"PMEV";
+const VERSION: u8 = 1;
+const HEADER_LEN: usize = MAGIC.len() + 4 + mem::size_of::<[u32; 2]>();
+
+const DIGEST_LEN: usize = 32;
+
+/// How many bytes the envelope adds to a payload without a key.
+pub const OVERHEAD: usize = HEADER_LEN + DIGEST_LEN;
+
+/// The chunk type keyed payloads are stored in unless another one is given.
+pub const KEYED_CHUNK_TYPE: ChunkType = ChunkType::pmKy;
+
+/// Set if the payload came from a file rather than a text message.
+const FLAG_BINARY: u8 = 1;
+/// Set if the header is followed by a key.
+const FLAG_KEYED: u8 = 2;
+/// Set if the header is followed by the SHA-256 of the body.
+const FLAG_HASHED: u8 = 4;
+const KNOWN_FLAGS: u8 = FLAG_BINARY | FLAG_KEYED | FLAG_HASHED;
+
+fn invalid(reason: impl Into<String>) -> crate::Error {
+	PngmeError::invalid("payload envelope", reason)
+}
+
+/// The largest payload that is compressed, and that a compressed body may inflate to.
+pub const MAX_COMPRESSED_PAYLOAD: usize = 1 << 30;
+
+/// How the body of an envelope is compressed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+	None = 0,
+	/// A zlib stream.
+	Deflate = 1,
+	/// A Zstandard frame.
+	Zstd = 2,
+}
+
+impl Compression {
+	/// Returns the lowercase name of the method.
+	pub fn name(&self) -> &'static str {
+		match self {
+			Self::None => "none",
+			Self::Deflate => "deflate",
+			Self::Zstd => "zstd",
+		}
+	}
+
+	pub fn compress(&self, data: &[u8]) -> Result<Vec<u8>> {
+		if *self != Self::None && data.len() > MAX_COMPRESSED_PAYLOAD {
+			let reason =
+				format!("payloads over {} bytes cannot be compressed", MAX_COMPRESSED_PAYLOAD);
+			return Err(PngmeError::InvalidArgument(reason));
+		}
+		match self {
+			Self::None => Ok(data.to_vec()),
+			Self::Deflate => Ok(zlib::compress_with_level(data, 9)),
+			Self::Zstd => Ok(zstd::encode_all(data, 19)?),
+		}
+	}
+
+	pub fn decompress(&self, data: &[u8]) -> Result<Vec<u8>> {
+		match self {
+			Self::None => Ok(data.to_vec()),
+			Self::Deflate => zlib::decompress(data, MAX_COMPRESSED_PAYLOAD),
+			Self::Zstd => zlib::read_at_most(zstd::Decoder::new(data)?, MAX_COMPRESSED_PAYLOAD),
+		}
+	}
+}
+
+impl TryFrom<u8> for Compression {
+	type Error = crate::Error;
+	fn try_from(value: u8) -> Result<Self> {
+		match value {
+			0 => Ok(Self::None),
+			1 => Ok(Self::Deflate),
+			2 => Ok(Self::Zstd),
+			_ => Err(invalid(format!("unknown compression method {}", value))),
+		}
+	}
+}
+
+/// How the body of an envelope is encrypted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encryption {
+	None = 0,
+	/// [`crate::crypto`]: a key derived from a passphrase.
+	Passphrase = 1,
+	/// [`crate::keyring`]: age X25519 recipients.
+	Recipients = 2,
+}
+
+impl Encryption {
+	/// Returns the lowercase name of the method.
+	pub fn name(&self) -> &'static str {
+		match self {
+			Self::None => "none",
+			Self::Passphrase => "passphrase",
+			Self::Recipients => "recipients",
+		}
+	}
+}
+
+impl TryFrom<u8> for Encryption {
+	type Error = crate::Error;
+	fn try_from(value: u8) -> Result<Self> {
+		match value {
+			0 => Ok(Self::None),
+			1 => Ok(Self::Passphrase),
+			2 => Ok(Self::Recipients),
+			_ => Err(invalid(format!("unknown encryption method {}", value))),
+		}
+	}
+}
+
+/// A payload together with a description of how it was encoded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Envelope {
+	/// Whether the payload came from a file rather than a text message.
+	pub binary: bool,
+	pub compression: Compression,
+	pub encryption: Encryption,
+	/// The key addressing the payload among others of the same chunk type.
+	pub key: Option<String>,
+	/// Whether the envelope carries the SHA-256 of the body, which [`parse`](Envelope::parse)
+	/// checks. Envelopes written before it was added only have the CRC-32.
+	pub hashed: bool,
+	/// The payload, compressed and encrypted as described by the other fields.
+	pub body: Vec<u8>,
+}
+
+impl Envelope {
+	/// Wraps a payload that is neither compressed nor encrypted, with its hash.
+	pub fn new(body: Vec<u8>) -> Self {
+		Self {
+			binary: false,
+			compression: Compression::None,
+			encryption: Encryption::None,
+			key: None,
+			hashed: true,
+			body,
+		}
+	}
+
+	/// Returns `true` if `data` starts with an envelope header, or is protected by error
+	/// correction.
+	pub fn is_envelope(data: &[u8]) -> bool {
+		data.len() >= HEADER_LEN && data.starts_with(MAGIC) || ecc::is_protected(data)
+	}
+
+	/// Returns `true` if `data` is an envelope holding the payload stored under `key`.
+	pub fn has_key(data: &[u8], key: &str) -> bool {
+		matches!(Self::parse(data), Ok(Some(envelope)) if envelope.key.as_deref() == Some(key))
+	}
+
+	/// Encodes the envelope, header first.
+	pub fn to_bytes(&self) -> Result<Vec<u8>> {
+		let key = self.key.as_deref().unwrap_or_default();
+		let mut bytes =
+			Vec::with_capacity(HEADER_LEN + DIGEST_LEN + 1 + key.len() + self.body.len());
+		bytes.extend_from_slice(MAGIC);
+		bytes.push(VERSION);
+		let mut flags = if self.binary { FLAG_BINARY } else { 0 };
+		if self.key.is_some() {
+			flags |= FLAG_KEYED;
+		}
+		if self.hashed {
+			flags |= FLAG_HASHED;
+		}
+		bytes.push(flags);
+		bytes.push(self.compression as u8);
+		bytes.push(self.encryption as u8);
+		bytes.extend_from_slice(&u32::try_from(self.body.len())?.to_be_bytes());
+		bytes.extend_from_slice(&compute_crc(&self.body).to_be_bytes());
+		if self.hashed {
+			bytes.extend_from_slice(&Sha256::digest(&self.body));
+		}
+		if self.key.is_some() {
+			if key.is_empty() {
+				return Err(invalid("the key is empty"));
+			}
+			let len = u8::try_from(key.len()).map_err(|_| invalid("the key is over 255 bytes"))?;
+			bytes.push(len);
+			bytes.extend_from_slice(key.as_bytes());
+		}
+		bytes.extend_from_slice(&self.body);
+		Ok(bytes)
+	}
+
+	/// Decodes an envelope, returning `None` for a raw payload without the header.
+	pub fn parse(data: &[u8]) -> Result<Option<Self>> {
+		if ecc::is_protected(data) {
+			let (data, corrected) = ecc::correct(data)?;
+			log::debug!("error correction fixed {} bytes", corrected);
+			return Self::parse(&data);
+		}
+		if !Self::is_envelope(data) {
+			return Ok(None);
+		}
+		let [version, flags, compression, encryption] = data[4..8] else { unreachable!() };
+		if version != VERSION {
+			return Err(invalid(format!("unsupported version {}", version)));
+		}
+		if flags & !KNOWN_FLAGS != 0 {
+			return Err(invalid(format!("unknown flags {:#04x}", flags & !KNOWN_FLAGS)));
+		}
+		let length = u32::from_be_bytes(data[8..12].try_into()?) as usize;
+		let checksum = u32::from_be_bytes(data[12..16].try_into()?);
+		let mut body = &data[HEADER_LEN..];
+		let digest = if flags & FLAG_HASHED != 0 {
+			if body.len() < DIGEST_LEN {
+				return Err(invalid("the SHA-256 is cut off"));
+			}
+			let (digest, rest) = body.split_at(DIGEST_LEN);
+			body = rest;
+			Some(digest)
+		} else {
+			None
+		};
+		let key = if flags & FLAG_KEYED != 0 {
+			let (&len, rest) = body.split_first().ok_or_else(|| invalid("missing the key"))?;
+			if len == 0 || rest.len() < len as usize {
+				return Err(invalid("the key is empty or cut off"));
+			}
+			let (key, rest) = rest.split_at(len as usize);
+			body = rest;
+			Some(String::from_utf8(key.to_vec()).map_err(|_| invalid("the key is not UTF-8"))?)
+		} else {
+			None
+		};
+		if body.len() != length {
+			return Err(invalid(format!("expected {} bytes, got {}", length, body.len())));
+		}
+		if compute_crc(body) != checksum {
+			return Err(invalid("checksum mismatch"));
+		}
+		if digest.is_some_and(|digest| Sha256::digest(body)[..] != *digest) {
+			return Err(invalid(
+				"the SHA-256 does not match, the payload was changed after it was stored",
+			));
+		}
+		let envelope = Self {
+			binary: flags & FLAG_BINARY != 0,
+			compression: compression.try_into()?,
+			encryption: encryption.try_into()?,
+			key,
+			hashed: digest.is_some(),
+			body: body.to_vec(),
+		};
+		log::debug!(
+			"envelope of {} bytes, {} compression, {} encryption, {}",
+			length,
+			envelope.compression.name(),
+			envelope.encryption.name(),
+			if envelope.hashed { "SHA-256 verified" } else { "CRC-32 checked" }
+		);
+		Ok(Some(envelope))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_round_trip() {
+		let envelope = Envelope {
+			binary: true,
+			encryption: Encryption::Passphrase,
+			..Envelope::new(b"body".to_vec())
+		};
+		let bytes = envelope.to_bytes().unwrap();
+		assert_eq!(bytes.len(), OVERHEAD + 4);
+		assert!(Envelope::is_envelope(&bytes));
+		assert_eq!(Envelope::parse(&bytes).unwrap().unwrap(), envelope);
+	}
+
+	#[test]
+	fn test_keyed() {
+		let envelope = Envelope { key: Some("notes".into()), ..Envelope::new(b"body".to_vec()) };
+		let bytes = envelope.to_bytes().unwrap();
+		assert_eq!(Envelope::parse(&bytes).unwrap().unwrap(), envelope);
+		assert!(Envelope::has_key(&bytes, "notes"));
+		assert!(!Envelope::has_key(&bytes, "other"));
+		assert!(!Envelope::has_key(&Envelope::new(b"body".to_vec()).to_bytes().unwrap(), "notes"));
+		assert!(Envelope::parse(&bytes[..OVERHEAD + 3]).is_err());
+
+		assert!(Envelope { key: Some(String::new()), ..envelope.clone() }.to_bytes().is_err());
+		assert!(Envelope { key: Some("k".repeat(256)), ..envelope }.to_bytes().is_err());
+	}
+
+	#[test]
+	fn test_compression() {
+		let data =
+			b"a payload that repeats, a payload that repeats, a payload that repeats".repeat(8);
+		for compression in [Compression::None, Compression::Deflate, Compression::Zstd] {
+			let compressed = compression.compress(&data).unwrap();
+			assert_eq!(compression.decompress(&compressed).unwrap(), data);
+			if compression != Compression::None {
+				assert!(compressed.len() < data.len() / 4);
+			}
+		}
+		assert!(Compression::Zstd.decompress(b"not zstd").is_err());
+	}
+
+	#[test]
+	fn test_raw_payload() {
+		assert_eq!(Envelope::parse(b"legacy message").unwrap(), None);
+		assert_eq!(Envelope::parse(b"").unwrap(), None);
+	}
+
+	#[test]
+	fn test_invalid_envelope() {
+		let bytes = Envelope::new(b"body".to_vec()).to_bytes().unwrap();
+		let corrupt = |n: usize, value: u8| {
+			let mut bytes = bytes.clone();
+			bytes[n] = value;
+			Envelope::parse(&bytes)
+		};
+		assert!(corrupt(4, 2).is_err());
+		assert!(corrupt(5, 0x80).is_err());
+		assert!(corrupt(6, 3).is_err());
+		assert!(corrupt(7, 3).is_err());
+		assert!(corrupt(HEADER_LEN, b'B').is_err());
+		assert!(Envelope::parse(&bytes[..bytes.len() - 1]).is_err());
+	}
+
+	#[test]
+	fn test_hash() {
+		let unhashed = Envelope { hashed: false, ..Envelope::new(b"body".to_vec()) };
+		let bytes = unhashed.to_bytes().unwrap();
+		assert_eq!(bytes.len(), HEADER_LEN + 4);
+		assert_eq!(Envelope::parse(&bytes).unwrap().unwrap(), unhashed);
+
+		// Edit the body and fix up the CRC-32, as a tool recomputing checksums would.
+		let mut bytes = Envelope::new(b"body".to_vec()).to_bytes().unwrap();
+		bytes[OVERHEAD] = b'B';
+		let checksum = compute_crc(&bytes[OVERHEAD..]).to_be_bytes();
+		bytes[12..16].copy_from_slice(&checksum);
+		let error = Envelope::parse(&bytes).unwrap_err();
+		assert!(error.to_string().contains("SHA-256"), "{}", error);
+		assert!(Envelope::parse(&bytes[..HEADER_LEN + 8]).is_err());
+	}
+
+	#[test]
+	fn test_protected() {
+		let envelope = Envelope { key: Some("notes".into()), ..Envelope::new(b"body".to_vec()) };
+		let mut bytes = ecc::protect(&envelope.to_bytes().unwrap(), 16).unwrap();
+		bytes[0] ^= 0xff;
+		*bytes.last_mut().unwrap() ^= 0xff;
+		assert!(Envelope::is_envelope(&bytes));
+		assert!(Envelope::has_key(&bytes, "notes"));
+		assert_eq!(Envelope::parse(&bytes).unwrap().unwrap(), envelope);
+	}
+}
@@ -0,0 +1,264 @@
+//! The error type of the library.
+
+use std::{fmt, io};
+
+use crate::{chunk_type::ChunkType, png::FileKind, Png};
+
+/// Everything that can go wrong in pngme, detailed enough to match on.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum PngmeError {
+	/// The data does not start with the PNG signature; `kind` is what it looks like instead.
+	InvalidSignature {
+		found: [u8; 8],
+		kind: FileKind,
+	},
+	/// Four bytes that are not a valid chunk type.
+	InvalidChunkType {
+		bytes: [u8; 4],
+	},
+	/// A chunk is shorter or longer than its length field says.
+	InvalidChunkLength {
+		expected: u32,
+		received: u32,
+	},
+	/// A chunk's CRC does not match its contents. The offset of the chunk in the file is
+	/// known when the error comes from parsing a whole PNG.
+	CrcMismatch {
+		expected: u32,
+		computed: u32,
+		offset: Option<u64>,
+	},
+	/// The PNG has no chunk of the given type.
+	ChunkNotFound {
+		chunk_type: ChunkType,
+	},
+	/// Something looked for, such as a message under a key, is not there; the message says
+	/// what.
+	NotFound(String),
+	/// Malformed data; `what` names the kind of data, as in "IHDR chunk" or "payload
+	/// envelope".
+	InvalidData {
+		what: String,
+		reason: String,
+	},
+	/// Data after `IEND` that is not made of pngme payloads, starting at `offset` within it.
+	InvalidTrailer {
+		offset: usize,
+	},
+	/// The data is valid but pngme cannot handle it.
+	Unsupported(String),
+	/// A payload expected to be encrypted is not.
+	NotEncrypted,
+	/// The passphrase or key is wrong, or the payload is corrupted.
+	DecryptionFailed,
+	Io(io::Error),
+	/// The arguments of a call contradict each other or are out of range.
+	InvalidArgument(String),
+	/// Anything else, described by a message.
+	Message(String),
+	/// An error from one of the libraries pngme builds on.
+	Other(Box<dyn std::error::Error + Send + Sync>),
+	/// `error` occurred parsing the chunk at `index`, which starts at byte `offset` of the
+	/// file; see [`PngmeError::root`] for the error itself.
+	InChunk {
+		index: usize,
+		/// The chunk type, unless it is the part that is invalid.
+		chunk_type: Option<ChunkType>,
+		offset: u64,
+		snippet: Snippet,
+		error: Box<PngmeError>,
+	},
+}
+
+/// Up to 16 bytes of a file around where parsing failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snippet {
+	/// The file offset of the first byte.
+	pub offset: u64,
+	pub bytes: Vec<u8>,
+	/// The file offset of the byte where the problem is.
+	pub failure: u64,
+}
+
+impl Snippet {
+	const LEN: usize = 16;
+
+	/// Takes the bytes around `failure` from `window`, which starts at file offset `offset`.
+	pub(crate) fn around(window: &[u8], offset: u64, failure: u64) -> Self {
+		let at = (failure.saturating_sub(offset) as usize).min(window.len());
+		let start = at.saturating_sub(Self::LEN / 2).min(window.len().saturating_sub(Self::LEN));
+		let end = (start + Self::LEN).min(window.len());
+		Self { offset: offset + start as u64, bytes: window[start..end].to_vec(), failure }
+	}
+}
+
+/// Shows the bytes as a hex dump line, with the failing byte marked on a second line.
+impl fmt::Display for Snippet {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let hex: Vec<_> = self.bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+		let ascii: String = self
+			.bytes
+			.iter()
+			.map(|&byte| if byte.is_ascii_graphic() || byte == b' ' { byte as char } else { '.' })
+			.collect();
+		writeln!(f, "{:#010x}  {:<47}  |{}|", self.offset, hex.join(" "), ascii)?;
+		let column = (self.failure.saturating_sub(self.offset) as usize).min(Self::LEN) * 3;
+		write!(f, "{:12}{:column$}^^", "", "", column = column)
+	}
+}
+
+impl PngmeError {
+	/// Builds an [`PngmeError::InvalidData`] error.
+	pub fn invalid(what: impl Into<String>, reason: impl Into<String>) -> Self {
+		Self::InvalidData { what: what.into(), reason: reason.into() }
+	}
+
+	/// Builds an [`PngmeError::InvalidSignature`] error for a file starting with `start`.
+	pub(crate) fn signature(start: &[u8]) -> Self {
+		let mut found = [0; 8];
+		let len = start.len().min(found.len());
+		found[..len].copy_from_slice(&start[..len]);
+		Self::InvalidSignature { found, kind: Png::sniff(start) }
+	}
+
+	/// Returns the error behind any [`PngmeError::InChunk`] context.
+	pub fn root(&self) -> &Self {
+		match self {
+			Self::InChunk { error, .. } => error.root(),
+			error => error,
+		}
+	}
+
+	/// Adds the location of the chunk that failed to parse. `window` holds the bytes of the
+	/// file from the start of the chunk on, or as many of them as are at hand.
+	pub(crate) fn in_chunk(self, index: usize, offset: u64, window: &[u8]) -> Self {
+		let chunk_type = window
+			.get(4..8)
+			.and_then(|bytes| ChunkType::try_from(<[u8; 4]>::try_from(bytes).ok()?).ok());
+		let length = window.get(..4).map_or(0, |len| u32::from_be_bytes(len.try_into().unwrap()));
+		// Where, relative to the start of the chunk, the problem is.
+		let (error, failure) = match self {
+			Self::CrcMismatch { expected, computed, offset: None } => {
+				let error = Self::CrcMismatch { expected, computed, offset: Some(offset) };
+				(error, 8 + length as u64)
+			}
+			error @ Self::InvalidChunkType { .. } => (error, 4),
+			error @ Self::InvalidChunkLength { .. } => (error, window.len() as u64),
+			error => (error, 0),
+		};
+		Self::InChunk {
+			index,
+			chunk_type,
+			offset,
+			snippet: Snippet::around(window, offset, offset + failure),
+			error: Box::new(error),
+		}
+	}
+}
+
+impl std::error::Error for PngmeError {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		match self {
+			Self::Io(error) => Some(error),
+			Self::Other(error) => Some(error.as_ref()),
+			Self::InChunk { error, .. } => Some(error.as_ref()),
+			_ => None,
+		}
+	}
+}
+
+impl fmt::Display for PngmeError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::InvalidSignature { kind: FileKind::MangledPng, .. } => f.write_str(
+				"Invalid PNG header: the signature was mangled, probably by a transfer in text mode",
+			),
+			Self::InvalidSignature { kind: FileKind::HeadlessPng, .. } => {
+				f.write_str("Invalid PNG header: the signature is missing before the IHDR chunk")
+			}
+			Self::InvalidSignature { kind: FileKind::TruncatedPng, .. } => {
+				f.write_str("Invalid PNG header: the file ends inside the signature")
+			}
+			Self::InvalidSignature { kind: FileKind::Unknown | FileKind::Png, found } => write!(
+				f,
+				"Invalid PNG header: expected {:x?}, got {:x?}",
+				Png::STANDARD_HEADER,
+				found
+			),
+			Self::InvalidSignature { kind, .. } => write!(f, "Not a PNG file: this is {}", kind),
+			Self::InvalidChunkType { bytes } => write!(f, "Invalid chunk type bytes: {:?}", bytes),
+			Self::InvalidChunkLength { expected, received } => {
+				write!(f, "Invalid chunk length: expected {}, got {}", expected, received)
+			}
+			Self::CrcMismatch { expected, computed, .. } => {
+				write!(f, "Invalid chunk crc: expected 0x{:x}, got 0x{:x}", expected, computed)
+			}
+			Self::ChunkNotFound { chunk_type } => write!(f, "No {} chunk found", chunk_type),
+			Self::InvalidData { what, reason } => write!(f, "Invalid {}: {}", what, reason),
+			Self::InvalidTrailer { offset } => {
+				write!(f, "Data after IEND is not a pngme payload at trailer offset {}", offset)
+			}
+			Self::NotFound(message)
+			| Self::Unsupported(message)
+			| Self::InvalidArgument(message)
+			| Self::Message(message) => f.write_str(message),
+			Self::NotEncrypted => f.write_str("Payload is not encrypted"),
+			Self::DecryptionFailed => {
+				f.write_str("Decryption failed: wrong passphrase or corrupted payload")
+			}
+			Self::Io(error) => error.fmt(f),
+			Self::Other(error) => error.fmt(f),
+			Self::InChunk { index, chunk_type, offset, error, .. } => {
+				write!(f, "{} in chunk {}", error, index)?;
+				if let Some(chunk_type) = chunk_type {
+					write!(f, " ({})", chunk_type)?;
+				}
+				write!(f, " at offset {:#010x}", offset)
+			}
+		}
+	}
+}
+
+impl From<io::Error> for PngmeError {
+	fn from(error: io::Error) -> Self {
+		Self::Io(error)
+	}
+}
+
+impl From<String> for PngmeError {
+	fn from(message: String) -> Self {
+		Self::Message(message)
+	}
+}
+
+impl From<&str> for PngmeError {
+	fn from(message: &str) -> Self {
+		Self::Message(message.to_owned())
+	}
+}
+
+macro_rules! from_other {
+	($($error:ty),* $(,)?) => {
+		$(impl From<$error> for PngmeError {
+			fn from(error: $error) -> Self {
+				Self::Other(Box::new(error))
+			}
+		})*
+	};
+}
+
+from_other!(
+	age::DecryptError,
+	age::EncryptError,
+	base64::DecodeError,
+	ed25519_dalek::pkcs8::Error,
+	ed25519_dalek::pkcs8::spki::Error,
+	ed25519_dalek::SignatureError,
+	serde_json::Error,
+	std::array::TryFromSliceError,
+	std::num::ParseIntError,
+	std::num::TryFromIntError,
+	std::str::Utf8Error,
+	std::string::FromUtf8Error,
+);
@@ -0,0 +1,367 @@
+//! The `eXIf` chunk and the EXIF tags it holds.
+//!
+//! The chunk data is a TIFF structure: a byte order mark (`II` or `MM`), the number 42, and
+//! the offset of the first image file directory (IFD). Each IFD entry is a tag, a value type,
+//! a count, and either the value itself or the offset of the value if it exceeds four bytes.
+//! The primary IFD may point to the EXIF and GPS IFDs, which are followed as well.
+
+use std::fmt;
+
+use crate::{chunk::Chunk, chunk_type::ChunkType, PngmeError, Result};
+
+const EXIF_IFD_POINTER: u16 = 0x8769;
+const GPS_IFD_POINTER: u16 = 0x8825;
+
+fn invalid(reason: impl Into<String>) -> crate::Error {
+	PngmeError::invalid("EXIF data", reason)
+}
+
+/// An `eXIf` chunk.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ExifChunk {
+	data: Vec<u8>,
+}
+
+impl ExifChunk {
+	pub const TYPE: ChunkType = ChunkType::eXIf;
+
+	/// Wraps raw EXIF data, checking its TIFF header. A leading `Exif\0\0` marker, as found in
+	/// JPEG files and `.exif` dumps, is removed since the PNG chunk does not carry it.
+	pub fn new(data: Vec<u8>) -> Result<Self> {
+		let data = match data.strip_prefix(b"Exif\0\0") {
+			Some(rest) => rest.to_vec(),
+			None => data,
+		};
+		Reader::new(&data)?;
+		Ok(Self { data })
+	}
+
+	pub fn data(&self) -> &[u8] {
+		&self.data
+	}
+
+	/// Parses the tags of the primary, EXIF, and GPS directories.
+	pub fn tags(&self) -> Result<Vec<ExifTag>> {
+		let reader = Reader::new(&self.data)?;
+		let mut tags = reader.read_ifd(reader.u32(4)?, Ifd::Primary)?;
+		for (pointer, ifd) in [(EXIF_IFD_POINTER, Ifd::Exif), (GPS_IFD_POINTER, Ifd::Gps)] {
+			let offset = tags.iter().find_map(|tag| match (&tag.value, tag.tag) {
+				(ExifValue::Unsigned(values), t) if t == pointer && tag.ifd == Ifd::Primary => {
+					values.first().copied()
+				}
+				_ => None,
+			});
+			if let Some(offset) = offset {
+				tags.extend(reader.read_ifd(offset, ifd)?);
+			}
+		}
+		tags.retain(|tag| tag.tag != EXIF_IFD_POINTER && tag.tag != GPS_IFD_POINTER);
+		Ok(tags)
+	}
+
+	/// Encodes the EXIF data as a raw [`Chunk`].
+	pub fn to_chunk(&self) -> Chunk {
+		Chunk::new(Self::TYPE, self.data.clone())
+	}
+}
+
+impl TryFrom<&Chunk> for ExifChunk {
+	type Error = crate::Error;
+	fn try_from(chunk: &Chunk) -> Result<Self> {
+		if chunk.chunk_type() != &Self::TYPE {
+			return Err(invalid(format!("expected eXIf, got {}", chunk.chunk_type())));
+		}
+		Self::new(chunk.data().to_vec())
+	}
+}
+
+/// The directory a tag was found in; tag numbers are only unique within a directory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ifd {
+	Primary,
+	Exif,
+	Gps,
+}
+
+/// The value of an EXIF tag, by TIFF value type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExifValue {
+	Ascii(String),
+	Unsigned(Vec<u32>),
+	Signed(Vec<i32>),
+	Rational(Vec<(u32, u32)>),
+	SignedRational(Vec<(i32, i32)>),
+	Undefined(Vec<u8>),
+}
+
+impl fmt::Display for ExifValue {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		fn list<T>(
+			f: &mut fmt::Formatter<'_>,
+			values: &[T],
+			show: impl Fn(&T) -> String,
+		) -> fmt::Result {
+			f.write_str(&values.iter().map(show).collect::<Vec<_>>().join(", "))
+		}
+		match self {
+			Self::Ascii(string) => f.write_str(string),
+			Self::Unsigned(values) => list(f, values, u32::to_string),
+			Self::Signed(values) => list(f, values, i32::to_string),
+			Self::Rational(values) => list(f, values, |(n, d)| rational(*n as f64, *d as f64)),
+			Self::SignedRational(values) => {
+				list(f, values, |(n, d)| rational(*n as f64, *d as f64))
+			}
+			Self::Undefined(bytes) => write!(f, "{} bytes", bytes.len()),
+		}
+	}
+}
+
+fn rational(numerator: f64, denominator: f64) -> String {
+	if denominator == 0.0 {
+		return "undefined".to_owned();
+	}
+	let value = numerator / denominator;
+	if value.fract() == 0.0 {
+		format!("{}", value)
+	} else if value.abs() < 1.0 && numerator == 1.0 {
+		format!("1/{}", denominator)
+	} else {
+		format!("{:.4}", value).trim_end_matches('0').to_owned()
+	}
+}
+
+/// A single EXIF tag.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExifTag {
+	pub ifd: Ifd,
+	pub tag: u16,
+	pub value: ExifValue,
+}
+
+impl ExifTag {
+	/// Returns the name of well-known tags.
+	pub fn name(&self) -> Option<&'static str> {
+		Some(match (self.ifd, self.tag) {
+			(Ifd::Gps, 0x0000) => "GPSVersionID",
+			(Ifd::Gps, 0x0001) => "GPSLatitudeRef",
+			(Ifd::Gps, 0x0002) => "GPSLatitude",
+			(Ifd::Gps, 0x0003) => "GPSLongitudeRef",
+			(Ifd::Gps, 0x0004) => "GPSLongitude",
+			(Ifd::Gps, 0x0005) => "GPSAltitudeRef",
+			(Ifd::Gps, 0x0006) => "GPSAltitude",
+			(Ifd::Gps, 0x0007) => "GPSTimeStamp",
+			(Ifd::Gps, 0x001d) => "GPSDateStamp",
+			(Ifd::Gps, _) => return None,
+			(_, 0x010e) => "ImageDescription",
+			(_, 0x010f) => "Make",
+			(_, 0x0110) => "Model",
+			(_, 0x0112) => "Orientation",
+			(_, 0x011a) => "XResolution",
+			(_, 0x011b) => "YResolution",
+			(_, 0x0128) => "ResolutionUnit",
+			(_, 0x0131) => "Software",
+			(_, 0x0132) => "DateTime",
+			(_, 0x013b) => "Artist",
+			(_, 0x8298) => "Copyright",
+			(_, 0x829a) => "ExposureTime",
+			(_, 0x829d) => "FNumber",
+			(_, 0x8827) => "ISOSpeedRatings",
+			(_, 0x9003) => "DateTimeOriginal",
+			(_, 0x9004) => "DateTimeDigitized",
+			(_, 0x920a) => "FocalLength",
+			(_, 0xa002) => "PixelXDimension",
+			(_, 0xa003) => "PixelYDimension",
+			(_, 0xa434) => "LensModel",
+			_ => return None,
+		})
+	}
+
+	/// Returns `true` for tags that reveal where the image was taken.
+	pub fn is_gps(&self) -> bool {
+		self.ifd == Ifd::Gps
+	}
+}
+
+impl fmt::Display for ExifTag {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self.name() {
+			Some(name) => write!(f, "{}: {}", name, self.value),
+			None => write!(f, "Tag 0x{:04x}: {}", self.tag, self.value),
+		}
+	}
+}
+
+/// Reads TIFF structures in either byte order, with bounds checks.
+struct Reader<'a> {
+	data: &'a [u8],
+	little_endian: bool,
+}
+
+impl<'a> Reader<'a> {
+	fn new(data: &'a [u8]) -> Result<Self> {
+		let little_endian = match data.get(..2) {
+			Some(b"II") => true,
+			Some(b"MM") => false,
+			_ => return Err(invalid("missing TIFF byte order mark")),
+		};
+		let reader = Self { data, little_endian };
+		if reader.u16(2)? != 42 {
+			return Err(invalid("missing TIFF magic number"));
+		}
+		Ok(reader)
+	}
+
+	fn bytes(&self, offset: usize, len: usize) -> Result<&'a [u8]> {
+		offset
+			.checked_add(len)
+			.and_then(|end| self.data.get(offset..end))
+			.ok_or_else(|| invalid(format!("offset {} is out of bounds", offset)))
+	}
+
+	fn u16(&self, offset: usize) -> Result<u16> {
+		let bytes = self.bytes(offset, 2)?.try_into()?;
+		Ok(if self.little_endian { u16::from_le_bytes(bytes) } else { u16::from_be_bytes(bytes) })
+	}
+
+	fn u32(&self, offset: usize) -> Result<u32> {
+		let bytes = self.bytes(offset, 4)?.try_into()?;
+		Ok(if self.little_endian { u32::from_le_bytes(bytes) } else { u32::from_be_bytes(bytes) })
+	}
+
+	fn read_ifd(&self, offset: u32, ifd: Ifd) -> Result<Vec<ExifTag>> {
+		let offset = offset as usize;
+		let count = self.u16(offset)? as usize;
+		let entries = (0..count).map(|n| self.read_entry(offset + 2 + n * 12, ifd));
+		entries.filter_map(Result::transpose).collect()
+	}
+
+	/// Reads the entry at `offset`, or `None` for a value type of unknown size.
+	fn read_entry(&self, offset: usize, ifd: Ifd) -> Result<Option<ExifTag>> {
+		let tag = self.u16(offset)?;
+		let value_type = self.u16(offset + 2)?;
+		let count = self.u32(offset + 4)? as usize;
+		let size = match value_type {
+			1 | 2 | 6 | 7 => 1,
+			3 | 8 => 2,
+			4 | 9 | 11 | 13 => 4,
+			5 | 10 | 12 => 8,
+			_ => return Ok(None),
+		};
+		let len = count.checked_mul(size).ok_or_else(|| invalid("value is too large"))?;
+		let start = if len <= 4 { offset + 8 } else { self.u32(offset + 8)? as usize };
+		let bytes = self.bytes(start, len)?;
+
+		let values = |size: usize| (0..count).map(move |n| start + n * size);
+		let value = match value_type {
+			2 => {
+				let text = bytes.split(|&b| b == 0).next().unwrap_or_default();
+				ExifValue::Ascii(String::from_utf8_lossy(text).into_owned())
+			}
+			1 => ExifValue::Unsigned(bytes.iter().map(|&b| b as u32).collect()),
+			6 => ExifValue::Signed(bytes.iter().map(|&b| b as i8 as i32).collect()),
+			7 => ExifValue::Undefined(bytes.to_vec()),
+			3 => ExifValue::Unsigned(
+				values(2).map(|n| self.u16(n).map(u32::from)).collect::<Result<_>>()?,
+			),
+			8 => ExifValue::Signed(
+				values(2).map(|n| self.u16(n).map(|v| v as i16 as i32)).collect::<Result<_>>()?,
+			),
+			4 => ExifValue::Unsigned(values(4).map(|n| self.u32(n)).collect::<Result<_>>()?),
+			9 => ExifValue::Signed(
+				values(4).map(|n| self.u32(n).map(|v| v as i32)).collect::<Result<_>>()?,
+			),
+			5 => ExifValue::Rational(
+				values(8).map(|n| Ok((self.u32(n)?, self.u32(n + 4)?))).collect::<Result<_>>()?,
+			),
+			10 => ExifValue::SignedRational(
+				values(8)
+					.map(|n| Ok((self.u32(n)? as i32, self.u32(n + 4)? as i32)))
+					.collect::<Result<_>>()?,
+			),
+			// FLOAT, DOUBLE, and IFD values are kept as they are stored.
+			_ => ExifValue::Undefined(bytes.to_vec()),
+		};
+		Ok(Some(ExifTag { ifd, tag, value }))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// Big-endian EXIF data with Make, an EXIF IFD holding ExposureTime, and a GPS IFD holding
+	/// GPSLatitudeRef.
+	fn testing_exif() -> Vec<u8> {
+		let mut data = b"MM\0\x2a\0\0\0\x08".to_vec();
+		// Primary IFD at 8: three entries, then the next IFD offset.
+		data.extend_from_slice(&[0, 3]);
+		data.extend_from_slice(&[0x01, 0x0f, 0, 2, 0, 0, 0, 4, b'A', b'c', b'm', 0]);
+		data.extend_from_slice(&[0x87, 0x69, 0, 4, 0, 0, 0, 1, 0, 0, 0, 50]);
+		data.extend_from_slice(&[0x88, 0x25, 0, 4, 0, 0, 0, 1, 0, 0, 0, 68]);
+		data.extend_from_slice(&[0, 0, 0, 0]);
+		// EXIF IFD at 50: ExposureTime 1/250 stored at 68 + 18 = 86.
+		data.extend_from_slice(&[0, 1]);
+		data.extend_from_slice(&[0x82, 0x9a, 0, 5, 0, 0, 0, 1, 0, 0, 0, 86]);
+		data.extend_from_slice(&[0, 0, 0, 0]);
+		// GPS IFD at 68: GPSLatitudeRef "N".
+		data.extend_from_slice(&[0, 1]);
+		data.extend_from_slice(&[0x00, 0x01, 0, 2, 0, 0, 0, 2, b'N', 0, 0, 0]);
+		data.extend_from_slice(&[0, 0, 0, 0]);
+		data.extend_from_slice(&[0, 0, 0, 1, 0, 0, 0, 250]);
+		data
+	}
+
+	#[test]
+	fn test_tags() {
+		let exif = ExifChunk::new(testing_exif()).unwrap();
+		let tags: Vec<String> = exif.tags().unwrap().iter().map(ExifTag::to_string).collect();
+		assert_eq!(tags, ["Make: Acm", "ExposureTime: 1/250", "GPSLatitudeRef: N"]);
+		assert!(exif.tags().unwrap()[2].is_gps());
+	}
+
+	#[test]
+	fn test_little_endian() {
+		let mut data = b"II\x2a\0\x08\0\0\0".to_vec();
+		data.extend_from_slice(&[1, 0]);
+		data.extend_from_slice(&[0x12, 0x01, 3, 0, 1, 0, 0, 0, 6, 0, 0, 0]);
+		data.extend_from_slice(&[0, 0, 0, 0]);
+		let tags = ExifChunk::new(data).unwrap().tags().unwrap();
+		assert_eq!(tags[0].to_string(), "Orientation: 6");
+	}
+
+	#[test]
+	fn test_other_value_types() {
+		let mut data = b"II\x2a\0\x08\0\0\0".to_vec();
+		data.extend_from_slice(&[3, 0]);
+		// A FLOAT, a value type of unknown size, and Orientation.
+		data.extend_from_slice(&[0x00, 0xa0, 11, 0, 1, 0, 0, 0, 0, 0, 0x80, 0x3f]);
+		data.extend_from_slice(&[0x01, 0xa0, 99, 0, 1, 0, 0, 0, 0, 0, 0, 0]);
+		data.extend_from_slice(&[0x12, 0x01, 3, 0, 1, 0, 0, 0, 6, 0, 0, 0]);
+		data.extend_from_slice(&[0, 0, 0, 0]);
+		let tags = ExifChunk::new(data).unwrap().tags().unwrap();
+		assert_eq!(tags.len(), 2);
+		assert_eq!(tags[0].value, ExifValue::Undefined(1f32.to_le_bytes().to_vec()));
+		assert_eq!(tags[1].to_string(), "Orientation: 6");
+	}
+
+	#[test]
+	fn test_chunk_round_trip() {
+		let exif = ExifChunk::new(testing_exif()).unwrap();
+		assert_eq!(ExifChunk::try_from(&exif.to_chunk()).unwrap(), exif);
+	}
+
+	#[test]
+	fn test_strips_jpeg_marker() {
+		let mut data = b"Exif\0\0".to_vec();
+		data.extend(testing_exif());
+		assert_eq!(ExifChunk::new(data).unwrap().data(), testing_exif());
+	}
+
+	#[test]
+	fn test_invalid() {
+		assert!(ExifChunk::new(b"not exif".to_vec()).is_err());
+		let mut data = testing_exif();
+		data.truncate(60);
+		assert!(ExifChunk::new(data).unwrap().tags().is_err());
+	}
+}
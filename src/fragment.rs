@@ -0,0 +1,109 @@
+//! Splitting large payloads across several chunks.
+//!
+//! Each fragment is laid out as `MAGIC | index (u32) | total (u32) | data`, with integers
+//! stored big-endian like the rest of the PNG format.
+
+use std::mem;
+
+use crate::{PngmeError, Result};
+
+const MAGIC: &[u8; 4] = b"PMEF";
+const HEADER_LEN: usize = MAGIC.len() + mem::size_of::<[u32; 2]>();
+
+/// Returns `true` if `data` starts with a fragment header.
+pub fn is_fragment(data: &[u8]) -> bool {
+	data.len() >= HEADER_LEN && data.starts_with(MAGIC)
+}
+
+/// Splits `payload` into fragments of at most `max_len` bytes each, headers included.
+pub fn split(payload: &[u8], max_len: usize) -> Result<Vec<Vec<u8>>> {
+	if max_len <= HEADER_LEN {
+		return Err(PngmeError::InvalidArgument(format!(
+			"Chunk size must be larger than {} bytes",
+			HEADER_LEN
+		)));
+	}
+	let pieces: Vec<&[u8]> = payload.chunks(max_len - HEADER_LEN).collect();
+	let total = u32::try_from(pieces.len())?;
+	Ok((0..total)
+		.zip(pieces)
+		.map(|(index, piece)| {
+			let mut fragment = Vec::with_capacity(HEADER_LEN + piece.len());
+			fragment.extend_from_slice(MAGIC);
+			fragment.extend_from_slice(&index.to_be_bytes());
+			fragment.extend_from_slice(&total.to_be_bytes());
+			fragment.extend_from_slice(piece);
+			fragment
+		})
+		.collect())
+}
+
+/// Reassembles a payload from its fragments, which may be given in any order.
+pub fn reassemble<'a>(fragments: impl IntoIterator<Item = &'a [u8]>) -> Result<Vec<u8>> {
+	let invalid = |reason: &str| PngmeError::invalid("payload fragments", reason);
+
+	let mut pieces = Vec::new();
+	for fragment in fragments {
+		if !is_fragment(fragment) {
+			return Err(invalid("missing fragment header"));
+		}
+		let index = u32::from_be_bytes(fragment[4..8].try_into()?);
+		let total = u32::from_be_bytes(fragment[8..12].try_into()?);
+		pieces.push((index, total, &fragment[HEADER_LEN..]));
+	}
+	pieces.sort_by_key(|&(index, _, _)| index);
+
+	let Some(&(_, total, _)) = pieces.first() else {
+		return Err(invalid("no fragments"));
+	};
+	if pieces.len() != total as usize {
+		return Err(invalid(&format!("expected {} fragments, found {}", total, pieces.len())));
+	}
+	if pieces.iter().enumerate().any(|(n, &(index, t, _))| index as usize != n || t != total) {
+		return Err(invalid("fragments are duplicated or inconsistent"));
+	}
+	Ok(pieces.into_iter().flat_map(|(_, _, piece)| piece).copied().collect())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_round_trip() {
+		let payload: Vec<u8> = (0..100).collect();
+		let fragments = split(&payload, HEADER_LEN + 30).unwrap();
+		assert_eq!(fragments.len(), 4);
+		assert!(fragments.iter().all(|fragment| is_fragment(fragment)));
+		assert!(fragments.iter().all(|fragment| fragment.len() <= HEADER_LEN + 30));
+
+		let reassembled = reassemble(fragments.iter().rev().map(Vec::as_slice)).unwrap();
+		assert_eq!(reassembled, payload);
+	}
+
+	#[test]
+	fn test_chunk_size_too_small() {
+		assert!(split(b"payload", HEADER_LEN).is_err());
+	}
+
+	#[test]
+	fn test_missing_fragment() {
+		let payload: Vec<u8> = (0..100).collect();
+		let fragments = split(&payload, HEADER_LEN + 30).unwrap();
+		assert!(reassemble(fragments[1..].iter().map(Vec::as_slice)).is_err());
+	}
+
+	#[test]
+	fn test_duplicate_fragment() {
+		let payload: Vec<u8> = (0..100).collect();
+		let mut fragments = split(&payload, HEADER_LEN + 30).unwrap();
+		fragments[1] = fragments[0].clone();
+		assert!(reassemble(fragments.iter().map(Vec::as_slice)).is_err());
+	}
+
+	#[test]
+	fn test_not_a_fragment() {
+		assert!(!is_fragment(b"payload"));
+		assert!(reassemble([b"payload".as_slice()]).is_err());
+	}
+}
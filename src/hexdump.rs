@@ -0,0 +1,101 @@
+//! Showing binary data as text: hex dumps in the style of `xxd`, and strings with escapes.
+
+use std::fmt;
+
+/// Bytes shown on each line.
+const WIDTH: usize = 16;
+
+/// Shows bytes as lines of an offset, sixteen bytes in groups of two, and their printable ASCII
+/// characters, with `.` for the others.
+#[derive(Debug, Clone, Copy)]
+pub struct HexDump<'a> {
+	data: &'a [u8],
+	offset: u64,
+}
+
+impl<'a> HexDump<'a> {
+	/// Dumps `data`, numbering its first byte `offset`, such as its position in the file.
+	pub fn new(data: &'a [u8], offset: u64) -> Self {
+		Self { data, offset }
+	}
+}
+
+/// Lines are separated by newlines, with none after the last one. Empty data gives no lines.
+impl fmt::Display for HexDump<'_> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		for (n, line) in self.data.chunks(WIDTH).enumerate() {
+			if n > 0 {
+				writeln!(f)?;
+			}
+			write!(f, "{:08x}:", self.offset + (n * WIDTH) as u64)?;
+			for column in 0..WIDTH {
+				if column % 2 == 0 {
+					f.write_str(" ")?;
+				}
+				match line.get(column) {
+					Some(byte) => write!(f, "{:02x}", byte)?,
+					None => f.write_str("  ")?,
+				}
+			}
+			let ascii: String = line
+				.iter()
+				.map(
+					|&byte| {
+						if byte.is_ascii_graphic() || byte == b' ' {
+							byte as char
+						} else {
+							'.'
+						}
+					},
+				)
+				.collect();
+			write!(f, "  {}", ascii)?;
+		}
+		Ok(())
+	}
+}
+
+/// Renders `data` as text, writing control characters, bytes that are not valid UTF-8, and
+/// backslashes as `\xNN` escapes, so that no two inputs render the same.
+pub fn escape(data: &[u8]) -> String {
+	let mut escaped = String::with_capacity(data.len());
+	let push_bytes = |escaped: &mut String, bytes: &[u8]| {
+		for byte in bytes {
+			escaped.push_str(&format!("\\x{:02x}", byte));
+		}
+	};
+	for chunk in data.utf8_chunks() {
+		for c in chunk.valid().chars() {
+			if c.is_control() || c == '\\' {
+				push_bytes(&mut escaped, c.encode_utf8(&mut [0; 4]).as_bytes());
+			} else {
+				escaped.push(c);
+			}
+		}
+		push_bytes(&mut escaped, chunk.invalid());
+	}
+	escaped
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_hexdump() {
+		let data: Vec<u8> = b"IHDR\0\x01 pngme, hex!\xff".to_vec();
+		assert_eq!(
+			HexDump::new(&data, 0x0c).to_string(),
+			"0000000c: 4948 4452 0001 2070 6e67 6d65 2c20 6865  IHDR.. pngme, he\n\
+			 0000001c: 7821 ff                                  x!."
+		);
+		assert_eq!(HexDump::new(&[], 0).to_string(), "");
+	}
+
+	#[test]
+	fn test_escape() {
+		assert_eq!(escape("ünï\\cødé ✓".as_bytes()), "ünï\\x5ccødé ✓");
+		assert_eq!(escape(b"a\0b\n\xff\xc3"), "a\\x00b\\x0a\\xff\\xc3");
+		assert_eq!(escape("\u{85}".as_bytes()), "\\xc2\\x85");
+	}
+}
@@ -0,0 +1,157 @@
+//! The `iCCP` chunk, which embeds an ICC color profile.
+//!
+//! The chunk data is a Latin-1 profile name following the textual chunk keyword rules, a NUL
+//! separator, a compression method byte, and the zlib-compressed profile. A PNG may hold at
+//! most one `iCCP` chunk, and it must come before `PLTE` and `IDAT`.
+
+use std::fmt;
+
+use crate::{
+	chunk::Chunk,
+	chunk_type::ChunkType,
+	png::Png,
+	text::{from_latin1, split_keyword, to_latin1, validate_keyword},
+	zlib, PngmeError, Result,
+};
+
+/// The only compression method defined for `iCCP`: zlib deflate.
+const COMPRESSION_METHOD_DEFLATE: u8 = 0;
+
+/// Every ICC profile has this signature at offset 36 of its 128-byte header.
+const PROFILE_SIGNATURE: &[u8; 4] = b"acsp";
+
+fn invalid(reason: impl Into<String>) -> crate::Error {
+	PngmeError::invalid("ICC profile", reason)
+}
+
+/// A decoded `iCCP` chunk.
+#[derive(Debug, PartialEq, Eq)]
+pub struct IccProfile {
+	name: String,
+	profile: Vec<u8>,
+}
+
+impl IccProfile {
+	pub const TYPE: ChunkType = ChunkType::iCCP;
+
+	/// Creates an `iCCP` chunk, checking the name and the profile header.
+	pub fn new(name: &str, profile: Vec<u8>) -> Result<Self> {
+		validate_keyword(name)?;
+		if profile.get(36..40) != Some(PROFILE_SIGNATURE) {
+			return Err(invalid("missing the 'acsp' profile signature"));
+		}
+		Ok(Self { name: name.to_owned(), profile })
+	}
+
+	/// Finds the profile of `png`, checking that there is only one and that it is in place.
+	pub fn find(png: &Png) -> Result<Option<Self>> {
+		let chunks = png.chunks();
+		let mut positions =
+			chunks.iter().enumerate().filter(|(_, c)| c.chunk_type() == &Self::TYPE);
+		let Some((position, chunk)) = positions.next() else {
+			return Ok(None);
+		};
+		if positions.next().is_some() {
+			return Err(invalid("more than one iCCP chunk"));
+		}
+		let before = [ChunkType::PLTE, ChunkType::IDAT];
+		if chunks[..position].iter().any(|c| before.contains(c.chunk_type())) {
+			return Err(invalid("the iCCP chunk must come before PLTE and IDAT"));
+		}
+		Self::try_from(chunk).map(Some)
+	}
+
+	pub fn name(&self) -> &str {
+		&self.name
+	}
+
+	/// The uncompressed profile, as stored in an `.icc` file.
+	pub fn profile(&self) -> &[u8] {
+		&self.profile
+	}
+
+	/// Compresses the profile and encodes the chunk as a raw [`Chunk`].
+	pub fn to_chunk(&self) -> Chunk {
+		// The name was validated as Latin-1 on construction.
+		let mut data = to_latin1(&self.name).unwrap();
+		data.push(0);
+		data.push(COMPRESSION_METHOD_DEFLATE);
+		data.extend(zlib::compress(&self.profile));
+		Chunk::new(Self::TYPE, data)
+	}
+}
+
+impl TryFrom<&Chunk> for IccProfile {
+	type Error = crate::Error;
+	fn try_from(chunk: &Chunk) -> Result<Self> {
+		if chunk.chunk_type() != &Self::TYPE {
+			return Err(invalid(format!("expected iCCP, got {}", chunk.chunk_type())));
+		}
+		let (name, rest) = split_keyword(chunk.data())?;
+		let (&method, compressed) =
+			rest.split_first().ok_or_else(|| invalid("missing compression method"))?;
+		if method != COMPRESSION_METHOD_DEFLATE {
+			return Err(invalid(format!("unknown compression method {}", method)));
+		}
+		Self::new(&from_latin1(name), zlib::decompress(compressed, zlib::MAX_METADATA)?)
+	}
+}
+
+impl From<IccProfile> for Chunk {
+	fn from(profile: IccProfile) -> Self {
+		profile.to_chunk()
+	}
+}
+
+impl fmt::Display for IccProfile {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "ICC profile \"{}\", {} bytes", self.name, self.profile.len())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn testing_profile() -> Vec<u8> {
+		let mut profile = vec![0; 128];
+		profile[36..40].copy_from_slice(PROFILE_SIGNATURE);
+		profile
+	}
+
+	fn testing_png(chunk_types: &[&str]) -> Png {
+		let chunks = chunk_types
+			.iter()
+			.map(|&chunk_type| match chunk_type {
+				"iCCP" => IccProfile::new("sRGB", testing_profile()).unwrap().to_chunk(),
+				_ => Chunk::new(chunk_type.parse().unwrap(), Vec::new()),
+			})
+			.collect();
+		Png::from_chunks(chunks)
+	}
+
+	#[test]
+	fn test_round_trip() {
+		let profile = IccProfile::new("Display P3", testing_profile()).unwrap();
+		let chunk = profile.to_chunk();
+		assert!(chunk.data().starts_with(b"Display P3\0\0"));
+		assert!(chunk.data().len() < profile.profile().len());
+		assert_eq!(IccProfile::try_from(&chunk).unwrap(), profile);
+	}
+
+	#[test]
+	fn test_invalid_profile() {
+		assert!(IccProfile::new("sRGB", vec![0; 128]).is_err());
+		assert!(IccProfile::new("", testing_profile()).is_err());
+	}
+
+	#[test]
+	fn test_find() {
+		assert_eq!(IccProfile::find(&testing_png(&["IHDR", "IDAT", "IEND"])).unwrap(), None);
+		let png = testing_png(&["IHDR", "iCCP", "PLTE", "IDAT", "IEND"]);
+		assert_eq!(IccProfile::find(&png).unwrap().unwrap().name(), "sRGB");
+		assert!(IccProfile::find(&testing_png(&["IHDR", "iCCP", "iCCP", "IDAT", "IEND"])).is_err());
+		assert!(IccProfile::find(&testing_png(&["IHDR", "PLTE", "iCCP", "IDAT", "IEND"])).is_err());
+		assert!(IccProfile::find(&testing_png(&["IHDR", "IDAT", "iCCP", "IEND"])).is_err());
+	}
+}
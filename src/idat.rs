@@ -0,0 +1,477 @@
+//! The image data stream: the zlib stream split across `IDAT` chunks.
+//!
+//! Once inflated, the stream is a sequence of scanlines, each a filter type byte followed by
+//! the filtered pixel bytes. Interlaced images store the seven Adam7 passes one after another,
+//! each as a reduced image with its own scanlines.
+
+use std::{fmt, ops::Range};
+
+use crate::{chunk_type::ChunkType, ihdr::IhdrData, png::Png, zlib, PngmeError, Result};
+
+/// Names of the scanline filter types, indexed by filter type byte.
+pub const FILTER_NAMES: [&str; 5] = ["None", "Sub", "Up", "Average", "Paeth"];
+
+/// Concatenates the data of all `IDAT` chunks.
+pub fn compressed_data(png: &Png) -> Vec<u8> {
+	png.chunks()
+		.iter()
+		.filter(|chunk| chunk.chunk_type() == &ChunkType::IDAT)
+		.flat_map(|chunk| chunk.data().iter().copied())
+		.collect()
+}
+
+/// Inflates the image data stream, failing if it holds more than the header calls for.
+pub fn decompressed_data(png: &Png) -> Result<Vec<u8>> {
+	zlib::decompress(&compressed_data(png), expected_size(&png.header()?))
+}
+
+/// Replaces the `IDAT` chunks of `png` with a single one holding `compressed`, at the position
+/// of the first of them. This counts as a change to critical chunks.
+pub fn set_compressed_data(png: &mut Png, compressed: Vec<u8>) {
+	png.replace_image_data(compressed, true)
+}
+
+/// Returns the width and height of each pass: all seven Adam7 passes for interlaced images,
+/// including empty ones, or the full image otherwise.
+pub fn passes(header: &IhdrData) -> Vec<(u32, u32)> {
+	grids(header)
+		.into_iter()
+		.map(|(x, y, dx, dy)| {
+			let size = |length: u32, start: u32, step: u32| (length + step - 1 - start) / step;
+			(size(header.width, x, dx), size(header.height, y, dy))
+		})
+		.collect()
+}
+
+/// Returns where the pixels of each pass lie in the full image, as the x start, y start, x step,
+/// and y step.
+fn grids(header: &IhdrData) -> Vec<(u32, u32, u32, u32)> {
+	if !header.is_interlaced() {
+		return vec![(0, 0, 1, 1)];
+	}
+	vec![
+		(0, 0, 8, 8),
+		(4, 0, 8, 8),
+		(0, 4, 4, 8),
+		(2, 0, 4, 4),
+		(0, 2, 2, 4),
+		(1, 0, 2, 2),
+		(0, 1, 1, 2),
+	]
+}
+
+/// Returns the length of a scanline of `width` pixels, without the filter type byte.
+pub fn row_length(header: &IhdrData, width: u32) -> usize {
+	(width as usize * header.bits_per_pixel()).div_ceil(8)
+}
+
+/// Returns where the pixel bytes of each scanline lie in the decompressed image data, that is
+/// each scanline without its filter type byte.
+pub fn rows(header: &IhdrData) -> Vec<Range<usize>> {
+	let mut rows = Vec::new();
+	let mut offset = 0;
+	for (width, height) in passes(header).into_iter().filter(|&(width, _)| width > 0) {
+		let length = row_length(header, width);
+		for _ in 0..height {
+			rows.push(offset + 1..offset + 1 + length);
+			offset += 1 + length;
+		}
+	}
+	rows
+}
+
+/// Lays out the pixels of unfiltered image data row by row over the full image, so that an
+/// image comes out the same whether or not it is interlaced. Rows are not padded, and pixels of
+/// under eight bits get a byte each.
+pub fn pixels(header: &IhdrData, unfiltered: &[u8]) -> Result<Vec<u8>> {
+	if unfiltered.len() != expected_size(header) {
+		let reason = format!("expected {} bytes, got {}", expected_size(header), unfiltered.len());
+		return Err(PngmeError::invalid("image data", reason));
+	}
+	let bits = header.bits_per_pixel();
+	let size = bits.div_ceil(8);
+	let width = header.width as usize;
+	let mut pixels = vec![0; width * header.height as usize * size];
+	let mut rows = rows(header).into_iter();
+	for ((pass_width, pass_height), (x, y, dx, dy)) in passes(header).into_iter().zip(grids(header))
+	{
+		if pass_width == 0 {
+			continue;
+		}
+		for row_y in (0..pass_height).map(|n| (y + n * dy) as usize) {
+			let row = &unfiltered[rows.next().unwrap()];
+			for (n, pixel_x) in (0..pass_width).map(|n| (n as usize, (x + n * dx) as usize)) {
+				let target = (row_y * width + pixel_x) * size;
+				if bits < 8 {
+					let offset = n * bits;
+					let shift = 8 - bits - offset % 8;
+					pixels[target] = row[offset / 8] >> shift & ((1 << bits) - 1);
+				} else {
+					pixels[target..target + size].copy_from_slice(&row[n * size..(n + 1) * size]);
+				}
+			}
+		}
+	}
+	Ok(pixels)
+}
+
+/// Returns the length a decompressed image data stream should have.
+pub fn expected_size(header: &IhdrData) -> usize {
+	passes(header)
+		.into_iter()
+		.filter(|&(width, height)| width > 0 && height > 0)
+		.map(|(width, height)| height as usize * (1 + row_length(header, width)))
+		.sum()
+}
+
+/// How [`filter`] chooses the filter type of each scanline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterStrategy {
+	/// The same filter type, from 0 to 4, for every scanline.
+	Fixed(u8),
+	/// Whichever filter type minimizes the sum of absolute differences, per scanline.
+	Adaptive,
+}
+
+/// Calls `f` with the filter type byte and the rest of each scanline of `data`, together with
+/// the previous scanline of the same pass, if any.
+fn for_each_scanline(
+	header: &IhdrData,
+	data: &mut [u8],
+	mut f: impl FnMut(&mut u8, &mut [u8], Option<&[u8]>) -> Result<()>,
+) -> Result<()> {
+	if data.len() != expected_size(header) {
+		let reason = format!("expected {} bytes, got {}", expected_size(header), data.len());
+		return Err(PngmeError::invalid("image data", reason));
+	}
+	let mut rest = data;
+	for (width, height) in passes(header) {
+		if width == 0 {
+			continue;
+		}
+		let stride = 1 + row_length(header, width);
+		let (pass, tail) = rest.split_at_mut(height as usize * stride);
+		rest = tail;
+		let mut previous: Option<&mut [u8]> = None;
+		for scanline in pass.chunks_exact_mut(stride) {
+			let (filter, row) = scanline.split_first_mut().unwrap();
+			f(filter, row, previous.as_deref())?;
+			previous = Some(row);
+		}
+	}
+	Ok(())
+}
+
+/// Distance in bytes between a byte and the corresponding byte of the previous pixel.
+fn filter_distance(header: &IhdrData) -> usize {
+	header.bits_per_pixel().div_ceil(8)
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+	let p = a as i16 + b as i16 - c as i16;
+	let (pa, pb, pc) = ((p - a as i16).abs(), (p - b as i16).abs(), (p - c as i16).abs());
+	if pa <= pb && pa <= pc {
+		a
+	} else if pb <= pc {
+		b
+	} else {
+		c
+	}
+}
+
+/// Reverses the filter of every scanline of the decompressed image data, leaving each with
+/// filter type 0 (None) and the actual pixel bytes.
+pub fn unfilter(header: &IhdrData, data: &mut [u8]) -> Result<()> {
+	let bpp = filter_distance(header);
+	for_each_scanline(header, data, |filter, row, previous| {
+		let up = |n: usize| previous.map_or(0, |previous| previous[n]);
+		for n in 0..row.len() {
+			let left = if n >= bpp { row[n - bpp] } else { 0 };
+			let upper_left = if n >= bpp { up(n - bpp) } else { 0 };
+			let predictor = match *filter {
+				0 => 0,
+				1 => left,
+				2 => up(n),
+				3 => ((left as u16 + up(n) as u16) / 2) as u8,
+				4 => paeth(left, up(n), upper_left),
+				other => {
+					let reason = format!("unknown filter type {}", other);
+					return Err(PngmeError::invalid("image data", reason));
+				}
+			};
+			row[n] = row[n].wrapping_add(predictor);
+		}
+		*filter = 0;
+		Ok(())
+	})
+}
+
+/// Filters unfiltered image data, as left by [`unfilter`], with the given strategy.
+pub fn filter(header: &IhdrData, data: &mut [u8], strategy: FilterStrategy) -> Result<()> {
+	let bpp = filter_distance(header);
+	let filtered = |filter: u8, row: &[u8], previous: Option<&[u8]>| -> Vec<u8> {
+		let up = |n: usize| previous.map_or(0, |previous| previous[n]);
+		(0..row.len())
+			.map(|n| {
+				let left = if n >= bpp { row[n - bpp] } else { 0 };
+				let upper_left = if n >= bpp { up(n - bpp) } else { 0 };
+				let predictor = match filter {
+					1 => left,
+					2 => up(n),
+					3 => ((left as u16 + up(n) as u16) / 2) as u8,
+					4 => paeth(left, up(n), upper_left),
+					_ => 0,
+				};
+				row[n].wrapping_sub(predictor)
+			})
+			.collect()
+	};
+	// Filtering refers to the unfiltered previous scanline, so keep a copy of it.
+	let mut unfiltered_previous: Option<Vec<u8>> = None;
+	for_each_scanline(header, data, |filter, row, previous| {
+		if *filter != 0 {
+			return Err(PngmeError::invalid("image data", "image data is already filtered"));
+		}
+		if previous.is_none() {
+			unfiltered_previous = None;
+		}
+		let previous = unfiltered_previous.as_deref();
+		let (best, bytes) = match strategy {
+			FilterStrategy::Fixed(filter) if filter <= 4 => {
+				(filter, filtered(filter, row, previous))
+			}
+			FilterStrategy::Fixed(other) => {
+				let reason = format!("unknown filter type {}", other);
+				return Err(PngmeError::invalid("image data", reason));
+			}
+			FilterStrategy::Adaptive => (0..=4)
+				.map(|filter| (filter, filtered(filter, row, previous)))
+				.min_by_key(|(_, bytes)| {
+					bytes.iter().map(|&b| (b as i8).unsigned_abs() as u32).sum::<u32>()
+				})
+				.unwrap(),
+		};
+		unfiltered_previous = Some(row.to_vec());
+		*filter = best;
+		row.copy_from_slice(&bytes);
+		Ok(())
+	})
+}
+
+/// Filters unfiltered image data, with a zero filter type byte before each scanline, and
+/// compresses it into an image data stream for [`set_compressed_data`].
+pub fn compress(header: &IhdrData, mut data: Vec<u8>, strategy: FilterStrategy) -> Result<Vec<u8>> {
+	if data.len() != expected_size(header) {
+		let reason = format!("expected {} bytes, got {}", expected_size(header), data.len());
+		return Err(PngmeError::invalid("image data", reason));
+	}
+	filter(header, &mut data, strategy)?;
+	Ok(zlib::compress(&data))
+}
+
+/// Statistics about the image data stream.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IdatStats {
+	pub chunks: usize,
+	pub compressed_size: usize,
+	pub decompressed_size: usize,
+	pub expected_size: usize,
+	/// How many scanlines use each filter type, indexed like [`FILTER_NAMES`].
+	pub filters: [usize; 5],
+	/// Scanlines whose filter type byte is not a known filter.
+	pub invalid_filters: usize,
+}
+
+impl IdatStats {
+	/// Inflates the image data of `png` and gathers statistics about it.
+	pub fn of(png: &Png) -> Result<Self> {
+		let header = png.header()?;
+		let compressed = compressed_data(png);
+		// Leaves room to report a stream longer than the header calls for.
+		let limit = expected_size(&header).max(zlib::MAX_METADATA);
+		let data = zlib::decompress(&compressed, limit)?;
+		let mut stats = Self {
+			chunks: png.chunks().iter().filter(|c| c.chunk_type() == &ChunkType::IDAT).count(),
+			compressed_size: compressed.len(),
+			decompressed_size: data.len(),
+			expected_size: expected_size(&header),
+			filters: [0; 5],
+			invalid_filters: 0,
+		};
+		let mut offset = 0;
+		for (width, height) in passes(&header) {
+			if width == 0 {
+				continue;
+			}
+			let stride = 1 + row_length(&header, width);
+			for _ in 0..height {
+				match data.get(offset).and_then(|&filter| stats.filters.get_mut(filter as usize)) {
+					Some(count) => *count += 1,
+					None if offset < data.len() => stats.invalid_filters += 1,
+					None => return Ok(stats),
+				}
+				offset += stride;
+			}
+		}
+		Ok(stats)
+	}
+
+	/// Decompressed size divided by compressed size.
+	pub fn compression_ratio(&self) -> f64 {
+		self.decompressed_size as f64 / self.compressed_size.max(1) as f64
+	}
+
+	pub fn scanlines(&self) -> usize {
+		self.filters.iter().sum::<usize>() + self.invalid_filters
+	}
+}
+
+impl fmt::Display for IdatStats {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		writeln!(f, "IDAT chunks:       {}", self.chunks)?;
+		writeln!(f, "Compressed size:   {} bytes", self.compressed_size)?;
+		write!(f, "Decompressed size: {} bytes", self.decompressed_size)?;
+		if self.decompressed_size != self.expected_size {
+			write!(f, " (expected {})", self.expected_size)?;
+		}
+		writeln!(f)?;
+		writeln!(f, "Compression ratio: {:.2}", self.compression_ratio())?;
+		write!(f, "Filters:")?;
+		let scanlines = self.scanlines().max(1) as f64;
+		for (name, &count) in FILTER_NAMES.iter().zip(&self.filters) {
+			write!(f, "\n  {:<8} {:>6} ({:.1}%)", name, count, count as f64 * 100.0 / scanlines)?;
+		}
+		if self.invalid_filters > 0 {
+			write!(f, "\n  {:<8} {:>6}", "invalid", self.invalid_filters)?;
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{chunk::Chunk, ihdr::ColorType};
+
+	fn header(width: u32, height: u32, interlace_method: u8) -> IhdrData {
+		IhdrData {
+			width,
+			height,
+			bit_depth: 8,
+			color_type: ColorType::Rgb,
+			compression_method: 0,
+			filter_method: 0,
+			interlace_method,
+		}
+	}
+
+	#[test]
+	fn test_expected_size() {
+		assert_eq!(expected_size(&header(4, 3, 0)), 3 * (1 + 12));
+		// Passes of an 8x8 image are 1x1, 1x1, 2x1, 2x2, 4x2, 4x4, and 8x4 pixels.
+		assert_eq!(
+			passes(&header(8, 8, 1)),
+			[(1, 1), (1, 1), (2, 1), (2, 2), (4, 2), (4, 4), (8, 4)]
+		);
+		assert_eq!(passes(&header(1, 1, 1))[1], (0, 1));
+		let gray = IhdrData { bit_depth: 1, color_type: ColorType::Grayscale, ..header(9, 2, 0) };
+		assert_eq!(expected_size(&gray), 2 * (1 + 2));
+		assert_eq!(rows(&gray), [1..3, 4..6]);
+	}
+
+	#[test]
+	fn test_filter_round_trip() {
+		for header in [header(5, 4, 0), header(9, 9, 1)] {
+			let mut data: Vec<u8> =
+				(0..expected_size(&header)).map(|n| (n * 37 % 251) as u8).collect();
+			let mut offset = 0;
+			for (width, height) in passes(&header).into_iter().filter(|&(width, _)| width > 0) {
+				for _ in 0..height {
+					data[offset] = 0;
+					offset += 1 + row_length(&header, width);
+				}
+			}
+			let original = data.clone();
+			for strategy in
+				[FilterStrategy::Fixed(1), FilterStrategy::Fixed(4), FilterStrategy::Adaptive]
+			{
+				filter(&header, &mut data, strategy).unwrap();
+				if let FilterStrategy::Fixed(filter) = strategy {
+					assert_eq!(data[0], filter);
+				}
+				unfilter(&header, &mut data).unwrap();
+				assert_eq!(data, original);
+			}
+		}
+	}
+
+	#[test]
+	fn test_compress() {
+		let header = header(2, 2, 0);
+		let data = vec![0, 1, 2, 3, 1, 2, 3, 0, 1, 2, 3, 1, 2, 3];
+		let mut decompressed = zlib::decompress(
+			&compress(&header, data.clone(), FilterStrategy::Adaptive).unwrap(),
+			14,
+		)
+		.unwrap();
+		unfilter(&header, &mut decompressed).unwrap();
+		assert_eq!(decompressed, data);
+		assert!(compress(&header, vec![0; 3], FilterStrategy::Adaptive).is_err());
+	}
+
+	#[test]
+	fn test_unfilter() {
+		let header = IhdrData { color_type: ColorType::Grayscale, ..header(3, 2, 0) };
+		// Sub, then Up.
+		let mut data = vec![1, 10, 1, 1, 2, 5, 5, 5];
+		unfilter(&header, &mut data).unwrap();
+		assert_eq!(data, [0, 10, 11, 12, 0, 15, 16, 17]);
+		assert!(unfilter(&header, &mut [5, 0, 0, 0, 0, 0, 0, 0]).is_err());
+		assert!(unfilter(&header, &mut [0, 0, 0]).is_err());
+	}
+
+	#[test]
+	fn test_pixels() {
+		let gray = |interlace_method| IhdrData {
+			color_type: ColorType::Grayscale,
+			..header(3, 3, interlace_method)
+		};
+		let plain = [0, 0, 1, 2, 0, 10, 11, 12, 0, 20, 21, 22];
+		let expected = [0, 1, 2, 10, 11, 12, 20, 21, 22];
+		assert_eq!(pixels(&gray(0), &plain).unwrap(), expected);
+		// Passes 1, 4, 5, 6, and 7; passes 2 and 3 are empty at this size.
+		let interlaced = [0, 0, 0, 2, 0, 20, 22, 0, 1, 0, 21, 0, 10, 11, 12];
+		assert_eq!(pixels(&gray(1), &interlaced).unwrap(), expected);
+		assert!(pixels(&gray(1), &plain).is_err());
+
+		let bits = IhdrData { bit_depth: 2, ..gray(0) };
+		assert_eq!(
+			pixels(&bits, &[0, 0b00011000, 0, 0b11100000, 0, 0]).unwrap()[..6],
+			[0, 1, 2, 3, 2, 0]
+		);
+	}
+
+	#[test]
+	fn test_stats() {
+		let header = header(2, 3, 0);
+		let mut data = Vec::new();
+		for filter in [0, 1, 4] {
+			data.push(filter);
+			data.extend_from_slice(&[0; 6]);
+		}
+		let compressed = zlib::compress(&data);
+		let (first, second) = compressed.split_at(compressed.len() / 2);
+		let png = Png::from_chunks(vec![
+			header.to_chunk(),
+			Chunk::new(ChunkType::IDAT, first.to_vec()),
+			Chunk::new(ChunkType::IDAT, second.to_vec()),
+			Chunk::new(ChunkType::IEND, Vec::new()),
+		]);
+		let stats = IdatStats::of(&png).unwrap();
+		assert_eq!(stats.chunks, 2);
+		assert_eq!(stats.compressed_size, compressed.len());
+		assert_eq!((stats.decompressed_size, stats.expected_size), (21, 21));
+		assert_eq!(stats.filters, [1, 1, 0, 0, 1]);
+		assert_eq!(stats.invalid_filters, 0);
+	}
+}
@@ -0,0 +1,209 @@
+//! The `IHDR` image header chunk.
+
+use std::fmt;
+
+use crate::{chunk::Chunk, chunk_type::ChunkType, PngmeError, Result};
+
+/// How pixels are laid out in the image data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorType {
+	Grayscale = 0,
+	Rgb = 2,
+	Indexed = 3,
+	GrayscaleAlpha = 4,
+	Rgba = 6,
+}
+
+impl ColorType {
+	/// Returns the number of samples per pixel.
+	pub fn channels(self) -> u8 {
+		match self {
+			Self::Grayscale | Self::Indexed => 1,
+			Self::GrayscaleAlpha => 2,
+			Self::Rgb => 3,
+			Self::Rgba => 4,
+		}
+	}
+
+	/// Returns the bit depths the specification allows for this color type.
+	pub fn allowed_bit_depths(self) -> &'static [u8] {
+		match self {
+			Self::Grayscale => &[1, 2, 4, 8, 16],
+			Self::Indexed => &[1, 2, 4, 8],
+			Self::Rgb | Self::GrayscaleAlpha | Self::Rgba => &[8, 16],
+		}
+	}
+}
+
+impl TryFrom<u8> for ColorType {
+	type Error = crate::Error;
+	fn try_from(value: u8) -> Result<Self> {
+		match value {
+			0 => Ok(Self::Grayscale),
+			2 => Ok(Self::Rgb),
+			3 => Ok(Self::Indexed),
+			4 => Ok(Self::GrayscaleAlpha),
+			6 => Ok(Self::Rgba),
+			_ => Err(invalid(format!("unknown color type {}", value))),
+		}
+	}
+}
+
+impl fmt::Display for ColorType {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(match self {
+			Self::Grayscale => "grayscale",
+			Self::Rgb => "RGB",
+			Self::Indexed => "indexed",
+			Self::GrayscaleAlpha => "grayscale with alpha",
+			Self::Rgba => "RGBA",
+		})
+	}
+}
+
+/// The decoded contents of an `IHDR` chunk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IhdrData {
+	pub width: u32,
+	pub height: u32,
+	pub bit_depth: u8,
+	pub color_type: ColorType,
+	pub compression_method: u8,
+	pub filter_method: u8,
+	pub interlace_method: u8,
+}
+
+fn invalid(reason: impl Into<String>) -> crate::Error {
+	PngmeError::invalid("IHDR chunk", reason)
+}
+
+impl IhdrData {
+	pub const TYPE: [u8; 4] = *b"IHDR";
+	const LENGTH: usize = 13;
+
+	/// Returns `true` if the image is Adam7-interlaced.
+	pub fn is_interlaced(&self) -> bool {
+		self.interlace_method == 1
+	}
+
+	/// Returns the number of bits used by a single pixel.
+	pub fn bits_per_pixel(&self) -> usize {
+		self.color_type.channels() as usize * self.bit_depth as usize
+	}
+
+	/// Encodes the header as a raw [`Chunk`].
+	pub fn to_chunk(&self) -> Chunk {
+		let mut data = Vec::with_capacity(Self::LENGTH);
+		data.extend_from_slice(&self.width.to_be_bytes());
+		data.extend_from_slice(&self.height.to_be_bytes());
+		data.extend_from_slice(&[
+			self.bit_depth,
+			self.color_type as u8,
+			self.compression_method,
+			self.filter_method,
+			self.interlace_method,
+		]);
+		Chunk::new(ChunkType::try_from(Self::TYPE).unwrap(), data)
+	}
+
+	fn validate(self) -> Result<Self> {
+		if self.width == 0 || self.height == 0 {
+			return Err(invalid("image dimensions must be non-zero"));
+		}
+		if !self.color_type.allowed_bit_depths().contains(&self.bit_depth) {
+			return Err(invalid(format!(
+				"bit depth {} is not allowed for {} images",
+				self.bit_depth, self.color_type
+			)));
+		}
+		if self.compression_method != 0 {
+			return Err(invalid(format!("unknown compression method {}", self.compression_method)));
+		}
+		if self.filter_method != 0 {
+			return Err(invalid(format!("unknown filter method {}", self.filter_method)));
+		}
+		if self.interlace_method > 1 {
+			return Err(invalid(format!("unknown interlace method {}", self.interlace_method)));
+		}
+		Ok(self)
+	}
+}
+
+impl TryFrom<&Chunk> for IhdrData {
+	type Error = crate::Error;
+	fn try_from(chunk: &Chunk) -> Result<Self> {
+		if chunk.chunk_type().bytes() != Self::TYPE {
+			return Err(invalid(format!("expected IHDR, got {}", chunk.chunk_type())));
+		}
+		let data = chunk.data();
+		if data.len() != Self::LENGTH {
+			return Err(invalid(format!("expected {} bytes, got {}", Self::LENGTH, data.len())));
+		}
+		Self {
+			width: u32::from_be_bytes(data[0..4].try_into()?),
+			height: u32::from_be_bytes(data[4..8].try_into()?),
+			bit_depth: data[8],
+			color_type: ColorType::try_from(data[9])?,
+			compression_method: data[10],
+			filter_method: data[11],
+			interlace_method: data[12],
+		}
+		.validate()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn testing_ihdr() -> IhdrData {
+		IhdrData {
+			width: 50,
+			height: 40,
+			bit_depth: 8,
+			color_type: ColorType::Rgba,
+			compression_method: 0,
+			filter_method: 0,
+			interlace_method: 0,
+		}
+	}
+
+	#[test]
+	fn test_ihdr_round_trip() {
+		let ihdr = testing_ihdr();
+		let chunk = ihdr.to_chunk();
+		assert_eq!(chunk.length(), 13);
+		assert_eq!(IhdrData::try_from(&chunk).unwrap(), ihdr);
+	}
+
+	#[test]
+	fn test_bits_per_pixel() {
+		assert_eq!(testing_ihdr().bits_per_pixel(), 32);
+	}
+
+	#[test]
+	fn test_invalid_bit_depth() {
+		let ihdr = IhdrData { color_type: ColorType::Rgb, bit_depth: 4, ..testing_ihdr() };
+		assert!(IhdrData::try_from(&ihdr.to_chunk()).is_err());
+	}
+
+	#[test]
+	fn test_invalid_dimensions() {
+		let ihdr = IhdrData { width: 0, ..testing_ihdr() };
+		assert!(IhdrData::try_from(&ihdr.to_chunk()).is_err());
+	}
+
+	#[test]
+	fn test_invalid_length() {
+		let chunk = Chunk::new(ChunkType::try_from(IhdrData::TYPE).unwrap(), vec![0; 12]);
+		assert!(IhdrData::try_from(&chunk).is_err());
+	}
+
+	#[test]
+	fn test_unknown_color_type() {
+		let mut data = testing_ihdr().to_chunk().data().to_vec();
+		data[9] = 5;
+		let chunk = Chunk::new(ChunkType::try_from(IhdrData::TYPE).unwrap(), data);
+		assert!(IhdrData::try_from(&chunk).is_err());
+	}
+}
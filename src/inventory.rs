@@ -0,0 +1,261 @@
+//! Listing the payloads stored in a PNG without decoding their contents.
+//!
+//! Payloads are found in chunks holding a payload envelope, fragments, or the bare data
+//! written by earlier versions; in the standard textual chunks; after `IEND`; and in the
+//! least significant bits of the pixels. Names come from the [`manifest`], if there is one.
+
+use std::collections::BTreeMap;
+
+use crate::{
+	appended,
+	chunk_type::ChunkType,
+	crypto,
+	envelope::{Compression, Encryption, Envelope},
+	fragment,
+	manifest::{self, Manifest},
+	png::Png,
+	stego,
+	text::TextualChunk,
+};
+
+/// Where a payload is stored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Location {
+	/// In one or more chunks of its type.
+	Chunk,
+	/// In a `tEXt`, `zTXt`, or `iTXt` chunk.
+	Text,
+	/// After the `IEND` chunk.
+	AfterIend,
+	/// In the pixels.
+	Lsb,
+}
+
+impl Location {
+	/// Returns the lowercase name of the location.
+	pub fn name(&self) -> &'static str {
+		match self {
+			Self::Chunk => "chunk",
+			Self::Text => "text",
+			Self::AfterIend => "after-iend",
+			Self::Lsb => "lsb",
+		}
+	}
+}
+
+/// What the envelope of a payload says about whether it is intact.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Integrity {
+	/// The payload has no envelope, so there is nothing to check it against.
+	Unchecked,
+	/// The envelope only has a CRC-32, which matches.
+	Checksum,
+	/// The SHA-256 in the envelope matches.
+	Verified,
+	/// The envelope is damaged or does not match the payload.
+	Failed(String),
+}
+
+impl Integrity {
+	/// Returns the lowercase name of the outcome.
+	pub fn name(&self) -> &'static str {
+		match self {
+			Self::Unchecked => "unchecked",
+			Self::Checksum => "checksum",
+			Self::Verified => "verified",
+			Self::Failed(_) => "failed",
+		}
+	}
+}
+
+/// A summary of one payload.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PayloadInfo {
+	pub location: Location,
+	/// The chunk type the payload is stored in or tagged with.
+	pub chunk_type: String,
+	/// The envelope key, or the keyword of a textual chunk.
+	pub key: Option<String>,
+	/// The name of the file or directory the payload came from, if the manifest records it.
+	pub name: Option<String>,
+	/// How many bytes the payload takes up, headers included.
+	pub size: usize,
+	/// How many chunks the payload is split across.
+	pub fragments: usize,
+	/// Whether the payload is wrapped in an [`Envelope`].
+	pub enveloped: bool,
+	pub compression: Compression,
+	pub encryption: Encryption,
+	pub integrity: Integrity,
+}
+
+impl PayloadInfo {
+	/// Describes a payload written by `encode`, with or without an envelope.
+	fn of(location: Location, chunk_type: String, data: &[u8]) -> Self {
+		let mut info = Self {
+			location,
+			chunk_type,
+			key: None,
+			name: None,
+			size: data.len(),
+			fragments: 1,
+			enveloped: false,
+			compression: Compression::None,
+			encryption: Encryption::None,
+			integrity: Integrity::Unchecked,
+		};
+		match Envelope::parse(data) {
+			Ok(Some(envelope)) => {
+				info.key = envelope.key;
+				info.enveloped = true;
+				info.compression = envelope.compression;
+				info.encryption = envelope.encryption;
+				info.integrity =
+					if envelope.hashed { Integrity::Verified } else { Integrity::Checksum };
+			}
+			Err(error) => info.integrity = Integrity::Failed(error.to_string()),
+			Ok(None) if crypto::is_encrypted(data) => info.encryption = Encryption::Passphrase,
+			Ok(None) => {}
+		}
+		info
+	}
+}
+
+/// Lists the payloads in `png`, in file order, followed by the one hidden in the pixels.
+///
+/// Registered public chunks other than the textual ones are not payloads, nor is pngme's own
+/// `siGN` chunk. A trailer that is not made of pngme frames is skipped.
+pub fn list(png: &Png) -> Vec<PayloadInfo> {
+	let mut payloads = Vec::new();
+	let mut fragments: BTreeMap<String, (usize, Vec<&[u8]>)> = BTreeMap::new();
+	for chunk in png.chunks() {
+		let chunk_type = chunk.chunk_type();
+		let data = chunk.data();
+		if let Ok(text) = TextualChunk::try_from(chunk) {
+			let compressed = match &text {
+				TextualChunk::Text(_) => false,
+				TextualChunk::Ztxt(_) => true,
+				TextualChunk::Itxt(itxt) => itxt.is_compressed(),
+			};
+			payloads.push(PayloadInfo {
+				key: Some(text.keyword().to_owned()),
+				compression: if compressed { Compression::Deflate } else { Compression::None },
+				..PayloadInfo::of(Location::Text, chunk_type.to_string(), data)
+			});
+		} else if chunk_type.is_standard()
+			|| chunk_type == &ChunkType::siGN
+			|| chunk_type == &manifest::CHUNK_TYPE
+		{
+			continue;
+		} else if fragment::is_fragment(data) {
+			// Listed in place of the first fragment, once all of them are collected.
+			let (_, pieces) = fragments.entry(chunk_type.to_string()).or_insert_with(|| {
+				payloads.push(PayloadInfo::of(Location::Chunk, chunk_type.to_string(), &[]));
+				(payloads.len() - 1, Vec::new())
+			});
+			pieces.push(data);
+		} else {
+			payloads.push(PayloadInfo::of(Location::Chunk, chunk_type.to_string(), data));
+		}
+	}
+	for (chunk_type, (index, pieces)) in fragments {
+		let payload = fragment::reassemble(pieces.iter().copied()).unwrap_or_default();
+		payloads[index] = PayloadInfo {
+			size: pieces.iter().map(|piece| piece.len()).sum(),
+			fragments: pieces.len(),
+			..PayloadInfo::of(Location::Chunk, chunk_type, &payload)
+		};
+	}
+	for (tag, payload) in appended::frames(png.trailer()).unwrap_or_default() {
+		payloads.push(PayloadInfo::of(Location::AfterIend, tag.to_string(), payload));
+	}
+	if let Ok(Some((tag, payload))) = stego::find(png) {
+		payloads.push(PayloadInfo::of(Location::Lsb, tag.to_string(), &payload));
+	}
+	if let Ok(Some(manifest)) = Manifest::read(png) {
+		for payload in &mut payloads {
+			payload.name = manifest
+				.entries
+				.iter()
+				.find(|entry| {
+					entry.location == payload.location
+						&& entry.chunk_type.to_string() == payload.chunk_type
+						&& entry.key == payload.key
+				})
+				.and_then(|entry| entry.name.clone());
+		}
+	}
+	payloads
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{chunk::Chunk, text::TextChunk};
+
+	fn envelope(key: &str) -> Vec<u8> {
+		let envelope = Envelope {
+			key: Some(key.to_owned()),
+			compression: Compression::Zstd,
+			..Envelope::new(b"body".to_vec())
+		};
+		envelope.to_bytes().unwrap()
+	}
+
+	#[test]
+	fn test_list() {
+		let rust: ChunkType = "ruSt".parse().unwrap();
+		let fragments = fragment::split(&envelope("split"), 20).unwrap();
+		let mut chunks = vec![
+			Chunk::new(ChunkType::IHDR, vec![0; 13]),
+			TextChunk::new("Author", "me").unwrap().to_chunk(),
+			Chunk::new(ChunkType::gAMA, vec![0; 4]),
+		];
+		chunks.extend(fragments.into_iter().map(|f| Chunk::new("frAg".parse().unwrap(), f)));
+		chunks.extend([
+			Chunk::new(ChunkType::pmKy, envelope("notes")),
+			Chunk::new("leGa".parse().unwrap(), b"legacy".to_vec()),
+			Chunk::new(ChunkType::IEND, Vec::new()),
+		]);
+		let mut png = Png::from_chunks(chunks);
+		png.set_trailer(appended::frame(&rust, &envelope("trailer")).unwrap());
+
+		let payloads = list(&png);
+		let summary: Vec<_> = payloads
+			.iter()
+			.map(|p| (p.location, p.chunk_type.as_str(), p.key.as_deref(), p.enveloped))
+			.collect();
+		assert_eq!(
+			summary,
+			[
+				(Location::Text, "tEXt", Some("Author"), false),
+				(Location::Chunk, "frAg", Some("split"), true),
+				(Location::Chunk, "pmKy", Some("notes"), true),
+				(Location::Chunk, "leGa", None, false),
+				(Location::AfterIend, "ruSt", Some("trailer"), true),
+			]
+		);
+		assert!(payloads[1].fragments > 1);
+		assert_eq!(payloads[2].compression, Compression::Zstd);
+		assert_eq!(payloads[3].size, 6);
+		assert_eq!(payloads[2].integrity, Integrity::Verified);
+		assert_eq!(payloads[3].integrity, Integrity::Unchecked);
+	}
+
+	#[test]
+	fn test_integrity() {
+		let unhashed = Envelope { hashed: false, ..Envelope::new(b"body".to_vec()) };
+		let mut tampered = Envelope::new(b"body".to_vec()).to_bytes().unwrap();
+		*tampered.last_mut().unwrap() = b'Y';
+		let png = Png::from_chunks(vec![
+			Chunk::new(ChunkType::IHDR, vec![0; 13]),
+			Chunk::new("olDe".parse().unwrap(), unhashed.to_bytes().unwrap()),
+			Chunk::new("baDd".parse().unwrap(), tampered),
+			Chunk::new(ChunkType::IEND, Vec::new()),
+		]);
+		let payloads = list(&png);
+		assert_eq!(payloads[0].integrity, Integrity::Checksum);
+		assert!(matches!(payloads[1].integrity, Integrity::Failed(_)));
+		assert!(!payloads[1].enveloped);
+	}
+}
@@ -0,0 +1,262 @@
+//! Undoing and redoing edits of a [`Png`].
+//!
+//! A [`Journal`] keeps the operations each edit was made of: the chunks it inserted, removed,
+//! and replaced, and where. Only the chunks an edit touched are kept, so a long session of small
+//! edits to a large image costs little more memory than the image itself.
+
+use crate::{
+	chunk::Chunk,
+	diff::{self, ChunkChange},
+	png::Png,
+};
+
+/// A change to the chunks or trailer of a PNG, with what it undoes kept along.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Operation {
+	/// `chunk` is inserted at `index`.
+	Insert { index: usize, chunk: Chunk },
+	/// `chunk`, at `index`, is removed.
+	Remove { index: usize, chunk: Chunk },
+	/// The chunk at `index` is replaced: `old` by `new`.
+	Replace { index: usize, old: Chunk, new: Chunk },
+	/// The data after `IEND` is replaced: `old` by `new`.
+	Trailer { old: Vec<u8>, new: Vec<u8> },
+}
+
+impl Operation {
+	/// Applies the operation to `png`, as it was when the operation was made.
+	///
+	/// # Panics
+	///
+	/// Panics if an index is past the chunks of `png`.
+	pub fn apply(&self, png: &mut Png) {
+		match self {
+			Self::Insert { index, chunk } => png.insert_chunk_at(*index, chunk.clone()),
+			Self::Remove { index, .. } => {
+				png.remove_chunk_at(*index).expect("the chunk to remove is there");
+			}
+			Self::Replace { index, new, .. } => {
+				png.replace_chunk_at(*index, new.clone());
+			}
+			Self::Trailer { new, .. } => png.set_trailer(new.clone()),
+		}
+	}
+
+	/// The operation that takes this one back.
+	pub fn inverse(&self) -> Self {
+		match self.clone() {
+			Self::Insert { index, chunk } => Self::Remove { index, chunk },
+			Self::Remove { index, chunk } => Self::Insert { index, chunk },
+			Self::Replace { index, old, new } => Self::Replace { index, old: new, new: old },
+			Self::Trailer { old, new } => Self::Trailer { old: new, new: old },
+		}
+	}
+}
+
+/// The operations that turn `old` into `new`, in the order they apply.
+///
+/// Chunks `new` lacks are removed and those it adds inserted, as [`diff::diff`] aligns them; the
+/// chunks then in the place of a different one are replaced.
+pub fn operations(old: &Png, new: &Png) -> Vec<Operation> {
+	if old == new {
+		return Vec::new();
+	}
+	let changes = diff::diff(old, new);
+	let mut operations = Vec::new();
+	// From the last, so that the indices of those before stay put.
+	let mut removed: Vec<usize> = (changes.iter())
+		.filter_map(|change| match change {
+			ChunkChange::Removed { old_index } => Some(*old_index),
+			_ => None,
+		})
+		.collect();
+	removed.sort_unstable();
+	for &index in removed.iter().rev() {
+		operations.push(Operation::Remove { index, chunk: old.chunks()[index].clone() });
+	}
+	let mut added: Vec<usize> = (changes.iter())
+		.filter_map(|change| match change {
+			ChunkChange::Added { new_index } => Some(*new_index),
+			_ => None,
+		})
+		.collect();
+	added.sort_unstable();
+	// The chunks kept and modified, in the order they stay in, with the added ones among them.
+	let mut chunks: Vec<Option<&Chunk>> = (old.chunks().iter().enumerate())
+		.filter(|(index, _)| removed.binary_search(index).is_err())
+		.map(|(_, chunk)| Some(chunk))
+		.collect();
+	for &index in &added {
+		operations.push(Operation::Insert { index, chunk: new.chunks()[index].clone() });
+		chunks.insert(index, None);
+	}
+	for (index, (chunk, new)) in chunks.iter().zip(new.chunks()).enumerate() {
+		if let Some(old) = chunk.filter(|old| *old != new) {
+			operations.push(Operation::Replace { index, old: old.clone(), new: new.clone() });
+		}
+	}
+	if old.trailer() != new.trailer() {
+		operations
+			.push(Operation::Trailer { old: old.trailer().to_vec(), new: new.trailer().to_vec() });
+	}
+	operations
+}
+
+/// The edits made to a PNG, to be undone from the last one on and redone again.
+#[derive(Debug, Clone)]
+pub struct Journal {
+	/// The operations of each edit, the last edit last.
+	done: Vec<Vec<Operation>>,
+	/// The edits undone, the last one undone last.
+	undone: Vec<Vec<Operation>>,
+	/// How many edits were done when the PNG was saved, unless the edits since undid it.
+	saved: Option<usize>,
+}
+
+impl Journal {
+	/// A journal of no edits, of a PNG as it is saved.
+	pub fn new() -> Self {
+		Self { done: Vec::new(), undone: Vec::new(), saved: Some(0) }
+	}
+
+	/// Applies `operations` to `png` as one edit. The edits undone before can no longer be
+	/// redone.
+	pub fn apply(&mut self, png: &mut Png, operations: Vec<Operation>) {
+		for operation in &operations {
+			operation.apply(png);
+		}
+		self.push(operations);
+	}
+
+	/// Records that `old` was edited into `new`, returning `false` if nothing changed. The
+	/// edits undone before can no longer be redone.
+	pub fn record(&mut self, old: &Png, new: &Png) -> bool {
+		let operations = operations(old, new);
+		if operations.is_empty() {
+			return false;
+		}
+		self.push(operations);
+		true
+	}
+
+	fn push(&mut self, operations: Vec<Operation>) {
+		if self.saved.is_some_and(|saved| saved > self.done.len()) {
+			self.saved = None;
+		}
+		self.undone.clear();
+		self.done.push(operations);
+	}
+
+	/// Undoes the last edit of `png`, returning `false` if there is none.
+	pub fn undo(&mut self, png: &mut Png) -> bool {
+		let Some(operations) = self.done.pop() else {
+			return false;
+		};
+		for operation in operations.iter().rev() {
+			operation.inverse().apply(png);
+		}
+		self.undone.push(operations);
+		true
+	}
+
+	/// Redoes the last edit of `png` undone, returning `false` if there is none.
+	pub fn redo(&mut self, png: &mut Png) -> bool {
+		let Some(operations) = self.undone.pop() else {
+			return false;
+		};
+		for operation in &operations {
+			operation.apply(png);
+		}
+		self.done.push(operations);
+		true
+	}
+
+	/// Marks the PNG as it is now as saved.
+	pub fn mark_saved(&mut self) {
+		self.saved = Some(self.done.len());
+	}
+
+	/// Whether the PNG differs from the one last saved, as far as the edits tell.
+	pub fn is_modified(&self) -> bool {
+		self.saved != Some(self.done.len())
+	}
+}
+
+impl Default for Journal {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn chunk(chunk_type: &str, data: &str) -> Chunk {
+		Chunk::new(chunk_type.parse().unwrap(), data.as_bytes().to_vec())
+	}
+
+	fn png(chunks: &[(&str, &str)]) -> Png {
+		Png::from_chunks(chunks.iter().map(|(t, d)| chunk(t, d)).collect())
+	}
+
+	#[test]
+	fn test_operations() {
+		let a = png(&[("IHDR", "a"), ("tEXt", "b"), ("zTXt", "c"), ("IDAT", "d"), ("IEND", "")]);
+		let mut b =
+			png(&[("IHDR", "a"), ("ruSt", "x"), ("tEXt", "B"), ("IDAT", "d"), ("IEND", "")]);
+		b.set_trailer(b"after".to_vec());
+		let operations = operations(&a, &b);
+		assert_eq!(
+			operations,
+			[
+				Operation::Remove { index: 2, chunk: chunk("zTXt", "c") },
+				Operation::Insert { index: 1, chunk: chunk("ruSt", "x") },
+				Operation::Replace { index: 2, old: chunk("tEXt", "b"), new: chunk("tEXt", "B") },
+				Operation::Trailer { old: Vec::new(), new: b"after".to_vec() },
+			]
+		);
+		let mut edited = a.clone();
+		for operation in &operations {
+			operation.apply(&mut edited);
+		}
+		assert_eq!(edited.as_bytes(), b.as_bytes());
+		assert!(super::operations(&a, &a).is_empty());
+	}
+
+	#[test]
+	fn test_undo_redo() {
+		let original = png(&[("IHDR", "a"), ("tEXt", "b"), ("IEND", "")]);
+		let mut png = original.clone();
+		let mut journal = Journal::new();
+		assert!(!journal.is_modified());
+		journal.apply(&mut png, vec![Operation::Insert { index: 2, chunk: chunk("ruSt", "x") }]);
+		let inserted = png.clone();
+		let mut edited = png.clone();
+		edited.remove_chunk_at(1);
+		assert!(journal.record(&png, &edited));
+		assert!(!journal.record(&edited, &edited));
+		png = edited.clone();
+		assert!(journal.is_modified());
+
+		assert!(journal.undo(&mut png));
+		assert_eq!(png.as_bytes(), inserted.as_bytes());
+		assert!(journal.undo(&mut png));
+		assert_eq!(png.as_bytes(), original.as_bytes());
+		assert!(!journal.is_modified());
+		assert!(!journal.undo(&mut png));
+
+		assert!(journal.redo(&mut png));
+		assert!(journal.redo(&mut png));
+		assert_eq!(png.as_bytes(), edited.as_bytes());
+		assert!(!journal.redo(&mut png));
+
+		// A new edit after undoing one drops it, and the saved PNG with it.
+		journal.mark_saved();
+		journal.undo(&mut png);
+		journal.apply(&mut png, vec![Operation::Remove { index: 0, chunk: chunk("IHDR", "a") }]);
+		assert!(!journal.redo(&mut png));
+		journal.undo(&mut png);
+		assert!(journal.is_modified());
+	}
+}
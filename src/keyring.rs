@@ -0,0 +1,132 @@
+//! Public-key payload encryption with age X25519 recipients.
+//!
+//! Payloads are encrypted to one or more `age1...` recipients and stored in the binary age
+//! format; any one of the matching `AGE-SECRET-KEY-1...` identities decrypts them. Identities
+//! are read from identity files as written by `age-keygen`: one key per line, with blank
+//! lines and `#` comments ignored.
+
+use std::{
+	io::{Read, Write},
+	str::FromStr,
+};
+
+pub use age::x25519::{Identity, Recipient};
+
+use crate::{PngmeError, Result};
+
+fn invalid(reason: impl Into<String>) -> crate::Error {
+	PngmeError::invalid("age key", reason)
+}
+
+/// Parses an `age1...` recipient.
+pub fn parse_recipient(recipient: &str) -> Result<Recipient> {
+	Recipient::from_str(recipient.trim()).map_err(|e| invalid(format!("{}: {}", recipient, e)))
+}
+
+/// A set of identities to decrypt payloads with.
+#[derive(Default)]
+pub struct Keyring {
+	identities: Vec<Identity>,
+}
+
+impl Keyring {
+	/// Parses the contents of an identity file.
+	pub fn parse(text: &str) -> Result<Self> {
+		let mut keyring = Self::default();
+		for (n, line) in text.lines().enumerate() {
+			let line = line.trim();
+			if line.is_empty() || line.starts_with('#') {
+				continue;
+			}
+			let identity = Identity::from_str(line)
+				.map_err(|_| invalid(format!("line {} is not an age secret key", n + 1)))?;
+			keyring.add(identity);
+		}
+		if keyring.is_empty() {
+			return Err(invalid("no secret keys in the identity file"));
+		}
+		Ok(keyring)
+	}
+
+	pub fn add(&mut self, identity: Identity) {
+		self.identities.push(identity);
+	}
+
+	pub fn identities(&self) -> &[Identity] {
+		&self.identities
+	}
+
+	pub fn len(&self) -> usize {
+		self.identities.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.identities.is_empty()
+	}
+
+	/// The recipients that payloads for this keyring are encrypted to.
+	pub fn recipients(&self) -> Vec<Recipient> {
+		self.identities.iter().map(Identity::to_public).collect()
+	}
+}
+
+/// Encrypts `plaintext` so that any of `recipients` can decrypt it.
+pub fn encrypt(plaintext: &[u8], recipients: &[Recipient]) -> Result<Vec<u8>> {
+	let recipients = recipients.iter().map(|recipient| recipient as &dyn age::Recipient);
+	let encryptor = age::Encryptor::with_recipients(recipients)?;
+	let mut payload = Vec::new();
+	let mut writer = encryptor.wrap_output(&mut payload)?;
+	writer.write_all(plaintext)?;
+	writer.finish()?;
+	Ok(payload)
+}
+
+/// Decrypts `payload` with whichever identity in `keyring` it was encrypted to.
+pub fn decrypt(payload: &[u8], keyring: &Keyring) -> Result<Vec<u8>> {
+	let decryptor = age::Decryptor::new_buffered(payload)?;
+	let identities = keyring.identities.iter().map(|identity| identity as &dyn age::Identity);
+	let mut plaintext = Vec::new();
+	decryptor.decrypt(identities)?.read_to_end(&mut plaintext)?;
+	Ok(plaintext)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use age::secrecy::ExposeSecret;
+
+	fn identity_file(identities: &[&Identity]) -> String {
+		let mut text = String::from("# created: today\n\n");
+		for identity in identities {
+			text.push_str(identity.to_string().expose_secret());
+			text.push('\n');
+		}
+		text
+	}
+
+	#[test]
+	fn test_round_trip() {
+		let (alice, bob) = (Identity::generate(), Identity::generate());
+		let recipients =
+			[alice.to_public(), parse_recipient(&bob.to_public().to_string()).unwrap()];
+		let payload = encrypt(b"secret message", &recipients).unwrap();
+		let keyring = Keyring::parse(&identity_file(&[&bob])).unwrap();
+		assert_eq!(keyring.len(), 1);
+		assert_eq!(keyring.recipients()[0].to_string(), bob.to_public().to_string());
+		assert_eq!(decrypt(&payload, &keyring).unwrap(), b"secret message");
+	}
+
+	#[test]
+	fn test_wrong_identity() {
+		let payload = encrypt(b"secret message", &[Identity::generate().to_public()]).unwrap();
+		let keyring = Keyring::parse(&identity_file(&[&Identity::generate()])).unwrap();
+		assert!(decrypt(&payload, &keyring).is_err());
+	}
+
+	#[test]
+	fn test_invalid_keys() {
+		assert!(parse_recipient("age1notakey").is_err());
+		assert!(Keyring::parse("# no keys\n").is_err());
+		assert!(Keyring::parse("AGE-SECRET-KEY-1NOTAKEY\n").is_err());
+	}
+}
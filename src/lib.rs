@@ -0,0 +1,68 @@
+//! Hide messages inside PNG files.
+//!
+//! A PNG file is a signature followed by a sequence of [`Chunk`]s, each tagged with a
+//! [`ChunkType`]. Messages are stored in chunks of a custom type, which decoders ignore.
+//!
+//! ```
+//! use pngme::{Chunk, Png};
+//!
+//! let mut png = Png::from_chunks(Vec::new());
+//! png.append_chunk(Chunk::new("ruSt".parse()?, b"hidden message".to_vec()));
+//!
+//! let png = Png::try_from(png.as_bytes().as_slice())?;
+//! assert_eq!(png.chunk_by_type("ruSt").unwrap().data_as_string()?, "hidden message");
+//! # Ok::<(), pngme::Error>(())
+//! ```
+
+pub mod analysis;
+pub mod ancillary;
+pub mod apng;
+pub mod appended;
+pub mod archive;
+pub mod builder;
+pub mod canonical;
+pub mod carve;
+pub mod check;
+pub mod chunk;
+pub mod chunk_type;
+pub mod content_hash;
+pub mod crypto;
+pub mod decoder;
+pub mod diff;
+pub mod ecc;
+pub mod envelope;
+mod error;
+pub mod exif;
+pub mod fragment;
+pub mod hexdump;
+pub mod icc;
+pub mod idat;
+pub mod ihdr;
+pub mod inventory;
+pub mod journal;
+pub mod keyring;
+pub mod manifest;
+pub mod metadata;
+pub mod optimize;
+pub mod ordering;
+pub mod png;
+pub mod repair;
+pub mod scan;
+#[cfg(feature = "serde")]
+mod serialize;
+pub mod signature;
+pub mod stego;
+pub mod text;
+pub mod walk;
+pub mod xmp;
+mod zlib;
+
+pub use builder::PngBuilder;
+pub use chunk::{Chunk, ChunkRef};
+pub use chunk_type::ChunkType;
+pub use error::{PngmeError, Snippet};
+pub use ihdr::IhdrData;
+pub use png::{FileKind, Png, PngRef, PreservationPolicy};
+
+pub type Error = PngmeError;
+pub type Result<T> = std::result::Result<T, Error>;
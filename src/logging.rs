@@ -0,0 +1,67 @@
+//! Messages on stderr, through the `log` crate.
+//!
+//! Errors, warnings, and status messages such as "Removed 3 chunks" are logged rather than
+//! printed, so that `-q` can keep everything but errors quiet and `-v` can add what the
+//! library reports while it works: each chunk read, CRC checks, and the decisions made about
+//! where chunks go and how files are written. With `--log-format json`, every message is a
+//! JSON object on a line of its own.
+
+use std::io::{self, Write};
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use serde_json::json;
+
+use crate::{
+	args::LogFormat,
+	output::{self, Style},
+};
+
+struct Logger {
+	format: LogFormat,
+}
+
+impl Log for Logger {
+	fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+		metadata.level() <= log::max_level()
+	}
+
+	fn log(&self, record: &Record<'_>) {
+		if !self.enabled(record.metadata()) {
+			return;
+		}
+		let line = match self.format {
+			LogFormat::Json => json!({
+				"level": record.level().as_str().to_lowercase(),
+				"target": record.target(),
+				"message": record.args().to_string(),
+			})
+			.to_string(),
+			LogFormat::Text => match record.level() {
+				Level::Error => {
+					format!("{} {}", output::paint_stderr(Style::Error, "Error:"), record.args())
+				}
+				Level::Warn => format!("Warning: {}", record.args()),
+				Level::Info => record.args().to_string(),
+				level => format!("{}: {}", level.as_str().to_lowercase(), record.args()),
+			},
+		};
+		// Nothing is left to report a failure to write to stderr on.
+		let _ = writeln!(io::stderr().lock(), "{}", line);
+	}
+
+	fn flush(&self) {}
+}
+
+/// Installs the logger. Only errors are shown with `quiet`; otherwise warnings and status
+/// messages are, and each `verbose` adds a level of detail.
+pub fn init(verbose: u8, quiet: bool, format: LogFormat) {
+	let level = match verbose {
+		_ if quiet => LevelFilter::Error,
+		0 => LevelFilter::Info,
+		1 => LevelFilter::Debug,
+		_ => LevelFilter::Trace,
+	};
+	if log::set_logger(Box::leak(Box::new(Logger { format }))).is_ok() {
+		log::set_max_level(level);
+	}
+}
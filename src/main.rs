@@ -1,72 +1,191 @@
-use std::{
-	fs::File,
-	io::{Read, Write},
-	path::PathBuf,
+use args::{
+	ApngCommands, Cli, Commands, ExifCommands, IccCommands, IdatCommands, TextCommands, XmpCommands,
 };
+use std::{env, ffi::OsString, io, process::ExitCode};
 
-use chunk::Chunk;
-use clap::{Parser, Subcommand};
-use png::Png;
+use pngme::{FileKind, PngmeError, Result, Snippet};
 
 mod args;
-mod chunk;
-mod chunk_type;
+mod atomic;
 mod commands;
-mod png;
+mod config;
+mod credentials;
+mod logging;
+mod output;
+mod progress;
+mod remote;
+mod shell;
+mod tui;
 
-pub type Error = Box<dyn std::error::Error>;
-pub type Result<T> = std::result::Result<T, Error>;
-
-#[derive(Parser)]
-#[command(author, version, about, long_about = None)]
-struct Cli {
-	#[command(subcommand)]
-	command: Commands,
+/// Exit codes scripts can rely on. Failures not listed, or a batch in which some files
+/// failed, exit with 1.
+mod exit {
+	/// A chunk or message is not in the file.
+	pub const NOT_FOUND: u8 = 2;
+	/// The file cannot be parsed or is damaged.
+	pub const CORRUPT: u8 = 3;
+	/// Reading or writing a file failed.
+	pub const IO: u8 = 4;
+	/// The arguments are malformed or contradict each other.
+	pub const USAGE: u8 = 5;
 }
 
-#[derive(Subcommand)]
-enum Commands {
-	Encode { filename: PathBuf, chunk_type: String, message: String },
-	Decode { filename: PathBuf, chunk_type: String },
-	Remove { filename: PathBuf, chunk_type: String },
-	Print { filename: PathBuf },
+fn main() -> ExitCode {
+	let argv: Vec<OsString> = env::args_os().collect();
+	let cli = Cli::parse_or_exit(&argv);
+	progress::init(cli.progress, cli.quiet);
+	output::init(cli.color);
+	logging::init(cli.verbose, cli.quiet, cli.log_format);
+	if let Some(path) = &cli.config {
+		log::debug!("Read defaults from {}", path.display());
+	}
+	if let Some(profile) = &cli.profile {
+		log::debug!("Using profile {}", profile);
+	}
+	atomic::init(&cli);
+	let read_only = matches!(
+		cli.command,
+		Commands::Print(_)
+			| Commands::Decode(_)
+			| Commands::List(_)
+			| Commands::Info(_)
+			| Commands::Check(_)
+			| Commands::Scan(_)
+	);
+	remote::init(&cli, read_only);
+	let result = match &cli.files {
+		Some(files) => commands::each_file(files, &argv, run),
+		None => run(cli.command),
+	};
+	match result {
+		Ok(()) => ExitCode::SUCCESS,
+		Err(error) => {
+			log::error!("{}", error);
+			diagnose(&error);
+			ExitCode::from(exit_code(&error))
+		}
+	}
 }
 
-fn main() -> Result<()> {
-	let cli = Cli::parse();
-
-	match cli.command {
-		Commands::Encode { filename, chunk_type, message } => {
-			let mut buf = Vec::new();
-			File::open(&filename)?.read_to_end(&mut buf)?;
-			let mut png = Png::try_from(buf.as_slice())?;
-			png.append_chunk(Chunk::new(chunk_type.parse()?, message.as_bytes().to_vec()));
-			File::create(filename)?.write_all(&png.as_bytes())?;
+/// Shows the bytes where parsing failed and suggests what to do about it.
+fn diagnose(error: &PngmeError) {
+	let snippet = match error {
+		PngmeError::InChunk { snippet, .. } => Some(snippet.clone()),
+		PngmeError::InvalidSignature { kind: FileKind::Empty, .. } => None,
+		PngmeError::InvalidSignature { found, kind: FileKind::TruncatedPng } => {
+			// The signature has no zero bytes, so the zeros are padding past the end of the file.
+			let len = found.iter().position(|&byte| byte == 0).unwrap_or(found.len());
+			Some(Snippet { offset: 0, bytes: found[..len].to_vec(), failure: 0 })
+		}
+		PngmeError::InvalidSignature { found, .. } => {
+			Some(Snippet { offset: 0, bytes: found.to_vec(), failure: 0 })
+		}
+		_ => None,
+	};
+	if let Some(snippet) = snippet {
+		for line in snippet.to_string().lines() {
+			log::info!("  {}", line);
 		}
-		Commands::Decode { filename, chunk_type } => {
-			let mut buf = Vec::new();
-			File::open(filename)?.read_to_end(&mut buf)?;
-			if let Some(chunk) = Png::try_from(buf.as_slice())?.chunk_by_type(&chunk_type) {
-				println!("{}", chunk);
-			}
+	}
+	let hint = match error.root() {
+		PngmeError::InvalidSignature { kind: FileKind::Unknown, .. } => {
+			"not a PNG file; `pngme carve` finds PNG files embedded in other data"
+		}
+		PngmeError::InvalidSignature { kind: FileKind::MangledPng, .. } => {
+			"transfer the file again in binary mode"
+		}
+		PngmeError::InvalidSignature { kind: FileKind::HeadlessPng, .. } => {
+			"put the 8-byte PNG signature back in front of the data"
+		}
+		PngmeError::InvalidSignature { kind: FileKind::TruncatedPng, .. } => {
+			"the file is cut off — transfer it again"
 		}
-		Commands::Remove { filename, chunk_type } => {
-			let mut buf = Vec::new();
-			File::open(&filename)?.read_to_end(&mut buf)?;
-			let mut png = Png::try_from(buf.as_slice())?;
-			png.remove_chunk(&chunk_type)?;
-			File::create(filename)?.write_all(&png.as_bytes())?;
+		PngmeError::CrcMismatch { .. } => "CRC mismatch — run `pngme repair` to recompute it",
+		PngmeError::InvalidChunkLength { .. } => {
+			"the file is cut off — run `pngme repair` to drop the partial chunk"
 		}
-		Commands::Print { filename } => {
-			let mut buf = Vec::new();
-			File::open(filename)?.read_to_end(&mut buf)?;
-			for chunk in Png::try_from(buf.as_slice())?.chunks() {
-				if let Ok(string) = chunk.data_as_string() {
-					println!("{}\t{}", chunk.chunk_type(), string);
-				}
-			}
+		PngmeError::InvalidChunkType { .. } => {
+			"this is not a chunk; the file is damaged or has data spliced into it"
 		}
+		_ => return,
+	};
+	log::info!("Hint: {}", hint);
+}
+
+fn exit_code(error: &PngmeError) -> u8 {
+	match error.root() {
+		PngmeError::ChunkNotFound { .. } | PngmeError::NotFound(_) => exit::NOT_FOUND,
+		// A file that ends early is cut off rather than unreadable.
+		PngmeError::Io(error) if error.kind() == io::ErrorKind::UnexpectedEof => exit::CORRUPT,
+		PngmeError::Io(_) => exit::IO,
+		PngmeError::InvalidArgument(_) => exit::USAGE,
+		PngmeError::InvalidSignature { .. }
+		| PngmeError::InvalidChunkType { .. }
+		| PngmeError::InvalidChunkLength { .. }
+		| PngmeError::CrcMismatch { .. }
+		| PngmeError::InvalidData { .. }
+		| PngmeError::InvalidTrailer { .. }
+		| PngmeError::NotEncrypted
+		| PngmeError::DecryptionFailed => exit::CORRUPT,
+		_ => 1,
 	}
+}
 
-	Ok(())
+fn run(command: Commands) -> Result<()> {
+	match command {
+		Commands::Encode(args) => commands::encode(args),
+		Commands::Decode(args) => commands::decode(args),
+		Commands::List(args) => commands::list(args),
+		Commands::Remove(args) => commands::remove(args),
+		Commands::Print(args) => commands::print(args),
+		Commands::Dump(args) => commands::dump(args),
+		Commands::Tui(args) => commands::tui(args),
+		Commands::Edit(args) => commands::edit(args),
+		Commands::Info(args) => commands::info(args),
+		Commands::Check(args) => commands::check(args),
+		Commands::Scan(args) => commands::scan(args),
+		Commands::Capacity(args) => commands::capacity(args),
+		Commands::Sign(args) => commands::sign(args),
+		Commands::VerifySig(args) => commands::verify_sig(args),
+		Commands::Strip(args) => commands::strip(args),
+		Commands::Optimize(args) => commands::optimize(args),
+		Commands::Canonicalize(args) => commands::canonicalize(args),
+		Commands::Hash(args) => commands::hash(args),
+		Commands::Trailer(args) => commands::trailer(args),
+		Commands::Repair(args) => commands::repair(args),
+		Commands::Restore(args) => commands::restore(args),
+		Commands::Carve(args) => commands::carve(args),
+		Commands::Copy(args) => commands::copy(args),
+		Commands::Diff(args) => commands::diff(args),
+		Commands::SameImage(args) => commands::same_image(args),
+		Commands::Export(args) => commands::export(args),
+		Commands::Import(args) => commands::import(args),
+		Commands::Create(args) => commands::create(args),
+		Commands::Pack(args) => commands::pack(args),
+		Commands::Unpack(args) => commands::unpack(args),
+		Commands::Verify(args) => commands::verify(args),
+		Commands::Text { command: TextCommands::Set(args) } => commands::text_set(args),
+		Commands::Text { command: TextCommands::Get(args) } => commands::text_get(args),
+		Commands::Exif { command: ExifCommands::Show(args) } => commands::exif_show(args),
+		Commands::Exif { command: ExifCommands::Strip(args) } => commands::exif_strip(args),
+		Commands::Exif { command: ExifCommands::Import(args) } => commands::exif_import(args),
+		Commands::Icc { command: IccCommands::Extract(args) } => commands::icc_extract(args),
+		Commands::Icc { command: IccCommands::Embed(args) } => commands::icc_embed(args),
+		Commands::Xmp { command: XmpCommands::Get(args) } => commands::xmp_get(args),
+		Commands::Xmp { command: XmpCommands::Set(args) } => commands::xmp_set(args),
+		Commands::Xmp { command: XmpCommands::Strip(args) } => commands::xmp_strip(args),
+		Commands::Apng { command: ApngCommands::Split(args) } => commands::apng_split(args),
+		Commands::Apng { command: ApngCommands::Build(args) } => commands::apng_build(args),
+		Commands::Idat { command: IdatCommands::Info(args) } => commands::idat_info(args),
+		Commands::Batch(args) => commands::batch(args, run),
+		Commands::Dedupe(args) => commands::dedupe(args),
+		Commands::Completions(args) => commands::completions(args),
+		Commands::Man(args) => commands::man(args),
+		Commands::Shell(args) => commands::shell(args, run),
+		Commands::Run(args) => commands::run_script(args, run),
+		Commands::Watch(args) => commands::watch(args, run),
+		Commands::GitFilter(args) => commands::git_filter(args),
+		Commands::GitTextconv(args) => commands::git_textconv(args),
+		Commands::Scrub(args) => commands::scrub(args),
+	}
 }
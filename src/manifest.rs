@@ -0,0 +1,361 @@
+//! The manifest: a chunk listing the payloads pngme stored in a file.
+//!
+//! Once a file holds more than one payload, `encode` and `pack` keep a `pmMf` chunk with the
+//! name, size, checksum, and location of each, and `remove` drops the entries of payloads it
+//! removes, in the same write as the payloads themselves. Readers then know what a file holds,
+//! and whether it changed, without guessing from chunk contents.
+//!
+//! The chunk is laid out as `MAGIC | version (1 byte) | count (u32) | entries`, each entry as
+//! `location (1 byte) | flags (1 byte) | chunk type (4 bytes) | key length (1 byte) | key |
+//! name length (u16) | name | size (u64) | fragments (u32) | checksum (u32)`, with integers
+//! stored big-endian. The checksum is the CRC-32 of the payload as stored, envelope included,
+//! before it was split into fragments.
+
+use std::mem;
+
+use crate::{
+	appended,
+	chunk::{compute_crc, Chunk},
+	chunk_type::ChunkType,
+	envelope::Envelope,
+	fragment,
+	inventory::{self, Location},
+	ordering,
+	png::Png,
+	stego, PngmeError, Result,
+};
+
+const MAGIC: &[u8; 4] = b"PMMF";
+const VERSION: u8 = 1;
+
+/// The chunk type of the manifest.
+pub const CHUNK_TYPE: ChunkType = ChunkType::pmMf;
+
+/// Set if the payload is a directory stored with `pack`.
+const FLAG_ARCHIVE: u8 = 1;
+
+fn invalid(reason: impl Into<String>) -> crate::Error {
+	PngmeError::invalid("manifest", reason)
+}
+
+/// One payload listed in the manifest.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+	pub location: Location,
+	/// The chunk type the payload is stored in or tagged with.
+	pub chunk_type: ChunkType,
+	/// The envelope key, which tells payloads of the same chunk type apart.
+	pub key: Option<String>,
+	/// The name of the file or directory the payload came from.
+	pub name: Option<String>,
+	/// Whether the payload is a directory stored with `pack`.
+	pub archive: bool,
+	/// The size of the payload as stored.
+	pub size: u64,
+	/// How many chunks the payload is split across.
+	pub fragments: u32,
+	pub checksum: u32,
+}
+
+impl Entry {
+	/// Describes `payload`, as stored before being split into `fragments` chunks.
+	pub fn new(
+		location: Location,
+		chunk_type: ChunkType,
+		key: Option<String>,
+		payload: &[u8],
+		fragments: usize,
+	) -> Self {
+		Self {
+			location,
+			chunk_type,
+			key,
+			name: None,
+			archive: false,
+			size: payload.len() as u64,
+			fragments: fragments as u32,
+			checksum: compute_crc(payload),
+		}
+	}
+
+	/// Returns `true` if both entries are for the payload stored in the same place.
+	fn same_place(&self, other: &Entry) -> bool {
+		self.location == other.location
+			&& self.chunk_type == other.chunk_type
+			&& self.key == other.key
+	}
+}
+
+/// What is wrong with one entry of a manifest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Problem {
+	/// The payload is no longer in the file.
+	Missing,
+	/// The payload is there, but not as it was stored.
+	Modified,
+}
+
+/// The list of payloads stored in a file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Manifest {
+	pub entries: Vec<Entry>,
+}
+
+impl Manifest {
+	/// Reads the manifest of `png`, if it has one.
+	pub fn read(png: &Png) -> Result<Option<Self>> {
+		png.chunk_by_type(&CHUNK_TYPE.to_string())
+			.map(|chunk| Self::parse(chunk.data()))
+			.transpose()
+	}
+
+	/// Decodes the data of a manifest chunk.
+	pub fn parse(data: &[u8]) -> Result<Self> {
+		let mut rest = data.strip_prefix(MAGIC).ok_or_else(|| invalid("missing the header"))?;
+		let version = take(&mut rest, 1)?[0];
+		if version != VERSION {
+			return Err(invalid(format!("unsupported version {}", version)));
+		}
+		let count = u32::from_be_bytes(take(&mut rest, mem::size_of::<u32>())?.try_into()?);
+		let mut entries = Vec::new();
+		for _ in 0..count {
+			let location = match take(&mut rest, 1)?[0] {
+				0 => Location::Chunk,
+				1 => Location::AfterIend,
+				2 => Location::Lsb,
+				location => return Err(invalid(format!("unknown location {}", location))),
+			};
+			let flags = take(&mut rest, 1)?[0];
+			let chunk_type = ChunkType::try_from(<[u8; 4]>::try_from(take(&mut rest, 4)?)?)?;
+			let key_len = take(&mut rest, 1)?[0] as usize;
+			let key = String::from_utf8(take(&mut rest, key_len)?.to_vec())?;
+			let name_len = u16::from_be_bytes(take(&mut rest, mem::size_of::<u16>())?.try_into()?);
+			let name = String::from_utf8(take(&mut rest, name_len as usize)?.to_vec())?;
+			let size = u64::from_be_bytes(take(&mut rest, mem::size_of::<u64>())?.try_into()?);
+			let fragments = u32::from_be_bytes(take(&mut rest, mem::size_of::<u32>())?.try_into()?);
+			let checksum = u32::from_be_bytes(take(&mut rest, mem::size_of::<u32>())?.try_into()?);
+			entries.push(Entry {
+				location,
+				chunk_type,
+				key: (!key.is_empty()).then_some(key),
+				name: (!name.is_empty()).then_some(name),
+				archive: flags & FLAG_ARCHIVE != 0,
+				size,
+				fragments,
+				checksum,
+			});
+		}
+		if !rest.is_empty() {
+			return Err(invalid("unexpected data after the entries"));
+		}
+		Ok(Self { entries })
+	}
+
+	/// Encodes the manifest as the data of its chunk.
+	pub fn to_bytes(&self) -> Result<Vec<u8>> {
+		let mut bytes = MAGIC.to_vec();
+		bytes.push(VERSION);
+		bytes.extend_from_slice(&u32::try_from(self.entries.len())?.to_be_bytes());
+		for entry in &self.entries {
+			bytes.push(match entry.location {
+				Location::Chunk => 0,
+				Location::AfterIend => 1,
+				Location::Lsb => 2,
+				Location::Text => return Err(invalid("text chunks are not listed")),
+			});
+			bytes.push(if entry.archive { FLAG_ARCHIVE } else { 0 });
+			bytes.extend_from_slice(&entry.chunk_type.bytes());
+			let key = entry.key.as_deref().unwrap_or_default();
+			bytes.push(u8::try_from(key.len()).map_err(|_| invalid("a key is over 255 bytes"))?);
+			bytes.extend_from_slice(key.as_bytes());
+			let name = entry.name.as_deref().unwrap_or_default();
+			let name_len = u16::try_from(name.len()).map_err(|_| invalid("a name is too long"))?;
+			bytes.extend_from_slice(&name_len.to_be_bytes());
+			bytes.extend_from_slice(name.as_bytes());
+			bytes.extend_from_slice(&entry.size.to_be_bytes());
+			bytes.extend_from_slice(&entry.fragments.to_be_bytes());
+			bytes.extend_from_slice(&entry.checksum.to_be_bytes());
+		}
+		Ok(bytes)
+	}
+
+	/// Returns the entries whose payload is missing from `png` or differs from what was stored.
+	pub fn verify(&self, png: &Png) -> Vec<(&Entry, Problem)> {
+		self.entries
+			.iter()
+			.filter_map(|entry| match stored_payload(png, entry) {
+				None => Some((entry, Problem::Missing)),
+				Some(payload) if compute_crc(&payload) != entry.checksum => {
+					Some((entry, Problem::Modified))
+				}
+				Some(_) => None,
+			})
+			.collect()
+	}
+}
+
+/// Splits the next `len` bytes off `rest`.
+fn take<'a>(rest: &mut &'a [u8], len: usize) -> Result<&'a [u8]> {
+	if rest.len() < len {
+		return Err(invalid("cut off inside an entry"));
+	}
+	let (taken, after) = rest.split_at(len);
+	*rest = after;
+	Ok(taken)
+}
+
+/// Returns the payload an entry describes as it is stored in `png` now, if it is there.
+fn stored_payload(png: &Png, entry: &Entry) -> Option<Vec<u8>> {
+	let matches_key = |data: &[u8]| match &entry.key {
+		Some(key) => Envelope::has_key(data, key),
+		None => true,
+	};
+	match entry.location {
+		Location::Chunk => {
+			let chunk_type = entry.chunk_type.to_string();
+			let mut chunks = png.chunks_by_type(&chunk_type).map(Chunk::data);
+			let first = chunks.next()?;
+			if fragment::is_fragment(first) {
+				let fragments = png
+					.chunks_by_type(&chunk_type)
+					.map(Chunk::data)
+					.filter(|data| fragment::is_fragment(data));
+				fragment::reassemble(fragments).ok().filter(|payload| matches_key(payload))
+			} else {
+				[first].into_iter().chain(chunks).find(|data| matches_key(data)).map(<[u8]>::to_vec)
+			}
+		}
+		Location::AfterIend => appended::frames(png.trailer())
+			.ok()?
+			.into_iter()
+			.find(|(tag, payload)| tag == &entry.chunk_type && matches_key(payload))
+			.map(|(_, payload)| payload.to_vec()),
+		Location::Lsb => stego::extract(png, &entry.chunk_type).ok()?.filter(|p| matches_key(p)),
+		Location::Text => None,
+	}
+}
+
+/// Brings the manifest of `png` up to date after its payloads changed, listing `added` in place
+/// of any entry for the same place and dropping the entries of payloads that are gone.
+///
+/// A file without a manifest gets one once it holds more than one payload, listing the payloads
+/// already there too. A manifest left without entries is removed.
+pub fn update(png: &mut Png, added: Option<Entry>) -> Result<()> {
+	let existing = Manifest::read(png)?;
+	let had_manifest = existing.is_some();
+	let mut manifest = existing.unwrap_or_default();
+	manifest.entries.retain(|entry| {
+		added.as_ref().is_none_or(|added| !added.same_place(entry))
+			&& stored_payload(png, entry).is_some()
+	});
+	if !had_manifest {
+		for payload in inventory::list(png) {
+			let Ok(chunk_type) = payload.chunk_type.parse() else { continue };
+			if payload.location == Location::Text {
+				continue;
+			}
+			let place =
+				Entry::new(payload.location, chunk_type, payload.key, &[], payload.fragments);
+			if added.as_ref().is_some_and(|added| added.same_place(&place)) {
+				continue;
+			}
+			if let Some(stored) = stored_payload(png, &place) {
+				let checksum = compute_crc(&stored);
+				manifest.entries.push(Entry { size: stored.len() as u64, checksum, ..place });
+			}
+		}
+	}
+	manifest.entries.extend(added);
+
+	let chunk_type = CHUNK_TYPE.to_string();
+	if !had_manifest && manifest.entries.len() < 2 {
+		return Ok(());
+	}
+	if manifest.entries.is_empty() {
+		png.remove_chunks(&chunk_type);
+		return Ok(());
+	}
+	let chunk = Chunk::new(CHUNK_TYPE, manifest.to_bytes()?);
+	if had_manifest {
+		png.replace_chunk(&chunk_type, chunk)?;
+	} else {
+		let position = ordering::default_position(png, &CHUNK_TYPE);
+		png.insert_chunk_at(position, chunk);
+	}
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn png_with(payloads: &[(&str, &[u8])]) -> Png {
+		let mut chunks = vec![Chunk::new(ChunkType::IHDR, vec![0; 13])];
+		for (chunk_type, data) in payloads {
+			chunks.push(Chunk::new(chunk_type.parse().unwrap(), data.to_vec()));
+		}
+		chunks.push(Chunk::new(ChunkType::IEND, Vec::new()));
+		Png::from_chunks(chunks)
+	}
+
+	fn add(png: &mut Png, chunk_type: &str, data: &[u8]) -> Entry {
+		let position = png.chunks().len() - 1;
+		png.insert_chunk_at(position, Chunk::new(chunk_type.parse().unwrap(), data.to_vec()));
+		Entry::new(Location::Chunk, chunk_type.parse().unwrap(), None, data, 1)
+	}
+
+	#[test]
+	fn test_round_trip() {
+		let mut entry = Entry::new(Location::AfterIend, ChunkType::pmKy, Some("k".into()), b"x", 1);
+		entry.name = Some("notes.txt".into());
+		entry.archive = true;
+		let manifest = Manifest { entries: vec![entry] };
+		assert_eq!(Manifest::parse(&manifest.to_bytes().unwrap()).unwrap(), manifest);
+		assert!(Manifest::parse(b"PMMF\x01\0\0\0\x01").is_err());
+	}
+
+	#[test]
+	fn test_created_for_second_payload() {
+		let mut png = png_with(&[]);
+		let first = add(&mut png, "ruSt", b"one");
+		update(&mut png, Some(first)).unwrap();
+		assert!(Manifest::read(&png).unwrap().is_none());
+
+		let mut second = add(&mut png, "otHr", b"two");
+		second.name = Some("two.txt".into());
+		update(&mut png, Some(second.clone())).unwrap();
+		let manifest = Manifest::read(&png).unwrap().unwrap();
+		let types: Vec<String> =
+			manifest.entries.iter().map(|e| e.chunk_type.to_string()).collect();
+		assert_eq!(types, ["ruSt", "otHr"]);
+		assert_eq!(manifest.entries[1], second);
+		assert!(manifest.verify(&png).is_empty());
+	}
+
+	#[test]
+	fn test_pruned_and_verified() {
+		let mut png = png_with(&[]);
+		for (chunk_type, data) in [("ruSt", b"one"), ("otHr", b"two"), ("thRd", b"333")] {
+			let entry = add(&mut png, chunk_type, data);
+			update(&mut png, Some(entry)).unwrap();
+		}
+		assert_eq!(Manifest::read(&png).unwrap().unwrap().entries.len(), 3);
+
+		png.remove_chunk("otHr").unwrap();
+		png.replace_chunk("thRd", Chunk::new("thRd".parse().unwrap(), b"444".to_vec())).unwrap();
+		let manifest = Manifest::read(&png).unwrap().unwrap();
+		let problems: Vec<_> =
+			manifest.verify(&png).into_iter().map(|(e, p)| (e.chunk_type.to_string(), p)).collect();
+		assert_eq!(
+			problems,
+			[("otHr".into(), Problem::Missing), ("thRd".into(), Problem::Modified)]
+		);
+
+		update(&mut png, None).unwrap();
+		assert_eq!(Manifest::read(&png).unwrap().unwrap().entries.len(), 2);
+		png.remove_chunk("ruSt").unwrap();
+		png.remove_chunk("thRd").unwrap();
+		update(&mut png, None).unwrap();
+		assert!(png.chunk_by_type("pmMf").is_none());
+	}
+}
@@ -0,0 +1,82 @@
+//! Telling apart kinds of metadata chunks.
+//!
+//! Volatile metadata changes whenever a file is saved, whether or not the image did: the `tIME`
+//! chunk and text chunks recording when and with what the file was written.
+//!
+//! Private metadata tells who made an image, and where and when: EXIF, which may hold a GPS
+//! location, XMP, the `tIME` chunk, and text chunks naming the author or the tool used.
+
+use crate::{chunk::Chunk, chunk_type::ChunkType, exif::ExifChunk, text::TextualChunk, xmp};
+
+/// Keywords of text chunks that image tools fill in on every save.
+pub const VOLATILE_KEYWORDS: [&str; 5] =
+	["Creation Time", "Software", "date:create", "date:modify", "date:timestamp"];
+
+/// Whether `chunk` is volatile metadata, which changes on every save.
+pub fn is_volatile(chunk: &Chunk) -> bool {
+	chunk.chunk_type() == &ChunkType::tIME
+		|| TextualChunk::try_from(chunk)
+			.is_ok_and(|text| VOLATILE_KEYWORDS.contains(&text.keyword()))
+}
+
+/// Keywords of text chunks that tell who made an image, or when and with what.
+pub const PRIVATE_KEYWORDS: [&str; 11] = [
+	"Author",
+	"Artist",
+	"Comment",
+	"Source",
+	"Creation Time",
+	"Software",
+	"date:create",
+	"date:modify",
+	"date:timestamp",
+	"Raw profile type exif",
+	"Raw profile type xmp",
+];
+
+/// What `chunk` tells about who made an image, and where and when, or `None` if it is not
+/// private metadata.
+pub fn private_data(chunk: &Chunk) -> Option<String> {
+	if chunk.chunk_type() == &ChunkType::eXIf {
+		let gps = ExifChunk::try_from(chunk)
+			.and_then(|exif| exif.tags())
+			.is_ok_and(|tags| tags.iter().any(|tag| tag.is_gps()));
+		return Some(if gps { "EXIF with a GPS location" } else { "EXIF" }.to_string());
+	}
+	if chunk.chunk_type() == &ChunkType::tIME {
+		return Some("modification time".to_string());
+	}
+	let text = TextualChunk::try_from(chunk).ok()?;
+	match text.keyword() {
+		xmp::KEYWORD => Some("XMP".to_string()),
+		keyword if PRIVATE_KEYWORDS.contains(&keyword) => Some(format!("{:?} text", keyword)),
+		_ => None,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::text::{ItxtChunk, TextChunk};
+
+	#[test]
+	fn test_is_volatile() {
+		let text = |keyword: &str| TextChunk::new(keyword, "x").unwrap().to_chunk();
+		assert!(is_volatile(&Chunk::new(ChunkType::tIME, vec![7, 232, 1, 2, 3, 4, 5])));
+		assert!(is_volatile(&text("date:modify")) && is_volatile(&text("Software")));
+		assert!(!is_volatile(&text("Author")));
+		assert!(!is_volatile(&Chunk::new(ChunkType::IDAT, Vec::new())));
+	}
+
+	#[test]
+	fn test_private_data() {
+		let text = |keyword: &str| TextChunk::new(keyword, "x").unwrap().to_chunk();
+		assert_eq!(private_data(&text("Author")).as_deref(), Some("\"Author\" text"));
+		assert_eq!(private_data(&text("Title")), None);
+		let xmp = ItxtChunk::new(xmp::KEYWORD, "", "", "<x:xmpmeta/>", false).unwrap();
+		assert_eq!(private_data(&xmp.to_chunk()).as_deref(), Some("XMP"));
+		let time = Chunk::new(ChunkType::tIME, vec![7, 232, 1, 2, 3, 4, 5]);
+		assert_eq!(private_data(&time).as_deref(), Some("modification time"));
+		assert_eq!(private_data(&Chunk::new(ChunkType::IDAT, Vec::new())), None);
+	}
+}
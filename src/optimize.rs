@@ -0,0 +1,160 @@
+//! Lossless size optimization: re-deflating the image data, merging `IDAT` chunks, and
+//! dropping ancillary chunks that do not affect the image.
+
+use crate::{
+	chunk::Chunk,
+	chunk_type::ChunkType,
+	idat::{self, FilterStrategy},
+	ihdr::ColorType,
+	png::Png,
+	zlib, Result,
+};
+
+/// Ancillary chunks that may appear at most once; later copies are ignored by decoders.
+const SINGLE_CHUNKS: &[&[u8; 4]] = &[
+	b"cHRM", b"gAMA", b"iCCP", b"sBIT", b"sRGB", b"cICP", b"mDCV", b"cLLI", b"bKGD", b"hIST",
+	b"tRNS", b"pHYs", b"tIME", b"eXIf", b"acTL",
+];
+
+/// Chunks holding metadata about the image rather than information needed to display it.
+const METADATA_CHUNKS: &[&[u8; 4]] = &[b"tEXt", b"zTXt", b"iTXt", b"tIME", b"eXIf"];
+
+/// What [`optimize`] does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OptimizeOptions {
+	/// The zlib compression level, from 0 to 9.
+	pub level: u32,
+	/// Refilter the image data with every filter strategy and keep the smallest result,
+	/// instead of keeping the existing filters.
+	pub try_filters: bool,
+	/// Also remove textual chunks, `tIME`, and `eXIf`.
+	pub strip_metadata: bool,
+}
+
+impl Default for OptimizeOptions {
+	fn default() -> Self {
+		Self { level: 9, try_filters: false, strip_metadata: false }
+	}
+}
+
+/// Optimizes `png` in place. The image data is only replaced if the new stream is smaller,
+/// but it always ends up in a single `IDAT` chunk. Private chunks are never removed.
+pub fn optimize(png: &mut Png, options: &OptimizeOptions) -> Result<()> {
+	let original = idat::compressed_data(png);
+	let mut data = idat::decompressed_data(png)?;
+	let mut best = zlib::compress_with_level(&data, options.level);
+	if options.try_filters {
+		let header = png.header()?;
+		idat::unfilter(&header, &mut data)?;
+		let strategies = (0..=4).map(FilterStrategy::Fixed).chain([FilterStrategy::Adaptive]);
+		for strategy in strategies {
+			let mut filtered = data.clone();
+			idat::filter(&header, &mut filtered, strategy)?;
+			let compressed = zlib::compress_with_level(&filtered, options.level);
+			if compressed.len() < best.len() {
+				best = compressed;
+			}
+		}
+	}
+	// The pixels stay the same, so chunks that are unsafe to copy remain valid.
+	png.replace_image_data(if best.len() < original.len() { best } else { original }, false);
+
+	let is_indexed = png.header()?.color_type == ColorType::Indexed;
+	let mut seen: Vec<ChunkType> = Vec::new();
+	png.retain_chunks(|chunk: &Chunk| {
+		let chunk_type = chunk.chunk_type();
+		let bytes = chunk_type.bytes();
+		if SINGLE_CHUNKS.contains(&&bytes) {
+			if seen.contains(chunk_type) {
+				return false;
+			}
+			seen.push(ChunkType::try_from(bytes).unwrap());
+		}
+		// The histogram only describes palette entries.
+		if &bytes == b"hIST" && !is_indexed {
+			return false;
+		}
+		!(options.strip_metadata && METADATA_CHUNKS.contains(&&bytes))
+	});
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::ihdr::IhdrData;
+
+	fn testing_png() -> Png {
+		let header = IhdrData {
+			width: 16,
+			height: 16,
+			bit_depth: 8,
+			color_type: ColorType::Rgb,
+			compression_method: 0,
+			filter_method: 0,
+			interlace_method: 0,
+		};
+		let mut data = Vec::new();
+		for y in 0..16u8 {
+			data.push(0);
+			data.extend((0..16u8).flat_map(|x| [x * 16, y * 16, x ^ y]));
+		}
+		let compressed = zlib::compress_with_level(&data, 0);
+		let (first, second) = compressed.split_at(100);
+		Png::from_chunks(vec![
+			header.to_chunk(),
+			Chunk::new(ChunkType::gAMA, 45455u32.to_be_bytes().to_vec()),
+			Chunk::new(ChunkType::gAMA, 100000u32.to_be_bytes().to_vec()),
+			Chunk::new(ChunkType::hIST, vec![0, 1]),
+			Chunk::new(ChunkType::IDAT, first.to_vec()),
+			Chunk::new(ChunkType::IDAT, second.to_vec()),
+			Chunk::new(ChunkType::tEXt, b"Author\0me".to_vec()),
+			Chunk::new("ruSt".parse().unwrap(), b"message".to_vec()),
+			Chunk::new(ChunkType::IEND, Vec::new()),
+		])
+	}
+
+	fn types(png: &Png) -> Vec<String> {
+		png.chunks().iter().map(|chunk| chunk.chunk_type().to_string()).collect()
+	}
+
+	#[test]
+	fn test_optimize() {
+		let mut png = testing_png();
+		let before = idat::decompressed_data(&png).unwrap();
+		let size = png.as_bytes().len();
+		optimize(&mut png, &OptimizeOptions::default()).unwrap();
+		assert!(png.as_bytes().len() < size);
+		assert_eq!(idat::decompressed_data(&png).unwrap(), before);
+		assert_eq!(types(&png), ["IHDR", "gAMA", "IDAT", "tEXt", "ruSt", "IEND"]);
+		assert_eq!(png.chunk_by_type("gAMA").unwrap().data(), 45455u32.to_be_bytes());
+	}
+
+	#[test]
+	fn test_keeps_unsafe_to_copy_chunks() {
+		let mut png = testing_png();
+		png.set_preservation_policy(crate::PreservationPolicy::Drop);
+		png.insert_chunk(Chunk::new(ChunkType::sRGB, vec![0]));
+		optimize(&mut png, &OptimizeOptions::default()).unwrap();
+		assert!(png.chunk_by_type("sRGB").is_some());
+	}
+
+	#[test]
+	fn test_try_filters_and_strip_metadata() {
+		let mut plain = testing_png();
+		optimize(&mut plain, &OptimizeOptions::default()).unwrap();
+		let mut png = testing_png();
+		let options =
+			OptimizeOptions { try_filters: true, strip_metadata: true, ..Default::default() };
+		optimize(&mut png, &options).unwrap();
+		assert!(png.as_bytes().len() < plain.as_bytes().len());
+		assert_eq!(types(&png), ["IHDR", "gAMA", "IDAT", "ruSt", "IEND"]);
+
+		let mut data = idat::decompressed_data(&png).unwrap();
+		let mut expected = idat::decompressed_data(&testing_png()).unwrap();
+		let header = png.header().unwrap();
+		idat::unfilter(&header, &mut data).unwrap();
+		idat::unfilter(&header, &mut expected).unwrap();
+		assert_eq!(data, expected);
+	}
+}
@@ -0,0 +1,168 @@
+//! Chunk ordering rules from the PNG specification.
+//!
+//! Positions are insertion indices into a PNG's chunk list: inserting at position `n` puts the
+//! new chunk before the chunk currently at index `n`, and position `len` appends it.
+
+use std::ops::RangeInclusive;
+
+use crate::{chunk::Chunk, chunk_type::ChunkType, png::Png};
+
+/// Where a chunk type may appear relative to the critical chunks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Placement {
+	/// `IHDR`: the very first chunk.
+	First,
+	/// `IEND`: the very last chunk.
+	Last,
+	/// `PLTE`: after `IHDR`, before the first `IDAT`.
+	Palette,
+	/// `IDAT`: next to the other `IDAT` chunks.
+	ImageData,
+	/// `cHRM`, `gAMA`, `iCCP`, `sBIT`, `sRGB`, ...: before `PLTE` and `IDAT`.
+	BeforePalette,
+	/// `bKGD`, `hIST`, `tRNS`: after `PLTE`, before `IDAT`.
+	AfterPalette,
+	/// `pHYs`, `sPLT`, `tIME`, `eXIf`, `acTL`, `oFFs`: before `IDAT`.
+	BeforeImageData,
+	/// Everything else: anywhere between `IHDR` and `IEND`.
+	Anywhere,
+}
+
+impl Placement {
+	pub fn of(chunk_type: &ChunkType) -> Self {
+		match &chunk_type.bytes() {
+			b"IHDR" => Self::First,
+			b"IEND" => Self::Last,
+			b"PLTE" => Self::Palette,
+			b"IDAT" => Self::ImageData,
+			b"cHRM" | b"gAMA" | b"iCCP" | b"sBIT" | b"sRGB" | b"cICP" | b"mDCV" | b"cLLI" => {
+				Self::BeforePalette
+			}
+			b"bKGD" | b"hIST" | b"tRNS" => Self::AfterPalette,
+			b"pHYs" | b"sPLT" | b"tIME" | b"eXIf" | b"acTL" | b"oFFs" => Self::BeforeImageData,
+			_ => Self::Anywhere,
+		}
+	}
+}
+
+/// Returns the range of positions at which a chunk of `chunk_type` may be inserted into `png`
+/// without breaking the ordering rules.
+///
+/// If `png` already breaks the rules so that no position is legal, the range holds only the
+/// position closest to the end of the file.
+pub fn legal_positions(png: &Png, chunk_type: &ChunkType) -> RangeInclusive<usize> {
+	let types: Vec<&ChunkType> = png.chunks().iter().map(Chunk::chunk_type).collect();
+	positions_among(&types, chunk_type)
+}
+
+fn positions_among(chunks: &[&ChunkType], chunk_type: &ChunkType) -> RangeInclusive<usize> {
+	let first = |name: &[u8; 4]| chunks.iter().position(|c| &c.bytes() == name);
+	let last = |name: &[u8; 4]| chunks.iter().rposition(|c| &c.bytes() == name);
+
+	let after_ihdr = last(b"IHDR").map_or(0, |n| n + 1);
+	let before_iend = first(b"IEND").unwrap_or(chunks.len());
+	let before_idat = first(b"IDAT").unwrap_or(before_iend);
+	let before_plte = first(b"PLTE").unwrap_or(before_idat);
+
+	let (start, end) = match Placement::of(chunk_type) {
+		Placement::First => (0, 0),
+		Placement::Last => (chunks.len(), chunks.len()),
+		Placement::Palette => (after_ihdr, before_idat),
+		Placement::ImageData => match (first(b"IDAT"), last(b"IDAT")) {
+			(Some(first), Some(last)) => (first, last + 1),
+			_ => (last(b"PLTE").map_or(after_ihdr, |n| n + 1), before_iend),
+		},
+		Placement::BeforePalette => (after_ihdr, before_plte.min(before_idat)),
+		Placement::AfterPalette => (last(b"PLTE").map_or(after_ihdr, |n| n + 1), before_idat),
+		Placement::BeforeImageData => (after_ihdr, before_idat),
+		Placement::Anywhere => (after_ihdr, before_iend),
+	};
+	start.min(end)..=end
+}
+
+/// Returns `true` if a chunk of `chunk_type` may be inserted at `position`.
+pub fn is_legal_position(png: &Png, chunk_type: &ChunkType, position: usize) -> bool {
+	legal_positions(png, chunk_type).contains(&position)
+}
+
+/// Returns the indices of the chunks in `png` that break the ordering rules, i.e. that would
+/// not be allowed where they are if the rest of the file stayed as it is.
+pub fn misplaced_chunks(png: &Png) -> Vec<usize> {
+	let types: Vec<&ChunkType> = png.chunks().iter().map(Chunk::chunk_type).collect();
+	(0..types.len())
+		.filter(|&n| {
+			let mut others = types.clone();
+			let chunk_type = others.remove(n);
+			!positions_among(&others, chunk_type).contains(&n)
+		})
+		.collect()
+}
+
+/// Returns the position at which new chunks of `chunk_type` are inserted by default: the
+/// latest legal one, so that new chunks end up as close to where appending would put them.
+pub fn default_position(png: &Png, chunk_type: &ChunkType) -> usize {
+	*legal_positions(png, chunk_type).end()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn png(types: &[&str]) -> Png {
+		Png::from_chunks(types.iter().map(|t| Chunk::new(t.parse().unwrap(), Vec::new())).collect())
+	}
+
+	fn positions(png: &Png, chunk_type: &str) -> RangeInclusive<usize> {
+		legal_positions(png, &chunk_type.parse().unwrap())
+	}
+
+	#[test]
+	fn test_placement_of() {
+		assert_eq!(Placement::of(&"IHDR".parse().unwrap()), Placement::First);
+		assert_eq!(Placement::of(&"gAMA".parse().unwrap()), Placement::BeforePalette);
+		assert_eq!(Placement::of(&"tEXt".parse().unwrap()), Placement::Anywhere);
+		assert_eq!(Placement::of(&"ruSt".parse().unwrap()), Placement::Anywhere);
+	}
+
+	#[test]
+	fn test_legal_positions() {
+		let png = png(&["IHDR", "gAMA", "PLTE", "tRNS", "IDAT", "IDAT", "tEXt", "IEND"]);
+		assert_eq!(positions(&png, "IHDR"), 0..=0);
+		assert_eq!(positions(&png, "cHRM"), 1..=2);
+		assert_eq!(positions(&png, "bKGD"), 3..=4);
+		assert_eq!(positions(&png, "pHYs"), 1..=4);
+		assert_eq!(positions(&png, "IDAT"), 4..=6);
+		assert_eq!(positions(&png, "tEXt"), 1..=7);
+		assert_eq!(positions(&png, "IEND"), 8..=8);
+	}
+
+	#[test]
+	fn test_legal_positions_without_palette() {
+		let png = png(&["IHDR", "IDAT", "IEND"]);
+		assert_eq!(positions(&png, "gAMA"), 1..=1);
+		assert_eq!(positions(&png, "PLTE"), 1..=1);
+		assert_eq!(positions(&png, "ruSt"), 1..=2);
+	}
+
+	#[test]
+	fn test_default_position() {
+		let png = png(&["IHDR", "IDAT", "IEND"]);
+		assert_eq!(default_position(&png, &"tEXt".parse().unwrap()), 2);
+		assert_eq!(default_position(&png, &"pHYs".parse().unwrap()), 1);
+		assert!(!is_legal_position(&png, &"ruSt".parse().unwrap(), 3));
+		assert!(!is_legal_position(&png, &"ruSt".parse().unwrap(), 0));
+	}
+
+	#[test]
+	fn test_misplaced_chunks() {
+		assert!(misplaced_chunks(&png(&["IHDR", "gAMA", "IDAT", "tEXt", "IEND"])).is_empty());
+		assert_eq!(misplaced_chunks(&png(&["IHDR", "IDAT", "gAMA", "IEND"])), [2]);
+		assert_eq!(misplaced_chunks(&png(&["IHDR", "IDAT", "IEND", "tEXt"])), [2, 3]);
+	}
+
+	#[test]
+	fn test_legal_positions_in_broken_file() {
+		let png = png(&["IDAT", "IHDR", "IEND"]);
+		assert_eq!(positions(&png, "gAMA"), 0..=0);
+	}
+}
@@ -0,0 +1,141 @@
+//! Standard output that `batch` can capture per file, and colors for it.
+//!
+//! Commands print through [`outln!`] and [`out!`] rather than `println!`, so that jobs
+//! running side by side in a batch each collect their output and it is printed in file order
+//! instead of interleaved. Warnings and errors still go straight to stderr.
+//!
+//! Text is colored through [`paint`] and [`paint_stderr`], which leave it plain unless colors
+//! are enabled for that stream: with `--color=always`, or by default when the stream is a
+//! terminal and `NO_COLOR` is not set.
+
+use std::{
+	cell::RefCell,
+	env, fmt,
+	io::{self, IsTerminal, Write},
+	sync::atomic::{AtomicBool, Ordering},
+};
+
+use pngme::ChunkType;
+
+use crate::args::ColorChoice;
+
+static STDOUT_COLOR: AtomicBool = AtomicBool::new(false);
+static STDERR_COLOR: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+	static CAPTURED: RefCell<Option<Vec<u8>>> = const { RefCell::new(None) };
+}
+
+/// Prints to stdout, or to the buffer of the [`capture`] running on this thread.
+pub fn write_fmt(args: fmt::Arguments<'_>) {
+	CAPTURED.with_borrow_mut(|captured| match captured {
+		Some(buffer) => buffer.write_fmt(args).expect("writing to a Vec cannot fail"),
+		None => print!("{}", args),
+	});
+}
+
+/// Runs `f`, returning what it printed along with its result.
+pub fn capture<T>(f: impl FnOnce() -> T) -> (T, Vec<u8>) {
+	let previous = CAPTURED.replace(Some(Vec::new()));
+	let result = f();
+	let captured = CAPTURED.replace(previous).unwrap_or_default();
+	(result, captured)
+}
+
+/// Decides once whether stdout and stderr are colored.
+pub fn init(choice: ColorChoice) {
+	let no_color = env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+	let enabled = |terminal: bool| match choice {
+		ColorChoice::Always => true,
+		ColorChoice::Never => false,
+		ColorChoice::Auto => terminal && !no_color,
+	};
+	STDOUT_COLOR.store(enabled(io::stdout().is_terminal()), Ordering::Relaxed);
+	STDERR_COLOR.store(enabled(io::stderr().is_terminal()), Ordering::Relaxed);
+}
+
+/// What a piece of text is, which decides its color.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Style {
+	Critical,
+	Ancillary,
+	Private,
+	Error,
+	Added,
+	Removed,
+	Modified,
+}
+
+impl Style {
+	/// The style of a chunk type: private chunks stand out from the standard ancillary ones.
+	pub fn of(chunk_type: &ChunkType) -> Self {
+		if chunk_type.is_critical() {
+			Self::Critical
+		} else if chunk_type.is_public() {
+			Self::Ancillary
+		} else {
+			Self::Private
+		}
+	}
+
+	/// The SGR parameters of the style.
+	fn code(self) -> &'static str {
+		match self {
+			Self::Critical => "1",
+			Self::Ancillary => "36",
+			Self::Private => "35",
+			Self::Error => "1;31",
+			Self::Added => "32",
+			Self::Removed => "31",
+			Self::Modified => "33",
+		}
+	}
+}
+
+/// Text that is shown in a style when colors are enabled.
+pub struct Painted<T> {
+	text: T,
+	style: Option<Style>,
+}
+
+impl<T: fmt::Display> fmt::Display for Painted<T> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self.style {
+			Some(style) => write!(f, "\x1b[{}m{}\x1b[0m", style.code(), self.text),
+			None => self.text.fmt(f),
+		}
+	}
+}
+
+/// Whether text printed to stdout is colored.
+pub fn is_colored() -> bool {
+	STDOUT_COLOR.load(Ordering::Relaxed)
+}
+
+/// Styles text printed to stdout.
+pub fn paint<T: fmt::Display>(style: Style, text: T) -> Painted<T> {
+	Painted { text, style: STDOUT_COLOR.load(Ordering::Relaxed).then_some(style) }
+}
+
+/// Styles text printed to stderr.
+pub fn paint_stderr<T: fmt::Display>(style: Style, text: T) -> Painted<T> {
+	Painted { text, style: STDERR_COLOR.load(Ordering::Relaxed).then_some(style) }
+}
+
+macro_rules! out {
+	($($arg:tt)*) => {
+		$crate::output::write_fmt(format_args!($($arg)*))
+	};
+}
+
+macro_rules! outln {
+	() => {
+		$crate::output::write_fmt(format_args!("\n"))
+	};
+	($($arg:tt)*) => {
+		$crate::output::write_fmt(format_args!("{}\n", format_args!($($arg)*)))
+	};
+}
+
+pub(crate) use out;
+pub(crate) use outln;
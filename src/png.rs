@@ -1,89 +1,632 @@
-use std::{fmt, mem, str::FromStr};
+use std::{
+	cmp,
+	collections::HashMap,
+	fmt,
+	hash::{Hash, Hasher},
+	io::{self, Read, Write},
+	mem,
+	str::FromStr,
+};
 
-use crate::{chunk::Chunk, chunk_type::ChunkType, Error, Result};
+use memmap2::Mmap;
 
+use crate::{
+	chunk::{Chunk, ChunkRef, DataPreview},
+	chunk_type::ChunkType,
+	ihdr::IhdrData,
+	ordering, Error, PngmeError, Result,
+};
+
+/// What happens to ancillary chunks that are not safe to copy once critical chunks change.
+///
+/// The specification says such chunks depend on the critical ones, so an editor that adds,
+/// modifies, or removes critical chunks must not carry them over unchanged.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PreservationPolicy {
+	/// Leave them in place.
+	#[default]
+	Keep,
+	/// Remove them as soon as a critical chunk changes.
+	Drop,
+	/// Leave them in place, but list them in [`Png::flagged_chunks`].
+	Warn,
+}
+
+/// What a file looks like from its first bytes; see [`Png::sniff`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum FileKind {
+	Png,
+	/// A PNG whose signature was damaged in transfer: line endings converted between `\r\n`
+	/// and `\n`, or the high bit of the first byte stripped, as text-mode transfers do.
+	MangledPng,
+	/// PNG chunks starting with `IHDR`, with the signature missing.
+	HeadlessPng,
+	/// The first bytes of a PNG signature, with the file ending before the rest.
+	TruncatedPng,
+	Jpeg,
+	Gif,
+	WebP,
+	Bmp,
+	Empty,
+	Unknown,
+}
+
+/// Names the kind of file with an article, as in "a JPEG image".
+impl fmt::Display for FileKind {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(match self {
+			Self::Png => "a PNG file",
+			Self::MangledPng => "a PNG file with a mangled signature",
+			Self::HeadlessPng => "a PNG file without its signature",
+			Self::TruncatedPng => "a PNG file cut off inside its signature",
+			Self::Jpeg => "a JPEG image",
+			Self::Gif => "a GIF image",
+			Self::WebP => "a WebP image",
+			Self::Bmp => "a BMP image",
+			Self::Empty => "an empty file",
+			Self::Unknown => "not an image pngme recognizes",
+		})
+	}
+}
+
+/// A PNG file represented as its sequence of chunks, plus any bytes that follow `IEND`.
+///
+/// PNGs compare and hash by their chunks and trailer, not by their preservation policy.
+#[derive(Clone)]
 pub struct Png {
 	chunks: Vec<Chunk>,
+	/// The positions in `chunks` of the chunks of each type, in order, so that looking up a
+	/// chunk type does not scan every chunk.
+	index: HashMap<[u8; 4], Vec<usize>>,
+	trailer: Vec<u8>,
+	policy: PreservationPolicy,
+	critical_changed: bool,
 }
 
 impl Png {
-	const STANDARD_HEADER: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
+	pub const STANDARD_HEADER: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
 
+	/// The fewest leading bytes [`Png::sniff`] needs to tell every [`FileKind`] apart.
+	pub const SNIFF_LEN: usize = 12;
+
+	/// Guesses what kind of file `bytes` is from its first [`Png::SNIFF_LEN`] bytes.
+	pub fn sniff(bytes: &[u8]) -> FileKind {
+		if bytes.is_empty() {
+			FileKind::Empty
+		} else if bytes.starts_with(&Self::STANDARD_HEADER) {
+			FileKind::Png
+		} else if Self::STANDARD_HEADER.starts_with(bytes) {
+			FileKind::TruncatedPng
+		} else if bytes.starts_with(b"\x89PNG") || bytes.starts_with(b"\x09PNG\r\n\x1a\n") {
+			FileKind::MangledPng
+		} else if bytes.starts_with(&[0, 0, 0, 13]) && bytes.get(4..8) == Some(b"IHDR") {
+			FileKind::HeadlessPng
+		} else if bytes.starts_with(&[0xff, 0xd8, 0xff]) {
+			FileKind::Jpeg
+		} else if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+			FileKind::Gif
+		} else if bytes.starts_with(b"RIFF") && bytes.get(8..12) == Some(b"WEBP") {
+			FileKind::WebP
+		} else if bytes.starts_with(b"BM") {
+			FileKind::Bmp
+		} else {
+			FileKind::Unknown
+		}
+	}
+
+	/// Creates a PNG from a list of chunks.
 	pub fn from_chunks(chunks: Vec<Chunk>) -> Self {
-		Self { chunks }
+		let mut png = Self {
+			chunks,
+			index: HashMap::new(),
+			trailer: Vec::new(),
+			policy: PreservationPolicy::default(),
+			critical_changed: false,
+		};
+		png.reindex();
+		png
+	}
+
+	/// Rebuilds the index after chunks moved.
+	fn reindex(&mut self) {
+		self.index.clear();
+		for (n, chunk) in self.chunks.iter().enumerate() {
+			self.index.entry(chunk.chunk_type().bytes()).or_default().push(n);
+		}
+	}
+
+	/// Adds the chunk just inserted at `index` to the index, moving the chunks after it up.
+	fn index_inserted(&mut self, index: usize) {
+		for positions in self.index.values_mut() {
+			positions.iter_mut().filter(|n| **n >= index).for_each(|n| *n += 1);
+		}
+		self.index_position(index);
+	}
+
+	/// Takes the chunk of type `chunk_type` just removed from `index` out of the index, moving
+	/// the chunks after it down.
+	fn index_removed(&mut self, index: usize, chunk_type: &ChunkType) {
+		self.unindex_position(index, chunk_type);
+		for positions in self.index.values_mut() {
+			positions.iter_mut().filter(|n| **n > index).for_each(|n| *n -= 1);
+		}
+	}
+
+	/// Moves the chunk at `index` in the index from `old_type` to its type, which replaced it.
+	fn index_retyped(&mut self, index: usize, old_type: &ChunkType) {
+		self.unindex_position(index, old_type);
+		self.index_position(index);
+	}
+
+	fn index_position(&mut self, index: usize) {
+		let positions = self.index.entry(self.chunks[index].chunk_type().bytes()).or_default();
+		positions.insert(positions.partition_point(|&n| n < index), index);
+	}
+
+	fn unindex_position(&mut self, index: usize, chunk_type: &ChunkType) {
+		let bytes = chunk_type.bytes();
+		let positions = self.index.get_mut(&bytes).expect("the chunk is indexed");
+		positions.retain(|&n| n != index);
+		if positions.is_empty() {
+			self.index.remove(&bytes);
+		}
+	}
+
+	/// The positions of the chunks of the given type.
+	fn positions(&self, chunk_type: &ChunkType) -> &[usize] {
+		self.index.get(&chunk_type.bytes()).map_or(&[], Vec::as_slice)
+	}
+
+	/// Sets how later mutations treat chunks that are not safe to copy.
+	pub fn set_preservation_policy(&mut self, policy: PreservationPolicy) {
+		self.policy = policy
 	}
 
+	/// Returns the ancillary chunks that are not safe to copy but were kept after critical
+	/// chunks changed. Always empty unless the policy is [`PreservationPolicy::Warn`].
+	pub fn flagged_chunks(&self) -> impl Iterator<Item = &Chunk> {
+		let flag = self.critical_changed && self.policy == PreservationPolicy::Warn;
+		self.chunks.iter().filter(move |chunk| flag && is_unsafe_to_copy(chunk))
+	}
+
+	/// Applies the preservation policy after a chunk was added or removed.
+	fn changed(&mut self, critical: bool) {
+		if !critical {
+			return;
+		}
+		self.critical_changed = true;
+		if self.policy == PreservationPolicy::Drop {
+			self.chunks.retain(|chunk| {
+				let unsafe_to_copy = is_unsafe_to_copy(chunk);
+				if unsafe_to_copy {
+					log::debug!("dropping {} chunk, which is not safe to copy", chunk.chunk_type());
+				}
+				!unsafe_to_copy
+			});
+			self.reindex();
+		}
+	}
+
+	/// Appends a chunk to the end of the PNG.
 	pub fn append_chunk(&mut self, chunk: Chunk) {
-		self.chunks.push(chunk)
+		let critical = chunk.chunk_type().is_critical();
+		self.index.entry(chunk.chunk_type().bytes()).or_default().push(self.chunks.len());
+		self.chunks.push(chunk);
+		self.changed(critical);
+	}
+
+	/// Inserts a chunk at `index`, shifting all chunks after it.
+	///
+	/// # Panics
+	///
+	/// Panics if `index` is greater than the number of chunks.
+	pub fn insert_chunk_at(&mut self, index: usize, chunk: Chunk) {
+		let critical = chunk.chunk_type().is_critical();
+		self.chunks.insert(index, chunk);
+		self.index_inserted(index);
+		self.changed(critical);
 	}
 
+	/// Inserts `chunk` at the latest position the PNG chunk ordering rules allow for its type.
+	pub fn insert_chunk(&mut self, chunk: Chunk) {
+		let index = ordering::default_position(self, chunk.chunk_type());
+		self.insert_chunk_at(index, chunk)
+	}
+
+	/// Removes and returns the first chunk of the given type.
 	pub fn remove_chunk(&mut self, chunk_type: &str) -> Result<Chunk> {
 		let chunk_type = ChunkType::from_str(chunk_type)?;
+		let Some(&index) = self.positions(&chunk_type).first() else {
+			return Err(PngmeError::ChunkNotFound { chunk_type });
+		};
+		let chunk = self.chunks.remove(index);
+		self.index_removed(index, &chunk_type);
+		self.changed(chunk.chunk_type().is_critical());
+		Ok(chunk)
+	}
 
-		let index = self
-			.chunks
-			.iter()
-			.enumerate()
-			.find_map(|(n, chunk)| (chunk.chunk_type() == &chunk_type).then_some(n))
-			.unwrap();
+	/// Puts `chunk` in the place of the first chunk of the given type, returning the old one.
+	pub fn replace_chunk(&mut self, chunk_type: &str, chunk: Chunk) -> Result<Chunk> {
+		let chunk_type = ChunkType::from_str(chunk_type)?;
+		let Some(&index) = self.positions(&chunk_type).first() else {
+			return Err(PngmeError::ChunkNotFound { chunk_type });
+		};
+		let critical = chunk.chunk_type().is_critical() || chunk_type.is_critical();
+		let same_type = chunk.chunk_type() == &chunk_type;
+		let old = mem::replace(&mut self.chunks[index], chunk);
+		if !same_type {
+			self.index_retyped(index, &chunk_type);
+		}
+		self.changed(critical);
+		Ok(old)
+	}
 
-		Ok(self.chunks.remove(index))
+	/// Puts `chunk` in the place of the chunk at `index`, returning the old one.
+	///
+	/// # Panics
+	///
+	/// Panics if `index` is not less than the number of chunks.
+	pub fn replace_chunk_at(&mut self, index: usize, chunk: Chunk) -> Chunk {
+		let critical =
+			chunk.chunk_type().is_critical() || self.chunks[index].chunk_type().is_critical();
+		let old = mem::replace(&mut self.chunks[index], chunk);
+		if old.chunk_type() != self.chunks[index].chunk_type() {
+			self.index_retyped(index, old.chunk_type());
+		}
+		self.changed(critical);
+		old
 	}
 
-	pub fn header(&self) -> &[u8; 8] {
+	/// Removes and returns every chunk of the given type.
+	pub fn remove_chunks(&mut self, chunk_type: &str) -> Vec<Chunk> {
+		let Ok(chunk_type) = ChunkType::from_str(chunk_type) else {
+			return Vec::new();
+		};
+		if self.positions(&chunk_type).is_empty() {
+			return Vec::new();
+		}
+		let removed = self.remove_all(&chunk_type);
+		self.changed(chunk_type.is_critical());
+		removed
+	}
+
+	/// Removes every chunk of the given type, moving each chunk left in the index down by the
+	/// number of chunks removed before it.
+	fn remove_all(&mut self, chunk_type: &ChunkType) -> Vec<Chunk> {
+		let Some(removed) = self.index.remove(&chunk_type.bytes()) else {
+			return Vec::new();
+		};
+		for positions in self.index.values_mut() {
+			positions.iter_mut().for_each(|n| *n -= removed.partition_point(|&r| r < *n));
+		}
+		let (removed, kept) = mem::take(&mut self.chunks)
+			.into_iter()
+			.partition(|chunk| chunk.chunk_type() == chunk_type);
+		self.chunks = kept;
+		removed
+	}
+
+	/// Removes and returns the chunk at `index`, if there is one.
+	pub fn remove_chunk_at(&mut self, index: usize) -> Option<Chunk> {
+		let chunk = (index < self.chunks.len()).then(|| self.chunks.remove(index))?;
+		self.index_removed(index, chunk.chunk_type());
+		self.changed(chunk.chunk_type().is_critical());
+		Some(chunk)
+	}
+
+	/// Keeps only the chunks for which `f` returns `true`, preserving their order.
+	pub fn retain_chunks(&mut self, mut f: impl FnMut(&Chunk) -> bool) {
+		let mut removed_critical = false;
+		self.chunks.retain(|chunk| {
+			let keep = f(chunk);
+			removed_critical |= !keep && chunk.chunk_type().is_critical();
+			keep
+		});
+		self.reindex();
+		self.changed(removed_critical);
+	}
+
+	/// Reorders the chunks by `compare`, keeping chunks that compare equal in their order.
+	pub fn sort_chunks_by(&mut self, compare: impl FnMut(&Chunk, &Chunk) -> cmp::Ordering) {
+		self.chunks.sort_by(compare);
+		self.reindex();
+	}
+
+	/// Replaces the `IDAT` chunks with a single one holding `compressed`, at the position of
+	/// the first of them. Re-encoding the same pixels does not count as a change to critical
+	/// chunks for the preservation policy, so callers say whether the pixels changed.
+	pub(crate) fn replace_image_data(&mut self, compressed: Vec<u8>, pixels_changed: bool) {
+		let position = self.positions(&ChunkType::IDAT).first().copied();
+		self.remove_all(&ChunkType::IDAT);
+		let position =
+			position.unwrap_or_else(|| ordering::default_position(self, &ChunkType::IDAT));
+		self.chunks.insert(position, Chunk::new(ChunkType::IDAT, compressed));
+		self.index_inserted(position);
+		self.changed(pixels_changed);
+	}
+
+	/// Returns the PNG file signature.
+	pub fn signature(&self) -> &[u8; 8] {
 		&Self::STANDARD_HEADER
 	}
 
+	/// Decodes the image header from the IHDR chunk.
+	pub fn header(&self) -> Result<IhdrData> {
+		let chunk = self
+			.chunks_by_type("IHDR")
+			.next()
+			.ok_or(PngmeError::ChunkNotFound { chunk_type: ChunkType::IHDR })?;
+		IhdrData::try_from(chunk)
+	}
+
+	/// Returns `true` if the PNG is animated, which an `acTL` chunk before `IDAT` signals.
+	pub fn is_apng(&self) -> bool {
+		self.chunks
+			.iter()
+			.take_while(|chunk| chunk.chunk_type() != &ChunkType::IDAT)
+			.any(|chunk| chunk.chunk_type() == &ChunkType::acTL)
+	}
+
+	/// Returns the bytes after the `IEND` chunk, which decoders ignore.
+	pub fn trailer(&self) -> &[u8] {
+		&self.trailer
+	}
+
+	/// Replaces the bytes written after the last chunk.
+	pub fn set_trailer(&mut self, trailer: Vec<u8>) {
+		self.trailer = trailer
+	}
+
+	/// Removes and returns the bytes after the last chunk.
+	pub fn take_trailer(&mut self) -> Vec<u8> {
+		mem::take(&mut self.trailer)
+	}
+
+	/// Returns all chunks in file order.
 	pub fn chunks(&self) -> &[Chunk] {
 		&self.chunks
 	}
 
+	/// Returns every chunk along with the byte offset it will have when the PNG is written.
+	///
+	/// For a PNG that was parsed and not modified since, these match [`Chunk::offset`].
+	pub fn chunks_with_offsets(&self) -> impl Iterator<Item = (u64, &Chunk)> {
+		self.chunks.iter().scan(Self::STANDARD_HEADER.len() as u64, |offset, chunk| {
+			let chunk_offset = *offset;
+			*offset += chunk.length() as u64 + mem::size_of::<[u32; 3]>() as u64;
+			Some((chunk_offset, chunk))
+		})
+	}
+
+	/// Returns the first chunk of the given type, if any.
 	pub fn chunk_by_type(&self, chunk_type: &str) -> Option<&Chunk> {
-		let chunk_type = ChunkType::from_str(chunk_type).ok()?;
-		self.chunks.iter().find(|chunk| chunk.chunk_type() == &chunk_type)
+		self.chunks_by_type(chunk_type).next()
+	}
+
+	/// Returns every chunk of the given type, in file order.
+	pub fn chunks_by_type(&self, chunk_type: &str) -> impl Iterator<Item = &Chunk> {
+		let positions = match ChunkType::from_str(chunk_type) {
+			Ok(chunk_type) => self.positions(&chunk_type),
+			Err(_) => &[],
+		};
+		positions.iter().map(|&n| &self.chunks[n])
 	}
 
+	/// Serializes the PNG, signature included.
 	pub fn as_bytes(&self) -> Vec<u8> {
-		self.header()
+		self.signature()
 			.iter()
 			.copied()
 			.chain(self.chunks().iter().flat_map(Chunk::as_bytes))
+			.chain(self.trailer.iter().copied())
 			.collect()
 	}
-}
 
-#[derive(Debug)]
-struct InvalidPngHeader {
-	header: [u8; 8],
-}
-impl std::error::Error for InvalidPngHeader {}
-impl fmt::Display for InvalidPngHeader {
-	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-		write!(
-			f,
-			"Invalid PNG header: expected {:x?}, got {:x?}",
-			Png::STANDARD_HEADER,
-			self.header
-		)
+	/// Parses a PNG from `reader` one chunk at a time.
+	pub fn from_reader(reader: impl Read) -> Result<Self> {
+		Self::read_chunks(reader, |bytes| Chunk::try_from(bytes))
 	}
+
+	/// Parses a PNG from `reader` like [`Png::from_reader`], keeping chunks whose CRC does not
+	/// match instead of failing; see [`Chunk::has_valid_crc`].
+	pub fn from_reader_lenient(reader: impl Read) -> Result<Self> {
+		Self::read_chunks(reader, Chunk::try_from_lenient)
+	}
+
+	/// Parses a PNG from `bytes` like [`Png::try_from`], tolerating bad CRCs.
+	pub fn try_from_lenient(bytes: &[u8]) -> Result<Self> {
+		PngRef::try_from_lenient(bytes).map(PngRef::into_png)
+	}
+
+	/// Parses a memory-mapped PNG file, reading only the chunk headers.
+	///
+	/// The chunks borrow their data from `map`, so the pages holding a payload are only read
+	/// from disk when it is used. CRCs are not checked either, as that reads every byte; see
+	/// [`ChunkRef::verify_crc`].
+	pub fn from_mmap(map: &Mmap) -> Result<PngRef<'_>> {
+		PngRef::try_from_lenient(map)
+	}
+
+	fn read_chunks(mut reader: impl Read, parse_chunk: fn(&[u8]) -> Result<Chunk>) -> Result<Self> {
+		// A few bytes past the signature tell formats such as WebP apart.
+		let mut start = Vec::with_capacity(Self::SNIFF_LEN);
+		(&mut reader).take(Self::STANDARD_HEADER.len() as u64).read_to_end(&mut start)?;
+		if start != Self::STANDARD_HEADER {
+			(&mut reader).take((Self::SNIFF_LEN - start.len()) as u64).read_to_end(&mut start)?;
+			return Err(PngmeError::signature(&start));
+		}
+		let mut chunks = Vec::new();
+		let mut offset = start.len() as u64;
+		let mut trailer = Vec::new();
+		for index in 0.. {
+			let Some(bytes) = Chunk::read_bytes(&mut reader)? else { break };
+			let chunk = parse_chunk(&bytes).map_err(|e| e.in_chunk(index, offset, &bytes))?;
+			log::debug!(
+				"{} chunk at {:#010x}, length {}",
+				chunk.chunk_type(),
+				offset,
+				chunk.length()
+			);
+			let len = chunk.length() as u64 + mem::size_of::<[u32; 3]>() as u64;
+			let is_iend = chunk.chunk_type() == &ChunkType::IEND;
+			chunks.push(chunk.with_offset(offset));
+			offset += len;
+			if is_iend {
+				reader.read_to_end(&mut trailer)?;
+				if !trailer.is_empty() {
+					log::debug!("{} bytes after IEND", trailer.len());
+				}
+				break;
+			}
+		}
+		Ok(Self { trailer, ..Self::from_chunks(chunks) })
+	}
+
+	/// Writes the PNG to `writer` one chunk at a time, signature included.
+	pub fn write_to(&self, mut writer: impl Write) -> io::Result<()> {
+		writer.write_all(self.signature())?;
+		for chunk in &self.chunks {
+			chunk.write_to(&mut writer)?;
+		}
+		writer.write_all(&self.trailer)
+	}
+}
+
+fn is_unsafe_to_copy(chunk: &Chunk) -> bool {
+	!chunk.chunk_type().is_critical() && !chunk.chunk_type().is_safe_to_copy()
 }
 
 impl TryFrom<&[u8]> for Png {
 	type Error = Error;
 	fn try_from(bytes: &[u8]) -> Result<Self> {
-		let (header, mut bytes) = bytes.split_at(Self::STANDARD_HEADER.len());
-		if header != Self::STANDARD_HEADER {
-			return Err(InvalidPngHeader { header: header.try_into()? }.into());
-		}
+		PngRef::try_from(bytes).map(PngRef::into_png)
+	}
+}
+
+/// A PNG parsed in place, its chunks borrowing their data from the buffer it was parsed from.
+///
+/// This is how [`Png::try_from`] parses; use it directly to look at a PNG without copying its
+/// chunks, and [`PngRef::into_png`] to edit it.
+#[derive(Clone)]
+pub struct PngRef<'a> {
+	chunks: Vec<ChunkRef<'a>>,
+	trailer: &'a [u8],
+}
+
+impl<'a> PngRef<'a> {
+	/// Returns all chunks in file order.
+	pub fn chunks(&self) -> &[ChunkRef<'a>] {
+		&self.chunks
+	}
+
+	/// Returns the first chunk of the given type, if any.
+	pub fn chunk_by_type(&self, chunk_type: &str) -> Option<&ChunkRef<'a>> {
+		let chunk_type = ChunkType::from_str(chunk_type).ok()?;
+		self.chunks.iter().find(|chunk| chunk.chunk_type() == &chunk_type)
+	}
+
+	/// Returns the bytes after the last chunk.
+	pub fn trailer(&self) -> &'a [u8] {
+		self.trailer
+	}
+
+	/// Returns the byte offset of the trailer in the file.
+	pub fn trailer_offset(&self) -> u64 {
+		self.chunks.last().map_or(Png::STANDARD_HEADER.len() as u64, |chunk| {
+			chunk.offset().unwrap_or_default() + chunk.length() as u64 + 12
+		})
+	}
+
+	/// Returns the chunks, for turning some of them into owned [`Chunk`]s.
+	pub fn into_chunks(self) -> Vec<ChunkRef<'a>> {
+		self.chunks
+	}
+
+	/// Copies the chunks and trailer into an owned [`Png`].
+	pub fn into_png(self) -> Png {
+		let chunks = self.chunks.into_iter().map(ChunkRef::into_chunk).collect();
+		Png { trailer: self.trailer.to_vec(), ..Png::from_chunks(chunks) }
+	}
+
+	/// Parses a PNG from `bytes` like [`PngRef::try_from`], tolerating bad CRCs.
+	pub fn try_from_lenient(bytes: &'a [u8]) -> Result<Self> {
+		Self::parse(bytes, ChunkRef::try_from_lenient)
+	}
+
+	fn parse(bytes: &'a [u8], parse_chunk: fn(&'a [u8]) -> Result<ChunkRef<'a>>) -> Result<Self> {
+		let Some((header, mut bytes)) =
+			bytes.split_first_chunk::<8>().filter(|(header, _)| header == &&Png::STANDARD_HEADER)
+		else {
+			return Err(PngmeError::signature(bytes));
+		};
 		let mut chunks = Vec::new();
-		while !bytes.is_empty() {
-			let len =
-				u32::from_be_bytes(bytes[..4].try_into()?) as usize + mem::size_of::<[u32; 3]>();
+		let mut offset = header.len();
+		for index in 0.. {
+			if bytes.is_empty() {
+				break;
+			}
+			let len = bytes.get(..4).map_or(0, |len| u32::from_be_bytes(len.try_into().unwrap()));
+			// A chunk running past the end is passed on whole, for the parser to reject.
+			let len = (len as usize).saturating_add(mem::size_of::<[u32; 3]>()).min(bytes.len());
 			let (chunk_bytes, rest) = bytes.split_at(len);
-			chunks.push(Chunk::try_from(chunk_bytes)?);
+			let chunk =
+				parse_chunk(chunk_bytes).map_err(|e| e.in_chunk(index, offset as u64, bytes))?;
+			log::debug!(
+				"{} chunk at {:#010x}, length {}",
+				chunk.chunk_type(),
+				offset,
+				chunk.length()
+			);
+			let is_iend = chunk.chunk_type() == &ChunkType::IEND;
+			chunks.push(chunk.with_offset(offset as u64));
+			offset += len;
 			bytes = rest;
+			if is_iend {
+				break;
+			}
 		}
-		Ok(Self::from_chunks(chunks))
+		Ok(Self { chunks, trailer: bytes })
+	}
+}
+
+impl fmt::Debug for PngRef<'_> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("PngRef")
+			.field("chunks", &self.chunks)
+			.field("trailer", &DataPreview(self.trailer))
+			.finish()
+	}
+}
+
+impl<'a> TryFrom<&'a [u8]> for PngRef<'a> {
+	type Error = Error;
+	fn try_from(bytes: &'a [u8]) -> Result<Self> {
+		Self::parse(bytes, ChunkRef::try_from)
+	}
+}
+
+impl PartialEq for Png {
+	fn eq(&self, other: &Self) -> bool {
+		self.chunks == other.chunks && self.trailer == other.trailer
+	}
+}
+
+impl Eq for Png {}
+
+impl Hash for Png {
+	fn hash<H: Hasher>(&self, state: &mut H) {
+		self.chunks.hash(state);
+		self.trailer.hash(state);
+	}
+}
+
+impl fmt::Debug for Png {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("Png")
+			.field("chunks", &self.chunks)
+			.field("trailer", &DataPreview(&self.trailer))
+			.finish_non_exhaustive()
 	}
 }
 
@@ -154,7 +697,99 @@ mod tests {
 
 		let png = Png::try_from(bytes.as_ref());
 
-		assert!(png.is_err());
+		assert!(matches!(
+			png,
+			Err(PngmeError::InvalidSignature { found: [13, ..], kind: FileKind::Unknown })
+		));
+	}
+
+	#[test]
+	fn test_png_ref() {
+		let png = PngRef::try_from(&PNG_FILE[..]).unwrap();
+		let ihdr = png.chunk_by_type("IHDR").unwrap();
+		assert!(std::ptr::eq(ihdr.data(), &PNG_FILE[16..29]));
+		assert_eq!(ihdr.offset(), Some(8));
+		assert!(png.trailer().is_empty());
+
+		let owned = png.into_png();
+		assert_eq!(owned.as_bytes(), PNG_FILE);
+		assert_eq!(owned.chunks()[0].offset(), Some(8));
+	}
+
+	#[test]
+	fn test_from_mmap() {
+		let path = std::env::temp_dir().join(format!("pngme-mmap-{}.png", std::process::id()));
+		let mut bytes = PNG_FILE.to_vec();
+		bytes[41] ^= 1;
+		bytes.extend_from_slice(b"trailer");
+		std::fs::write(&path, &bytes).unwrap();
+		let map = unsafe { Mmap::map(&std::fs::File::open(&path).unwrap()) }.unwrap();
+		std::fs::remove_file(&path).unwrap();
+
+		let png = Png::from_mmap(&map).unwrap();
+		assert_eq!(png.chunks().len(), Png::try_from(&PNG_FILE[..]).unwrap().chunks().len());
+		assert_eq!(png.trailer(), b"trailer");
+		assert_eq!(png.trailer_offset(), PNG_FILE.len() as u64);
+		let bad = png.chunks().iter().find(|chunk| chunk.verify_crc().is_err()).unwrap();
+		assert_eq!(bad.offset(), Some(33));
+	}
+
+	#[test]
+	fn test_sniff() {
+		let headless = &PNG_FILE[8..];
+		let mut crlf_to_lf = PNG_FILE.to_vec();
+		crlf_to_lf.remove(4);
+		let mut lf_to_crlf = PNG_FILE.to_vec();
+		lf_to_crlf.splice(5..5, [b'\r']);
+		let mut seven_bit = PNG_FILE.to_vec();
+		seven_bit[0] &= 0x7f;
+
+		assert_eq!(Png::sniff(&PNG_FILE), FileKind::Png);
+		assert_eq!(Png::sniff(headless), FileKind::HeadlessPng);
+		assert_eq!(Png::sniff(&crlf_to_lf), FileKind::MangledPng);
+		assert_eq!(Png::sniff(&lf_to_crlf), FileKind::MangledPng);
+		assert_eq!(Png::sniff(&seven_bit), FileKind::MangledPng);
+		assert_eq!(Png::sniff(b"\xff\xd8\xff\xe0\0\x10JFIF\0"), FileKind::Jpeg);
+		assert_eq!(Png::sniff(b"GIF89a\x01\0\x01\0"), FileKind::Gif);
+		assert_eq!(Png::sniff(b"RIFF\x24\0\0\0WEBPVP8 "), FileKind::WebP);
+		assert_eq!(Png::sniff(b"RIFF\x24\0\0\0WAVEfmt "), FileKind::Unknown);
+		assert_eq!(Png::sniff(b"BM\x36\0\0\0"), FileKind::Bmp);
+		assert_eq!(Png::sniff(b"\x89PNG"), FileKind::TruncatedPng);
+		assert_eq!(Png::sniff(b""), FileKind::Empty);
+	}
+
+	#[test]
+	fn test_misidentified() {
+		let webp = b"RIFF\x24\0\0\0WEBPVP8 ";
+		let from_reader = Png::from_reader(&webp[..]).err().unwrap();
+		let from_slice = Png::try_from(&webp[..]).err().unwrap();
+
+		for error in [from_reader, from_slice] {
+			assert!(matches!(error, PngmeError::InvalidSignature { kind: FileKind::WebP, .. }));
+			assert_eq!(error.to_string(), "Not a PNG file: this is a WebP image");
+		}
+	}
+
+	#[test]
+	fn test_cut_off() {
+		let mut cut_in_length = Png::STANDARD_HEADER.to_vec();
+		cut_in_length.extend([0, 0]);
+		let cases: [(&[u8], &str); 3] = [
+			(b"", "Not a PNG file: this is an empty file"),
+			(b"\x89PNG", "Invalid PNG header: the file ends inside the signature"),
+			(&cut_in_length, "Invalid chunk: cut off inside its length"),
+		];
+
+		for (bytes, message) in cases {
+			let from_reader = Png::from_reader(bytes).err().unwrap();
+			let from_slice = Png::try_from(bytes).err().unwrap();
+			for error in [from_reader, from_slice] {
+				assert!(!matches!(error, PngmeError::Io(_)), "{:?}", error);
+				assert_eq!(error.root().to_string(), message);
+			}
+		}
+		let error = Png::from_reader(cut_in_length.as_slice()).err().unwrap();
+		assert!(matches!(error, PngmeError::InChunk { offset: 8, .. }), "{:?}", error);
 	}
 
 	#[test]
@@ -201,6 +836,60 @@ mod tests {
 		assert_eq!(&chunk.data_as_string().unwrap(), "Message");
 	}
 
+	#[test]
+	fn test_insert_chunk_at() {
+		let mut png = testing_png();
+		png.insert_chunk_at(1, chunk_from_strings("TeSt", "Message").unwrap());
+		png.insert_chunk_at(4, chunk_from_strings("LaSt", "Message").unwrap());
+		let types: Vec<String> = png.chunks().iter().map(|c| c.chunk_type().to_string()).collect();
+		assert_eq!(types, ["FrSt", "TeSt", "miDl", "LASt", "LaSt"]);
+	}
+
+	#[test]
+	fn test_insert_chunk() {
+		let mut png = Png::from_chunks(
+			["IHDR", "IDAT", "IEND"]
+				.iter()
+				.map(|t| Chunk::new(t.parse().unwrap(), Vec::new()))
+				.collect(),
+		);
+		png.insert_chunk(chunk_from_strings("tEXt", "late").unwrap());
+		png.insert_chunk(chunk_from_strings("pHYs", "early").unwrap());
+		let types: Vec<String> = png.chunks().iter().map(|c| c.chunk_type().to_string()).collect();
+		assert_eq!(types, ["IHDR", "pHYs", "IDAT", "tEXt", "IEND"]);
+	}
+
+	#[test]
+	fn test_preservation_policy() {
+		let chunks = || {
+			["IHDR", "ruSt", "ruST", "IDAT", "IEND"]
+				.iter()
+				.map(|t| Chunk::new(t.parse().unwrap(), Vec::new()))
+				.collect()
+		};
+		let types =
+			|png: &Png| png.chunks().iter().map(|c| c.chunk_type().to_string()).collect::<Vec<_>>();
+
+		let mut png = Png::from_chunks(chunks());
+		png.set_preservation_policy(PreservationPolicy::Drop);
+		png.insert_chunk(chunk_from_strings("teXt", "ancillary").unwrap());
+		assert_eq!(types(&png).len(), 6);
+		png.remove_chunk("IDAT").unwrap();
+		assert_eq!(types(&png), ["IHDR", "ruSt", "teXt", "IEND"]);
+
+		let mut png = Png::from_chunks(chunks());
+		png.set_preservation_policy(PreservationPolicy::Warn);
+		assert_eq!(png.flagged_chunks().count(), 0);
+		png.insert_chunk(chunk_from_strings("IDAT", "more").unwrap());
+		let flagged: Vec<String> =
+			png.flagged_chunks().map(|c| c.chunk_type().to_string()).collect();
+		assert_eq!(flagged, ["ruST"]);
+
+		let mut png = Png::from_chunks(chunks());
+		png.retain_chunks(|chunk| chunk.chunk_type().bytes() != *b"IDAT");
+		assert_eq!(types(&png).len(), 4);
+	}
+
 	#[test]
 	fn test_remove_chunk() {
 		let mut png = testing_png();
@@ -210,13 +899,187 @@ mod tests {
 		assert!(chunk.is_none());
 	}
 
+	#[test]
+	fn test_remove_missing_chunk() {
+		let mut png = testing_png();
+		assert!(matches!(png.remove_chunk("TeSt"), Err(PngmeError::ChunkNotFound { .. })));
+		assert_eq!(png.chunks().len(), 3);
+	}
+
+	#[test]
+	fn test_remove_chunks() {
+		let mut png = testing_png();
+		png.append_chunk(chunk_from_strings("TeSt", "Message").unwrap());
+		png.append_chunk(chunk_from_strings("TeSt", "Another message").unwrap());
+		let removed = png.remove_chunks("TeSt");
+		assert_eq!(removed.len(), 2);
+		assert_eq!(&removed[1].data_as_string().unwrap(), "Another message");
+		assert_eq!(png.chunks().len(), 3);
+		assert!(png.remove_chunks("TeSt").is_empty());
+		assert!(png.remove_chunks("T3St").is_empty());
+	}
+
+	#[test]
+	fn test_chunks_by_type() {
+		let mut png = testing_png();
+		png.append_chunk(chunk_from_strings("TeSt", "First").unwrap());
+		png.append_chunk(chunk_from_strings("TeSt", "Second").unwrap());
+		let found: Vec<_> =
+			png.chunks_by_type("TeSt").map(|chunk| chunk.data_as_string().unwrap()).collect();
+		assert_eq!(found, ["First", "Second"]);
+		assert_eq!(png.chunks_by_type("NoNe").count(), 0);
+		assert_eq!(png.chunks_by_type("bad!").count(), 0);
+	}
+
+	#[test]
+	fn test_index_stays_in_sync() {
+		let indexed = |png: &Png, chunk_type: &str| png.chunks_by_type(chunk_type).count();
+		let scanned = |png: &Png, chunk_type: &str| {
+			png.chunks().iter().filter(|chunk| chunk.chunk_type().to_string() == chunk_type).count()
+		};
+		let mut png = testing_png();
+		png.set_preservation_policy(PreservationPolicy::Drop);
+		png.append_chunk(chunk_from_strings("TeSt", "a").unwrap());
+		png.insert_chunk_at(0, chunk_from_strings("TeSt", "b").unwrap());
+		png.insert_chunk_at(2, chunk_from_strings("teSt", "c").unwrap());
+		png.remove_chunk("FrSt").unwrap();
+		png.replace_chunk("TeSt", chunk_from_strings("miDl", "d").unwrap()).unwrap();
+		png.remove_chunk_at(1);
+		png.retain_chunks(|chunk| chunk.data() != b"a");
+		png.append_chunk(chunk_from_strings("TEST", "e").unwrap());
+		png.remove_chunks("teSt");
+		for chunk_type in ["FrSt", "miDl", "LASt", "TeSt", "teSt", "TEST"] {
+			assert_eq!(indexed(&png, chunk_type), scanned(&png, chunk_type), "{}", chunk_type);
+		}
+		assert_eq!(png.chunk_by_type("miDl").unwrap().data(), b"d");
+	}
+
+	#[test]
+	fn test_incremental_index() {
+		let types = ["TeSt", "teSt", "miDl", "IDAT"];
+		let mut png = testing_png();
+		// A fixed sequence of pseudo-random operations, the same on every run.
+		let mut state = 0x2545_f491_u32;
+		let mut next = |bound: usize| {
+			state ^= state << 13;
+			state ^= state >> 17;
+			state ^= state << 5;
+			state as usize % bound
+		};
+		for step in 0..500 {
+			let chunk = chunk_from_strings(types[next(types.len())], "x").unwrap();
+			let len = png.chunks().len();
+			match next(6) {
+				0 => png.insert_chunk_at(next(len + 1), chunk),
+				1 => png.append_chunk(chunk),
+				2 => drop(png.remove_chunk_at(next(len + 1))),
+				3 => drop(png.remove_chunk(types[next(types.len())])),
+				4 if len > 0 => drop(png.replace_chunk_at(next(len), chunk)),
+				4 => {}
+				_ => drop(png.remove_chunks(types[next(types.len())])),
+			}
+			if step % 50 == 0 {
+				png.replace_image_data(vec![step as u8], false);
+			}
+			let mut rebuilt = png.clone();
+			rebuilt.reindex();
+			assert_eq!(png.index, rebuilt.index, "after step {}", step);
+		}
+	}
+
+	#[test]
+	fn test_clone_and_compare() {
+		let png = testing_png();
+		let mut copy = png.clone();
+		assert_eq!(copy, png);
+		copy.set_preservation_policy(PreservationPolicy::Drop);
+		assert_eq!(copy, png);
+		copy.remove_chunk("miDl").unwrap();
+		assert_ne!(copy, png);
+		assert_eq!(png.chunks().len(), 3);
+
+		let bytes = png.as_bytes();
+		let parsed = Png::try_from(bytes.as_slice()).unwrap();
+		assert_eq!(parsed, png);
+		let debug = format!("{:?}", parsed);
+		assert!(debug.starts_with("Png { chunks: [Chunk { chunk_type: ChunkType(\"FrSt\")"));
+	}
+
+	#[test]
+	fn test_replace_chunk() {
+		let mut png = testing_png();
+		png.append_chunk(chunk_from_strings("TeSt", "Second").unwrap());
+		let old = png.replace_chunk("miDl", chunk_from_strings("miDl", "New").unwrap()).unwrap();
+		assert_eq!(&old.chunk_type().to_string(), "miDl");
+		assert_eq!(&png.chunks()[1].data_as_string().unwrap(), "New");
+		assert_eq!(png.chunks().len(), 4);
+
+		let missing = png.replace_chunk("NoNe", chunk_from_strings("NoNe", "x").unwrap());
+		assert!(matches!(missing, Err(PngmeError::ChunkNotFound { .. })));
+	}
+
+	#[test]
+	fn test_remove_chunk_at() {
+		let mut png = testing_png();
+		let removed = png.remove_chunk_at(1).unwrap();
+		assert_eq!(&removed.chunk_type().to_string(), "miDl");
+		assert_eq!(png.chunks().len(), 2);
+		assert!(png.remove_chunk_at(2).is_none());
+	}
+
+	#[test]
+	fn test_retain_chunks() {
+		let mut png = testing_png();
+		png.retain_chunks(|chunk| chunk.chunk_type().is_critical());
+		let types: Vec<String> = png.chunks().iter().map(|c| c.chunk_type().to_string()).collect();
+		assert_eq!(types, ["FrSt", "LASt"]);
+	}
+
+	#[test]
+	fn test_header() {
+		let png = Png::try_from(&PNG_FILE[..]).unwrap();
+		let header = png.header().unwrap();
+		assert_eq!((header.width, header.height), (50, 50));
+		assert_eq!(header.bit_depth, 8);
+		assert_eq!(header.color_type, crate::ihdr::ColorType::Rgba);
+		assert!(!header.is_interlaced());
+	}
+
+	#[test]
+	fn test_missing_header() {
+		assert!(testing_png().header().is_err());
+	}
+
+	#[test]
+	fn test_chunk_offsets() {
+		let from_slice = Png::try_from(&PNG_FILE[..]).unwrap();
+		let from_reader = Png::from_reader(&PNG_FILE[..]).unwrap();
+		for png in [from_slice, from_reader] {
+			let offsets: Vec<_> = png.chunks().iter().map(Chunk::offset).collect();
+			assert_eq!(offsets[..3], [Some(8), Some(33), Some(46)]);
+			for (offset, chunk) in png.chunks_with_offsets() {
+				assert_eq!(Some(offset), chunk.offset());
+				assert_eq!(PNG_FILE[offset as usize + 4..][..4], chunk.chunk_type().bytes());
+			}
+		}
+	}
+
+	#[test]
+	fn test_chunks_with_offsets_after_append() {
+		let mut png = testing_png();
+		assert!(png.chunks().iter().all(|chunk| chunk.offset().is_none()));
+		png.append_chunk(chunk_from_strings("TeSt", "Message").unwrap());
+		let offsets: Vec<u64> = png.chunks_with_offsets().map(|(offset, _)| offset).collect();
+		assert_eq!(offsets, [8, 40, 70, 101]);
+	}
+
 	#[test]
 	fn test_png_from_image_file() {
 		let png = Png::try_from(&PNG_FILE[..]);
 		assert!(png.is_ok());
 	}
 
-	//#[test]
+	#[test]
 	fn test_as_bytes() {
 		let png = Png::try_from(&PNG_FILE[..]).unwrap();
 		let actual = png.as_bytes();
@@ -224,6 +1087,81 @@ mod tests {
 		assert_eq!(actual, expected);
 	}
 
+	#[test]
+	fn test_from_reader() {
+		let png = Png::from_reader(&PNG_FILE[..]).unwrap();
+		assert_eq!(png.as_bytes(), PNG_FILE.to_vec());
+	}
+
+	#[test]
+	fn test_from_reader_invalid_header() {
+		let mut bytes = PNG_FILE.to_vec();
+		bytes[0] = 13;
+		assert!(Png::from_reader(bytes.as_slice()).is_err());
+	}
+
+	#[test]
+	fn test_lenient_bad_crc() {
+		let mut bytes = PNG_FILE.to_vec();
+		// Last byte of the IHDR CRC.
+		bytes[32] ^= 1;
+		for result in [Png::try_from(bytes.as_slice()), Png::from_reader(bytes.as_slice())] {
+			let error = result.err().unwrap();
+			assert!(matches!(error.root(), PngmeError::CrcMismatch { offset: Some(8), .. }));
+			let PngmeError::InChunk { index: 0, chunk_type: Some(chunk_type), snippet, .. } = error
+			else {
+				panic!("expected the location of the error, got {:?}", error);
+			};
+			assert_eq!(chunk_type, ChunkType::IHDR);
+			// The CRC follows the signature, length, type, and 13 bytes of data.
+			assert_eq!(snippet.failure, 29);
+			assert_eq!(snippet.bytes, bytes[snippet.offset as usize..][..16]);
+			assert!((snippet.offset..snippet.offset + 16).contains(&29));
+		}
+
+		for png in [
+			Png::try_from_lenient(bytes.as_slice()).unwrap(),
+			Png::from_reader_lenient(bytes.as_slice()).unwrap(),
+		] {
+			assert!(!png.chunks()[0].has_valid_crc());
+			assert!(png.chunks()[1..].iter().all(Chunk::has_valid_crc));
+			assert_eq!(png.as_bytes(), bytes);
+		}
+	}
+
+	#[test]
+	fn test_truncated() {
+		for len in [3, 20, PNG_FILE.len() - 1] {
+			let bytes = &PNG_FILE[..len];
+			assert!(Png::try_from(bytes).is_err());
+			assert!(Png::from_reader(bytes).is_err());
+		}
+		let error = Png::try_from(&PNG_FILE[..20]).err().unwrap();
+		assert!(matches!(error.root(), PngmeError::InvalidChunkLength { .. }));
+	}
+
+	#[test]
+	fn test_trailer() {
+		let mut bytes = PNG_FILE.to_vec();
+		bytes.extend_from_slice(b"hidden");
+		for mut png in
+			[Png::try_from(bytes.as_slice()).unwrap(), Png::from_reader(bytes.as_slice()).unwrap()]
+		{
+			assert_eq!(png.trailer(), b"hidden");
+			assert_eq!(png.as_bytes(), bytes);
+			assert_eq!(png.take_trailer(), b"hidden");
+			assert_eq!(png.as_bytes(), PNG_FILE);
+		}
+	}
+
+	#[test]
+	fn test_write_to() {
+		let png = Png::try_from(&PNG_FILE[..]).unwrap();
+		let mut bytes = Vec::new();
+		png.write_to(&mut bytes).unwrap();
+		assert_eq!(bytes, PNG_FILE.to_vec());
+	}
+
 	#[test]
 	fn test_png_trait_impls() {
 		let chunk_bytes: Vec<u8> =
@@ -0,0 +1,145 @@
+//! Progress bars on stderr, for reading large files and for batches.
+//!
+//! Bars are shown with `--progress`, or by default when stderr is a terminal, and never with
+//! `--quiet`. A bar only appears once its work has taken a moment, so quick commands print
+//! nothing extra.
+
+use std::{
+	io::{self, IsTerminal, Read, Write},
+	sync::atomic::{AtomicBool, Ordering},
+	time::{Duration, Instant},
+};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+/// Set while a batch shows its own bar, which per-file bars from its jobs would garble.
+static IN_BATCH: AtomicBool = AtomicBool::new(false);
+
+/// How long work runs before its bar appears, and how often the bar is redrawn.
+const DELAY: Duration = Duration::from_millis(250);
+const INTERVAL: Duration = Duration::from_millis(100);
+const WIDTH: usize = 30;
+
+/// Decides once whether bars are shown at all.
+pub fn init(progress: bool, quiet: bool) {
+	ENABLED.store(!quiet && (progress || io::stderr().is_terminal()), Ordering::Relaxed);
+}
+
+/// What a bar counts.
+#[derive(Clone, Copy)]
+enum Unit {
+	Bytes,
+	Files,
+}
+
+/// A progress bar for one piece of work; it is cleared when dropped.
+pub struct Bar {
+	label: String,
+	unit: Unit,
+	/// Zero if unknown, in which case only the count is shown.
+	total: u64,
+	done: u64,
+	started: Instant,
+	drawn: Option<Instant>,
+}
+
+impl Bar {
+	fn new(label: impl Into<String>, unit: Unit, total: u64) -> Option<Self> {
+		ENABLED.load(Ordering::Relaxed).then(|| Self {
+			label: label.into(),
+			unit,
+			total,
+			done: 0,
+			started: Instant::now(),
+			drawn: None,
+		})
+	}
+
+	/// A bar counting the files of a batch, which suppresses the per-file bars until dropped.
+	pub fn files(total: usize) -> Option<Self> {
+		let bar = Self::new("Files", Unit::Files, total as u64)?;
+		IN_BATCH.store(true, Ordering::Relaxed);
+		Some(bar)
+	}
+
+	/// Moves the bar forward by `amount`.
+	pub fn advance(&mut self, amount: u64) {
+		self.done += amount;
+		let now = Instant::now();
+		let due = match self.drawn {
+			Some(drawn) => now - drawn >= INTERVAL,
+			None => now - self.started >= DELAY,
+		};
+		if due || (self.drawn.is_some() && self.done == self.total) {
+			self.drawn = Some(now);
+			self.draw();
+		}
+	}
+
+	/// Removes the bar so that other output can be printed, until it is next drawn.
+	pub fn clear(&mut self) {
+		if self.drawn.is_some() {
+			eprint!("\r\x1b[K");
+		}
+	}
+
+	fn draw(&self) {
+		let count = |n: u64| match self.unit {
+			Unit::Bytes => format!("{:.1} MiB", n as f64 / (1024.0 * 1024.0)),
+			Unit::Files => n.to_string(),
+		};
+		let mut line = format!("\r\x1b[K{} ", self.label);
+		let done = self.done.min(self.total);
+		if let Some(percent) = (done * 100).checked_div(self.total) {
+			let filled = percent as usize * WIDTH / 100;
+			line += &format!(
+				"[{}{}] {:>3}% {}/{}",
+				"#".repeat(filled),
+				"-".repeat(WIDTH - filled),
+				percent,
+				count(self.done),
+				count(self.total)
+			);
+		} else {
+			line += &count(self.done);
+		}
+		let mut stderr = io::stderr().lock();
+		// A progress bar that cannot be drawn is not worth failing over.
+		let _ = stderr.write_all(line.as_bytes()).and_then(|_| stderr.flush());
+	}
+}
+
+impl Drop for Bar {
+	fn drop(&mut self) {
+		self.clear();
+		if matches!(self.unit, Unit::Files) {
+			IN_BATCH.store(false, Ordering::Relaxed);
+		}
+	}
+}
+
+/// Reports how far reading has got through a file of `total` bytes, if known.
+pub struct ProgressReader<R> {
+	inner: R,
+	bar: Option<Bar>,
+}
+
+impl<R: Read> ProgressReader<R> {
+	pub fn new(inner: R, label: impl Into<String>, total: u64) -> Self {
+		let bar = if IN_BATCH.load(Ordering::Relaxed) {
+			None
+		} else {
+			Bar::new(label, Unit::Bytes, total)
+		};
+		Self { inner, bar }
+	}
+}
+
+impl<R: Read> Read for ProgressReader<R> {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		let n = self.inner.read(buf)?;
+		if let Some(bar) = &mut self.bar {
+			bar.advance(n as u64);
+		}
+		Ok(n)
+	}
+}
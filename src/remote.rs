@@ -0,0 +1,91 @@
+//! Reading PNGs from `http://` and `https://` URLs given in place of a file name.
+//!
+//! Only the commands that write nothing take URLs: `print`, `decode`, `list`, `info`, `check`,
+//! and `scan`. The response is streamed to the parser as it arrives, and reading fails once it
+//! grows past `--max-bytes`.
+
+use std::{
+	io::{self, Read},
+	path::Path,
+	sync::atomic::{AtomicBool, AtomicU64, Ordering},
+	time::Duration,
+};
+
+use pngme::{PngmeError, Result};
+
+use crate::{args::Cli, progress::ProgressReader};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static MAX_BYTES: AtomicU64 = AtomicU64::new(u64::MAX);
+
+/// Decides once from the command line whether URLs are read, and how much of them.
+pub fn init(cli: &Cli, read_only: bool) {
+	ENABLED.store(read_only, Ordering::Relaxed);
+	MAX_BYTES.store(cli.max_bytes, Ordering::Relaxed);
+}
+
+/// Whether `path` is a URL to download rather than a file name.
+pub fn is_url(path: &Path) -> bool {
+	path.to_str().is_some_and(|path| {
+		let scheme = path.split_once("://").map_or("", |(scheme, _)| scheme);
+		scheme.eq_ignore_ascii_case("http") || scheme.eq_ignore_ascii_case("https")
+	})
+}
+
+/// Opens the response body of a GET request to `url`.
+pub fn open(url: &Path) -> Result<Box<dyn Read>> {
+	let url = url.to_str().expect("URLs are UTF-8");
+	if !ENABLED.load(Ordering::Relaxed) {
+		return Err(PngmeError::InvalidArgument(format!(
+			"{} is a URL, which only commands that write nothing can read",
+			url
+		)));
+	}
+	let agent = ureq::AgentBuilder::new()
+		.timeout_connect(Duration::from_secs(30))
+		.timeout_read(Duration::from_secs(60))
+		.user_agent(concat!("pngme/", env!("CARGO_PKG_VERSION")))
+		.build();
+	log::debug!("Downloading {}", url);
+	let response = match agent.get(url).call() {
+		Ok(response) => response,
+		Err(ureq::Error::Status(code, response)) => {
+			let error = format!("{}: HTTP {} {}", url, code, response.status_text());
+			return Err(match code {
+				404 | 410 => PngmeError::NotFound(error),
+				_ => error.into(),
+			});
+		}
+		Err(ureq::Error::Transport(error)) => return Err(io::Error::other(error).into()),
+	};
+	let max_bytes = MAX_BYTES.load(Ordering::Relaxed);
+	let total = response.header("Content-Length").and_then(|length| length.parse().ok());
+	if let Some(total) = total.filter(|&total| total > max_bytes) {
+		let error = format!("{} is {} bytes, more than --max-bytes allows", url, total);
+		return Err(error.into());
+	}
+	let label = url.rsplit('/').find(|name| !name.is_empty()).unwrap_or(url).to_owned();
+	let body = Limited { inner: response.into_reader(), url: url.to_owned(), left: max_bytes };
+	Ok(Box::new(ProgressReader::new(body, label, total.unwrap_or(0))))
+}
+
+/// A response body that fails, rather than ends, past `left` more bytes.
+struct Limited<R> {
+	inner: R,
+	url: String,
+	left: u64,
+}
+
+impl<R: Read> Read for Limited<R> {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		// One byte past the limit tells a body that is too large from one that just fits.
+		let len = buf.len().min(usize::try_from(self.left.saturating_add(1)).unwrap_or(usize::MAX));
+		let n = self.inner.read(&mut buf[..len])?;
+		if n as u64 > self.left {
+			let error = format!("{} is larger than --max-bytes allows", self.url);
+			return Err(io::Error::other(error));
+		}
+		self.left -= n as u64;
+		Ok(n)
+	}
+}
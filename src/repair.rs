@@ -0,0 +1,174 @@
+//! Fixing recoverable corruption in PNG files.
+
+use std::str::FromStr;
+
+use crate::{
+	check::Problem,
+	chunk::{compute_crc, Chunk},
+	chunk_type::ChunkType,
+	png::Png,
+	PngmeError, Result,
+};
+
+/// Which fixes [`repair`] applies; all of them by default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RepairOptions {
+	/// Recompute CRCs that do not match their chunk.
+	pub fix_crcs: bool,
+	/// Append an `IEND` chunk if the file has none.
+	pub add_iend: bool,
+	/// Drop any bytes after `IEND`.
+	pub strip_trailer: bool,
+	/// Drop a final chunk that is cut short or cannot be parsed.
+	pub truncate_partial: bool,
+}
+
+impl Default for RepairOptions {
+	fn default() -> Self {
+		Self { fix_crcs: true, add_iend: true, strip_trailer: true, truncate_partial: true }
+	}
+}
+
+/// Repairs the PNG in `bytes`, returning the repaired file and a description of each fix
+/// applied, located by its offset in the original file.
+///
+/// Fails only if `bytes` does not start with the PNG signature.
+pub fn repair(bytes: &[u8], options: RepairOptions) -> Result<(Vec<u8>, Vec<Problem>)> {
+	if !bytes.starts_with(&Png::STANDARD_HEADER) {
+		return Err(PngmeError::signature(bytes));
+	}
+	let mut output = Png::STANDARD_HEADER.to_vec();
+	let mut fixes = Vec::new();
+	let mut fixed =
+		|offset: usize, message: String| fixes.push(Problem { offset: offset as u64, message });
+
+	let mut offset = Png::STANDARD_HEADER.len();
+	let mut seen_iend = false;
+	while offset < bytes.len() {
+		let rest = &bytes[offset..];
+		if seen_iend {
+			if options.strip_trailer {
+				fixed(offset, format!("stripped {} bytes after IEND", rest.len()));
+			} else {
+				output.extend_from_slice(rest);
+			}
+			break;
+		}
+		let Some(end) = chunk_end(rest) else {
+			if options.truncate_partial {
+				fixed(offset, format!("truncated {} bytes of partial chunk", rest.len()));
+			} else {
+				output.extend_from_slice(rest);
+			}
+			break;
+		};
+		let chunk = &rest[..end];
+		let stored = u32::from_be_bytes(chunk[end - 4..].try_into()?);
+		let computed = compute_crc(&chunk[4..end - 4]);
+		output.extend_from_slice(&chunk[..end - 4]);
+		if stored != computed && options.fix_crcs {
+			fixed(
+				offset,
+				format!(
+					"recomputed {} chunk CRC 0x{:08x} as 0x{:08x}",
+					String::from_utf8_lossy(&chunk[4..8]),
+					stored,
+					computed
+				),
+			);
+			output.extend_from_slice(&computed.to_be_bytes());
+		} else {
+			output.extend_from_slice(&stored.to_be_bytes());
+		}
+		seen_iend = &chunk[4..8] == b"IEND";
+		offset += end;
+	}
+
+	if !seen_iend && options.add_iend {
+		fixed(offset, "added missing IEND chunk".to_owned());
+		output.extend_from_slice(&Chunk::new(ChunkType::from_str("IEND")?, Vec::new()).as_bytes());
+	}
+	Ok((output, fixes))
+}
+
+/// Returns the length of the complete, well-formed chunk at the start of `bytes`, if any.
+fn chunk_end(bytes: &[u8]) -> Option<usize> {
+	let length = u32::from_be_bytes(bytes.get(0..4)?.try_into().ok()?);
+	ChunkType::try_from(<[u8; 4]>::try_from(bytes.get(4..8)?).ok()?).ok()?;
+	let end = 12 + usize::try_from(length).ok().filter(|&length| length <= i32::MAX as usize)?;
+	(end <= bytes.len()).then_some(end)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::ihdr::{ColorType, IhdrData};
+
+	fn file() -> Vec<u8> {
+		let ihdr = IhdrData {
+			width: 1,
+			height: 1,
+			bit_depth: 8,
+			color_type: ColorType::Rgb,
+			compression_method: 0,
+			filter_method: 0,
+			interlace_method: 0,
+		};
+		let chunks = vec![
+			ihdr.to_chunk(),
+			Chunk::new("IDAT".parse().unwrap(), b"data".to_vec()),
+			Chunk::new("IEND".parse().unwrap(), Vec::new()),
+		];
+		Png::from_chunks(chunks).as_bytes()
+	}
+
+	#[test]
+	fn test_valid_file_unchanged() {
+		let (repaired, fixes) = repair(&file(), RepairOptions::default()).unwrap();
+		assert_eq!(repaired, file());
+		assert!(fixes.is_empty());
+	}
+
+	#[test]
+	fn test_fix_crc() {
+		let mut bytes = file();
+		bytes[32] ^= 1;
+		let (repaired, fixes) = repair(&bytes, RepairOptions::default()).unwrap();
+		assert_eq!(repaired, file());
+		assert_eq!(fixes.len(), 1);
+		assert_eq!(fixes[0].offset, 8);
+
+		let options = RepairOptions { fix_crcs: false, ..Default::default() };
+		assert_eq!(repair(&bytes, options).unwrap().0, bytes);
+	}
+
+	#[test]
+	fn test_strip_trailer() {
+		let mut bytes = file();
+		bytes.extend_from_slice(b"payload");
+		let (repaired, fixes) = repair(&bytes, RepairOptions::default()).unwrap();
+		assert_eq!(repaired, file());
+		assert_eq!(fixes[0].message, "stripped 7 bytes after IEND");
+
+		let options = RepairOptions { strip_trailer: false, ..Default::default() };
+		assert_eq!(repair(&bytes, options).unwrap().0, bytes);
+	}
+
+	#[test]
+	fn test_truncated_file() {
+		let bytes = file();
+		let cut = &bytes[..bytes.len() - 14];
+		let (repaired, fixes) = repair(cut, RepairOptions::default()).unwrap();
+		assert_eq!(fixes.len(), 2);
+		assert_eq!(repaired, file()[..33].iter().chain(&file()[49..]).copied().collect::<Vec<_>>());
+
+		let options =
+			RepairOptions { truncate_partial: false, add_iend: false, ..Default::default() };
+		assert_eq!(repair(cut, options).unwrap().0, cut);
+	}
+
+	#[test]
+	fn test_not_a_png() {
+		assert!(repair(b"GIF89a", RepairOptions::default()).is_err());
+	}
+}
@@ -0,0 +1,210 @@
+//! Heuristic detection of hidden data, for inspecting files that did not come from pngme.
+//!
+//! Each finding is ranked by how strongly it suggests that something was hidden on purpose:
+//! recognizable payloads and data after `IEND` rank high, unusual but legitimate content
+//! such as private chunks ranks lower.
+
+use std::fmt;
+
+use crate::{
+	analysis, appended, chunk_type::ChunkType, crypto, envelope::Envelope, fragment, ordering,
+	png::Png, stego, text::TextualChunk,
+};
+
+/// Ancillary chunks larger than this are reported; metadata is rarely this big.
+const LARGE_CHUNK: usize = 64 * 1024;
+
+/// Text at least this long with at least this entropy is likely encoded binary data; prose
+/// stays well below 5 bits per byte, while base64 reaches 6.
+const ENTROPY_MIN_LEN: usize = 64;
+const ENTROPY_THRESHOLD: f64 = 5.0;
+
+/// How strongly a finding suggests hidden data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+	Low,
+	Medium,
+	High,
+}
+
+impl Severity {
+	/// Returns the lowercase name of the severity.
+	pub fn name(&self) -> &'static str {
+		match self {
+			Self::Low => "low",
+			Self::Medium => "medium",
+			Self::High => "high",
+		}
+	}
+}
+
+/// Something suspicious, located by the byte offset where it starts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+	pub severity: Severity,
+	pub offset: u64,
+	pub message: String,
+}
+
+impl fmt::Display for Finding {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(
+			f,
+			"{:<6}\t{:#010x}\t{}",
+			self.severity.name().to_uppercase(),
+			self.offset,
+			self.message
+		)
+	}
+}
+
+/// Scans `png` for signs of hidden data, most severe findings first and in file order
+/// otherwise.
+pub fn scan(png: &Png) -> Vec<Finding> {
+	let mut findings = Vec::new();
+	let mut finding =
+		|severity, offset, message: String| findings.push(Finding { severity, offset, message });
+
+	let misplaced = ordering::misplaced_chunks(png);
+	let mut seen_critical = Vec::new();
+	let mut last_idat = None;
+	for (n, (offset, chunk)) in png.chunks_with_offsets().enumerate() {
+		let chunk_type = chunk.chunk_type();
+		let data = chunk.data();
+		let name = chunk_type.to_string();
+
+		if Envelope::is_envelope(data) || fragment::is_fragment(data) || crypto::is_encrypted(data)
+		{
+			finding(Severity::High, offset, format!("{} chunk holds a pngme payload", name));
+		}
+		if chunk_type.is_critical() {
+			if name == "IDAT" {
+				if last_idat.is_some_and(|last| last + 1 != n) {
+					finding(Severity::Medium, offset, "IDAT chunks are not consecutive".to_owned());
+				}
+				last_idat = Some(n);
+			} else if seen_critical.contains(&name) {
+				finding(Severity::High, offset, format!("duplicate {} chunk", name));
+			} else {
+				seen_critical.push(name.clone());
+			}
+		} else if data.len() > LARGE_CHUNK {
+			finding(
+				Severity::Medium,
+				offset,
+				format!("{} chunk is unusually large: {} bytes", name, data.len()),
+			);
+		}
+		if !chunk_type.is_standard() {
+			finding(
+				Severity::Medium,
+				offset,
+				format!(
+					"non-standard {} chunk, {} bytes, entropy {:.2} bits/byte",
+					name,
+					data.len(),
+					analysis::entropy(data)
+				),
+			);
+		}
+		if let Ok(text) = TextualChunk::try_from(chunk) {
+			let entropy = analysis::entropy(text.text().as_bytes());
+			if text.text().len() >= ENTROPY_MIN_LEN && entropy >= ENTROPY_THRESHOLD {
+				finding(
+					Severity::Medium,
+					offset,
+					format!(
+						"{} text under {:?} looks encoded: entropy {:.2} bits/byte",
+						name,
+						text.keyword(),
+						entropy
+					),
+				);
+			}
+		}
+		if misplaced.contains(&n) {
+			finding(Severity::Low, offset, format!("{} chunk breaks the ordering rules", name));
+		}
+	}
+
+	let trailer = png.trailer();
+	if !trailer.is_empty() {
+		let offset = (png.as_bytes().len() - trailer.len()) as u64;
+		let message = match appended::frames(trailer) {
+			Ok(frames) if frames.len() == 1 => "a pngme payload after IEND".to_owned(),
+			Ok(frames) => format!("{} pngme payloads after IEND", frames.len()),
+			Err(_) => format!(
+				"{} bytes after IEND, entropy {:.2} bits/byte",
+				trailer.len(),
+				analysis::entropy(trailer)
+			),
+		};
+		finding(Severity::High, offset, message);
+	}
+	if let Ok(Some((tag, payload))) = stego::find(png) {
+		let offset = png.chunks_with_offsets().find(|(_, c)| c.chunk_type() == &ChunkType::IDAT);
+		finding(
+			Severity::High,
+			offset.map_or(0, |(offset, _)| offset),
+			format!("{} bytes tagged {} hidden in the pixel LSBs", payload.len(), tag),
+		);
+	}
+
+	findings.sort_by(|a, b| b.severity.cmp(&a.severity).then(a.offset.cmp(&b.offset)));
+	findings
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{chunk::Chunk, text::TextChunk};
+
+	fn severities(png: &Png) -> Vec<(Severity, String)> {
+		scan(png).into_iter().map(|finding| (finding.severity, finding.message)).collect()
+	}
+
+	fn testing_png(extra: Vec<Chunk>) -> Png {
+		let mut chunks = vec![
+			Chunk::new(ChunkType::IHDR, vec![0, 0, 0, 1, 0, 0, 0, 1, 8, 0, 0, 0, 0]),
+			Chunk::new(ChunkType::IDAT, vec![0; 8]),
+		];
+		chunks.extend(extra);
+		chunks.push(Chunk::new(ChunkType::IEND, Vec::new()));
+		Png::from_chunks(chunks)
+	}
+
+	#[test]
+	fn test_clean() {
+		let png = testing_png(vec![TextChunk::new("Author", "me").unwrap().to_chunk()]);
+		assert!(scan(&png).is_empty());
+	}
+
+	#[test]
+	fn test_findings() {
+		let encoded: String = (0..200).map(|n| char::from(b'!' + (n * 37 % 90) as u8)).collect();
+		let mut png = testing_png(vec![
+			Chunk::new("ruSt".parse().unwrap(), Envelope::new(b"hi".to_vec()).to_bytes().unwrap()),
+			TextChunk::new("Comment", &encoded).unwrap().to_chunk(),
+			Chunk::new(ChunkType::IHDR, vec![0; 13]),
+		]);
+		png.set_trailer(b"junk".to_vec());
+		let findings = severities(&png);
+		assert_eq!(findings[0].0, Severity::High);
+		assert!(findings.windows(2).all(|pair| pair[0].0 >= pair[1].0));
+		let has = |severity, text: &str| {
+			findings.iter().any(|(s, message)| *s == severity && message.contains(text))
+		};
+		assert!(has(Severity::High, "ruSt chunk holds a pngme payload"));
+		assert!(has(Severity::High, "duplicate IHDR"));
+		assert!(has(Severity::High, "4 bytes after IEND"));
+		assert!(has(Severity::Medium, "non-standard ruSt"));
+		assert!(has(Severity::Medium, "looks encoded"));
+		assert!(has(Severity::Low, "IHDR chunk breaks the ordering rules"));
+	}
+
+	#[test]
+	fn test_large_chunk() {
+		let png = testing_png(vec![Chunk::new(ChunkType::tEXt, vec![b'a'; LARGE_CHUNK + 1])]);
+		assert_eq!(severities(&png)[0].0, Severity::Medium);
+	}
+}
@@ -0,0 +1,154 @@
+//! `serde` support, behind the `serde` feature.
+//!
+//! A [`ChunkType`] is its four letters and a [`Chunk`] is its type, its data in base64, and its
+//! CRC:
+//!
+//! ```json
+//! { "chunk_type": "ruSt", "data": "aGlkZGVu", "crc": 2882656334 }
+//! ```
+//!
+//! A [`Png`] is its chunks and, if there is one, its trailer in base64. The CRC may be left out
+//! when deserializing a chunk; when it is given, it must match the data.
+
+use base64::{prelude::BASE64_STANDARD, Engine};
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{Chunk, ChunkType, Png};
+
+impl Serialize for ChunkType {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		serializer.collect_str(self)
+	}
+}
+
+impl<'de> Deserialize<'de> for ChunkType {
+	fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		String::deserialize(deserializer)?.parse().map_err(D::Error::custom)
+	}
+}
+
+#[derive(Serialize)]
+struct ChunkOut<'a> {
+	chunk_type: &'a ChunkType,
+	#[serde(serialize_with = "serialize_base64")]
+	data: &'a [u8],
+	crc: u32,
+}
+
+#[derive(Deserialize)]
+struct ChunkIn {
+	chunk_type: ChunkType,
+	#[serde(deserialize_with = "deserialize_base64")]
+	data: Vec<u8>,
+	#[serde(default)]
+	crc: Option<u32>,
+}
+
+impl Serialize for Chunk {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		ChunkOut { chunk_type: self.chunk_type(), data: self.data(), crc: self.crc() }
+			.serialize(serializer)
+	}
+}
+
+impl<'de> Deserialize<'de> for Chunk {
+	fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		let ChunkIn { chunk_type, data, crc } = ChunkIn::deserialize(deserializer)?;
+		let chunk = Chunk::new(chunk_type, data);
+		match crc {
+			Some(crc) if crc != chunk.crc() => Err(D::Error::custom(format!(
+				"{} chunk CRC mismatch: expected 0x{:x}, computed 0x{:x}",
+				chunk.chunk_type(),
+				crc,
+				chunk.crc()
+			))),
+			_ => Ok(chunk),
+		}
+	}
+}
+
+#[derive(Serialize)]
+struct PngOut<'a> {
+	chunks: &'a [Chunk],
+	#[serde(skip_serializing_if = "is_empty", serialize_with = "serialize_base64")]
+	trailer: &'a [u8],
+}
+
+#[derive(Deserialize)]
+struct PngIn {
+	chunks: Vec<Chunk>,
+	#[serde(default, deserialize_with = "deserialize_base64")]
+	trailer: Vec<u8>,
+}
+
+impl Serialize for Png {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		PngOut { chunks: self.chunks(), trailer: self.trailer() }.serialize(serializer)
+	}
+}
+
+impl<'de> Deserialize<'de> for Png {
+	fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		let PngIn { chunks, trailer } = PngIn::deserialize(deserializer)?;
+		let mut png = Png::from_chunks(chunks);
+		png.set_trailer(trailer);
+		Ok(png)
+	}
+}
+
+fn is_empty(data: &&[u8]) -> bool {
+	data.is_empty()
+}
+
+fn serialize_base64<S: Serializer>(data: &&[u8], serializer: S) -> Result<S::Ok, S::Error> {
+	serializer.serialize_str(&BASE64_STANDARD.encode(data))
+}
+
+fn deserialize_base64<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+	BASE64_STANDARD.decode(String::deserialize(deserializer)?).map_err(D::Error::custom)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_chunk_round_trip() {
+		let chunk = Chunk::new("ruSt".parse().unwrap(), b"hidden".to_vec());
+		let json = serde_json::to_value(&chunk).unwrap();
+		assert_eq!(
+			json,
+			serde_json::json!({ "chunk_type": "ruSt", "data": "aGlkZGVu", "crc": chunk.crc() })
+		);
+		assert_eq!(serde_json::from_value::<Chunk>(json).unwrap(), chunk);
+
+		let without_crc = serde_json::json!({ "chunk_type": "ruSt", "data": "aGlkZGVu" });
+		assert_eq!(serde_json::from_value::<Chunk>(without_crc).unwrap(), chunk);
+	}
+
+	#[test]
+	fn test_rejects_bad_chunks() {
+		let wrong_crc = serde_json::json!({ "chunk_type": "ruSt", "data": "aGlkZGVu", "crc": 1 });
+		let error = serde_json::from_value::<Chunk>(wrong_crc).unwrap_err();
+		assert!(error.to_string().contains("CRC mismatch"), "{}", error);
+		let bad_type = serde_json::json!({ "chunk_type": "ru5t", "data": "" });
+		assert!(serde_json::from_value::<Chunk>(bad_type).is_err());
+		let bad_data = serde_json::json!({ "chunk_type": "ruSt", "data": "not base64!" });
+		assert!(serde_json::from_value::<Chunk>(bad_data).is_err());
+	}
+
+	#[test]
+	fn test_png_round_trip() {
+		let mut png = Png::from_chunks(vec![
+			Chunk::new(ChunkType::IHDR, vec![0; 13]),
+			Chunk::new(ChunkType::IEND, Vec::new()),
+		]);
+		let json = serde_json::to_value(&png).unwrap();
+		assert!(json.get("trailer").is_none());
+		assert_eq!(serde_json::from_value::<Png>(json).unwrap(), png);
+
+		png.set_trailer(b"after".to_vec());
+		let json = serde_json::to_string(&png).unwrap();
+		assert_eq!(serde_json::from_str::<Png>(&json).unwrap(), png);
+	}
+}
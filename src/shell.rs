@@ -0,0 +1,350 @@
+//! `pngme shell` and `pngme run`: commands run on a PNG in memory.
+//!
+//! Each line is a command given as it would be to pngme, without the file name: `print`,
+//! `encode`, `remove`, `strip`, `text set`, and the others in [`COMMANDS`] with their usual
+//! options. The commands run as they always do, but while a file is open in memory, reading it
+//! gives the PNG held in memory and writing it replaces that PNG, so that edits add up until
+//! they are all written at once. Nothing else is written meanwhile.
+//!
+//! The shell reads lines from stdin, with `undo`, `redo`, `save`, `help`, and `quit` of its own.
+//! A script is a file of lines run one after the other, in which `#` starts a comment. The
+//! files a script edits are each written to a temporary file, and only renamed over the files
+//! once all of them were written; only a failed rename, or `--no-atomic`, which writes each file
+//! in place, can leave some files changed and others not.
+
+use std::{
+	cell::RefCell,
+	env,
+	ffi::OsString,
+	fs,
+	io::{self, IsTerminal, Write},
+	path::{Path, PathBuf},
+};
+
+use clap::Command;
+use pngme::{journal::Journal, Png, PngmeError, Result};
+
+use crate::{
+	args::{Cli, Commands},
+	config,
+	output::{out, outln},
+};
+
+/// The commands of pngme that work on a PNG in memory.
+const COMMANDS: [&str; 18] = [
+	"print",
+	"decode",
+	"encode",
+	"remove",
+	"strip",
+	"optimize",
+	"canonicalize",
+	"sign",
+	"trailer",
+	"text get",
+	"text set",
+	"exif show",
+	"exif strip",
+	"exif import",
+	"icc embed",
+	"xmp get",
+	"xmp set",
+	"xmp strip",
+];
+
+const HELP: &str = "\
+print, encode, remove, strip, text set, ...
+                       run the command on the file, which is left out; --help shows its options
+undo                   take back the last change
+redo                   make the last change taken back again
+save                   write the changes to the file
+help                   show this
+quit, exit             leave, asking first if there are unsaved changes";
+
+thread_local! {
+	/// The file open in memory, and the PNG that stands for it.
+	static OPEN: RefCell<Option<(PathBuf, Png)>> = const { RefCell::new(None) };
+}
+
+/// The bytes of the PNG in memory, if `path` is the file open.
+pub fn contents(path: &Path) -> Option<Vec<u8>> {
+	OPEN.with_borrow(|open| {
+		open.as_ref().filter(|(open, _)| open == path).map(|(_, png)| png.as_bytes())
+	})
+}
+
+/// Replaces the PNG in memory with `png` if `path` is the file open, returning whether it did.
+pub fn store(path: &Path, png: &Png) -> bool {
+	OPEN.with_borrow_mut(|open| match open {
+		Some((open, held)) if open == path => {
+			*held = png.clone();
+			true
+		}
+		_ => false,
+	})
+}
+
+/// Whether `path` is the file open in memory.
+pub fn is_open(path: &Path) -> bool {
+	OPEN.with_borrow(|open| open.as_ref().is_some_and(|(open, _)| open == path))
+}
+
+/// Whether a file is open in memory, when no other file may be written.
+pub fn is_active() -> bool {
+	OPEN.with_borrow(Option::is_some)
+}
+
+fn with_png<T>(f: impl FnOnce(&mut Png) -> T) -> T {
+	OPEN.with_borrow_mut(|open| f(&mut open.as_mut().expect("a file is open").1))
+}
+
+/// Runs `f` with the PNG in memory, closed meanwhile so that files are written as usual.
+fn closed<T>(f: impl FnOnce(&Png) -> T) -> T {
+	let open = OPEN.take();
+	let result = f(&open.as_ref().expect("a file is open").1);
+	OPEN.set(open);
+	result
+}
+
+/// The command line interface, with the defaults from the configuration file.
+fn interface() -> Result<Command> {
+	// The options given before `shell` or `run` chose the configuration file, which was
+	// already read without errors to parse them.
+	let argv: Vec<OsString> = env::args_os().collect();
+	let (command, _) = config::command(&argv).map_err(PngmeError::InvalidArgument)?;
+	Ok(command)
+}
+
+/// Parses a line of `words` into a command on `path`, or prints the help it asks for and returns
+/// `None`.
+fn parse(interface: &Command, path: &Path, words: &[String]) -> Result<Option<Commands>> {
+	let depth = COMMANDS
+		.iter()
+		.map(|name| name.split(' ').collect::<Vec<_>>())
+		.find(|name| words.len() >= name.len() && name.iter().zip(words).all(|(a, b)| a == b))
+		.map(|name| name.len())
+		.ok_or_else(|| usage(format!("`{}` does not work on a PNG in memory", words[0])))?;
+	// The file name goes right after the command name, where every command expects it.
+	let mut argv = vec![OsString::from("pngme")];
+	argv.extend(words[..depth].iter().map(OsString::from));
+	argv.push(path.into());
+	argv.extend(words[depth..].iter().map(OsString::from));
+	match Cli::try_parse_with(interface.clone(), argv) {
+		Ok(Cli { files: Some(_), .. }) => Err(usage("commands run on the open file only")),
+		Ok(cli) => Ok(Some(cli.command)),
+		Err(error) if error.use_stderr() => {
+			let message = error.to_string();
+			let message = message.lines().next().unwrap_or_default();
+			Err(usage(message.strip_prefix("error: ").unwrap_or(message)))
+		}
+		// Help requests are "errors" too.
+		Err(error) => {
+			let _ = error.print();
+			Ok(None)
+		}
+	}
+}
+
+/// Runs the commands read from stdin on `png`, read from `path`, until `quit` or the end of
+/// the input. `run` runs a command of pngme, and `save` writes a PNG to `path`.
+pub fn run(
+	path: &Path,
+	png: Png,
+	run: fn(Commands) -> Result<()>,
+	mut save: impl FnMut(&Png) -> Result<()>,
+) -> Result<()> {
+	let interface = interface()?;
+	OPEN.set(Some((path.to_owned(), png)));
+	let mut shell = Shell { path, interface, journal: Journal::new(), quitting: false };
+	let interactive = io::stdin().is_terminal();
+	let mut line = String::new();
+	loop {
+		if interactive {
+			out!("pngme> ");
+			io::stdout().flush()?;
+		}
+		line.clear();
+		// Not holding the lock between lines leaves stdin to commands that read it.
+		if io::stdin().read_line(&mut line)? == 0 {
+			break;
+		}
+		let result = split_words(&line).and_then(|words| match words.is_empty() {
+			true => Ok(true),
+			false => shell.execute(&words, run, &mut save),
+		});
+		match result {
+			Ok(true) => {}
+			Ok(false) => break,
+			Err(error) => log::error!("{}", error),
+		}
+	}
+	if shell.journal.is_modified() && !shell.quitting {
+		log::warn!("Discarded the unsaved changes");
+	}
+	OPEN.set(None);
+	Ok(())
+}
+
+struct Shell<'a> {
+	path: &'a Path,
+	interface: Command,
+	/// The changes made to the PNG in memory.
+	journal: Journal,
+	/// Whether `quit` was refused once because of the unsaved changes.
+	quitting: bool,
+}
+
+impl Shell<'_> {
+	/// Runs a line of `words`, returning whether the shell goes on.
+	fn execute(
+		&mut self,
+		words: &[String],
+		run: fn(Commands) -> Result<()>,
+		save: &mut impl FnMut(&Png) -> Result<()>,
+	) -> Result<bool> {
+		let name = words[0].as_str();
+		if name != "quit" && name != "exit" {
+			self.quitting = false;
+		}
+		match name {
+			"undo" => {
+				if !with_png(|png| self.journal.undo(png)) {
+					return Err(usage("nothing to undo"));
+				}
+			}
+			"redo" => {
+				if !with_png(|png| self.journal.redo(png)) {
+					return Err(usage("nothing to redo"));
+				}
+			}
+			"save" => {
+				closed(|png| save(png))?;
+				log::info!("Saved {}", self.path.display());
+				self.journal.mark_saved();
+			}
+			"help" => outln!("{}", HELP),
+			"quit" | "exit" if self.journal.is_modified() && !self.quitting => {
+				log::warn!("There are unsaved changes; save them, or quit again to discard them");
+				self.quitting = true;
+			}
+			"quit" | "exit" => return Ok(false),
+			_ => {
+				let Some(command) = parse(&self.interface, self.path, words)? else {
+					return Ok(true);
+				};
+				let before = with_png(|png| png.clone());
+				run(command)?;
+				with_png(|after| self.journal.record(&before, after));
+			}
+		}
+		Ok(true)
+	}
+}
+
+/// Runs the script at `script` on each PNG of `files`, read from the file paths that come with
+/// them, and then writes them all with `save`. Nothing is written unless every command
+/// succeeds on every file.
+pub fn run_script(
+	script: &Path,
+	files: Vec<(PathBuf, Png)>,
+	run: fn(Commands) -> Result<()>,
+	save: impl FnOnce(&[(PathBuf, Png)]) -> Result<()>,
+) -> Result<()> {
+	let text = fs::read_to_string(script)?;
+	let mut lines = Vec::new();
+	for (n, line) in text.lines().enumerate() {
+		let words = split_words(line)
+			.map_err(|error| usage(format!("{}:{}: {}", script.display(), n + 1, error)))?;
+		if !words.is_empty() {
+			lines.push((n + 1, words));
+		}
+	}
+	let interface = interface()?;
+	let mut edited = Vec::new();
+	for (path, png) in files {
+		OPEN.set(Some((path.clone(), png)));
+		for (n, words) in &lines {
+			log::debug!("{}: running line {}", path.display(), n);
+			let result =
+				parse(&interface, &path, words).and_then(|command| command.map_or(Ok(()), run));
+			if let Err(error) = result {
+				OPEN.set(None);
+				log::error!("{}:{}: {}: {}", script.display(), n, path.display(), error);
+				return Err("the script failed, no file was changed".into());
+			}
+		}
+		edited.push((path, OPEN.take().expect("a file is open").1));
+	}
+	save(&edited)
+}
+
+/// Splits a line into words at whitespace, taking quotes and backslashes as a POSIX shell does.
+/// A `#` at the start of a word starts a comment, which is left out.
+pub fn split_words(line: &str) -> Result<Vec<String>> {
+	let unterminated = || usage("unterminated quote");
+	let mut words = Vec::new();
+	let mut word: Option<String> = None;
+	let mut chars = line.chars();
+	while let Some(c) = chars.next() {
+		if c.is_whitespace() {
+			words.extend(word.take());
+			continue;
+		}
+		if c == '#' && word.is_none() {
+			break;
+		}
+		let word = word.get_or_insert_with(String::new);
+		match c {
+			'\'' => loop {
+				match chars.next().ok_or_else(unterminated)? {
+					'\'' => break,
+					c => word.push(c),
+				}
+			},
+			'"' => loop {
+				match chars.next().ok_or_else(unterminated)? {
+					'"' => break,
+					// Only these lose their backslash inside double quotes.
+					'\\' => match chars.next().ok_or_else(unterminated)? {
+						c @ ('"' | '\\' | '$' | '`') => word.push(c),
+						c => {
+							word.push('\\');
+							word.push(c);
+						}
+					},
+					c => word.push(c),
+				}
+			},
+			'\\' => word.extend(chars.next().filter(|&c| c != '\n')),
+			c => word.push(c),
+		}
+	}
+	words.extend(word);
+	Ok(words)
+}
+
+fn usage(message: impl Into<String>) -> PngmeError {
+	PngmeError::InvalidArgument(message.into())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn words(line: &str) -> Vec<String> {
+		split_words(line).unwrap()
+	}
+
+	#[test]
+	fn test_split_words() {
+		assert_eq!(words("text set\tTitle 'Two  words' "), ["text", "set", "Title", "Two  words"]);
+		assert_eq!(words(r#"encode "a \"b\" \n" c\ d"#), ["encode", r#"a "b" \n"#, "c d"]);
+		assert_eq!(words("a'b'\"c\" ''"), ["abc", ""]);
+		assert_eq!(words("remove tEXt # and the rest"), ["remove", "tEXt"]);
+		assert_eq!(words("a#b '#c'"), ["a#b", "#c"]);
+		assert!(words("# only a comment").is_empty());
+		for line in ["'open", "\"open", "\"open\\"] {
+			assert_eq!(split_words(line).err().unwrap().to_string(), "unterminated quote");
+		}
+	}
+}
@@ -0,0 +1,245 @@
+//! Ed25519 signatures over payload chunks, stored in pngme's private `siGN` chunk.
+//!
+//! The chunk data is `version (1 byte) | flags (1 byte) | target chunk type (4 bytes) |
+//! signature (64 bytes)`. The signature covers every chunk of the target type, in file order,
+//! and with [`FLAG_CRITICAL`] also every critical chunk other than `IEND`, so that the payload
+//! cannot be moved to another image. Each signed chunk contributes its type, length, and data.
+
+use std::fmt;
+
+use ed25519_dalek::{
+	pkcs8::{DecodePrivateKey, DecodePublicKey},
+	Signature, Signer, SigningKey, VerifyingKey, SIGNATURE_LENGTH,
+};
+
+use crate::{chunk::Chunk, chunk_type::ChunkType, png::Png, PngmeError, Result};
+
+const VERSION: u8 = 1;
+const LENGTH: usize = 6 + SIGNATURE_LENGTH;
+
+/// Set if the signature also covers the critical chunks.
+const FLAG_CRITICAL: u8 = 1;
+
+/// Separates pngme signatures from any other use of the same key.
+const CONTEXT: &[u8] = b"pngme payload signature\0";
+
+fn invalid(reason: impl Into<String>) -> crate::Error {
+	PngmeError::invalid("signature", reason)
+}
+
+/// A decoded `siGN` chunk.
+#[derive(Debug, PartialEq, Eq)]
+pub struct SignatureChunk {
+	target: ChunkType,
+	covers_critical: bool,
+	signature: Signature,
+}
+
+impl SignatureChunk {
+	pub const TYPE: ChunkType = ChunkType::siGN;
+
+	/// The type of the payload chunks the signature covers.
+	pub fn target(&self) -> &ChunkType {
+		&self.target
+	}
+
+	/// Whether the signature also covers the critical chunks.
+	pub fn covers_critical(&self) -> bool {
+		self.covers_critical
+	}
+
+	/// Encodes the chunk as a raw [`Chunk`].
+	pub fn to_chunk(&self) -> Chunk {
+		let mut data = Vec::with_capacity(LENGTH);
+		data.push(VERSION);
+		data.push(if self.covers_critical { FLAG_CRITICAL } else { 0 });
+		data.extend_from_slice(&self.target.bytes());
+		data.extend_from_slice(&self.signature.to_bytes());
+		Chunk::new(Self::TYPE, data)
+	}
+}
+
+impl TryFrom<&Chunk> for SignatureChunk {
+	type Error = crate::Error;
+	fn try_from(chunk: &Chunk) -> Result<Self> {
+		if chunk.chunk_type() != &Self::TYPE {
+			return Err(invalid(format!("expected siGN, got {}", chunk.chunk_type())));
+		}
+		let data = chunk.data();
+		if data.len() != LENGTH {
+			return Err(invalid(format!("expected {} bytes, got {}", LENGTH, data.len())));
+		}
+		if data[0] != VERSION {
+			return Err(invalid(format!("unsupported version {}", data[0])));
+		}
+		if data[1] & !FLAG_CRITICAL != 0 {
+			return Err(invalid(format!("unknown flags {:#04x}", data[1])));
+		}
+		Ok(Self {
+			target: ChunkType::try_from(<[u8; 4]>::try_from(&data[2..6])?)?,
+			covers_critical: data[1] & FLAG_CRITICAL != 0,
+			signature: Signature::from_slice(&data[6..])?,
+		})
+	}
+}
+
+impl From<SignatureChunk> for Chunk {
+	fn from(signature: SignatureChunk) -> Self {
+		signature.to_chunk()
+	}
+}
+
+impl fmt::Display for SignatureChunk {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "Ed25519 signature over {}", self.target)?;
+		if self.covers_critical {
+			write!(f, " and the critical chunks")?;
+		}
+		Ok(())
+	}
+}
+
+/// Reads an Ed25519 private key from a PKCS#8 PEM file.
+pub fn signing_key_from_pem(pem: &str) -> Result<SigningKey> {
+	Ok(SigningKey::from_pkcs8_pem(pem)?)
+}
+
+/// Reads an Ed25519 public key from a PEM file; a private key yields its public half.
+pub fn verifying_key_from_pem(pem: &str) -> Result<VerifyingKey> {
+	match VerifyingKey::from_public_key_pem(pem) {
+		Ok(key) => Ok(key),
+		Err(_) => Ok(signing_key_from_pem(pem)?.verifying_key()),
+	}
+}
+
+/// Returns the bytes a signature over `target` covers.
+fn signed_message(png: &Png, target: &ChunkType, covers_critical: bool) -> Result<Vec<u8>> {
+	if png.chunks().iter().all(|chunk| chunk.chunk_type() != target) {
+		return Err(invalid(format!("no {} chunk to sign", target)));
+	}
+	let mut message = CONTEXT.to_vec();
+	message.push(if covers_critical { FLAG_CRITICAL } else { 0 });
+	message.extend_from_slice(&target.bytes());
+	let covered = png.chunks().iter().filter(|chunk| {
+		let chunk_type = chunk.chunk_type();
+		chunk_type == target
+			|| covers_critical && chunk_type.is_critical() && chunk_type != &ChunkType::IEND
+	});
+	for chunk in covered {
+		message.extend_from_slice(&chunk.chunk_type().bytes());
+		message.extend_from_slice(&chunk.length().to_be_bytes());
+		message.extend_from_slice(chunk.data());
+	}
+	Ok(message)
+}
+
+/// Finds the signature over `target` in `png`.
+pub fn find(png: &Png, target: &ChunkType) -> Result<Option<SignatureChunk>> {
+	for chunk in png.chunks().iter().filter(|chunk| chunk.chunk_type() == &SignatureChunk::TYPE) {
+		let signature = SignatureChunk::try_from(chunk)?;
+		if signature.target() == target {
+			return Ok(Some(signature));
+		}
+	}
+	Ok(None)
+}
+
+/// Signs the `target` chunks of `png`, replacing any earlier signature over them.
+pub fn sign(
+	png: &mut Png,
+	target: &ChunkType,
+	key: &SigningKey,
+	covers_critical: bool,
+) -> Result<()> {
+	if target == &SignatureChunk::TYPE {
+		return Err(invalid("signature chunks cannot be signed"));
+	}
+	let signature = key.sign(&signed_message(png, target, covers_critical)?);
+	png.retain_chunks(|chunk| {
+		chunk.chunk_type() != &SignatureChunk::TYPE
+			|| SignatureChunk::try_from(chunk)
+				.map_or(true, |signature| signature.target() != target)
+	});
+	let target = ChunkType::try_from(target.bytes())?;
+	png.insert_chunk(SignatureChunk { target, covers_critical, signature }.to_chunk());
+	Ok(())
+}
+
+/// Checks the signature over the `target` chunks of `png`, returning `false` if it does not
+/// match and failing if there is none.
+pub fn verify(png: &Png, target: &ChunkType, key: &VerifyingKey) -> Result<bool> {
+	let signature =
+		find(png, target)?.ok_or_else(|| invalid(format!("{} is not signed", target)))?;
+	let message = signed_message(png, target, signature.covers_critical)?;
+	Ok(key.verify_strict(&message, &signature.signature).is_ok())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn testing_key(seed: u8) -> SigningKey {
+		SigningKey::from_bytes(&[seed; 32])
+	}
+
+	fn testing_png() -> Png {
+		Png::from_chunks(vec![
+			Chunk::new(ChunkType::IHDR, vec![0, 0, 0, 1, 0, 0, 0, 1, 8, 0, 0, 0, 0]),
+			Chunk::new(ChunkType::IDAT, vec![1, 2, 3]),
+			Chunk::new("ruSt".parse().unwrap(), b"payload".to_vec()),
+			Chunk::new(ChunkType::IEND, Vec::new()),
+		])
+	}
+
+	#[test]
+	fn test_sign_and_verify() {
+		let key = testing_key(1);
+		let target: ChunkType = "ruSt".parse().unwrap();
+		let mut png = testing_png();
+		sign(&mut png, &target, &key, false).unwrap();
+		assert!(verify(&png, &target, &key.verifying_key()).unwrap());
+		assert!(!verify(&png, &target, &testing_key(2).verifying_key()).unwrap());
+
+		let signature = find(&png, &target).unwrap().unwrap();
+		assert!(!signature.covers_critical());
+		let chunk = signature.to_chunk();
+		assert_eq!(SignatureChunk::try_from(&chunk).unwrap(), signature);
+	}
+
+	#[test]
+	fn test_tampered_payload() {
+		let key = testing_key(1);
+		let target: ChunkType = "ruSt".parse().unwrap();
+		let mut png = testing_png();
+		sign(&mut png, &target, &key, false).unwrap();
+		png.remove_chunk("ruSt").unwrap();
+		png.insert_chunk(Chunk::new("ruSt".parse().unwrap(), b"tampered".to_vec()));
+		assert!(!verify(&png, &target, &key.verifying_key()).unwrap());
+	}
+
+	#[test]
+	fn test_covers_critical() {
+		let key = testing_key(1);
+		let target: ChunkType = "ruSt".parse().unwrap();
+		let mut png = testing_png();
+		sign(&mut png, &target, &key, false).unwrap();
+		sign(&mut png, &target, &key, true).unwrap();
+		assert_eq!(png.chunks().iter().filter(|c| c.chunk_type() == &ChunkType::siGN).count(), 1);
+		assert!(verify(&png, &target, &key.verifying_key()).unwrap());
+
+		let mut moved = testing_png();
+		moved.remove_chunk("IDAT").unwrap();
+		moved.insert_chunk(Chunk::new(ChunkType::IDAT, vec![4, 5, 6]));
+		let signature = png.chunk_by_type("siGN").unwrap().data().to_vec();
+		moved.insert_chunk(Chunk::new(ChunkType::siGN, signature));
+		assert!(!verify(&moved, &target, &key.verifying_key()).unwrap());
+	}
+
+	#[test]
+	fn test_missing() {
+		let key = testing_key(1);
+		let mut png = testing_png();
+		assert!(sign(&mut png, &"noNe".parse().unwrap(), &key, false).is_err());
+		assert!(verify(&png, &"ruSt".parse().unwrap(), &key.verifying_key()).is_err());
+	}
+}
@@ -0,0 +1,209 @@
+//! Least-significant-bit steganography: payloads hidden in the pixels themselves.
+//!
+//! The payload is framed as `MAGIC | tag (4 bytes) | length (u32) | data` and written one bit
+//! per sample, most significant bit first, into the lowest bit of each sample of the
+//! unfiltered image data. Only images with 8 or 16 bits per sample are supported, since
+//! changing a palette index or a low-depth sample would visibly change the pixel; for 16-bit
+//! samples the lowest bit of the low byte is used.
+
+use std::{iter, mem};
+
+use crate::{
+	chunk_type::ChunkType,
+	idat::{self, FilterStrategy},
+	ihdr::{ColorType, IhdrData},
+	png::Png,
+	zlib, PngmeError, Result,
+};
+
+const MAGIC: &[u8; 4] = b"PMEL";
+const HEADER_LEN: usize = MAGIC.len() + mem::size_of::<[u32; 2]>();
+
+fn unsupported(reason: impl Into<String>) -> crate::Error {
+	PngmeError::Unsupported(format!(
+		"Cannot hide data in the pixels of this image: {}",
+		reason.into()
+	))
+}
+
+/// Returns how many bytes each sample takes, the lowest bit of the last one carrying payload
+/// bits.
+fn sample_size(header: &IhdrData) -> Result<usize> {
+	if header.color_type == ColorType::Indexed {
+		return Err(unsupported("indexed images store palette indices, not samples"));
+	}
+	match header.bit_depth {
+		8 => Ok(1),
+		16 => Ok(2),
+		depth => Err(unsupported(format!("{}-bit samples are too coarse", depth))),
+	}
+}
+
+/// Walks the offsets of the bytes whose lowest bit carries payload bits, in image data as large
+/// as `header` calls for.
+fn carrier_offsets(header: &IhdrData, step: usize) -> impl Iterator<Item = usize> {
+	idat::rows(header).into_iter().flat_map(move |row| row.skip(step - 1).step_by(step))
+}
+
+/// Returns the unfiltered image data of `png`, which must be as large as `header` calls for
+/// before any offset into it is worked out.
+fn pixels(png: &Png, header: &IhdrData) -> Result<Vec<u8>> {
+	let mut data = idat::decompressed_data(png)?;
+	let expected = idat::expected_size(header);
+	if data.len() != expected {
+		let reason = format!("expected {} bytes, got {}", expected, data.len());
+		return Err(PngmeError::invalid("image data", reason));
+	}
+	idat::unfilter(header, &mut data)?;
+	Ok(data)
+}
+
+/// Returns how many payload bytes fit in the pixels of an image with `header`.
+fn header_capacity(header: &IhdrData) -> Result<usize> {
+	let step = sample_size(header)?;
+	let bits: usize = (idat::passes(header).into_iter())
+		.filter(|&(width, _)| width > 0)
+		.map(|(width, height)| height as usize * (idat::row_length(header, width) / step))
+		.sum();
+	Ok((bits / 8).saturating_sub(HEADER_LEN))
+}
+
+/// Returns how many payload bytes fit in the pixels of `png`.
+pub fn capacity(png: &Png) -> Result<usize> {
+	header_capacity(&png.header()?)
+}
+
+/// Hides `payload` under `tag` in the pixels of `png`, replacing any payload hidden before.
+pub fn embed(png: &mut Png, tag: &ChunkType, payload: &[u8]) -> Result<()> {
+	let header = png.header()?;
+	let capacity = header_capacity(&header)?;
+	if payload.len() > capacity {
+		return Err(unsupported(format!(
+			"the payload is {} bytes, but only {} fit",
+			payload.len(),
+			capacity
+		)));
+	}
+	let mut framed = Vec::with_capacity(HEADER_LEN + payload.len());
+	framed.extend_from_slice(MAGIC);
+	framed.extend_from_slice(&tag.bytes());
+	framed.extend_from_slice(&u32::try_from(payload.len())?.to_be_bytes());
+	framed.extend_from_slice(payload);
+
+	let mut data = pixels(png, &header)?;
+	let bits = framed.iter().flat_map(|&byte| (0..8).rev().map(move |n| (byte >> n) & 1));
+	for (offset, bit) in carrier_offsets(&header, sample_size(&header)?).zip(bits) {
+		data[offset] = (data[offset] & !1) | bit;
+	}
+	idat::filter(&header, &mut data, FilterStrategy::Adaptive)?;
+	idat::set_compressed_data(png, zlib::compress(&data));
+	Ok(())
+}
+
+/// Recovers the payload hidden in the pixels of `png` and its tag, if there is one.
+pub fn find(png: &Png) -> Result<Option<(ChunkType, Vec<u8>)>> {
+	let header = png.header()?;
+	let step = sample_size(&header)?;
+	let data = pixels(png, &header)?;
+	let mut offsets = carrier_offsets(&header, step);
+	let mut bytes = iter::from_fn(|| {
+		let mut byte = 0;
+		for _ in 0..8 {
+			byte = (byte << 1) | (data[offsets.next()?] & 1);
+		}
+		Some(byte)
+	});
+	let header: Vec<u8> = bytes.by_ref().take(HEADER_LEN).collect();
+	if header.len() < HEADER_LEN || !header.starts_with(MAGIC) {
+		return Ok(None);
+	}
+	let Ok(tag) = ChunkType::try_from(<[u8; 4]>::try_from(&header[4..8])?) else {
+		return Ok(None);
+	};
+	let len = u32::from_be_bytes(header[8..12].try_into()?) as usize;
+	let payload: Vec<u8> = bytes.take(len).collect();
+	if payload.len() < len {
+		return Ok(None);
+	}
+	Ok(Some((tag, payload)))
+}
+
+/// Recovers the payload hidden under `tag` in the pixels of `png`, if there is one.
+pub fn extract(png: &Png, tag: &ChunkType) -> Result<Option<Vec<u8>>> {
+	Ok(find(png)?.filter(|(found, _)| found == tag).map(|(_, payload)| payload))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::chunk::Chunk;
+
+	fn testing_png(bit_depth: u8, color_type: ColorType, interlace_method: u8) -> Png {
+		let header = IhdrData {
+			width: 16,
+			height: 16,
+			bit_depth,
+			color_type,
+			compression_method: 0,
+			filter_method: 0,
+			interlace_method,
+		};
+		let mut data: Vec<u8> =
+			(0..idat::expected_size(&header)).map(|n| (n * 7 % 256) as u8).collect();
+		for row in idat::rows(&header) {
+			data[row.start - 1] = 0;
+		}
+		Png::from_chunks(vec![
+			header.to_chunk(),
+			Chunk::new(ChunkType::IDAT, zlib::compress(&data)),
+			Chunk::new(ChunkType::IEND, Vec::new()),
+		])
+	}
+
+	#[test]
+	fn test_round_trip() {
+		let tag: ChunkType = "ruSt".parse().unwrap();
+		for (bit_depth, color_type, interlace_method) in
+			[(8, ColorType::Rgb, 0), (16, ColorType::GrayscaleAlpha, 0), (8, ColorType::Rgba, 1)]
+		{
+			let mut png = testing_png(bit_depth, color_type, interlace_method);
+			let before = pixels(&png, &png.header().unwrap()).unwrap();
+			embed(&mut png, &tag, b"hidden message").unwrap();
+			assert_eq!(extract(&png, &tag).unwrap().unwrap(), b"hidden message");
+			assert_eq!(extract(&png, &"noNe".parse().unwrap()).unwrap(), None);
+
+			let after = pixels(&png, &png.header().unwrap()).unwrap();
+			assert!(before.iter().zip(&after).all(|(a, b)| a >> 1 == b >> 1));
+		}
+	}
+
+	#[test]
+	fn test_capacity() {
+		// 16x16 RGB pixels hold 768 bits, or 96 bytes.
+		let png = testing_png(8, ColorType::Rgb, 0);
+		assert_eq!(capacity(&png).unwrap(), 96 - HEADER_LEN);
+		let mut png = testing_png(8, ColorType::Rgb, 0);
+		assert!(embed(&mut png, &"ruSt".parse().unwrap(), &[0; 85]).is_err());
+		assert!(capacity(&testing_png(4, ColorType::Grayscale, 0)).is_err());
+		assert!(capacity(&testing_png(8, ColorType::Indexed, 0)).is_err());
+	}
+
+	#[test]
+	fn test_oversized_header() {
+		// A header claiming 60000x60000 pixels over a few bytes of image data.
+		let mut png = testing_png(8, ColorType::Rgb, 0);
+		let mut header = png.header().unwrap();
+		(header.width, header.height) = (60000, 60000);
+		png.replace_chunk_at(0, header.to_chunk());
+		assert_eq!(capacity(&png).unwrap(), 60000 * 60000 * 3 / 8 - HEADER_LEN);
+		assert!(find(&png).is_err());
+		assert!(embed(&mut png, &"ruSt".parse().unwrap(), b"x").is_err());
+	}
+
+	#[test]
+	fn test_no_payload() {
+		let png = testing_png(8, ColorType::Rgb, 0);
+		assert_eq!(extract(&png, &"ruSt".parse().unwrap()).unwrap(), None);
+		assert_eq!(find(&png).unwrap(), None);
+	}
+}
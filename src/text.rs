@@ -0,0 +1,461 @@
+//! Standard textual chunks.
+//!
+//! A `tEXt` chunk holds a Latin-1 keyword of 1–79 bytes, a NUL separator, and Latin-1 text.
+//! A `zTXt` chunk holds the same keyword and separator, a compression method byte, and the
+//! zlib-compressed text. An `iTXt` chunk holds the keyword and separator, a compression flag
+//! and method, a NUL-terminated language tag, a NUL-terminated UTF-8 translated keyword, and
+//! UTF-8 text, compressed if the flag is set.
+
+use std::str;
+
+use crate::{chunk::Chunk, chunk_type::ChunkType, zlib, PngmeError, Result};
+
+/// The only compression method defined by the PNG specification: zlib deflate.
+const COMPRESSION_METHOD_DEFLATE: u8 = 0;
+
+/// A decoded `tEXt` chunk.
+#[derive(Debug, PartialEq, Eq)]
+pub struct TextChunk {
+	keyword: String,
+	text: String,
+}
+
+fn invalid(reason: impl Into<String>) -> crate::Error {
+	PngmeError::invalid("text chunk", reason)
+}
+
+impl TextChunk {
+	pub const TYPE: [u8; 4] = *b"tEXt";
+
+	/// Creates a `tEXt` chunk, checking that both fields are representable in Latin-1.
+	pub fn new(keyword: &str, text: &str) -> Result<Self> {
+		validate_keyword(keyword)?;
+		if to_latin1(text)?.contains(&0) {
+			return Err(invalid("text contains a NUL byte"));
+		}
+		Ok(Self { keyword: keyword.to_owned(), text: text.to_owned() })
+	}
+
+	pub fn keyword(&self) -> &str {
+		&self.keyword
+	}
+
+	pub fn text(&self) -> &str {
+		&self.text
+	}
+
+	/// Encodes the text chunk as a raw [`Chunk`].
+	pub fn to_chunk(&self) -> Chunk {
+		// Both fields were validated as Latin-1 on construction.
+		let mut data = to_latin1(&self.keyword).unwrap();
+		data.push(0);
+		data.extend(to_latin1(&self.text).unwrap());
+		Chunk::new(ChunkType::try_from(Self::TYPE).unwrap(), data)
+	}
+}
+
+impl TryFrom<&Chunk> for TextChunk {
+	type Error = crate::Error;
+	fn try_from(chunk: &Chunk) -> Result<Self> {
+		if chunk.chunk_type().bytes() != Self::TYPE {
+			return Err(invalid(format!("expected tEXt, got {}", chunk.chunk_type())));
+		}
+		let (keyword, text) = split_keyword(chunk.data())?;
+		Self::new(&from_latin1(keyword), &from_latin1(text))
+	}
+}
+
+/// A decoded `zTXt` chunk.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ZtxtChunk {
+	keyword: String,
+	text: String,
+}
+
+impl ZtxtChunk {
+	pub const TYPE: [u8; 4] = *b"zTXt";
+
+	/// Creates a `zTXt` chunk, checking that both fields are representable in Latin-1.
+	pub fn new(keyword: &str, text: &str) -> Result<Self> {
+		validate_keyword(keyword)?;
+		to_latin1(text)?;
+		Ok(Self { keyword: keyword.to_owned(), text: text.to_owned() })
+	}
+
+	pub fn keyword(&self) -> &str {
+		&self.keyword
+	}
+
+	pub fn text(&self) -> &str {
+		&self.text
+	}
+
+	/// Compresses the text and encodes the chunk as a raw [`Chunk`].
+	pub fn to_chunk(&self) -> Chunk {
+		// Both fields were validated as Latin-1 on construction.
+		let mut data = to_latin1(&self.keyword).unwrap();
+		data.push(0);
+		data.push(COMPRESSION_METHOD_DEFLATE);
+		data.extend(zlib::compress(&to_latin1(&self.text).unwrap()));
+		Chunk::new(ChunkType::try_from(Self::TYPE).unwrap(), data)
+	}
+}
+
+impl TryFrom<&Chunk> for ZtxtChunk {
+	type Error = crate::Error;
+	fn try_from(chunk: &Chunk) -> Result<Self> {
+		if chunk.chunk_type().bytes() != Self::TYPE {
+			return Err(invalid(format!("expected zTXt, got {}", chunk.chunk_type())));
+		}
+		let (keyword, rest) = split_keyword(chunk.data())?;
+		let (&method, compressed) =
+			rest.split_first().ok_or_else(|| invalid("missing compression method"))?;
+		if method != COMPRESSION_METHOD_DEFLATE {
+			return Err(invalid(format!("unknown compression method {}", method)));
+		}
+		Self::new(
+			&from_latin1(keyword),
+			&from_latin1(&zlib::decompress(compressed, zlib::MAX_METADATA)?),
+		)
+	}
+}
+
+/// A decoded `iTXt` chunk.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ItxtChunk {
+	keyword: String,
+	compressed: bool,
+	language_tag: String,
+	translated_keyword: String,
+	text: String,
+}
+
+impl ItxtChunk {
+	pub const TYPE: [u8; 4] = *b"iTXt";
+
+	/// Creates an `iTXt` chunk. The language tag may be empty, and is otherwise made of
+	/// hyphen-separated alphanumeric ASCII words, such as `en-GB`.
+	pub fn new(
+		keyword: &str,
+		language_tag: &str,
+		translated_keyword: &str,
+		text: &str,
+		compressed: bool,
+	) -> Result<Self> {
+		validate_keyword(keyword)?;
+		if !language_tag.is_empty()
+			&& !language_tag.split('-').all(|word| {
+				(1..=8).contains(&word.len()) && word.bytes().all(|b| b.is_ascii_alphanumeric())
+			}) {
+			return Err(invalid(format!("invalid language tag {:?}", language_tag)));
+		}
+		if translated_keyword.contains('\0') {
+			return Err(invalid("translated keyword contains a NUL byte"));
+		}
+		Ok(Self {
+			keyword: keyword.to_owned(),
+			compressed,
+			language_tag: language_tag.to_owned(),
+			translated_keyword: translated_keyword.to_owned(),
+			text: text.to_owned(),
+		})
+	}
+
+	pub fn keyword(&self) -> &str {
+		&self.keyword
+	}
+
+	pub fn is_compressed(&self) -> bool {
+		self.compressed
+	}
+
+	pub fn language_tag(&self) -> &str {
+		&self.language_tag
+	}
+
+	pub fn translated_keyword(&self) -> &str {
+		&self.translated_keyword
+	}
+
+	pub fn text(&self) -> &str {
+		&self.text
+	}
+
+	/// Encodes the chunk as a raw [`Chunk`], compressing the text if requested.
+	pub fn to_chunk(&self) -> Chunk {
+		// The keyword was validated as Latin-1 on construction.
+		let mut data = to_latin1(&self.keyword).unwrap();
+		data.push(0);
+		data.push(self.compressed.into());
+		data.push(COMPRESSION_METHOD_DEFLATE);
+		data.extend_from_slice(self.language_tag.as_bytes());
+		data.push(0);
+		data.extend_from_slice(self.translated_keyword.as_bytes());
+		data.push(0);
+		if self.compressed {
+			data.extend(zlib::compress(self.text.as_bytes()));
+		} else {
+			data.extend_from_slice(self.text.as_bytes());
+		}
+		Chunk::new(ChunkType::try_from(Self::TYPE).unwrap(), data)
+	}
+}
+
+impl TryFrom<&Chunk> for ItxtChunk {
+	type Error = crate::Error;
+	fn try_from(chunk: &Chunk) -> Result<Self> {
+		if chunk.chunk_type().bytes() != Self::TYPE {
+			return Err(invalid(format!("expected iTXt, got {}", chunk.chunk_type())));
+		}
+		let (keyword, rest) = split_keyword(chunk.data())?;
+		let [flag, method, rest @ ..] = rest else {
+			return Err(invalid("missing compression flag and method"));
+		};
+		let compressed = match flag {
+			0 => false,
+			1 => true,
+			_ => return Err(invalid(format!("invalid compression flag {}", flag))),
+		};
+		if compressed && *method != COMPRESSION_METHOD_DEFLATE {
+			return Err(invalid(format!("unknown compression method {}", method)));
+		}
+		let (language_tag, rest) = split_keyword(rest)?;
+		let (translated_keyword, text) = split_keyword(rest)?;
+		let text =
+			if compressed { zlib::decompress(text, zlib::MAX_METADATA)? } else { text.to_vec() };
+		Self::new(
+			&from_latin1(keyword),
+			str::from_utf8(language_tag)?,
+			str::from_utf8(translated_keyword)?,
+			&String::from_utf8(text)?,
+			compressed,
+		)
+	}
+}
+
+/// Any of the standard textual chunks.
+#[derive(Debug, PartialEq, Eq)]
+pub enum TextualChunk {
+	Text(TextChunk),
+	Ztxt(ZtxtChunk),
+	Itxt(ItxtChunk),
+}
+
+impl TextualChunk {
+	pub fn keyword(&self) -> &str {
+		match self {
+			Self::Text(text) => text.keyword(),
+			Self::Ztxt(ztxt) => ztxt.keyword(),
+			Self::Itxt(itxt) => itxt.keyword(),
+		}
+	}
+
+	pub fn text(&self) -> &str {
+		match self {
+			Self::Text(text) => text.text(),
+			Self::Ztxt(ztxt) => ztxt.text(),
+			Self::Itxt(itxt) => itxt.text(),
+		}
+	}
+
+	pub fn to_chunk(&self) -> Chunk {
+		match self {
+			Self::Text(text) => text.to_chunk(),
+			Self::Ztxt(ztxt) => ztxt.to_chunk(),
+			Self::Itxt(itxt) => itxt.to_chunk(),
+		}
+	}
+
+	/// Returns the same kind of chunk, with the same keyword and other fields, holding `text`.
+	/// Fails if a `tEXt` or `zTXt` chunk cannot hold the text in Latin-1.
+	pub fn with_text(&self, text: &str) -> Result<Self> {
+		match self {
+			Self::Text(old) => TextChunk::new(old.keyword(), text).map(Self::Text),
+			Self::Ztxt(old) => ZtxtChunk::new(old.keyword(), text).map(Self::Ztxt),
+			Self::Itxt(old) => ItxtChunk::new(
+				old.keyword(),
+				old.language_tag(),
+				old.translated_keyword(),
+				text,
+				old.is_compressed(),
+			)
+			.map(Self::Itxt),
+		}
+	}
+}
+
+impl TryFrom<&Chunk> for TextualChunk {
+	type Error = crate::Error;
+	fn try_from(chunk: &Chunk) -> Result<Self> {
+		match chunk.chunk_type().bytes() {
+			TextChunk::TYPE => TextChunk::try_from(chunk).map(Self::Text),
+			ZtxtChunk::TYPE => ZtxtChunk::try_from(chunk).map(Self::Ztxt),
+			ItxtChunk::TYPE => ItxtChunk::try_from(chunk).map(Self::Itxt),
+			_ => Err(invalid(format!("{} is not a textual chunk", chunk.chunk_type()))),
+		}
+	}
+}
+
+/// Splits chunk data at the NUL byte terminating the keyword.
+pub(crate) fn split_keyword(data: &[u8]) -> Result<(&[u8], &[u8])> {
+	let nul = data.iter().position(|&b| b == 0).ok_or_else(|| invalid("missing NUL separator"))?;
+	Ok((&data[..nul], &data[nul + 1..]))
+}
+
+/// Checks the keyword rules shared by `tEXt`, `zTXt`, and `iTXt`.
+pub(crate) fn validate_keyword(keyword: &str) -> Result<()> {
+	let bytes = to_latin1(keyword)?;
+	if !(1..=79).contains(&bytes.len()) {
+		return Err(invalid(format!("keyword must be 1-79 bytes long, got {}", bytes.len())));
+	}
+	if !bytes.iter().all(|&b| matches!(b, 32..=126 | 161..=255)) {
+		return Err(invalid("keyword contains non-printable characters"));
+	}
+	if keyword.starts_with(' ') || keyword.ends_with(' ') || keyword.contains("  ") {
+		return Err(invalid("keyword has leading, trailing, or consecutive spaces"));
+	}
+	Ok(())
+}
+
+pub(crate) fn to_latin1(s: &str) -> Result<Vec<u8>> {
+	s.chars()
+		.map(|c| {
+			u8::try_from(c).map_err(|_| invalid(format!("{:?} is not a Latin-1 character", c)))
+		})
+		.collect()
+}
+
+pub(crate) fn from_latin1(bytes: &[u8]) -> String {
+	bytes.iter().copied().map(char::from).collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_text_chunk_round_trip() {
+		let text = TextChunk::new("Author", "Jöhn Doe").unwrap();
+		let chunk = text.to_chunk();
+		assert_eq!(chunk.chunk_type().to_string(), "tEXt");
+		assert_eq!(chunk.data(), b"Author\0J\xf6hn Doe");
+		assert_eq!(TextChunk::try_from(&chunk).unwrap(), text);
+	}
+
+	#[test]
+	fn test_invalid_keyword() {
+		assert!(TextChunk::new("", "text").is_err());
+		assert!(TextChunk::new(&"k".repeat(80), "text").is_err());
+		assert!(TextChunk::new(" Author", "text").is_err());
+		assert!(TextChunk::new("Au  thor", "text").is_err());
+		assert!(TextChunk::new("Au\nthor", "text").is_err());
+		assert!(TextChunk::new("Автор", "text").is_err());
+	}
+
+	#[test]
+	fn test_with_text() {
+		let itxt = TextualChunk::Itxt(ItxtChunk::new("Title", "de", "Titel", "alt", true).unwrap());
+		let TextualChunk::Itxt(edited) = itxt.with_text("neü").unwrap() else {
+			panic!("the kind of chunk changed");
+		};
+		assert_eq!(edited.text(), "neü");
+		assert_eq!(edited.translated_keyword(), "Titel");
+		assert!(edited.is_compressed());
+
+		let ztxt = TextualChunk::Ztxt(ZtxtChunk::new("Comment", "old").unwrap());
+		assert_eq!(ztxt.with_text("new").unwrap().to_chunk().chunk_type().to_string(), "zTXt");
+		assert!(ztxt.with_text("Иван").is_err());
+	}
+
+	#[test]
+	fn test_invalid_text() {
+		assert!(TextChunk::new("Author", "Иван").is_err());
+		assert!(TextChunk::new("Author", "a\0b").is_err());
+	}
+
+	#[test]
+	fn test_missing_separator() {
+		let chunk = Chunk::new(ChunkType::try_from(TextChunk::TYPE).unwrap(), b"Author".to_vec());
+		assert!(TextChunk::try_from(&chunk).is_err());
+	}
+
+	#[test]
+	fn test_wrong_chunk_type() {
+		let chunk = Chunk::new("ruSt".parse().unwrap(), b"Author\0text".to_vec());
+		assert!(TextChunk::try_from(&chunk).is_err());
+		assert!(ZtxtChunk::try_from(&chunk).is_err());
+	}
+
+	#[test]
+	fn test_ztxt_chunk_round_trip() {
+		let ztxt = ZtxtChunk::new("Comment", &"Compressible text. ".repeat(100)).unwrap();
+		let chunk = ztxt.to_chunk();
+		assert_eq!(chunk.chunk_type().to_string(), "zTXt");
+		assert!(chunk.data().starts_with(b"Comment\0\0"));
+		assert!(chunk.data().len() < ztxt.text().len());
+		assert_eq!(ZtxtChunk::try_from(&chunk).unwrap(), ztxt);
+	}
+
+	#[test]
+	fn test_ztxt_unknown_compression_method() {
+		let mut data = ZtxtChunk::new("Comment", "text").unwrap().to_chunk().data().to_vec();
+		data[8] = 1;
+		let chunk = Chunk::new(ChunkType::try_from(ZtxtChunk::TYPE).unwrap(), data);
+		assert!(ZtxtChunk::try_from(&chunk).is_err());
+	}
+
+	#[test]
+	fn test_itxt_chunk_round_trip() {
+		for compressed in [false, true] {
+			let itxt =
+				ItxtChunk::new("Title", "ru", "Заголовок", "Привет, мир", compressed).unwrap();
+			let chunk = itxt.to_chunk();
+			assert_eq!(chunk.chunk_type().to_string(), "iTXt");
+			assert_eq!(ItxtChunk::try_from(&chunk).unwrap(), itxt);
+		}
+	}
+
+	#[test]
+	fn test_itxt_layout() {
+		let chunk = ItxtChunk::new("Title", "en-GB", "Title", "Hello", false).unwrap().to_chunk();
+		assert_eq!(chunk.data(), b"Title\0\0\0en-GB\0Title\0Hello");
+	}
+
+	#[test]
+	fn test_itxt_invalid_fields() {
+		assert!(ItxtChunk::new("Title", "not a tag", "", "text", false).is_err());
+		assert!(ItxtChunk::new("Title", "toolongword", "", "text", false).is_err());
+		assert!(ItxtChunk::new("Title", "en", "a\0b", "text", false).is_err());
+		assert!(ItxtChunk::new("", "en", "", "text", false).is_err());
+	}
+
+	#[test]
+	fn test_itxt_invalid_utf8() {
+		let chunk = Chunk::new(
+			ChunkType::try_from(ItxtChunk::TYPE).unwrap(),
+			b"Title\0\0\0en\0\0\xff".to_vec(),
+		);
+		assert!(ItxtChunk::try_from(&chunk).is_err());
+	}
+
+	#[test]
+	fn test_textual_chunk() {
+		let chunk = ZtxtChunk::new("Comment", "text").unwrap().to_chunk();
+		let textual = TextualChunk::try_from(&chunk).unwrap();
+		assert!(matches!(textual, TextualChunk::Ztxt(_)));
+		assert_eq!(textual.keyword(), "Comment");
+		assert_eq!(textual.text(), "text");
+
+		let chunk = Chunk::new("ruSt".parse().unwrap(), b"Comment\0text".to_vec());
+		assert!(TextualChunk::try_from(&chunk).is_err());
+	}
+
+	#[test]
+	fn test_ztxt_corrupt_stream() {
+		let chunk = Chunk::new(
+			ChunkType::try_from(ZtxtChunk::TYPE).unwrap(),
+			b"Comment\0\0not zlib".to_vec(),
+		);
+		assert!(ZtxtChunk::try_from(&chunk).is_err());
+	}
+}
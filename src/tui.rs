@@ -0,0 +1,458 @@
+//! An interactive browser for the chunks of a PNG file.
+//!
+//! The chunks are listed on the left, and the selected one is shown on the right as a hex
+//! dump, as text, or decoded, for the types the [`DecoderRegistry`] knows. Chunks can be
+//! deleted, their data extracted to a file, and their text edited. Changes are made to the PNG
+//! in memory, where they can be undone and redone, and only written when they are saved.
+
+use std::{
+	mem,
+	path::{Path, PathBuf},
+	str,
+};
+
+use pngme::{
+	chunk::Chunk,
+	decoder::DecoderRegistry,
+	hexdump::{self, HexDump},
+	journal::{Journal, Operation},
+	text::TextualChunk,
+	Png, Result,
+};
+use ratatui::{
+	crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+	layout::{Constraint, Layout},
+	style::{Color, Modifier, Style as TextStyle},
+	text::{Line, Span},
+	widgets::{Block, List, ListItem, ListState, Paragraph, Wrap},
+	DefaultTerminal, Frame,
+};
+
+use crate::output::{self, Style};
+
+/// Lines scrolled by Page Up and Page Down.
+const PAGE: u16 = 10;
+
+const HELP: &str =
+	"↑↓ select  tab view  pgup/pgdn scroll  d delete  x extract  e edit  u undo  r redo  s save  q quit";
+
+/// Browses `png` until the user quits, calling `save` to write it and `extract` to write the
+/// data of a chunk to a file.
+pub fn browse(
+	png: Png,
+	name: &str,
+	mut save: impl FnMut(&Png) -> Result<()>,
+	mut extract: impl FnMut(&Path, &[u8]) -> Result<()>,
+) -> Result<()> {
+	let mut terminal = ratatui::try_init()?;
+	let result = run(&mut terminal, Browser::new(png, name), &mut save, &mut extract);
+	ratatui::restore();
+	result
+}
+
+fn run(
+	terminal: &mut DefaultTerminal,
+	mut browser: Browser,
+	save: &mut impl FnMut(&Png) -> Result<()>,
+	extract: &mut impl FnMut(&Path, &[u8]) -> Result<()>,
+) -> Result<()> {
+	loop {
+		terminal.draw(|frame| browser.draw(frame))?;
+		let Event::Key(key) = event::read()? else {
+			continue;
+		};
+		if key.kind != KeyEventKind::Press {
+			continue;
+		}
+		let (result, printed, done) = match browser.handle(key) {
+			Action::None => continue,
+			Action::Quit => return Ok(()),
+			Action::Save => {
+				let (result, printed) = output::capture(|| save(&browser.png));
+				if result.is_ok() {
+					browser.journal.mark_saved();
+				}
+				(result, printed, format!("Saved {}", browser.name))
+			}
+			Action::Extract(path) => {
+				let data = browser.chunk().map_or(&[][..], Chunk::data);
+				let (result, printed) = output::capture(|| extract(&path, data));
+				(result, printed, format!("Extracted {} bytes to {}", data.len(), path.display()))
+			}
+		};
+		// Dry runs print what they would write, the last line summing it up.
+		browser.status = match result {
+			Ok(()) => String::from_utf8_lossy(&printed).lines().last().map_or(done, str::to_owned),
+			Err(error) => format!("Error: {}", error),
+		};
+		// Warnings are written to stderr, over the screen.
+		terminal.clear()?;
+	}
+}
+
+/// What the preview pane shows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum View {
+	Hex,
+	Text,
+	Decoded,
+}
+
+impl View {
+	fn next(self) -> Self {
+		match self {
+			Self::Hex => Self::Text,
+			Self::Text => Self::Decoded,
+			Self::Decoded => Self::Hex,
+		}
+	}
+
+	fn title(self) -> &'static str {
+		match self {
+			Self::Hex => "Hex",
+			Self::Text => "Text",
+			Self::Decoded => "Decoded",
+		}
+	}
+}
+
+/// A line being typed at the bottom of the screen, and what it is for.
+enum Prompt {
+	/// The file to extract the data of the selected chunk to.
+	Extract(String),
+	/// The new text of the selected chunk, with its newlines and backslashes escaped.
+	Edit(String),
+}
+
+/// What the browser needs done outside of it after a key press.
+enum Action {
+	None,
+	Quit,
+	Save,
+	Extract(PathBuf),
+}
+
+struct Browser {
+	png: Png,
+	name: String,
+	decoders: DecoderRegistry,
+	selected: usize,
+	view: View,
+	scroll: u16,
+	prompt: Option<Prompt>,
+	/// The message shown in place of the help line until the next key press.
+	status: String,
+	journal: Journal,
+	/// Set when `q` is pressed with unsaved changes, so that pressing it again quits.
+	quitting: bool,
+}
+
+impl Browser {
+	fn new(png: Png, name: &str) -> Self {
+		Self {
+			png,
+			name: name.to_owned(),
+			decoders: DecoderRegistry::default(),
+			selected: 0,
+			view: View::Hex,
+			scroll: 0,
+			prompt: None,
+			status: String::new(),
+			journal: Journal::new(),
+			quitting: false,
+		}
+	}
+
+	fn chunk(&self) -> Option<&Chunk> {
+		self.png.chunks().get(self.selected)
+	}
+
+	fn handle(&mut self, key: KeyEvent) -> Action {
+		if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+			return Action::Quit;
+		}
+		if self.prompt.is_some() {
+			return self.handle_prompt(key);
+		}
+		let quitting = mem::take(&mut self.quitting);
+		self.status.clear();
+		let last = self.png.chunks().len().saturating_sub(1);
+		match key.code {
+			KeyCode::Char('q') | KeyCode::Esc if self.journal.is_modified() && !quitting => {
+				self.quitting = true;
+				self.status = "Unsaved changes: s saves them, q again quits without".to_owned();
+			}
+			KeyCode::Char('q') | KeyCode::Esc => return Action::Quit,
+			KeyCode::Up | KeyCode::Char('k') => self.select(self.selected.saturating_sub(1)),
+			KeyCode::Down | KeyCode::Char('j') => self.select((self.selected + 1).min(last)),
+			KeyCode::Home | KeyCode::Char('g') => self.select(0),
+			KeyCode::End | KeyCode::Char('G') => self.select(last),
+			KeyCode::Tab | KeyCode::Char('v') => {
+				self.view = self.view.next();
+				self.scroll = 0;
+			}
+			KeyCode::PageDown | KeyCode::Char(' ') => {
+				self.scroll = self.scroll.saturating_add(PAGE)
+			}
+			KeyCode::PageUp => self.scroll = self.scroll.saturating_sub(PAGE),
+			KeyCode::Char('d') | KeyCode::Delete => self.delete(),
+			KeyCode::Char('x') => {
+				if let Some(chunk) = self.chunk() {
+					let name = format!("{}-{}.bin", chunk.chunk_type(), self.selected);
+					self.prompt = Some(Prompt::Extract(name));
+				}
+			}
+			KeyCode::Char('e') => self.start_edit(),
+			KeyCode::Char('u') => self.undo(),
+			KeyCode::Char('r') => self.redo(),
+			KeyCode::Char('s') => return Action::Save,
+			_ => {}
+		}
+		Action::None
+	}
+
+	fn handle_prompt(&mut self, key: KeyEvent) -> Action {
+		let (Some(Prompt::Extract(line)) | Some(Prompt::Edit(line))) = &mut self.prompt else {
+			return Action::None;
+		};
+		match key.code {
+			KeyCode::Char(c) => line.push(c),
+			KeyCode::Backspace => {
+				line.pop();
+			}
+			KeyCode::Esc => self.prompt = None,
+			KeyCode::Enter => match self.prompt.take() {
+				Some(Prompt::Extract(path)) if !path.is_empty() => {
+					return Action::Extract(path.into())
+				}
+				Some(Prompt::Edit(line)) => self.finish_edit(&unescape_line(&line)),
+				_ => {}
+			},
+			_ => {}
+		}
+		Action::None
+	}
+
+	fn select(&mut self, index: usize) {
+		if index != self.selected {
+			self.selected = index;
+			self.scroll = 0;
+		}
+	}
+
+	fn delete(&mut self) {
+		let Some(chunk) = self.chunk() else {
+			return;
+		};
+		let chunk_type = *chunk.chunk_type();
+		if chunk_type.is_critical() {
+			self.status =
+				format!("{} is a critical chunk, the image cannot do without it", chunk_type);
+			return;
+		}
+		let operation = Operation::Remove { index: self.selected, chunk: chunk.clone() };
+		self.journal.apply(&mut self.png, vec![operation]);
+		self.select(self.selected.min(self.png.chunks().len().saturating_sub(1)));
+		self.scroll = 0;
+		self.status = format!("Deleted {} chunk", chunk_type);
+	}
+
+	fn undo(&mut self) {
+		if self.journal.undo(&mut self.png) {
+			self.status = "Undone".to_owned();
+			self.select(self.selected.min(self.png.chunks().len().saturating_sub(1)));
+		} else {
+			self.status = "Nothing to undo".to_owned();
+		}
+	}
+
+	fn redo(&mut self) {
+		if self.journal.redo(&mut self.png) {
+			self.status = "Redone".to_owned();
+			self.select(self.selected.min(self.png.chunks().len().saturating_sub(1)));
+		} else {
+			self.status = "Nothing to redo".to_owned();
+		}
+	}
+
+	fn start_edit(&mut self) {
+		let Some(chunk) = self.chunk() else {
+			return;
+		};
+		let text = match TextualChunk::try_from(chunk) {
+			Ok(text) => text.text().to_owned(),
+			Err(_) => match str::from_utf8(chunk.data()) {
+				Ok(text) => text.to_owned(),
+				Err(_) => {
+					self.status =
+						"Only text can be edited, this chunk holds binary data".to_owned();
+					return;
+				}
+			},
+		};
+		self.prompt = Some(Prompt::Edit(escape_line(&text)));
+	}
+
+	/// Replaces the text of the selected chunk, keeping the kind of textual chunk it is.
+	fn finish_edit(&mut self, text: &str) {
+		let Some(chunk) = self.chunk() else {
+			return;
+		};
+		let edited = match TextualChunk::try_from(chunk) {
+			Ok(textual) => textual.with_text(text).map(|textual| textual.to_chunk()),
+			Err(_) => Ok(Chunk::new(*chunk.chunk_type(), text.as_bytes().to_vec())),
+		};
+		match edited {
+			Ok(edited) if edited == *chunk => self.status = "Unchanged".to_owned(),
+			Ok(edited) => {
+				let status = format!("Edited {} chunk", edited.chunk_type());
+				let operation =
+					Operation::Replace { index: self.selected, old: chunk.clone(), new: edited };
+				self.journal.apply(&mut self.png, vec![operation]);
+				self.status = status;
+			}
+			Err(error) => self.status = format!("Error: {}", error),
+		}
+	}
+
+	fn draw(&self, frame: &mut Frame<'_>) {
+		let [main, bottom] =
+			Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(frame.area());
+		let [list_area, preview_area] =
+			Layout::horizontal([Constraint::Length(24), Constraint::Min(0)]).areas(main);
+
+		let items: Vec<ListItem<'_>> = (self.png.chunks().iter().enumerate())
+			.map(|(index, chunk)| {
+				ListItem::new(Line::from(vec![
+					Span::raw(format!("{:>3} ", index)),
+					Span::styled(chunk.chunk_type().to_string(), type_style(chunk)),
+					Span::raw(format!(" {:>10}", chunk.length())),
+				]))
+			})
+			.collect();
+		let modified = if self.journal.is_modified() { " [modified]" } else { "" };
+		let list = List::new(items)
+			.block(Block::bordered().title(format!(" {}{} ", self.name, modified)))
+			.highlight_style(Modifier::REVERSED);
+		let mut state = ListState::default().with_selected(Some(self.selected));
+		frame.render_stateful_widget(list, list_area, &mut state);
+
+		let mut preview = Paragraph::new(self.preview())
+			.block(Block::bordered().title(format!(" {} ", self.view.title())))
+			.scroll((self.scroll, 0));
+		if self.view != View::Hex {
+			preview = preview.wrap(Wrap { trim: false });
+		}
+		frame.render_widget(preview, preview_area);
+
+		let line = match &self.prompt {
+			Some(Prompt::Extract(path)) => format!("Extract to: {}█", path),
+			Some(Prompt::Edit(text)) => format!("Text, \\n for new lines: {}█", text),
+			None if !self.status.is_empty() => self.status.clone(),
+			None => HELP.to_owned(),
+		};
+		// Long lines show their end, where the cursor is.
+		let width = bottom.width as usize;
+		let skip = line.chars().count().saturating_sub(width);
+		frame.render_widget(Paragraph::new(line.chars().skip(skip).collect::<String>()), bottom);
+	}
+
+	fn preview(&self) -> Vec<Line<'_>> {
+		let Some((offset, chunk)) = self.png.chunks_with_offsets().nth(self.selected) else {
+			return vec![Line::raw("No chunks")];
+		};
+		let data = chunk.data();
+		match self.view {
+			View::Hex => HexDump::new(data, offset + 8).to_string().lines().map(line).collect(),
+			View::Text => match TextualChunk::try_from(chunk) {
+				Ok(text) => {
+					let mut lines = vec![Line::raw(format!("{}:", text.keyword())), Line::raw("")];
+					lines.extend(text.text().lines().map(line));
+					lines
+				}
+				Err(_) => {
+					data.split(|&byte| byte == b'\n').map(|l| line(hexdump::escape(l))).collect()
+				}
+			},
+			View::Decoded => {
+				let chunk_type = chunk.chunk_type();
+				let crc = if chunk.has_valid_crc() {
+					Span::raw("valid")
+				} else {
+					Span::styled("bad", paint(Style::Error))
+				};
+				let mut lines = vec![
+					Line::from(vec![
+						Span::styled(chunk_type.to_string(), type_style(chunk)),
+						Span::raw(format!(
+							": {}",
+							chunk_type.description().unwrap_or("unregistered chunk type")
+						)),
+					]),
+					Line::raw(format!("Offset {:#010x}, {} bytes of data", offset, chunk.length())),
+					Line::from(vec![Span::raw(format!("CRC {:08x}, ", chunk.crc())), crc]),
+					Line::raw(""),
+				];
+				match self.decoders.decode(chunk) {
+					Some(decoded) => {
+						lines.push(line(decoded.summary));
+						for (name, value) in decoded.fields {
+							lines.push(line(format!("  {}: {}", name, value)));
+						}
+					}
+					None => lines.push(line(format!("No decoder for {} chunks", chunk_type))),
+				}
+				lines
+			}
+		}
+	}
+}
+
+fn line<'a>(text: impl Into<String>) -> Line<'a> {
+	Line::raw(text.into())
+}
+
+/// The colors of [`output::paint`], for the terminal the browser draws on.
+fn paint(style: Style) -> TextStyle {
+	if !output::is_colored() {
+		return TextStyle::default();
+	}
+	match style {
+		Style::Critical => TextStyle::default().add_modifier(Modifier::BOLD),
+		Style::Ancillary => TextStyle::default().fg(Color::Cyan),
+		Style::Private => TextStyle::default().fg(Color::Magenta),
+		Style::Error => TextStyle::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+		Style::Added => TextStyle::default().fg(Color::Green),
+		Style::Removed => TextStyle::default().fg(Color::Red),
+		Style::Modified => TextStyle::default().fg(Color::Yellow),
+	}
+}
+
+fn type_style(chunk: &Chunk) -> TextStyle {
+	paint(Style::of(chunk.chunk_type()))
+}
+
+/// Puts text on one line, writing newlines as `\n` and backslashes as `\\`.
+fn escape_line(text: &str) -> String {
+	text.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+/// Reverses [`escape_line`]. Other backslashes are kept as they are.
+fn unescape_line(line: &str) -> String {
+	let mut text = String::with_capacity(line.len());
+	let mut chars = line.chars().peekable();
+	while let Some(c) = chars.next() {
+		match (c, chars.peek()) {
+			('\\', Some('n')) => {
+				text.push('\n');
+				chars.next();
+			}
+			('\\', Some('\\')) => {
+				text.push('\\');
+				chars.next();
+			}
+			_ => text.push(c),
+		}
+	}
+	text
+}
@@ -0,0 +1,338 @@
+//! Expanding the paths given on the command line into the files they name.
+//!
+//! A path is a file, a directory to search, or a glob pattern. Patterns support `?` and `*`
+//! within one path component, `**` across any number of components, and `[...]` character
+//! classes, negated as `[!...]`. The path separator is always written as `/`.
+
+use std::{
+	fmt, fs,
+	path::{Path, PathBuf},
+	str::FromStr,
+};
+
+use crate::{PngmeError, Result};
+
+fn invalid(reason: impl Into<String>) -> crate::Error {
+	PngmeError::invalid("pattern", reason)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+	Literal(char),
+	/// `?`
+	AnyChar,
+	/// `*`
+	AnyChars,
+	/// `**/`, which also matches no directories at all.
+	AnyDirectories,
+	/// `**` not followed by `/`.
+	Anything,
+	Class {
+		negated: bool,
+		ranges: Vec<(char, char)>,
+	},
+}
+
+/// A compiled glob pattern.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pattern {
+	source: String,
+	tokens: Vec<Token>,
+}
+
+impl Pattern {
+	/// Returns `true` if `text` contains any of the special characters of a pattern.
+	pub fn is_pattern(text: &str) -> bool {
+		text.contains(['*', '?', '['])
+	}
+
+	/// Returns `true` if the whole of `path` matches the pattern.
+	pub fn matches(&self, path: &str) -> bool {
+		let path: Vec<char> = path.chars().collect();
+		matches_tokens(&self.tokens, &path)
+	}
+
+	/// Returns `true` if the pattern is matched against whole paths rather than file names.
+	fn has_directories(&self) -> bool {
+		self.source.contains('/')
+	}
+}
+
+fn matches_tokens(tokens: &[Token], path: &[char]) -> bool {
+	let Some((token, rest)) = tokens.split_first() else {
+		return path.is_empty();
+	};
+	match token {
+		Token::Literal(c) => path.first() == Some(c) && matches_tokens(rest, &path[1..]),
+		Token::AnyChar => {
+			path.first().is_some_and(|&c| c != '/') && matches_tokens(rest, &path[1..])
+		}
+		Token::Class { negated, ranges } => {
+			path.first().is_some_and(|&c| {
+				let in_class = ranges.iter().any(|&(low, high)| (low..=high).contains(&c));
+				c != '/' && in_class != *negated
+			}) && matches_tokens(rest, &path[1..])
+		}
+		Token::AnyChars => {
+			let component = path.iter().position(|&c| c == '/').unwrap_or(path.len());
+			(0..=component).any(|n| matches_tokens(rest, &path[n..]))
+		}
+		Token::AnyDirectories => {
+			matches_tokens(rest, path)
+				|| (0..path.len()).any(|n| path[n] == '/' && matches_tokens(rest, &path[n + 1..]))
+		}
+		Token::Anything => (0..=path.len()).any(|n| matches_tokens(rest, &path[n..])),
+	}
+}
+
+impl FromStr for Pattern {
+	type Err = crate::Error;
+	fn from_str(s: &str) -> Result<Self> {
+		let mut tokens = Vec::new();
+		let mut chars = s.chars().peekable();
+		while let Some(c) = chars.next() {
+			tokens.push(match c {
+				'?' => Token::AnyChar,
+				'*' if chars.peek() == Some(&'*') => {
+					chars.next();
+					if chars.peek() == Some(&'/') {
+						chars.next();
+						Token::AnyDirectories
+					} else {
+						Token::Anything
+					}
+				}
+				'*' => Token::AnyChars,
+				'[' => {
+					let negated = chars.next_if_eq(&'!').is_some();
+					let mut ranges = Vec::new();
+					loop {
+						let low = match chars.next() {
+							Some(']') if !ranges.is_empty() => break,
+							Some(c) => c,
+							None => return Err(invalid(format!("unclosed [ in {:?}", s))),
+						};
+						let high = if chars.peek() == Some(&'-') {
+							chars.next();
+							match chars.next() {
+								Some(']') => {
+									ranges.extend([(low, low), ('-', '-')]);
+									break;
+								}
+								Some(high) if high >= low => high,
+								_ => return Err(invalid(format!("bad range in {:?}", s))),
+							}
+						} else {
+							low
+						};
+						ranges.push((low, high));
+					}
+					Token::Class { negated, ranges }
+				}
+				c => Token::Literal(c),
+			});
+		}
+		Ok(Self { source: s.to_owned(), tokens })
+	}
+}
+
+impl fmt::Display for Pattern {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(&self.source)
+	}
+}
+
+/// Which files [`expand`] picks from directories.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WalkOptions {
+	/// Search subdirectories as well.
+	pub recursive: bool,
+	/// Files found in directories must match one of these, if any are given.
+	pub include: Vec<Pattern>,
+	/// Files found in directories or through a pattern must match none of these.
+	pub exclude: Vec<Pattern>,
+}
+
+impl WalkOptions {
+	/// Patterns without a `/` are matched against the file name, others against the path.
+	fn is_match(pattern: &Pattern, path: &str) -> bool {
+		if pattern.has_directories() {
+			pattern.matches(path)
+		} else {
+			pattern.matches(path.rsplit('/').next().unwrap_or(path))
+		}
+	}
+
+	/// Whether the file at `path`, found in a directory, is taken.
+	pub fn picks(&self, path: &Path) -> bool {
+		let name = path_to_string(path);
+		self.is_included(&name) && !self.is_excluded(&name)
+	}
+
+	fn is_included(&self, path: &str) -> bool {
+		self.include.is_empty() || self.include.iter().any(|p| Self::is_match(p, path))
+	}
+
+	fn is_excluded(&self, path: &str) -> bool {
+		self.exclude.iter().any(|p| Self::is_match(p, path))
+	}
+}
+
+/// Expands `paths` into a list of files, in the order given and sorted by name within each
+/// directory, naming each file once.
+///
+/// Files, including ones that do not exist, are passed through as they are, so that whoever
+/// opens them reports the error. Fails if a pattern matches nothing.
+pub fn expand(paths: &[PathBuf], options: &WalkOptions) -> Result<Vec<PathBuf>> {
+	let mut files = Vec::new();
+	for path in paths {
+		let text = path_to_string(path);
+		if path.is_dir() {
+			walk(
+				path,
+				if options.recursive { usize::MAX } else { 1 },
+				&mut |file| options.picks(file),
+				&mut files,
+			)?;
+		} else if !path.exists() && Pattern::is_pattern(&text) {
+			let found = glob(&text.parse()?, options)?;
+			if found.is_empty() {
+				return Err(PngmeError::NotFound(format!("No files match {}", text)));
+			}
+			files.extend(found);
+		} else {
+			files.push(path.clone());
+		}
+	}
+	let mut seen = std::collections::HashSet::new();
+	files.retain(|file| seen.insert(file.clone()));
+	Ok(files)
+}
+
+/// Finds the files matching `pattern`, starting from the directories before its first
+/// special character.
+fn glob(pattern: &Pattern, options: &WalkOptions) -> Result<Vec<PathBuf>> {
+	let components: Vec<&str> = pattern.source.split('/').collect();
+	let fixed = components.iter().take_while(|c| !Pattern::is_pattern(c)).count();
+	let base = match components[..fixed].join("/") {
+		base if base.is_empty() && fixed > 0 => PathBuf::from("/"),
+		base if base.is_empty() => PathBuf::from("."),
+		base => PathBuf::from(base),
+	};
+	let depth = if pattern.source.contains("**") { usize::MAX } else { components.len() - fixed };
+	let mut files = Vec::new();
+	if base.is_dir() {
+		walk(
+			&base,
+			depth,
+			&mut |file| {
+				let mut name = path_to_string(file);
+				if fixed == 0 {
+					name = name.trim_start_matches("./").to_owned();
+				}
+				pattern.matches(&name) && !options.is_excluded(&name)
+			},
+			&mut files,
+		)?;
+	}
+	if fixed == 0 {
+		for file in &mut files {
+			*file = file.strip_prefix(".").map(Path::to_path_buf).unwrap_or_else(|_| file.clone());
+		}
+	}
+	Ok(files)
+}
+
+/// Collects the files under `dir` up to `depth` levels down that `wanted` accepts. Symbolic
+/// links to directories are not followed, which rules out loops.
+fn walk(
+	dir: &Path,
+	depth: usize,
+	wanted: &mut dyn FnMut(&Path) -> bool,
+	files: &mut Vec<PathBuf>,
+) -> Result<()> {
+	if depth == 0 {
+		return Ok(());
+	}
+	let mut entries = fs::read_dir(dir)?.collect::<std::io::Result<Vec<_>>>()?;
+	entries.sort_by_key(|entry| entry.file_name());
+	for entry in entries {
+		let path = entry.path();
+		if entry.file_type()?.is_dir() {
+			walk(&path, depth - 1, wanted, files)?;
+		} else if path.is_file() && wanted(&path) {
+			files.push(path);
+		}
+	}
+	Ok(())
+}
+
+fn path_to_string(path: &Path) -> String {
+	let text = path.to_string_lossy();
+	if std::path::MAIN_SEPARATOR == '/' {
+		text.into_owned()
+	} else {
+		text.replace(std::path::MAIN_SEPARATOR, "/")
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn pattern(text: &str) -> Pattern {
+		text.parse().unwrap()
+	}
+
+	#[test]
+	fn test_pattern() {
+		assert!(pattern("*.png").matches("cat.png"));
+		assert!(!pattern("*.png").matches("dir/cat.png"));
+		assert!(pattern("dir/?at.png").matches("dir/cat.png"));
+		assert!(pattern("**/*.png").matches("cat.png"));
+		assert!(pattern("**/*.png").matches("a/b/cat.png"));
+		assert!(pattern("a/**").matches("a/b/c"));
+		assert!(pattern("*.[pP][nN][gG]").matches("CAT.PNG"));
+		assert!(pattern("[!a-c]*").matches("dog"));
+		assert!(!pattern("[!a-c]*").matches("cat"));
+		assert!(pattern("[a-]").matches("-"));
+		assert!(!pattern("c?t").matches("c/t"));
+		assert!(Pattern::is_pattern("*.png") && !Pattern::is_pattern("cat.png"));
+		assert!("[abc".parse::<Pattern>().is_err());
+		assert!("[z-a]".parse::<Pattern>().is_err());
+	}
+
+	#[test]
+	fn test_expand() {
+		let dir = std::env::temp_dir().join(format!("pngme-walk-{}", std::process::id()));
+		fs::create_dir_all(dir.join("sub")).unwrap();
+		for name in ["b.png", "a.png", "notes.txt", "skip.png", "sub/c.png"] {
+			fs::write(dir.join(name), b"").unwrap();
+		}
+		let names = |paths: &[PathBuf], options: &WalkOptions| -> Vec<String> {
+			let files = expand(paths, options).unwrap();
+			files.iter().map(|f| path_to_string(f.strip_prefix(&dir).unwrap())).collect()
+		};
+
+		let mut options = WalkOptions {
+			include: vec![pattern("*.png")],
+			exclude: vec![pattern("skip*")],
+			..WalkOptions::default()
+		};
+		assert_eq!(names(std::slice::from_ref(&dir), &options), ["a.png", "b.png"]);
+		options.recursive = true;
+		assert_eq!(names(std::slice::from_ref(&dir), &options), ["a.png", "b.png", "sub/c.png"]);
+
+		let glob = |pattern: &str| dir.join(pattern);
+		assert_eq!(names(&[glob("*.txt"), glob("a.png")], &options), ["notes.txt", "a.png"]);
+		assert_eq!(names(&[glob("**/c.png"), glob("sub/*")], &options), ["sub/c.png"]);
+		assert!(expand(&[glob("*.jpg")], &options).is_err());
+
+		assert!(options.picks(&dir.join("new.png")) && !options.picks(&dir.join("skip.png")));
+		assert!(!options.picks(&dir.join("a.png.tmp.0001")));
+
+		let missing = dir.join("missing.png");
+		assert_eq!(expand(std::slice::from_ref(&missing), &options).unwrap(), [missing]);
+		fs::remove_dir_all(&dir).unwrap();
+	}
+}
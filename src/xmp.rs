@@ -0,0 +1,129 @@
+//! XMP metadata, stored by convention in an uncompressed `iTXt` chunk with the keyword
+//! `XML:com.adobe.xmp` and empty language tag and translated keyword.
+
+use crate::{png::Png, text::ItxtChunk, Result};
+
+pub const KEYWORD: &str = "XML:com.adobe.xmp";
+
+fn is_xmp(itxt: &ItxtChunk) -> bool {
+	itxt.keyword() == KEYWORD
+}
+
+/// Returns the XMP packet of `png`, if it has one.
+pub fn get(png: &Png) -> Option<String> {
+	png.chunks()
+		.iter()
+		.filter_map(|chunk| ItxtChunk::try_from(chunk).ok())
+		.find(is_xmp)
+		.map(|itxt| itxt.text().to_owned())
+}
+
+/// Stores `packet` as the XMP metadata of `png`, replacing any earlier packet.
+pub fn set(png: &mut Png, packet: &str) -> Result<()> {
+	let itxt = ItxtChunk::new(KEYWORD, "", "", packet, false)?;
+	strip(png);
+	png.insert_chunk(itxt.to_chunk());
+	Ok(())
+}
+
+/// Removes the XMP metadata of `png`, returning whether there was any.
+pub fn strip(png: &mut Png) -> bool {
+	let before = png.chunks().len();
+	png.retain_chunks(|chunk| !ItxtChunk::try_from(chunk).is_ok_and(|itxt| is_xmp(&itxt)));
+	png.chunks().len() != before
+}
+
+/// Re-indents an XML document with one element per line. Elements holding only text stay on
+/// a single line, and whitespace between tags is discarded.
+pub fn pretty_print(xml: &str) -> String {
+	let mut tokens = Vec::new();
+	let mut rest = xml;
+	while !rest.is_empty() {
+		let end = if rest.starts_with('<') {
+			rest.find('>').map_or(rest.len(), |n| n + 1)
+		} else {
+			rest.find('<').unwrap_or(rest.len())
+		};
+		let (token, tail) = rest.split_at(end);
+		if !token.trim().is_empty() {
+			tokens.push(token.trim());
+		}
+		rest = tail;
+	}
+
+	let mut output = String::new();
+	let mut depth = 0usize;
+	let mut n = 0;
+	while n < tokens.len() {
+		let token = tokens[n];
+		if token.starts_with("</") {
+			depth = depth.saturating_sub(1);
+		}
+		output.push_str(&"  ".repeat(depth));
+		output.push_str(token);
+		if is_opening_tag(token) {
+			match tokens.get(n + 1..n + 3) {
+				Some(&[text, close]) if !text.starts_with('<') && close.starts_with("</") => {
+					output.push_str(text);
+					output.push_str(close);
+					n += 2;
+				}
+				_ => depth += 1,
+			}
+		}
+		output.push('\n');
+		n += 1;
+	}
+	output
+}
+
+fn is_opening_tag(token: &str) -> bool {
+	token.starts_with('<')
+		&& !token.starts_with("</")
+		&& !token.starts_with("<?")
+		&& !token.starts_with("<!")
+		&& !token.ends_with("/>")
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::chunk::Chunk;
+
+	fn testing_png() -> Png {
+		let chunks = ["IHDR", "IDAT", "IEND"]
+			.iter()
+			.map(|chunk_type| Chunk::new(chunk_type.parse().unwrap(), Vec::new()))
+			.collect();
+		Png::from_chunks(chunks)
+	}
+
+	#[test]
+	fn test_set_get_strip() {
+		let mut png = testing_png();
+		assert_eq!(get(&png), None);
+		set(&mut png, "<x:xmpmeta/>").unwrap();
+		set(&mut png, "<x:xmpmeta>new</x:xmpmeta>").unwrap();
+		assert_eq!(png.chunks().len(), 4);
+		assert_eq!(get(&png).unwrap(), "<x:xmpmeta>new</x:xmpmeta>");
+		assert!(strip(&mut png));
+		assert!(!strip(&mut png));
+		assert_eq!(get(&png), None);
+	}
+
+	#[test]
+	fn test_pretty_print() {
+		let xml = "<?xpacket begin=''?><x:xmpmeta><rdf:RDF> <rdf:Description a='1'/>\
+			<dc:title>Sunset</dc:title></rdf:RDF></x:xmpmeta>";
+		assert_eq!(
+			pretty_print(xml),
+			"<?xpacket begin=''?>\n\
+			<x:xmpmeta>\n\
+			\x20 <rdf:RDF>\n\
+			\x20   <rdf:Description a='1'/>\n\
+			\x20   <dc:title>Sunset</dc:title>\n\
+			\x20 </rdf:RDF>\n\
+			</x:xmpmeta>\n"
+		);
+	}
+}
@@ -0,0 +1,48 @@
+use std::io::{Read, Write};
+
+use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
+
+use crate::{PngmeError, Result};
+
+/// The most a compressed text chunk or color profile inflates to. Real ones come nowhere near
+/// it; past it, the data is taken for a decompression bomb rather than read.
+pub(crate) const MAX_METADATA: usize = 64 << 20;
+
+pub(crate) fn compress(bytes: &[u8]) -> Vec<u8> {
+	compress_with_level(bytes, Compression::default().level())
+}
+
+/// Compresses at `level`, from 0 (store) to 9 (smallest).
+pub(crate) fn compress_with_level(bytes: &[u8], level: u32) -> Vec<u8> {
+	let mut encoder = ZlibEncoder::new(Vec::new(), Compression::new(level));
+	encoder.write_all(bytes).expect("writing to a Vec cannot fail");
+	encoder.finish().expect("writing to a Vec cannot fail")
+}
+
+/// Inflates `bytes`, failing if they inflate to more than `limit` bytes.
+pub(crate) fn decompress(bytes: &[u8], limit: usize) -> Result<Vec<u8>> {
+	read_at_most(ZlibDecoder::new(bytes), limit)
+}
+
+/// Reads all of `reader`, failing if it holds more than `limit` bytes.
+pub(crate) fn read_at_most(reader: impl Read, limit: usize) -> Result<Vec<u8>> {
+	let mut buf = Vec::new();
+	reader.take(limit as u64 + 1).read_to_end(&mut buf)?;
+	if buf.len() > limit {
+		let reason = format!("inflates to more than {} bytes", limit);
+		return Err(PngmeError::invalid("compressed data", reason));
+	}
+	Ok(buf)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_decompress_limit() {
+		let compressed = compress(&[0; 1000]);
+		assert_eq!(decompress(&compressed, 1000).unwrap(), [0; 1000]);
+		assert!(decompress(&compressed, 999).is_err());
+	}
+}
@@ -0,0 +1,197 @@
+//! Runs the `pngme` binary on files in a temporary directory.
+
+use std::{
+	env, fs,
+	path::{Path, PathBuf},
+	process::{Command, Output},
+};
+
+/// An empty directory for the files of the test `name`.
+fn dir(name: &str) -> PathBuf {
+	let dir = env::temp_dir().join(format!("pngme-cli-{}-{}", name, std::process::id()));
+	let _ = fs::remove_dir_all(&dir);
+	fs::create_dir(&dir).unwrap();
+	dir
+}
+
+/// Runs pngme in `dir` with `args`, away from any configuration file of the user.
+fn pngme(dir: &Path, args: &[&str]) -> Output {
+	Command::new(env!("CARGO_BIN_EXE_pngme"))
+		.args(args)
+		.current_dir(dir)
+		.env("HOME", dir)
+		.env_remove("XDG_CONFIG_HOME")
+		.output()
+		.unwrap()
+}
+
+fn names(dir: &Path) -> Vec<String> {
+	let mut names: Vec<String> = fs::read_dir(dir)
+		.unwrap()
+		.map(|entry| entry.unwrap().file_name().into_string().unwrap())
+		.collect();
+	names.sort();
+	names
+}
+
+/// The number of chunks of type `chunk_type` in the PNG at `path`.
+fn count(path: &Path, chunk_type: &str) -> usize {
+	let png = pngme::Png::try_from(fs::read(path).unwrap().as_slice()).unwrap();
+	png.chunks_by_type(chunk_type).count()
+}
+
+#[test]
+fn test_failing_script() {
+	let dir = dir("failing-script");
+	let create = ["create", "a.png", "--message", "hi", "--chunk-type", "ruSt"];
+	assert!(pngme(&dir, &create).status.success());
+	assert!(pngme(&dir, &["create", "b.png"]).status.success());
+	// Only a.png has a ruSt chunk to remove, so the script fails on b.png.
+	fs::write(dir.join("script"), "text set Title edited\nremove ruSt\n").unwrap();
+	let before = [fs::read(dir.join("a.png")).unwrap(), fs::read(dir.join("b.png")).unwrap()];
+
+	let output = pngme(&dir, &["run", "script", "a.png", "b.png"]);
+	assert!(!output.status.success());
+	let stderr = String::from_utf8_lossy(&output.stderr);
+	assert!(stderr.contains("script:2: b.png"), "{}", stderr);
+	assert!(stderr.contains("no file was changed"), "{}", stderr);
+	let after = [fs::read(dir.join("a.png")).unwrap(), fs::read(dir.join("b.png")).unwrap()];
+	assert!(before == after, "a file was changed");
+	assert_eq!(names(&dir), ["a.png", "b.png", "script"]);
+
+	fs::write(dir.join("script"), "text set Title edited\n").unwrap();
+	assert!(pngme(&dir, &["run", "script", "a.png", "b.png"]).status.success());
+	for path in ["a.png", "b.png"] {
+		let output = pngme(&dir, &["text", "get", path, "Title"]);
+		assert_eq!(String::from_utf8_lossy(&output.stdout).trim_end(), "edited");
+	}
+	fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_many_files() {
+	let dir = dir("many-files");
+	fs::create_dir(dir.join("sub")).unwrap();
+	for path in ["a.png", "b.png", "sub/c.png"] {
+		assert!(pngme(&dir, &["create", path]).status.success());
+	}
+
+	let output = pngme(&dir, &["text", "set", "*.png", "Title", "many"]);
+	assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+	let output = pngme(&dir, &["text", "get", "*.png", "Title"]);
+	let stdout = String::from_utf8_lossy(&output.stdout);
+	assert_eq!(stdout, "==> a.png <==\nmany\n==> b.png <==\nmany\n");
+	assert!(pngme(&dir, &["text", "get", "sub/c.png", "Title"]).stdout.is_empty());
+
+	let output = pngme(&dir, &["check", "-r", ".", "--exclude", "b.png"]);
+	let stdout = String::from_utf8_lossy(&output.stdout);
+	assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+	assert!(stdout.contains("a.png: OK") && stdout.contains("c.png: OK"), "{}", stdout);
+	assert!(!stdout.contains("b.png"), "{}", stdout);
+
+	// Each file fails or succeeds on its own, and a failure fails the whole run.
+	let output = pngme(&dir, &["print", "a.png", "missing.png", "b.png"]);
+	assert_eq!(output.status.code(), Some(1));
+	let stderr = String::from_utf8_lossy(&output.stderr);
+	assert!(stderr.contains("1 of 3 files failed"), "{}", stderr);
+	fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_exit_codes() {
+	let dir = dir("exit-codes");
+	assert!(pngme(&dir, &["create", "a.png"]).status.success());
+	let mut bad_crc = fs::read(dir.join("a.png")).unwrap();
+	// The last byte of the IHDR chunk's CRC.
+	bad_crc[32] ^= 0xff;
+	fs::write(dir.join("bad-crc.png"), bad_crc).unwrap();
+
+	let code = |args: &[&str]| pngme(&dir, args).status.code();
+	assert_eq!(code(&["print", "a.png"]), Some(0));
+	assert_eq!(code(&["decode", "a.png", "ruSt"]), Some(2));
+	assert_eq!(code(&["print", "bad-crc.png"]), Some(3));
+	assert_eq!(code(&["print", "missing.png"]), Some(4));
+	assert_eq!(code(&["print", "a.png", "--no-such-option"]), Some(5));
+	assert_eq!(code(&["encode", "a.png", "ruSt", "hi", "--passphrase", "x"]), Some(5));
+	let lsb = ["encode", "a.png", "zTXt", "hi", "--compressed-text", "Comment", "--method", "lsb"];
+	assert_eq!(code(&lsb), Some(5));
+	fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_existing_output() {
+	let dir = dir("existing-output");
+	assert!(pngme(&dir, &["create", "a.png"]).status.success());
+	assert!(pngme(&dir, &["create", "b.png"]).status.success());
+	let before = fs::read(dir.join("b.png")).unwrap();
+
+	let output = pngme(&dir, &["encode", "a.png", "ruSt", "hi", "-o", "b.png"]);
+	assert_eq!(output.status.code(), Some(5));
+	let stderr = String::from_utf8_lossy(&output.stderr);
+	assert!(stderr.contains("b.png already exists, pass --force"), "{}", stderr);
+	assert_eq!(fs::read(dir.join("b.png")).unwrap(), before);
+
+	for force in ["--force", "-f"] {
+		let output = pngme(&dir, &["encode", "a.png", "ruSt", "hi", force, "-o", "b.png"]);
+		assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+	}
+	let output = pngme(&dir, &["decode", "b.png", "ruSt"]);
+	assert_eq!(String::from_utf8_lossy(&output.stdout).trim_end(), "hi");
+	assert_eq!(count(&dir.join("a.png"), "ruSt"), 0);
+
+	// Nothing is reported as repaired when the output is refused.
+	let mut bad_crc = fs::read(dir.join("a.png")).unwrap();
+	bad_crc[32] ^= 0xff;
+	fs::write(dir.join("bad-crc.png"), bad_crc).unwrap();
+	let output = pngme(&dir, &["--verbose", "repair", "bad-crc.png", "-o", "b.png"]);
+	assert_eq!(output.status.code(), Some(5));
+	let stderr = String::from_utf8_lossy(&output.stderr);
+	assert!(!stderr.contains("recomputed"), "{}", stderr);
+	fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_duplicate_chunks() {
+	let dir = dir("duplicate-chunks");
+	let path = dir.join("a.png");
+	assert!(pngme(&dir, &["create", "a.png"]).status.success());
+	assert!(pngme(&dir, &["encode", "a.png", "ruSt", "one"]).status.success());
+
+	assert_eq!(pngme(&dir, &["encode", "a.png", "ruSt", "two"]).status.code(), Some(5));
+	assert_eq!(count(&path, "ruSt"), 1);
+	let append = ["encode", "a.png", "ruSt", "two", "--append-duplicate"];
+	assert!(pngme(&dir, &append).status.success());
+	assert_eq!(count(&path, "ruSt"), 2);
+	assert!(pngme(&dir, &["encode", "a.png", "ruSt", "three", "--replace"]).status.success());
+	assert_eq!(count(&path, "ruSt"), 1);
+	let output = pngme(&dir, &["decode", "a.png", "ruSt"]);
+	assert_eq!(String::from_utf8_lossy(&output.stdout).trim_end(), "three");
+
+	let both = ["encode", "a.png", "ruSt", "four", "--replace", "--append-duplicate"];
+	assert_eq!(pngme(&dir, &both).status.code(), Some(5));
+	assert_eq!(pngme(&dir, &["encode", "a.png", "miSs", "x", "--replace"]).status.code(), Some(2));
+	assert_eq!(count(&path, "ruSt"), 1);
+	fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_backup_restore() {
+	let dir = dir("backup-restore");
+	assert!(pngme(&dir, &["create", "a.png"]).status.success());
+	let original = fs::read(dir.join("a.png")).unwrap();
+
+	assert!(pngme(&dir, &["--backup", "encode", "a.png", "ruSt", "hi"]).status.success());
+	assert_eq!(fs::read(dir.join("a.png.bak")).unwrap(), original);
+	assert_eq!(count(&dir.join("a.png"), "ruSt"), 1);
+
+	assert!(pngme(&dir, &["restore", "a.png"]).status.success());
+	assert_eq!(fs::read(dir.join("a.png")).unwrap(), original);
+	assert_eq!(names(&dir), ["a.png"]);
+	assert_eq!(pngme(&dir, &["restore", "a.png"]).status.code(), Some(2));
+
+	let backup = ["--backup=.orig", "encode", "a.png", "ruSt", "hi"];
+	assert!(pngme(&dir, &backup).status.success());
+	assert!(pngme(&dir, &["restore", "a.png", "--suffix", ".orig"]).status.success());
+	assert_eq!(fs::read(dir.join("a.png")).unwrap(), original);
+	fs::remove_dir_all(dir).unwrap();
+}